@@ -0,0 +1,107 @@
+//! 管理接口认证
+//! 通过 ADMIN_TOKEN 保护 /admin/* 路由，通过 GITHUB_WEBHOOK_SECRET 校验 Webhook 签名
+
+use crate::config::CONFIG;
+use crate::error::ApiError;
+use axum::{
+    extract::Request,
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// 管理接口中间件：校验 `Authorization: Bearer <token>` 或 `X-Admin-Token`
+pub async fn require_admin(req: Request, next: Next) -> Response {
+    if CONFIG.admin_token.is_empty() {
+        return ApiError::new(
+            StatusCode::FORBIDDEN,
+            "admin_disabled",
+            "管理接口未启用 (未配置 ADMIN_TOKEN)",
+        )
+        .into_response();
+    }
+
+    if !is_admin(req.headers()) {
+        return ApiError::new(StatusCode::UNAUTHORIZED, "unauthorized", "无效的管理令牌")
+            .into_response();
+    }
+
+    next.run(req).await
+}
+
+/// 判断请求头是否携带有效的管理令牌
+pub fn is_admin(headers: &HeaderMap) -> bool {
+    if CONFIG.admin_token.is_empty() {
+        return false;
+    }
+
+    let token = headers
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or_else(|| headers.get("X-Admin-Token").and_then(|v| v.to_str().ok()));
+
+    token.is_some_and(|t| constant_time_eq(t.as_bytes(), CONFIG.admin_token.as_bytes()))
+}
+
+/// 常量时间比较，避免通过响应时间猜测令牌
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// 校验 GitHub Webhook 的 `X-Hub-Signature-256` 签名 (未配置密钥时始终失败)
+pub fn verify_github_signature(body: &[u8], signature: Option<&str>) -> bool {
+    if CONFIG.github_webhook_secret.is_empty() {
+        return false;
+    }
+    signature.is_some_and(|s| verify_signature(CONFIG.github_webhook_secret.as_bytes(), body, s))
+}
+
+/// 校验 `sha256=<hex>` 格式的 HMAC-SHA256 签名 (常量时间比较)
+fn verify_signature(secret: &[u8], body: &[u8], signature: &str) -> bool {
+    let Some(expected) = signature
+        .strip_prefix("sha256=")
+        .and_then(|hex| hex::decode(hex).ok())
+    else {
+        return false;
+    };
+
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret) else {
+        return false;
+    };
+    mac.update(body);
+    mac.verify_slice(&expected).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // GitHub 文档中的示例
+    const SECRET: &[u8] = b"It's a Secret to Everybody";
+    const BODY: &[u8] = b"Hello, World!";
+    const SIGNATURE: &str =
+        "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+
+    #[test]
+    fn accepts_valid_signature() {
+        assert!(verify_signature(SECRET, BODY, SIGNATURE));
+    }
+
+    #[test]
+    fn rejects_tampered_or_malformed_signature() {
+        assert!(!verify_signature(SECRET, b"Hello, World?", SIGNATURE));
+        assert!(!verify_signature(b"wrong", BODY, SIGNATURE));
+        assert!(!verify_signature(
+            SECRET,
+            BODY,
+            SIGNATURE.trim_start_matches("sha256=")
+        ));
+        assert!(!verify_signature(SECRET, BODY, "sha256=not-hex"));
+    }
+}
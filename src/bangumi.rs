@@ -2,14 +2,23 @@
 //! https://bangumi.github.io/api/
 //! User Agent 规范: https://github.com/bangumi/api/blob/master/docs-raw/user%20agent.md
 //! 
-//! 注意：这些类型和函数目前未使用（通过 /bgm/* 通用代理访问 Bangumi API）
-//! 保留以便将来可能的直接集成使用
+//! 注意：大部分类型和函数目前未使用（通过 /bgm/* 通用代理访问 Bangumi API）
+//! 目前 GraphQL、热力图与别名扩展搜索直接调用，其余保留以便将来集成使用
 
 #![allow(dead_code)]
 
-use crate::http_client::HTTP_CLIENT;
+use crate::bounded;
+use crate::http_cache::{self, Lookup};
+use crate::http_client::{send_with_backoff, HttpClientError, HTTP_CLIENT};
+use chrono::{DateTime, Datelike, Local, NaiveDate};
+use once_cell::sync::Lazy;
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use tracing::warn;
 
 const BANGUMI_API: &str = "https://api.bgm.tv";
@@ -37,7 +46,6 @@ pub fn get_effective_token(user_token: Option<&str>) -> Option<&str> {
 
 /// 获取服务端配置的默认 token (从环境变量 BANGUMI_ACCESS_TOKEN)
 fn get_server_token() -> Option<&'static str> {
-    use once_cell::sync::Lazy;
     static SERVER_TOKEN: Lazy<Option<String>> = Lazy::new(|| {
         std::env::var("BANGUMI_ACCESS_TOKEN").ok().filter(|s| !s.is_empty())
     });
@@ -60,6 +68,8 @@ pub struct BangumiSearchResult {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BangumiSubject {
     pub id: i64,
+    /// 旧版 API 返回，v0 接口无此字段
+    #[serde(default)]
     pub url: String,
     #[serde(rename = "type")]
     pub subject_type: i32,
@@ -68,7 +78,8 @@ pub struct BangumiSubject {
     pub name_cn: String,
     #[serde(default)]
     pub summary: String,
-    #[serde(default)]
+    /// 放送日期 (v0 接口字段名为 date)
+    #[serde(default, alias = "date")]
     pub air_date: String,
     #[serde(default)]
     pub air_weekday: i32,
@@ -630,14 +641,51 @@ impl From<BangumiSubject> for AnimeInfo {
 // HTTP 请求辅助函数
 // ============================================================================
 
+/// 获取公开资源 (GET)
+/// 未携带 token 时使用 HTTP 缓存 (按 Cache-Control 缓存，过期后通过 ETag 重新验证)；携带 token 的个性化结果不缓存
+async fn get_public_json<T: DeserializeOwned>(url: &str, token: Option<&str>) -> anyhow::Result<T> {
+    let mut req = HTTP_CLIENT.get(url).header("User-Agent", USER_AGENT);
+    let stale = match token {
+        Some(t) => {
+            req = req.header("Authorization", format!("Bearer {}", t));
+            None
+        }
+        None => match http_cache::lookup(url).await {
+            Lookup::Fresh(entry) => return Ok(serde_json::from_slice(&entry.body)?),
+            Lookup::Stale(entry) => {
+                for (name, value) in entry.validators() {
+                    req = req.header(name, value);
+                }
+                Some(entry)
+            }
+            Lookup::Miss => None,
+        },
+    };
+
+    let response = send_with_backoff(req).await?;
+    if let (StatusCode::NOT_MODIFIED, Some(entry)) = (response.status(), stale) {
+        let entry = http_cache::revalidated(url, entry, response.headers()).await;
+        return Ok(serde_json::from_slice(&entry.body)?);
+    }
+    if !response.status().is_success() {
+        anyhow::bail!("Bangumi API 返回错误: {}", response.status());
+    }
+
+    let headers = response.headers().clone();
+    let body = response.bytes().await?;
+    if token.is_none() {
+        http_cache::store(url, url, &headers, &body).await;
+    }
+    Ok(serde_json::from_slice(&body)?)
+}
+
 /// 发送带认证的 GET 请求
 async fn get_with_auth<T: for<'de> Deserialize<'de>>(url: &str, token: &str) -> anyhow::Result<T> {
-    let response = HTTP_CLIENT
+    let req = HTTP_CLIENT
         .get(url)
         .header("User-Agent", USER_AGENT)
-        .header("Authorization", format!("Bearer {}", token))
-        .send()
-        .await?;
+        .header("Authorization", format!("Bearer {}", token));
+    let response = send_with_backoff(req).await?;
 
     if !response.status().is_success() {
         anyhow::bail!("Bangumi API 返回错误: {} - {}", response.status(), response.text().await.unwrap_or_default());
@@ -654,14 +702,13 @@ async fn post_with_auth<T: for<'de> Deserialize<'de>, B: Serialize>(
     token: &str,
     body: &B,
 ) -> anyhow::Result<T> {
-    let response = HTTP_CLIENT
+    let req = HTTP_CLIENT
         .post(url)
         .header("User-Agent", USER_AGENT)
         .header("Authorization", format!("Bearer {}", token))
         .header("Content-Type", "application/json")
-        .json(body)
-        .send()
-        .await?;
+        .json(body);
+    let response = send_with_backoff(req).await?;
 
     if !response.status().is_success() {
         anyhow::bail!("Bangumi API 返回错误: {} - {}", response.status(), response.text().await.unwrap_or_default());
@@ -673,14 +720,13 @@ async fn post_with_auth<T: for<'de> Deserialize<'de>, B: Serialize>(
 
 /// 发送带认证的 POST 请求 (无响应体)
 async fn post_with_auth_empty<B: Serialize>(url: &str, token: &str, body: &B) -> anyhow::Result<()> {
-    let response = HTTP_CLIENT
+    let req = HTTP_CLIENT
         .post(url)
         .header("User-Agent", USER_AGENT)
         .header("Authorization", format!("Bearer {}", token))
         .header("Content-Type", "application/json")
-        .json(body)
-        .send()
-        .await?;
+        .json(body);
+    let response = send_with_backoff(req).await?;
 
     if !response.status().is_success() {
         anyhow::bail!("Bangumi API 返回错误: {} - {}", response.status(), response.text().await.unwrap_or_default());
@@ -691,14 +737,13 @@ async fn post_with_auth_empty<B: Serialize>(url: &str, token: &str, body: &B) ->
 
 /// 发送带认证的 PATCH 请求
 async fn patch_with_auth<B: Serialize>(url: &str, token: &str, body: &B) -> anyhow::Result<()> {
-    let response = HTTP_CLIENT
+    let req = HTTP_CLIENT
         .patch(url)
         .header("User-Agent", USER_AGENT)
         .header("Authorization", format!("Bearer {}", token))
         .header("Content-Type", "application/json")
-        .json(body)
-        .send()
-        .await?;
+        .json(body);
+    let response = send_with_backoff(req).await?;
 
     if !response.status().is_success() {
         anyhow::bail!("Bangumi API 返回错误: {} - {}", response.status(), response.text().await.unwrap_or_default());
@@ -709,12 +754,11 @@ async fn patch_with_auth<B: Serialize>(url: &str, token: &str, body: &B) -> anyh
 
 /// 发送带认证的 DELETE 请求
 async fn delete_with_auth(url: &str, token: &str) -> anyhow::Result<()> {
-    let response = HTTP_CLIENT
+    let req = HTTP_CLIENT
         .delete(url)
         .header("User-Agent", USER_AGENT)
-        .header("Authorization", format!("Bearer {}", token))
-        .send()
-        .await?;
+        .header("Authorization", format!("Bearer {}", token));
+    let response = send_with_backoff(req).await?;
 
     if !response.status().is_success() {
         anyhow::bail!("Bangumi API 返回错误: {} - {}", response.status(), response.text().await.unwrap_or_default());
@@ -736,54 +780,21 @@ pub async fn search_anime(keyword: &str) -> anyhow::Result<BangumiSearchResult>
         urlencoding::encode(keyword)
     );
 
-    let response = HTTP_CLIENT
-        .get(&url)
-        .header("User-Agent", USER_AGENT)
-        .send()
-        .await?;
-
-    if !response.status().is_success() {
-        anyhow::bail!("Bangumi API 返回错误: {}", response.status());
-    }
-
-    let result: BangumiSearchResult = response.json().await?;
-    Ok(result)
+    get_public_json(&url, None).await
 }
 
 /// 获取条目详情
 pub async fn get_subject(id: i64) -> anyhow::Result<BangumiSubject> {
     let url = format!("{}/subject/{}", BANGUMI_API, id);
 
-    let response = HTTP_CLIENT
-        .get(&url)
-        .header("User-Agent", USER_AGENT)
-        .send()
-        .await?;
-
-    if !response.status().is_success() {
-        anyhow::bail!("Bangumi API 返回错误: {}", response.status());
-    }
-
-    let subject: BangumiSubject = response.json().await?;
-    Ok(subject)
+    get_public_json(&url, None).await
 }
 
 /// 获取每日放送
 pub async fn get_calendar() -> anyhow::Result<Vec<CalendarItem>> {
     let url = format!("{}/calendar", BANGUMI_API);
 
-    let response = HTTP_CLIENT
-        .get(&url)
-        .header("User-Agent", USER_AGENT)
-        .send()
-        .await?;
-
-    if !response.status().is_success() {
-        anyhow::bail!("Bangumi API 返回错误: {}", response.status());
-    }
-
-    let calendar: Vec<CalendarItem> = response.json().await?;
-    Ok(calendar)
+    get_public_json(&url, None).await
 }
 
 /// 搜索并返回简化信息
@@ -830,7 +841,7 @@ pub async fn search_subjects_v0(
         req = req.header("Authorization", format!("Bearer {}", t));
     }
 
-    let response = req.send().await?;
+    let response = send_with_backoff(req).await?;
 
     if !response.status().is_success() {
         anyhow::bail!("Bangumi API 返回错误: {}", response.status());
@@ -844,76 +855,28 @@ pub async fn search_subjects_v0(
 pub async fn get_subject_v0(id: i64, token: Option<&str>) -> anyhow::Result<BangumiSubject> {
     let url = format!("{}/v0/subjects/{}", BANGUMI_API, id);
 
-    let mut req = HTTP_CLIENT.get(&url).header("User-Agent", USER_AGENT);
-    if let Some(t) = token {
-        req = req.header("Authorization", format!("Bearer {}", t));
-    }
-
-    let response = req.send().await?;
-
-    if !response.status().is_success() {
-        anyhow::bail!("Bangumi API 返回错误: {}", response.status());
-    }
-
-    let subject: BangumiSubject = response.json().await?;
-    Ok(subject)
+    get_public_json(&url, token).await
 }
 
 /// 获取条目角色 (GET /v0/subjects/{id}/characters)
 pub async fn get_subject_characters(id: i64, token: Option<&str>) -> anyhow::Result<Vec<Character>> {
     let url = format!("{}/v0/subjects/{}/characters", BANGUMI_API, id);
 
-    let mut req = HTTP_CLIENT.get(&url).header("User-Agent", USER_AGENT);
-    if let Some(t) = token {
-        req = req.header("Authorization", format!("Bearer {}", t));
-    }
-
-    let response = req.send().await?;
-
-    if !response.status().is_success() {
-        anyhow::bail!("Bangumi API 返回错误: {}", response.status());
-    }
-
-    let chars: Vec<Character> = response.json().await?;
-    Ok(chars)
+    get_public_json(&url, token).await
 }
 
 /// 获取条目制作人员 (GET /v0/subjects/{id}/persons)
 pub async fn get_subject_persons(id: i64, token: Option<&str>) -> anyhow::Result<Vec<Person>> {
     let url = format!("{}/v0/subjects/{}/persons", BANGUMI_API, id);
 
-    let mut req = HTTP_CLIENT.get(&url).header("User-Agent", USER_AGENT);
-    if let Some(t) = token {
-        req = req.header("Authorization", format!("Bearer {}", t));
-    }
-
-    let response = req.send().await?;
-
-    if !response.status().is_success() {
-        anyhow::bail!("Bangumi API 返回错误: {}", response.status());
-    }
-
-    let persons: Vec<Person> = response.json().await?;
-    Ok(persons)
+    get_public_json(&url, token).await
 }
 
 /// 获取条目关联条目 (GET /v0/subjects/{id}/subjects)
 pub async fn get_subject_relations(id: i64, token: Option<&str>) -> anyhow::Result<Vec<RelatedSubject>> {
     let url = format!("{}/v0/subjects/{}/subjects", BANGUMI_API, id);
 
-    let mut req = HTTP_CLIENT.get(&url).header("User-Agent", USER_AGENT);
-    if let Some(t) = token {
-        req = req.header("Authorization", format!("Bearer {}", t));
-    }
-
-    let response = req.send().await?;
-
-    if !response.status().is_success() {
-        anyhow::bail!("Bangumi API 返回错误: {}", response.status());
-    }
-
-    let relations: Vec<RelatedSubject> = response.json().await?;
-    Ok(relations)
+    get_public_json(&url, token).await
 }
 
 /// 获取章节列表 (GET /v0/episodes)
@@ -937,92 +900,35 @@ pub async fn get_episodes(
 
     let url = format!("{}/v0/episodes?{}", BANGUMI_API, params.join("&"));
 
-    let mut req = HTTP_CLIENT.get(&url).header("User-Agent", USER_AGENT);
-    if let Some(t) = token {
-        req = req.header("Authorization", format!("Bearer {}", t));
-    }
-
-    let response = req.send().await?;
-
-    if !response.status().is_success() {
-        anyhow::bail!("Bangumi API 返回错误: {}", response.status());
-    }
-
-    let episodes: EpisodeList = response.json().await?;
-    Ok(episodes)
+    get_public_json(&url, token).await
 }
 
 /// 获取章节详情 (GET /v0/episodes/{id})
 pub async fn get_episode(id: i64, token: Option<&str>) -> anyhow::Result<Episode> {
     let url = format!("{}/v0/episodes/{}", BANGUMI_API, id);
 
-    let mut req = HTTP_CLIENT.get(&url).header("User-Agent", USER_AGENT);
-    if let Some(t) = token {
-        req = req.header("Authorization", format!("Bearer {}", t));
-    }
-
-    let response = req.send().await?;
-
-    if !response.status().is_success() {
-        anyhow::bail!("Bangumi API 返回错误: {}", response.status());
-    }
-
-    let episode: Episode = response.json().await?;
-    Ok(episode)
+    get_public_json(&url, token).await
 }
 
 /// 获取角色详情 (GET /v0/characters/{id})
 pub async fn get_character(id: i64) -> anyhow::Result<CharacterDetail> {
     let url = format!("{}/v0/characters/{}", BANGUMI_API, id);
 
-    let response = HTTP_CLIENT
-        .get(&url)
-        .header("User-Agent", USER_AGENT)
-        .send()
-        .await?;
-
-    if !response.status().is_success() {
-        anyhow::bail!("Bangumi API 返回错误: {}", response.status());
-    }
-
-    let character: CharacterDetail = response.json().await?;
-    Ok(character)
+    get_public_json(&url, None).await
 }
 
 /// 获取人物详情 (GET /v0/persons/{id})
 pub async fn get_person(id: i64) -> anyhow::Result<PersonDetail> {
     let url = format!("{}/v0/persons/{}", BANGUMI_API, id);
 
-    let response = HTTP_CLIENT
-        .get(&url)
-        .header("User-Agent", USER_AGENT)
-        .send()
-        .await?;
-
-    if !response.status().is_success() {
-        anyhow::bail!("Bangumi API 返回错误: {}", response.status());
-    }
-
-    let person: PersonDetail = response.json().await?;
-    Ok(person)
+    get_public_json(&url, None).await
 }
 
 /// 获取用户信息 (GET /v0/users/{username})
 pub async fn get_user(username: &str) -> anyhow::Result<User> {
     let url = format!("{}/v0/users/{}", BANGUMI_API, urlencoding::encode(username));
 
-    let response = HTTP_CLIENT
-        .get(&url)
-        .header("User-Agent", USER_AGENT)
-        .send()
-        .await?;
-
-    if !response.status().is_success() {
-        anyhow::bail!("Bangumi API 返回错误: {}", response.status());
-    }
-
-    let user: User = response.json().await?;
-    Ok(user)
+    get_public_json(&url, None).await
 }
 
 // ============================================================================
@@ -1150,14 +1056,13 @@ pub async fn update_episode_collection(
     let url = format!("{}/v0/users/-/collections/-/episodes/{}", BANGUMI_API, episode_id);
     let body = serde_json::json!({ "type": collection_type });
 
-    let response = HTTP_CLIENT
+    let req = HTTP_CLIENT
         .put(&url)
         .header("User-Agent", USER_AGENT)
         .header("Authorization", format!("Bearer {}", token))
         .header("Content-Type", "application/json")
-        .json(&body)
-        .send()
-        .await?;
+        .json(&body);
+    let response = send_with_backoff(req).await?;
 
     if !response.status().is_success() {
         anyhow::bail!("Bangumi API 返回错误: {} - {}", response.status(), response.text().await.unwrap_or_default());
@@ -1196,19 +1101,7 @@ pub async fn uncollect_person(person_id: i64, token: &str) -> anyhow::Result<()>
 pub async fn get_index(index_id: i64, token: Option<&str>) -> anyhow::Result<Index> {
     let url = format!("{}/v0/indices/{}", BANGUMI_API, index_id);
 
-    let mut req = HTTP_CLIENT.get(&url).header("User-Agent", USER_AGENT);
-    if let Some(t) = token {
-        req = req.header("Authorization", format!("Bearer {}", t));
-    }
-
-    let response = req.send().await?;
-
-    if !response.status().is_success() {
-        anyhow::bail!("Bangumi API 返回错误: {}", response.status());
-    }
-
-    let index: Index = response.json().await?;
-    Ok(index)
+    get_public_json(&url, token).await
 }
 
 /// 获取目录条目 (GET /v0/indices/{index_id}/subjects)
@@ -1231,19 +1124,7 @@ pub async fn get_index_subjects(
         url = format!("{}?{}", url, params.join("&"));
     }
 
-    let mut req = HTTP_CLIENT.get(&url).header("User-Agent", USER_AGENT);
-    if let Some(t) = token {
-        req = req.header("Authorization", format!("Bearer {}", t));
-    }
-
-    let response = req.send().await?;
-
-    if !response.status().is_success() {
-        anyhow::bail!("Bangumi API 返回错误: {}", response.status());
-    }
-
-    let subjects: IndexSubjectList = response.json().await?;
-    Ok(subjects)
+    get_public_json(&url, token).await
 }
 
 /// 收藏目录 (POST /v0/indices/{index_id}/collect)
@@ -1258,3 +1139,299 @@ pub async fn uncollect_index(index_id: i64, token: &str) -> anyhow::Result<()> {
     let url = format!("{}/v0/indices/{}/collect", BANGUMI_API, index_id);
     delete_with_auth(&url, token).await
 }
+
+// ============================================================================
+// 标题别名解析
+// ============================================================================
+
+/// 使用关键词在 Bangumi 中匹配最相关的动画条目，返回其所有标题变体
+/// (原名、中文名、infobox 中的中文名/别名)
+pub async fn resolve_title_variants(keyword: &str) -> anyhow::Result<Vec<String>> {
+    let request = SearchRequest {
+        keyword: keyword.to_string(),
+        filter: Some(SearchFilter {
+            subject_type: Some(vec![2]),
+            tag: None,
+            air_date: None,
+            rating: None,
+            rank: None,
+            nsfw: None,
+        }),
+    };
+
+    let token = get_effective_token(None);
+    let result = search_subjects_v0(&request, Some(1), None, token).await?;
+    let Some(found) = result.data.into_iter().next() else {
+        return Ok(Vec::new());
+    };
+
+    // 搜索结果不一定包含 infobox，需要获取完整条目
+    let subject = get_subject_v0(found.id, token).await?;
+    Ok(subject_title_variants(&subject))
+}
+
+/// 提取条目的所有标题变体 (去重，保持顺序)
+fn subject_title_variants(subject: &BangumiSubject) -> Vec<String> {
+    let mut variants = vec![subject.name.clone(), subject.name_cn.clone()];
+
+    for item in subject.infobox.iter().flatten() {
+        if item.key != "别名" && item.key != "中文名" {
+            continue;
+        }
+        match &item.value {
+            Value::String(s) => variants.push(s.clone()),
+            // 别名为数组: [{"v": "..."}, ...]
+            Value::Array(values) => variants.extend(
+                values
+                    .iter()
+                    .filter_map(|v| v.get("v").and_then(|v| v.as_str()))
+                    .map(|v| v.to_string()),
+            ),
+            _ => {}
+        }
+    }
+
+    let mut unique: Vec<String> = Vec::new();
+    for variant in variants {
+        let variant = variant.trim();
+        if !variant.is_empty() && !unique.iter().any(|u| u == variant) {
+            unique.push(variant.to_string());
+        }
+    }
+    unique
+}
+
+// ============================================================================
+// 用户活动热力图
+// ============================================================================
+
+/// 热力图缓存有效期 (秒)
+const HEATMAP_CACHE_TTL_SECS: u64 = 3600;
+/// 收藏分页大小 (v0 接口上限 100)
+const HEATMAP_PAGE_SIZE: i32 = 100;
+/// 热力图覆盖天数
+const HEATMAP_DAYS: i64 = 365;
+/// 热力图缓存最多条目数
+const HEATMAP_CACHE_MAX_ENTRIES: usize = 256;
+
+/// 热力图缓存 (key: 用户名 + 条目类型；只缓存未携带令牌的请求)
+static HEATMAP_CACHE: Lazy<Mutex<HashMap<String, (Instant, Heatmap)>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// 用户活动热力图
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Heatmap {
+    pub username: String,
+    /// 起始日期 (含)
+    pub start: NaiveDate,
+    /// 结束日期 (含)
+    pub end: NaiveDate,
+    /// 区间内的活动总数
+    pub total: u32,
+    /// 单日最大活动数
+    pub max_count: u32,
+    /// 按周分组 (周日开始)，第一周可能不足 7 天
+    pub weeks: Vec<Vec<HeatmapDay>>,
+}
+
+/// 热力图中的单日数据
+#[derive(Debug, Clone, Serialize)]
+pub struct HeatmapDay {
+    pub date: NaiveDate,
+    /// 0 = 周日 ... 6 = 周六
+    pub weekday: u32,
+    pub count: u32,
+    /// 强度等级 0-4 (与 GitHub 贡献图一致)
+    pub level: u8,
+}
+
+/// 获取用户过去一年的收藏活动热力图 (带缓存)
+/// 携带令牌时结果可能包含私密收藏，不读写缓存
+pub async fn get_user_heatmap(
+    username: &str,
+    subject_type: Option<i32>,
+    token: Option<&str>,
+) -> anyhow::Result<Heatmap> {
+    let cache_key = format!("{}:{}", username, subject_type.unwrap_or(0));
+    let cacheable = token.is_none();
+    if cacheable {
+        let cache = HEATMAP_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((fetched_at, heatmap)) = cache.get(&cache_key) {
+            if fetched_at.elapsed() < Duration::from_secs(HEATMAP_CACHE_TTL_SECS) {
+                return Ok(heatmap.clone());
+            }
+        }
+    }
+
+    let today = Local::now().date_naive();
+    let start = today - chrono::Duration::days(HEATMAP_DAYS - 1);
+    let mut dates = Vec::new();
+    let mut offset = 0;
+
+    // 收藏按 updated_at 倒序返回，遇到早于起始日期的记录即可停止翻页
+    'pages: loop {
+        let page = fetch_collections_page(username, subject_type, offset, token).await?;
+        for collection in &page.data {
+            let Ok(updated_at) = DateTime::parse_from_rfc3339(&collection.updated_at) else {
+                continue;
+            };
+            let date = updated_at.date_naive();
+            if date < start {
+                break 'pages;
+            }
+            dates.push(date);
+        }
+
+        offset += page.data.len() as i32;
+        if page.data.is_empty() || offset >= page.total {
+            break;
+        }
+    }
+
+    let heatmap = build_heatmap(username, &dates, today);
+    if cacheable {
+        let mut cache = HEATMAP_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+        let ttl = Duration::from_secs(HEATMAP_CACHE_TTL_SECS);
+        bounded::make_room(&mut cache, &cache_key, HEATMAP_CACHE_MAX_ENTRIES, |(fetched_at, _)| {
+            fetched_at.elapsed() < ttl
+        });
+        cache.insert(cache_key, (Instant::now(), heatmap.clone()));
+    }
+    Ok(heatmap)
+}
+
+/// 获取一页用户收藏 (公开收藏无需认证，token 可选)
+async fn fetch_collections_page(
+    username: &str,
+    subject_type: Option<i32>,
+    offset: i32,
+    token: Option<&str>,
+) -> anyhow::Result<UserCollectionList> {
+    let mut url = format!(
+        "{}/v0/users/{}/collections?limit={}&offset={}",
+        BANGUMI_API,
+        urlencoding::encode(username),
+        HEATMAP_PAGE_SIZE,
+        offset
+    );
+    if let Some(t) = subject_type {
+        url.push_str(&format!("&subject_type={}", t));
+    }
+
+    let mut req = HTTP_CLIENT.get(&url).header("User-Agent", USER_AGENT);
+    if let Some(t) = token {
+        req = req.header("Authorization", format!("Bearer {}", t));
+    }
+
+    let response = send_with_backoff(req).await?;
+
+    if !response.status().is_success() {
+        return Err(anyhow::Error::new(HttpClientError::BadStatus(response.status().as_u16()))
+            .context("Bangumi API 返回错误"));
+    }
+
+    let list: UserCollectionList = response.json().await?;
+    Ok(list)
+}
+
+/// 将活动日期聚合为以 today 结束的一年热力图
+fn build_heatmap(username: &str, dates: &[NaiveDate], today: NaiveDate) -> Heatmap {
+    let start = today - chrono::Duration::days(HEATMAP_DAYS - 1);
+
+    let mut counts: HashMap<NaiveDate, u32> = HashMap::new();
+    for date in dates.iter().filter(|d| **d >= start && **d <= today) {
+        *counts.entry(*date).or_default() += 1;
+    }
+
+    let total = counts.values().sum();
+    let max_count = counts.values().copied().max().unwrap_or(0);
+
+    let mut weeks: Vec<Vec<HeatmapDay>> = Vec::new();
+    for date in start.iter_days().take_while(|d| *d <= today) {
+        let weekday = date.weekday().num_days_from_sunday();
+        if weekday == 0 || weeks.is_empty() {
+            weeks.push(Vec::with_capacity(7));
+        }
+
+        let count = counts.get(&date).copied().unwrap_or(0);
+        weeks.last_mut().unwrap().push(HeatmapDay {
+            date,
+            weekday,
+            count,
+            level: heatmap_level(count, max_count),
+        });
+    }
+
+    Heatmap {
+        username: username.to_string(),
+        start,
+        end: today,
+        total,
+        max_count,
+        weeks,
+    }
+}
+
+/// 按单日最大值的四分位计算强度等级
+fn heatmap_level(count: u32, max_count: u32) -> u8 {
+    if count == 0 || max_count == 0 {
+        return 0;
+    }
+    (count * 4).div_ceil(max_count).clamp(1, 4) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subject_title_variants() {
+        let subject: BangumiSubject = serde_json::from_value(serde_json::json!({
+            "id": 400602,
+            "type": 2,
+            "name": "葬送のフリーレン",
+            "name_cn": "葬送的芙莉莲",
+            "infobox": [
+                {"key": "中文名", "value": "葬送的芙莉莲"},
+                {"key": "别名", "value": [{"v": "Sousou no Frieren"}, {"v": "Frieren: Beyond Journey's End"}]},
+                {"key": "话数", "value": "28"}
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(
+            subject_title_variants(&subject),
+            vec![
+                "葬送のフリーレン",
+                "葬送的芙莉莲",
+                "Sousou no Frieren",
+                "Frieren: Beyond Journey's End"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_heatmap() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 16).unwrap();
+        let dates = vec![
+            today,
+            today,
+            today - chrono::Duration::days(3),
+            // 超出一年范围，应被忽略
+            today - chrono::Duration::days(400),
+        ];
+
+        let heatmap = build_heatmap("sai", &dates, today);
+        let days: Vec<&HeatmapDay> = heatmap.weeks.iter().flatten().collect();
+
+        assert_eq!(days.len(), HEATMAP_DAYS as usize);
+        assert_eq!(heatmap.total, 3);
+        assert_eq!(heatmap.max_count, 2);
+        assert_eq!(days.last().unwrap().count, 2);
+        assert_eq!(days.last().unwrap().level, 4);
+        assert_eq!(days[days.len() - 4].level, 2);
+        // 除第一周外每周都从周日开始
+        assert!(heatmap.weeks[1..].iter().all(|w| w[0].weekday == 0));
+    }
+}
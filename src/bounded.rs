@@ -0,0 +1,51 @@
+//! 有上限的内存表
+//! 按域名、用户名等外部输入建立条目的缓存与状态表在插入前调用 make_room，避免无限增长
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// 为插入 key 腾出位置：表已满且 key 不在表中时，先去掉 keep 返回 false 的条目
+/// (如已过期的缓存)，仍然已满时整体清空
+pub fn make_room<K, V, Q>(
+    map: &mut HashMap<K, V>,
+    key: &Q,
+    capacity: usize,
+    mut keep: impl FnMut(&V) -> bool,
+) where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + ?Sized,
+{
+    if map.len() < capacity || map.contains_key(key) {
+        return;
+    }
+    map.retain(|_, value| keep(value));
+    if map.len() >= capacity {
+        map.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_make_room() {
+        let mut map: HashMap<String, u32> = (0..4).map(|i| (format!("k{}", i), i)).collect();
+
+        // 未满或已存在时不清理
+        make_room(&mut map, "k0", 4, |_| false);
+        assert_eq!(map.len(), 4);
+        make_room(&mut map, "new", 8, |_| false);
+        assert_eq!(map.len(), 4);
+
+        // 先去掉不需要保留的条目
+        make_room(&mut map, "new", 4, |v| *v >= 2);
+        assert_eq!(map.len(), 2);
+        assert!(map.contains_key("k2") && map.contains_key("k3"));
+
+        // 仍然已满时清空
+        make_room(&mut map, "new", 2, |_| true);
+        assert!(map.is_empty());
+    }
+}
@@ -2,7 +2,9 @@
 //! 支持从环境变量读取配置，提供默认值
 
 use once_cell::sync::Lazy;
+use std::collections::HashMap;
 use std::env;
+use std::net::IpAddr;
 
 /// 全局配置
 pub static CONFIG: Lazy<Config> = Lazy::new(Config::from_env);
@@ -22,10 +24,25 @@ pub struct Config {
     /// HTTP User-Agent
     pub user_agent: String,
 
-    /// 反代前缀 (用于网络问题时重试)
+    /// 反代前缀 (用于网络问题时重试)，可以是含 {url} / {url_encoded} / {host} / {path} 占位符的模板
     pub proxy_prefix: String,
 
-    /// GitHub 代理前缀 (用于 GitHub 资源加速)
+    /// 出站代理 (socks5:// 或 http://，为空时直连)
+    pub outbound_proxy: String,
+
+    /// 出站代理适用范围：all (所有请求) 或 magic (只有 magic 或 outboundProxy 规则)
+    pub outbound_proxy_scope: String,
+
+    /// 静态 DNS 映射 (域名 → IP，优先于其它解析方式)
+    pub dns_hosts: HashMap<String, Vec<IpAddr>>,
+
+    /// DNS-over-HTTPS 服务地址 (JSON 格式，为空时使用系统解析)
+    pub doh_url: String,
+
+    /// 不校验证书的站点域名 (含子域名，其余请求均校验证书)
+    pub insecure_tls_hosts: Vec<String>,
+
+    /// GitHub 代理前缀 (用于 GitHub 资源加速)，同样支持模板占位符
     pub github_proxy: String,
 
     /// Bangumi API 地址
@@ -39,6 +56,107 @@ pub struct Config {
 
     /// 规则仓库分支
     pub rules_branch: String,
+
+    /// 固定使用的规则版本 (commit SHA 或 tag，为空时跟随分支最新提交)
+    pub rules_commit: String,
+
+    /// 规则文件下载地址前缀 (为空时使用 raw.githubusercontent.com，支持 {repo}/{branch} 占位符)
+    pub rules_raw_base: String,
+
+    /// 规则目录变动的合并时间 (毫秒，文件变动时热重载；0 = 不监视)
+    pub rules_watch_debounce_ms: u64,
+
+    /// GitHub 访问令牌 (为空时匿名请求，每小时 60 次限额)
+    pub github_token: String,
+
+    /// 规则定时更新间隔 (小时，0 = 不定时更新)
+    pub update_interval_hours: u64,
+
+    /// 规则健康检查间隔 (分钟，0 = 不定时检查)
+    pub health_check_interval_mins: u64,
+
+    /// 健康检查使用的探测关键词
+    pub health_check_keyword: String,
+
+    /// 健康检查并发数
+    pub health_check_concurrency: usize,
+
+    /// 连续多少次健康检查失败后隔离规则 (0 = 不隔离)
+    pub health_quarantine_threshold: u32,
+
+    /// 管理接口令牌 (为空时禁用 /admin/*)
+    pub admin_token: String,
+
+    /// GitHub Webhook 密钥 (为空时禁用 /webhooks/github)
+    pub github_webhook_secret: String,
+
+    /// 缓存数据目录
+    pub cache_dir: String,
+
+    /// HTTP 响应缓存: off (关闭) / memory (仅内存) / disk (内存 + 磁盘)
+    pub http_cache: String,
+
+    /// HTTP 页面缓存上限 (MB，0 = 不限制)
+    pub http_cache_max_mb: u64,
+
+    /// 图片缓存上限 (MB，0 = 不限制)
+    pub image_cache_max_mb: u64,
+
+    /// 图片缓存有效期 (秒)
+    pub image_cache_ttl_secs: u64,
+
+    /// 规则快照上限 (MB，0 = 不限制)
+    pub rule_snapshot_max_mb: u64,
+
+    /// 任务结果上限 (MB，0 = 不限制)
+    pub job_results_max_mb: u64,
+
+    /// 存储清理间隔 (秒)
+    pub storage_janitor_interval_secs: u64,
+
+    /// 每次搜索最多为多少个结果获取集数 (0 = 不限制)
+    pub max_episode_items: usize,
+
+    /// 搜索请求默认最多尝试次数 (1 = 不重试)
+    pub retry_attempts: u32,
+
+    /// 重试退避基准时间/毫秒 (每次重试翻倍)
+    pub retry_backoff_ms: u64,
+
+    /// 重试退避随机抖动上限/毫秒
+    pub retry_jitter_ms: u64,
+
+    /// 抓取页面的响应体上限 (MB，超出部分截断；0 = 不限制)
+    pub max_response_mb: usize,
+
+    /// 同一域名连续失败多少次后熔断 (0 = 不熔断)
+    pub circuit_failure_threshold: u32,
+
+    /// 熔断冷却时间 (秒)
+    pub circuit_cooldown_secs: u64,
+
+    /// 同一域名同时进行的请求数上限，也是保留的空闲连接数上限 (0 = 不限制)
+    pub host_max_concurrency: usize,
+
+    /// 单次请求最多跟随的重定向次数
+    pub max_redirects: usize,
+
+    /// 只跟随同一域名内的重定向
+    pub redirect_same_host: bool,
+
+    /// FlareSolverr 服务地址 (为空时不处理 Cloudflare 验证)
+    pub flaresolverr_url: String,
+
+    /// FlareSolverr 单次求解超时 (秒)
+    pub flaresolverr_timeout_secs: u64,
+
+    /// Chromium 可执行文件路径 (webview 特性，为空时自动查找)
+    #[cfg_attr(not(feature = "webview"), allow(dead_code))]
+    pub chrome_path: String,
+
+    /// 页面加载后等待脚本渲染的时间 (毫秒，webview 特性)
+    #[cfg_attr(not(feature = "webview"), allow(dead_code))]
+    pub webview_wait_ms: u64,
 }
 
 impl Config {
@@ -67,6 +185,26 @@ impl Config {
             proxy_prefix: env::var("PROXY_PREFIX")
                 .unwrap_or_else(|_| "https://rp.30hb.cn/?target=".to_string()),
 
+            outbound_proxy: env::var("OUTBOUND_PROXY").unwrap_or_default(),
+
+            outbound_proxy_scope: env::var("OUTBOUND_PROXY_SCOPE")
+                .unwrap_or_else(|_| "all".to_string()),
+
+            dns_hosts: env::var("DNS_HOSTS")
+                .map(|v| parse_dns_hosts(&v))
+                .unwrap_or_default(),
+
+            doh_url: env::var("DOH_URL").unwrap_or_default(),
+
+            insecure_tls_hosts: env::var("INSECURE_TLS_HOSTS")
+                .map(|v| {
+                    v.split(',')
+                        .map(|host| host.trim().trim_start_matches("*.").to_ascii_lowercase())
+                        .filter(|host| !host.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+
             github_proxy: env::var("GITHUB_PROXY")
                 .unwrap_or_else(|_| "https://gh-proxy.com/".to_string()),
 
@@ -81,32 +219,217 @@ impl Config {
 
             rules_branch: env::var("RULES_BRANCH")
                 .unwrap_or_else(|_| "main".to_string()),
+
+            rules_commit: env::var("RULES_COMMIT").unwrap_or_default(),
+
+            rules_raw_base: env::var("RULES_RAW_BASE").unwrap_or_default(),
+
+            rules_watch_debounce_ms: env::var("RULES_WATCH_DEBOUNCE_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(500),
+
+            github_token: env::var("GITHUB_TOKEN").unwrap_or_default(),
+
+            update_interval_hours: env::var("UPDATE_INTERVAL_HOURS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0),
+
+            health_check_interval_mins: env::var("HEALTH_CHECK_INTERVAL_MINS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0),
+
+            health_check_keyword: env::var("HEALTH_CHECK_KEYWORD")
+                .unwrap_or_else(|_| "海贼王".to_string()),
+
+            health_check_concurrency: env::var("HEALTH_CHECK_CONCURRENCY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(4),
+
+            health_quarantine_threshold: env::var("HEALTH_QUARANTINE_THRESHOLD")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3),
+
+            admin_token: env::var("ADMIN_TOKEN").unwrap_or_default(),
+
+            github_webhook_secret: env::var("GITHUB_WEBHOOK_SECRET").unwrap_or_default(),
+
+            cache_dir: env::var("CACHE_DIR")
+                .unwrap_or_else(|_| "cache".to_string()),
+
+            http_cache: env::var("HTTP_CACHE")
+                .unwrap_or_else(|_| "memory".to_string()),
+
+            http_cache_max_mb: env::var("HTTP_CACHE_MAX_MB")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(200),
+
+            image_cache_max_mb: env::var("IMAGE_CACHE_MAX_MB")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(500),
+
+            image_cache_ttl_secs: env::var("IMAGE_CACHE_TTL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(7 * 24 * 3600),
+
+            rule_snapshot_max_mb: env::var("RULE_SNAPSHOT_MAX_MB")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(50),
+
+            job_results_max_mb: env::var("JOB_RESULTS_MAX_MB")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(50),
+
+            storage_janitor_interval_secs: env::var("STORAGE_JANITOR_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(600),
+
+            max_episode_items: env::var("MAX_EPISODE_ITEMS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
+
+            retry_attempts: env::var("RETRY_ATTEMPTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(2),
+
+            retry_backoff_ms: env::var("RETRY_BACKOFF_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(500),
+
+            retry_jitter_ms: env::var("RETRY_JITTER_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(250),
+
+            max_response_mb: env::var("MAX_RESPONSE_MB")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
+
+            circuit_failure_threshold: env::var("CIRCUIT_FAILURE_THRESHOLD")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
+
+            circuit_cooldown_secs: env::var("CIRCUIT_COOLDOWN_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60),
+
+            host_max_concurrency: env::var("HOST_MAX_CONCURRENCY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(4),
+
+            max_redirects: env::var("MAX_REDIRECTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10),
+
+            redirect_same_host: env::var("REDIRECT_SAME_HOST").is_ok_and(|v| v == "1"),
+
+            flaresolverr_url: env::var("FLARESOLVERR_URL").unwrap_or_default(),
+
+            flaresolverr_timeout_secs: env::var("FLARESOLVERR_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60),
+
+            chrome_path: env::var("CHROME_PATH").unwrap_or_default(),
+
+            webview_wait_ms: env::var("WEBVIEW_WAIT_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1500),
+        }
+    }
+
+    /// 规则更新跟随的版本：固定的 RULES_COMMIT，未设置时为分支
+    pub fn rules_ref(&self) -> &str {
+        if self.rules_commit.is_empty() {
+            &self.rules_branch
+        } else {
+            &self.rules_commit
         }
     }
 
-    /// GitHub API: 获取 commit
-    pub fn github_api_commits(&self) -> String {
+    /// GitHub API: 获取指定版本 (分支、tag 或 SHA) 的 commit
+    pub fn github_api_commits(&self, git_ref: &str) -> String {
         format!(
             "https://api.github.com/repos/{}/commits/{}",
-            self.rules_repo, self.rules_branch
+            self.rules_repo, git_ref
         )
     }
 
-    /// GitHub API: 获取仓库内容
-    pub fn github_api_contents(&self) -> String {
+    /// GitHub API: 比较两个 commit
+    pub fn github_api_compare(&self, base: &str, head: &str) -> String {
         format!(
-            "https://api.github.com/repos/{}/contents",
-            self.rules_repo
+            "https://api.github.com/repos/{}/compare/{}...{}",
+            self.rules_repo, base, head
         )
     }
 
-    /// GitHub Raw: 规则文件基础 URL
-    pub fn github_raw_base(&self) -> String {
+    /// GitHub API: 获取指定版本的仓库内容
+    pub fn github_api_contents(&self, git_ref: &str) -> String {
         format!(
-            "https://raw.githubusercontent.com/{}/{}/",
-            self.rules_repo, self.rules_branch
+            "https://api.github.com/repos/{}/contents?ref={}",
+            self.rules_repo, git_ref
         )
     }
+
+    /// 指定版本的规则文件基础 URL (配置了 RULES_RAW_BASE 时使用镜像，否则为 GitHub Raw)
+    pub fn rules_raw_base(&self, git_ref: &str) -> String {
+        if !self.has_raw_mirror() {
+            return format!(
+                "https://raw.githubusercontent.com/{}/{}/",
+                self.rules_repo, git_ref
+            );
+        }
+
+        let base = self
+            .rules_raw_base
+            .replace("{repo}", &self.rules_repo)
+            .replace("{branch}", git_ref);
+        if base.ends_with('/') {
+            base
+        } else {
+            format!("{}/", base)
+        }
+    }
+
+    /// 是否配置了规则文件镜像
+    pub fn has_raw_mirror(&self) -> bool {
+        !self.rules_raw_base.is_empty()
+    }
+}
+
+/// 解析静态 DNS 映射：`域名=IP[|IP...]`，多条以逗号分隔 (无效的条目会被忽略)
+fn parse_dns_hosts(value: &str) -> HashMap<String, Vec<IpAddr>> {
+    let mut hosts = HashMap::new();
+    for entry in value.split(',') {
+        let Some((host, ips)) = entry.split_once('=') else {
+            continue;
+        };
+        let ips: Vec<IpAddr> = ips.split('|').filter_map(|ip| ip.trim().parse().ok()).collect();
+        let host = host.trim().to_ascii_lowercase();
+        if !host.is_empty() && !ips.is_empty() {
+            hosts.insert(host, ips);
+        }
+    }
+    hosts
 }
 
 impl Default for Config {
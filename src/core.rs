@@ -1,25 +1,40 @@
 //! 核心搜索逻辑
 //! 处理并发搜索和 SSE 流式响应
 
-use crate::engine::search_with_rule;
-use crate::types::{Rule, StreamEvent, StreamProgress, StreamResult};
-use futures::stream::Stream;
+use crate::bangumi;
+use crate::engine::{search_with_keywords, OnPartial};
+use crate::stats;
+use crate::types::{
+    PlatformSearchResult, Rule, SearchOptions, SearchResultItem, StreamEvent, StreamProgress,
+    StreamResult,
+};
+use futures::future::join_all;
+use futures::stream::{FuturesUnordered, Stream, StreamExt};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::mpsc;
+use tokio::task::JoinSet;
 use tokio_stream::wrappers::ReceiverStream;
-use tracing::{debug, info};
+use tracing::{debug, info, warn, Instrument};
+
+/// 别名扩展时最多使用的关键词数量 (含原关键词)
+const MAX_KEYWORD_VARIANTS: usize = 5;
 
 /// 使用指定规则执行流式搜索
 pub fn search_stream_with_rules(
     keyword: String,
     rules: Vec<Arc<Rule>>,
+    options: SearchOptions,
 ) -> impl Stream<Item = String> {
     let (tx, rx) = mpsc::channel::<String>(100);
 
-    tokio::spawn(async move {
-        execute_parallel_search(keyword, rules, tx).await;
-    });
+    tokio::spawn(
+        async move {
+            execute_parallel_search(keyword, rules, options, tx).await;
+        }
+        .in_current_span(),
+    );
 
     ReceiverStream::new(rx)
 }
@@ -28,29 +43,52 @@ pub fn search_stream_with_rules(
 async fn execute_parallel_search(
     keyword: String,
     rules: Vec<Arc<Rule>>,
+    options: SearchOptions,
     tx: mpsc::Sender<String>,
 ) {
     let total = rules.len();
     let completed = Arc::new(AtomicUsize::new(0));
+    // 快速模式下已返回非空结果的规则数
+    let hits = Arc::new(AtomicUsize::new(0));
+    let first = options.first.filter(|n| *n > 0);
 
     info!("开始搜索: {}, 共 {} 个规则", keyword, total);
 
+    let keywords = Arc::new(resolve_keywords(&keyword, &options).await);
+
     // 发送初始事件
-    let init_event = StreamEvent::Init { total };
+    let init_event = StreamEvent::Init {
+        total,
+        keywords: options.expand_aliases.then(|| keywords.to_vec()),
+    };
     if tx.send(format_event(&init_event)).await.is_err() {
         return;
     }
 
     // 并行搜索所有平台
-    let mut handles = Vec::new();
+    let mut tasks = JoinSet::new();
 
+    let options = Arc::new(options);
     for rule in rules {
-        let keyword = keyword.clone();
+        let keywords = keywords.clone();
+        let options = options.clone();
         let tx = tx.clone();
         let completed = completed.clone();
+        let hits = hits.clone();
+
+        let task = async move {
+            // 阶段性结果：通道已满时直接丢弃，最终结果仍会完整发送
+            let partial_tx = tx.clone();
+            let partial_rule = rule.clone();
+            let on_partial = move |items: &[SearchResultItem]| {
+                let partial = to_stream_result(
+                    &partial_rule,
+                    PlatformSearchResult::with_items(items.to_vec()),
+                );
+                let _ = partial_tx.try_send(format_event(&StreamEvent::Partial { partial }));
+            };
 
-        let handle = tokio::spawn(async move {
-            let result = search_with_rule(&rule, &keyword).await;
+            let result = search_tracked(&rule, &keywords, &options, Some(&on_partial)).await;
             let current = completed.fetch_add(1, Ordering::SeqCst) + 1;
 
             let progress = StreamProgress {
@@ -60,36 +98,42 @@ async fn execute_parallel_search(
 
             debug!("规则 {} 搜索完成: {} 个结果", rule.name, result.count);
 
+            // 快速模式：名额已满时 (其他规则同时完成) 丢弃多余的结果
+            let hit = result.count > 0;
+            if let (Some(n), true) = (first, hit) {
+                if hits.fetch_add(1, Ordering::SeqCst) >= n {
+                    return false;
+                }
+            }
+
             // 只有有结果或有错误时才发送结果
             let event = if result.count > 0 || result.error.is_some() {
-                let stream_result = StreamResult {
-                    name: rule.name.clone(),
-                    color: if result.error.is_some() {
-                        "red".to_string()
-                    } else {
-                        rule.color.clone()
-                    },
-                    tags: rule.tags.clone(),
-                    items: result.items,
-                    error: result.error,
-                };
                 StreamEvent::Result {
                     progress,
-                    result: stream_result,
+                    result: to_stream_result(&rule, result),
                 }
             } else {
                 StreamEvent::Progress { progress }
             };
 
             let _ = tx.send(format_event(&event)).await;
-        });
+            hit
+        };
 
-        handles.push(handle);
+        tasks.spawn(task.in_current_span());
     }
 
-    // 等待所有搜索完成
-    for handle in handles {
-        let _ = handle.await;
+    // 等待所有搜索完成 (快速模式下凑够 N 个有结果的规则后取消其余搜索)
+    let mut found = 0;
+    while let Some(joined) = tasks.join_next().await {
+        if matches!(joined, Ok(true)) {
+            found += 1;
+        }
+        if first.is_some_and(|n| found >= n) {
+            info!("快速模式: 已获得 {} 个有结果的规则，取消剩余 {} 个", found, tasks.len());
+            tasks.abort_all();
+            break;
+        }
     }
 
     // 发送完成信号
@@ -99,6 +143,101 @@ async fn execute_parallel_search(
     info!("搜索完成: {}", keyword);
 }
 
+/// 并行搜索并一次性返回所有规则的结果 (非流式，供 GraphQL 等使用；按传入的规则顺序返回)
+pub async fn search_collect(
+    keyword: &str,
+    rules: Vec<Arc<Rule>>,
+    options: &SearchOptions,
+) -> Vec<StreamResult> {
+    let keywords = resolve_keywords(keyword, options).await;
+    let tasks = rules.into_iter().map(|rule| {
+        let keywords = &keywords;
+        async move {
+            let result = search_tracked(&rule, keywords, options, None).await;
+            to_stream_result(&rule, result)
+        }
+    });
+
+    let Some(first) = options.first.filter(|n| *n > 0) else {
+        return join_all(tasks).await;
+    };
+
+    // 快速模式：按完成顺序收集，凑够 N 个有结果的规则后丢弃 (取消) 其余搜索
+    let mut pending: FuturesUnordered<_> = tasks.collect();
+    let mut results = Vec::new();
+    while let Some(result) = pending.next().await {
+        if !result.items.is_empty() {
+            results.push(result);
+            if results.len() >= first {
+                break;
+            }
+        }
+    }
+    // 与非快速模式一致，按优先级排列
+    results.sort_by_key(|r| std::cmp::Reverse(r.priority));
+    results
+}
+
+/// 搜索单个规则并记录使用统计 (被取消的搜索只计入选用次数)
+async fn search_tracked(
+    rule: &Rule,
+    keywords: &[String],
+    options: &SearchOptions,
+    on_partial: Option<&OnPartial<'_>>,
+) -> PlatformSearchResult {
+    stats::record_selected(&rule.name);
+    let started = Instant::now();
+    let result = search_with_keywords(rule, keywords, options, on_partial).await;
+    stats::record_result(&rule.name, &result, started.elapsed());
+    result
+}
+
+/// 确定实际搜索使用的关键词 (启用别名扩展时追加 Bangumi 标题别名)
+async fn resolve_keywords(keyword: &str, options: &SearchOptions) -> Vec<String> {
+    let mut keywords = vec![keyword.to_string()];
+    if !options.expand_aliases {
+        return keywords;
+    }
+
+    match bangumi::resolve_title_variants(keyword).await {
+        Ok(variants) => {
+            for variant in variants {
+                let variant = variant.trim();
+                if variant.is_empty() || keywords.iter().any(|k| k.eq_ignore_ascii_case(variant)) {
+                    continue;
+                }
+                keywords.push(variant.to_string());
+                if keywords.len() >= MAX_KEYWORD_VARIANTS {
+                    break;
+                }
+            }
+            info!("别名扩展: {} -> {:?}", keyword, keywords);
+        }
+        Err(e) => warn!("解析别名失败 {}: {}", keyword, e),
+    }
+
+    keywords
+}
+
+/// 将单个规则的搜索结果转换为输出格式 (出错时颜色标红)
+fn to_stream_result(rule: &Rule, result: PlatformSearchResult) -> StreamResult {
+    StreamResult {
+        name: rule.name.clone(),
+        color: if result.error.is_some() {
+            "red".to_string()
+        } else {
+            rule.color.clone()
+        },
+        tags: rule.tags.clone(),
+        items: result.items,
+        error: result.error,
+        attempts: result.attempts,
+        priority: crate::rules::priority(&rule.name),
+        diagnostics: result.diagnostics,
+        retry_after: result.retry_after,
+    }
+}
+
 /// 格式化 SSE 事件
 fn format_event(event: &StreamEvent) -> String {
     format!("{}\n", serde_json::to_string(event).unwrap_or_default())
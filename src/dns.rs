@@ -0,0 +1,212 @@
+//! DNS-over-HTTPS 解析
+//! 部分地区规则站点域名被污染时，通过 DoH (JSON 格式) 解析，失败时回退到系统解析
+
+use crate::bounded;
+use crate::config::CONFIG;
+use once_cell::sync::Lazy;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// DoH 查询超时
+const DOH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 解析结果的最短缓存时间 (避免 TTL 过短导致频繁查询)
+const MIN_TTL: Duration = Duration::from_secs(60);
+
+/// DNS 记录类型: A / AAAA
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+
+/// 查询 DoH 使用的客户端 (使用系统解析，DoH 地址建议直接写 IP)
+static DOH_CLIENT: Lazy<Client> = Lazy::new(|| {
+    Client::builder()
+        .timeout(DOH_TIMEOUT)
+        .build()
+        .expect("Failed to create DoH client")
+});
+
+/// 最多缓存的域名数
+const MAX_CACHE_ENTRIES: usize = 1024;
+
+/// 解析到的地址与过期时间
+type CacheEntry = (Vec<IpAddr>, Instant);
+
+/// 解析结果缓存 (按域名)
+static CACHE: Lazy<Mutex<HashMap<String, CacheEntry>>> = Lazy::new(Default::default);
+
+#[derive(Debug, Deserialize)]
+struct DohResponse {
+    #[serde(rename = "Status")]
+    status: u32,
+    #[serde(rename = "Answer", default)]
+    answer: Vec<DohAnswer>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DohAnswer {
+    #[serde(rename = "type")]
+    record_type: u16,
+    #[serde(rename = "TTL", default)]
+    ttl: u64,
+    data: String,
+}
+
+/// 从 DoH 响应中提取地址与最短 TTL (CNAME 等其它记录会被忽略)
+fn parse_answers(response: &DohResponse) -> (Vec<IpAddr>, Duration) {
+    let mut ttl = None;
+    let ips = response
+        .answer
+        .iter()
+        .filter(|a| matches!(a.record_type, TYPE_A | TYPE_AAAA))
+        .filter_map(|a| {
+            let ip = a.data.parse().ok()?;
+            ttl = Some(ttl.map_or(a.ttl, |t: u64| t.min(a.ttl)));
+            Some(ip)
+        })
+        .collect();
+    (ips, Duration::from_secs(ttl.unwrap_or(0)).max(MIN_TTL))
+}
+
+/// 查询单一类型的记录
+async fn query(host: &str, record_type: &str) -> anyhow::Result<(Vec<IpAddr>, Duration)> {
+    let mut url = url::Url::parse(&CONFIG.doh_url)?;
+    url.query_pairs_mut()
+        .append_pair("name", host)
+        .append_pair("type", record_type);
+    let response: DohResponse = DOH_CLIENT
+        .get(url)
+        .header("Accept", "application/dns-json")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    if response.status != 0 {
+        anyhow::bail!("DoH 返回错误码 {}", response.status);
+    }
+    Ok(parse_answers(&response))
+}
+
+/// 放入缓存 (已满时优先清除过期的记录)
+fn remember(cache: &mut HashMap<String, CacheEntry>, host: &str, ips: &[IpAddr], now: Instant, ttl: Duration) {
+    bounded::make_room(cache, host, MAX_CACHE_ENTRIES, |(_, expires_at)| *expires_at > now);
+    cache.insert(host.to_string(), (ips.to_vec(), now + ttl));
+}
+
+/// 通过 DoH 解析域名 (优先使用缓存)
+async fn resolve_doh(host: &str) -> anyhow::Result<Vec<IpAddr>> {
+    if let Some((ips, expires_at)) = CACHE.lock().unwrap_or_else(|e| e.into_inner()).get(host) {
+        if *expires_at > Instant::now() {
+            return Ok(ips.clone());
+        }
+    }
+
+    let (v4, v6) = tokio::join!(query(host, "A"), query(host, "AAAA"));
+    let mut ips = Vec::new();
+    let mut ttl = None;
+    for (found, found_ttl) in [v4, v6].into_iter().flatten() {
+        if !found.is_empty() {
+            ttl = Some(ttl.map_or(found_ttl, |t: Duration| t.min(found_ttl)));
+            ips.extend(found);
+        }
+    }
+    let Some(ttl) = ttl else {
+        anyhow::bail!("DoH 未返回 {} 的地址", host);
+    };
+
+    remember(&mut CACHE.lock().unwrap_or_else(|e| e.into_inner()), host, &ips, Instant::now(), ttl);
+    Ok(ips)
+}
+
+/// 按客户端相同的顺序解析域名 (DNS_HOSTS → DoH → 系统解析)，用于连接前检查目标地址
+pub async fn lookup(host: &str) -> std::io::Result<Vec<IpAddr>> {
+    let host = host.to_ascii_lowercase();
+    if let Some(ips) = CONFIG.dns_hosts.get(&host) {
+        return Ok(ips.clone());
+    }
+    if !CONFIG.doh_url.is_empty() {
+        match resolve_doh(&host).await {
+            Ok(ips) => return Ok(ips),
+            Err(e) => warn!("DoH 解析 {} 失败，使用系统解析: {}", host, e),
+        }
+    }
+    let addrs = tokio::net::lookup_host((host.as_str(), 0)).await?;
+    Ok(addrs.map(|addr| addr.ip()).collect())
+}
+
+/// reqwest 使用的解析器：DoH 优先，失败时回退到系统解析
+/// (DNS_HOSTS 静态映射由 reqwest 在调用解析器之前处理)
+#[derive(Debug, Default)]
+pub struct DohResolver;
+
+impl Resolve for DohResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str().to_ascii_lowercase();
+            let addrs: Vec<SocketAddr> = match resolve_doh(&host).await {
+                Ok(ips) => ips.into_iter().map(|ip| SocketAddr::new(ip, 0)).collect(),
+                Err(e) => {
+                    warn!("DoH 解析 {} 失败，使用系统解析: {}", host, e);
+                    tokio::net::lookup_host((host.as_str(), 0)).await?.collect()
+                }
+            };
+            Ok::<Addrs, Box<dyn std::error::Error + Send + Sync>>(Box::new(addrs.into_iter()))
+        })
+    }
+}
+
+/// 配置了 DOH_URL 时返回解析器
+pub fn resolver() -> Option<Arc<DohResolver>> {
+    (!CONFIG.doh_url.is_empty()).then(|| Arc::new(DohResolver))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_answers() {
+        let response: DohResponse = serde_json::from_str(
+            r#"{"Status":0,"Answer":[
+                {"name":"example.com","type":5,"TTL":30,"data":"cdn.example.net."},
+                {"name":"cdn.example.net","type":1,"TTL":300,"data":"93.184.216.34"},
+                {"name":"cdn.example.net","type":1,"TTL":120,"data":"93.184.216.35"}
+            ]}"#,
+        )
+        .unwrap();
+        let (ips, ttl) = parse_answers(&response);
+        assert_eq!(
+            ips,
+            vec![
+                "93.184.216.34".parse::<IpAddr>().unwrap(),
+                "93.184.216.35".parse().unwrap()
+            ]
+        );
+        assert_eq!(ttl, Duration::from_secs(120));
+
+        let empty: DohResponse = serde_json::from_str(r#"{"Status":3}"#).unwrap();
+        let (ips, ttl) = parse_answers(&empty);
+        assert!(ips.is_empty());
+        assert_eq!(ttl, MIN_TTL);
+    }
+
+    #[test]
+    fn test_remember_bounded() {
+        let mut cache = HashMap::new();
+        let now = Instant::now();
+        let ips = ["127.0.0.1".parse::<IpAddr>().unwrap()];
+        for i in 0..MAX_CACHE_ENTRIES {
+            remember(&mut cache, &format!("h{}.example.com", i), &ips, now, Duration::ZERO);
+        }
+        remember(&mut cache, "fresh.example.com", &ips, now, MIN_TTL);
+        // 已过期的记录被清除，新记录保留
+        assert_eq!(cache.len(), 1);
+        assert!(cache.contains_key("fresh.example.com"));
+    }
+}
@@ -2,59 +2,281 @@
 //! 完全兼容 Kazumi 规则格式: https://github.com/Predidit/Kazumi
 //! 使用纯 Rust 库 (scraper) 进行 HTML 解析，通过 XPath→CSS 转换支持规则
 
-use crate::http_client::{get_text, post_form_text};
-use crate::types::{Episode, EpisodeRoad, PlatformSearchResult, Rule, SearchResultItem};
-use crate::xpath_to_css::{xpath_to_css, PositionFilter};
+use crate::config::CONFIG;
+use crate::http_client::{get_page, HttpClientError, Page, RequestConfig};
+use crate::http_service::{self, PageRequest, RetryPolicy};
+use crate::script;
+use crate::title;
+use crate::types::{
+    DebugMode, Episode, EpisodeRoad, ParseDiagnostics, PlatformSearchResult, PlaySource,
+    ProcessStep, RoadSelector, Rule, SearchOptions, SearchResultItem, StageCount, RULE_TYPE_JSON,
+};
+use crate::xpath_eval;
+use crate::xpath_to_css::{
+    attribute_step, xpath_to_css, xpath_to_css_with, AxisStep, CssSelector, PositionFilter,
+    PositionMode,
+};
+use base64::Engine as _;
+use regex::Regex;
 use scraper::{Html, Selector, ElementRef};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, LazyLock, RwLock};
+use std::time::Duration;
+use tower::ServiceExt;
 use tracing::{debug, warn};
 
+/// 单次搜索最多抓取的结果页数
+const MAX_SEARCH_PAGES: usize = 5;
+
+/// 规则最多允许的搜索请求尝试次数
+const MAX_RETRY_ATTEMPTS: u32 = 5;
+
+/// 选择器调试返回的样本数量上限
+const DEBUG_SAMPLE_LIMIT: usize = 20;
+
+/// 诊断信息中 HTML 快照的最大字节数
+const DEBUG_SNAPSHOT_BYTES: usize = 64 * 1024;
+
+/// 常见 CMS 播放器配置: var player_aaaa = {...};
+static RE_PLAYER_CONFIG: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?s)player_[A-Za-z0-9_]+\s*=\s*(\{.*?\})\s*(?:;|</script>)").unwrap()
+});
+
+/// 内联脚本中的媒体地址 (兼容 JSON 转义的 \/)
+static RE_MEDIA_URL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"https?:(?:\\?/){2}[^"'\s<>]+?\.(?:m3u8|mp4|flv)(?:\?[^"'\s<>]*)?"#).unwrap()
+});
+
+/// 页面中的磁力链接 (文本、input、脚本中)
+static RE_MAGNET: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"magnet:\?xt=urn:bt[im]h:[A-Za-z0-9]+[^"'\s<>]*"#).unwrap()
+});
+
+/// 未配置 searchMagnet 时自动查找的链接
+static MAGNET_SELECTOR: LazyLock<Selector> = LazyLock::new(|| {
+    Selector::parse(r#"a[href^="magnet:"], a[href$=".torrent"], a[href*=".torrent?"]"#).unwrap()
+});
+
+/// 页面中的 `<base href>`，存在时相对链接以它为基准解析
+static BASE_SELECTOR: LazyLock<Selector> = LazyLock::new(|| Selector::parse("base[href]").unwrap());
+
+/// 规则完成前推送阶段性结果的回调 (参数为目前已解析的全部结果)
+pub type OnPartial<'a> = dyn Fn(&[SearchResultItem]) + Send + Sync + 'a;
+
+/// 获取集数时每完成多少个结果推送一次阶段性结果
+const PARTIAL_BATCH_SIZE: usize = 10;
+
 /// 使用规则搜索动漫 (自动获取集数信息)
-pub async fn search_with_rule(rule: &Rule, keyword: &str) -> PlatformSearchResult {
-    match execute_search(rule, keyword).await {
+pub async fn search_with_rule(
+    rule: &Rule,
+    keyword: &str,
+    options: &SearchOptions,
+    on_partial: Option<&OnPartial<'_>>,
+) -> PlatformSearchResult {
+    let mut trace = SearchTrace::default();
+    let mut result = match execute_search(rule, keyword, options, on_partial, &mut trace).await {
         Ok(items) => PlatformSearchResult::with_items(items),
         Err(e) => {
             warn!("规则 {} 搜索失败: {}", rule.name, e);
-            PlatformSearchResult::with_error(e.to_string())
+            let mut result = PlatformSearchResult::with_error(e.to_string());
+            result.retry_after = rate_limit_secs(&e);
+            result
         }
+    };
+    if result.items.is_empty() {
+        result.diagnostics = trace.diagnostics;
     }
+    result.with_attempts(trace.attempts.max(1))
 }
 
-async fn execute_search(rule: &Rule, keyword: &str) -> anyhow::Result<Vec<SearchResultItem>> {
-    // 构建搜索 URL
-    let search_url = rule.search_url.replace("@keyword", &urlencoding::encode(keyword));
-    debug!("搜索 URL: {}", search_url);
+/// 错误由站点限流引起时返回建议的等待时间/秒
+fn rate_limit_secs(error: &anyhow::Error) -> Option<u64> {
+    error.chain().find_map(|e| match e.downcast_ref::<HttpClientError>() {
+        Some(HttpClientError::RateLimited(wait)) => Some(wait.as_secs().max(1)),
+        _ => None,
+    })
+}
 
-    // 发送请求
-    let html = if rule.use_post {
-        // POST 请求
-        let uri = url::Url::parse(&search_url)?;
-        let query_params: std::collections::HashMap<String, String> = uri
-            .query_pairs()
-            .map(|(k, v)| (k.to_string(), v.to_string()))
-            .collect();
-        let base_url = format!("{}://{}{}", uri.scheme(), uri.host_str().unwrap_or(""), uri.path());
-        post_form_text(&base_url, &query_params, Some(&rule.base_url)).await?
+/// 单次搜索过程中记录的附加信息
+#[derive(Default)]
+struct SearchTrace {
+    /// 第一页的请求尝试次数
+    attempts: u32,
+    /// 第一页的解析诊断 (仅 debug 模式)
+    diagnostics: Option<ParseDiagnostics>,
+}
+
+/// 使用多个关键词 (如标题别名) 搜索，合并结果并按链接去重
+/// 仅当所有关键词都失败时才返回错误
+pub async fn search_with_keywords(
+    rule: &Rule,
+    keywords: &[String],
+    options: &SearchOptions,
+    on_partial: Option<&OnPartial<'_>>,
+) -> PlatformSearchResult {
+    if let [keyword] = keywords {
+        return search_with_rule(rule, keyword, options, on_partial).await;
+    }
+
+    let mut items: Vec<SearchResultItem> = Vec::new();
+    let mut first_error = None;
+    let mut retry_after = None;
+    let mut succeeded = false;
+    let mut attempts = 1;
+    let mut diagnostics = None;
+
+    for (index, keyword) in keywords.iter().enumerate() {
+        // 阶段性结果需要带上之前关键词已得到的结果
+        let merged_partial = on_partial.map(|callback| {
+            let previous = items.clone();
+            move |partial: &[SearchResultItem]| {
+                let mut merged = previous.clone();
+                merge_items(&mut merged, partial.iter().cloned());
+                callback(&merged);
+            }
+        });
+        let merged_partial = merged_partial.as_ref().map(|f| f as &OnPartial<'_>);
+
+        let result = search_with_rule(rule, keyword, options, merged_partial).await;
+        attempts = attempts.max(result.attempts);
+        if diagnostics.is_none() {
+            diagnostics = result.diagnostics;
+        }
+        match result.error {
+            Some(error) => {
+                if first_error.is_none() {
+                    retry_after = result.retry_after;
+                }
+                first_error.get_or_insert(error);
+            }
+            None => {
+                succeeded = true;
+                merge_items(&mut items, result.items);
+            }
+        }
+
+        if let Some(callback) = on_partial {
+            if index + 1 < keywords.len() && !items.is_empty() {
+                callback(&items);
+            }
+        }
+    }
+
+    let mut result = match first_error {
+        Some(error) if !succeeded => {
+            let mut result = PlatformSearchResult::with_error(error);
+            result.retry_after = retry_after;
+            result
+        }
+        _ => PlatformSearchResult::with_items(items),
+    };
+    if result.items.is_empty() {
+        result.diagnostics = diagnostics;
+    }
+    result.with_attempts(attempts)
+}
+
+/// 追加结果并按链接去重，返回新增数量
+fn merge_items(items: &mut Vec<SearchResultItem>, new_items: impl IntoIterator<Item = SearchResultItem>) -> usize {
+    let before = items.len();
+    for item in new_items {
+        if !items.iter().any(|existing| existing.url == item.url) {
+            items.push(item);
+        }
+    }
+    items.len() - before
+}
+
+async fn execute_search(
+    rule: &Rule,
+    keyword: &str,
+    options: &SearchOptions,
+    on_partial: Option<&OnPartial<'_>>,
+    trace: &mut SearchTrace,
+) -> anyhow::Result<Vec<SearchResultItem>> {
+    // 只有 searchURL 含 @page 占位符时才支持翻页
+    let pages = if rule.search_url.contains("@page") {
+        options.pages.clamp(1, MAX_SEARCH_PAGES)
     } else {
-        // GET 请求
-        get_text(&search_url, Some(&rule.base_url)).await?
+        1
     };
+    let fetch_episodes_enabled = options.fetch_episodes && has_episode_selectors(rule);
+    let timeout = request_timeout(rule, options.timeout);
+
+    let mut items: Vec<SearchResultItem> = Vec::new();
+    for page in 1..=pages {
+        let (search_page, page_attempts) = fetch_search_page(rule, keyword, page, timeout, true).await;
+        // 以第一页 (决定搜索成败) 的尝试次数为准
+        if page == 1 {
+            trace.attempts = page_attempts;
+        }
+        let search_page = match search_page {
+            Ok(search_page) => search_page,
+            // 第一页失败视为搜索失败，后续页失败则保留已有结果
+            Err(e) if page == 1 => return Err(e),
+            Err(e) => {
+                debug!("规则 {} 第 {} 页获取失败: {}", rule.name, page, e);
+                break;
+            }
+        };
+
+        // 解析 HTML 并提取结果
+        let parsed = parse_search_results(rule, &search_page.body);
+        // debug 模式下第一页无结果 (或解析失败) 时记录诊断信息
+        let has_items = parsed.as_ref().is_ok_and(|items| !items.is_empty());
+        if page == 1 && options.debug.enabled() && !has_items {
+            trace.diagnostics = Some(diagnose_search(rule, &search_page, options.debug));
+        }
+        let mut page_items = parsed?;
+
+        // 站点已跳转到新域名时，记录结果在实际站点上的地址
+        for item in page_items.iter_mut() {
+            if let Some(resolved) = rebase_url(&item.url, &rule.base_url, &search_page.final_url) {
+                set_resolved_url(item, resolved);
+            }
+        }
+
+        // 没有新结果说明已到最后一页 (部分站点越界时返回第一页内容)
+        if merge_items(&mut items, page_items) == 0 {
+            break;
+        }
+
+        // 还有后续页或集数要获取时，先推送已解析的结果
+        if let Some(callback) = on_partial {
+            if page < pages || fetch_episodes_enabled {
+                callback(&items);
+            }
+        }
+    }
 
-    // 解析 HTML 并提取结果
-    let mut items = parse_search_results(rule, &html)?;
-    
     debug!("规则 {} 找到 {} 个结果", rule.name, items.len());
 
-    // 如果规则有章节选择器，获取每个结果的章节信息
-    if !rule.chapter_roads.is_empty() && !rule.chapter_result.is_empty() {
-        for item in items.iter_mut() {
-            match fetch_episodes(rule, &item.url).await {
-                Ok(episodes) => {
-                    if !episodes.is_empty() {
-                        item.episodes = Some(episodes);
+    // 如果规则有章节选择器，获取前 N 个结果的章节信息
+    if fetch_episodes_enabled {
+        let limit = episode_item_limit(options).min(items.len());
+        for start in (0..limit).step_by(PARTIAL_BATCH_SIZE) {
+            let end = (start + PARTIAL_BATCH_SIZE).min(limit);
+            for item in items[start..end].iter_mut() {
+                let detail_url = item.resolved_url.clone().unwrap_or_else(|| item.url.clone());
+                match fetch_detail(rule, &detail_url, None, DebugMode::Off, timeout).await {
+                    Ok(detail) => {
+                        if !detail.roads.is_empty() {
+                            item.episodes = Some(detail.roads);
+                        }
+                        merge_magnets(item, detail.magnets);
+                        set_resolved_url(item, detail.final_url);
+                    }
+                    Err(e) => {
+                        debug!("获取章节失败 {}: {}", item.url, e);
                     }
                 }
-                Err(e) => {
-                    debug!("获取章节失败 {}: {}", item.url, e);
+            }
+
+            if let Some(callback) = on_partial {
+                if end < limit {
+                    callback(&items);
                 }
             }
         }
@@ -63,58 +285,642 @@ async fn execute_search(rule: &Rule, keyword: &str) -> anyhow::Result<Vec<Search
     Ok(items)
 }
 
+/// 规则的重试策略 (规则字段优先，否则使用全局配置)
+fn retry_policy(rule: &Rule) -> RetryPolicy {
+    RetryPolicy {
+        attempts: rule
+            .retry_attempts
+            .unwrap_or(CONFIG.retry_attempts)
+            .clamp(1, MAX_RETRY_ATTEMPTS),
+        backoff_ms: rule.retry_backoff_ms.unwrap_or(CONFIG.retry_backoff_ms),
+        jitter_ms: CONFIG.retry_jitter_ms,
+    }
+}
+
+/// 本次搜索获取集数的结果数量：请求值不超过服务端上限 (上限为 0 表示不限制)
+fn episode_item_limit(options: &SearchOptions) -> usize {
+    let max = match CONFIG.max_episode_items {
+        0 => usize::MAX,
+        max => max,
+    };
+    options.episode_items.map_or(max, |n| n.min(max))
+}
+
+/// 根据规则生成请求配置 (Referer、自定义 User-Agent、请求头、Cookie 与是否经由出站代理)
+/// 规则未显式指定 referer 时使用 baseURL
+pub fn request_config(rule: &Rule) -> RequestConfig {
+    let referer = if rule.referer.trim().is_empty() {
+        &rule.base_url
+    } else {
+        rule.referer.trim()
+    };
+
+    RequestConfig {
+        referer: Some(referer.to_string()),
+        user_agent: Some(rule.user_agent.trim())
+            .filter(|ua| !ua.is_empty())
+            .map(|ua| ua.to_string()),
+        headers: rule
+            .headers
+            .iter()
+            .filter(|(name, _)| !name.trim().is_empty())
+            .map(|(name, value)| (name.trim().to_string(), value.clone()))
+            .collect(),
+        cookie: Some(
+            rule.cookies
+                .iter()
+                .map(|(name, value)| format!("{}={}", name.trim(), value.trim()))
+                .collect::<Vec<_>>()
+                .join("; "),
+        )
+        .filter(|cookie| !cookie.is_empty()),
+        outbound_proxy: rule.magic || rule.outbound_proxy,
+        hedge_delay: rule.hedge_delay_ms.map(Duration::from_millis),
+        insecure_tls: rule.insecure_tls,
+        rule: Some(rule.name.clone()),
+        public_only: false,
+    }
+}
+
+/// 单次请求的超时：规则的 timeoutSecs 覆盖全局超时，limit (搜索请求指定) 为上限
+/// 返回 None 时使用 HTTP 客户端的默认超时
+pub fn request_timeout(rule: &Rule, limit: Option<Duration>) -> Option<Duration> {
+    let rule_timeout = rule.timeout_secs.filter(|secs| *secs > 0).map(Duration::from_secs);
+    match (rule_timeout, limit) {
+        (Some(timeout), Some(limit)) => Some(timeout.min(limit)),
+        (None, Some(limit)) => Some(limit.min(Duration::from_secs(CONFIG.timeout_seconds))),
+        (timeout, None) => timeout,
+    }
+}
+
+/// 获取搜索结果页，retry 为 true 时临时故障按规则的重试策略退避重试 (由服务栈的重试层完成)
+/// 返回结果与实际尝试次数
+async fn fetch_search_page(
+    rule: &Rule,
+    keyword: &str,
+    page: usize,
+    timeout: Option<Duration>,
+    retry: bool,
+) -> (anyhow::Result<Page>, u32) {
+    let mut request = match search_request(rule, keyword, page, timeout) {
+        Ok(request) => request,
+        Err(e) => return (Err(e), 1),
+    };
+
+    if retry {
+        request = request.with_retry(retry_policy(rule));
+    }
+    let attempts = request.attempts.clone();
+    let result = send_page(rule, request).await;
+    let attempts = attempts.load(std::sync::atomic::Ordering::Relaxed);
+    (result, attempts)
+}
+
+/// 搜索结果页的请求
+fn search_request(
+    rule: &Rule,
+    keyword: &str,
+    page: usize,
+    timeout: Option<Duration>,
+) -> anyhow::Result<PageRequest> {
+    // 构建搜索 URL
+    let mut search_url = rule
+        .search_url
+        .replace("@keyword", &encode_keyword(keyword, &rule.keyword_encoding))
+        .replace("@page", &page.to_string());
+    if let Some(url) = script::transform_search_url(rule, &search_url, keyword, page)? {
+        search_url = url;
+    }
+    debug!("搜索 URL: {}", search_url);
+
+    if rule.use_post {
+        let post = build_post_request(rule, &search_url, keyword, page)?;
+        debug!("POST {} ({}): {}", post.url, post.content_type, post.body);
+        Ok(PageRequest::post(&post.url, &post.body, &post.content_type, &request_config(rule), timeout))
+    } else {
+        Ok(PageRequest::get(&search_url, &request_config(rule), timeout))
+    }
+}
+
+/// 获取页面 (GET)
+async fn fetch_page(rule: &Rule, url: &str, timeout: Option<Duration>) -> anyhow::Result<Page> {
+    send_page(rule, PageRequest::get(url, &request_config(rule), timeout)).await
+}
+
+/// 按规则选择请求方式发送页面请求：
+/// useWebview 规则 (GET) 在启用 webview 特性时通过无头浏览器渲染，
+/// 设置 impersonate 的规则在启用 impersonate 特性时以浏览器的 TLS 指纹请求，否则经过页面服务栈
+#[cfg_attr(not(any(feature = "webview", feature = "impersonate")), allow(unused_variables))]
+async fn send_page(rule: &Rule, request: PageRequest) -> anyhow::Result<Page> {
+    #[cfg(feature = "webview")]
+    if rule.use_webview && request.body.is_none() {
+        return crate::webview::render(&request.url, &request.config).await;
+    }
+
+    #[cfg(feature = "impersonate")]
+    if !rule.impersonate.trim().is_empty() {
+        return Ok(crate::impersonate::fetch_page(request, &rule.impersonate).await?);
+    }
+
+    let fetched = http_service::page_service().oneshot(request).await?;
+    Ok(fetched.into_page())
+}
+
+/// 将指向原站点的链接改写到实际落地的站点 (站点整体跳转到新域名/镜像时)
+/// 链接不属于原站点或站点未发生跳转时返回 None
+fn rebase_url(url: &str, original_base: &str, landed_url: &str) -> Option<String> {
+    let original = url::Url::parse(original_base).ok()?;
+    let landed = url::Url::parse(landed_url).ok()?;
+    if original.host_str() == landed.host_str() {
+        return None;
+    }
+
+    let mut target = url::Url::parse(url).ok()?;
+    if target.host_str() != original.host_str() {
+        return None;
+    }
+    target.set_scheme(landed.scheme()).ok()?;
+    target.set_host(landed.host_str()).ok()?;
+    target.set_port(landed.port()).ok()?;
+    Some(target.to_string())
+}
+
+/// 记录结果的最终地址 (与原链接相同时不记录)
+fn set_resolved_url(item: &mut SearchResultItem, resolved: String) {
+    if resolved == item.url {
+        return;
+    }
+    item.resolved_host = url::Url::parse(&resolved)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_string()));
+    item.resolved_url = Some(resolved);
+}
+
+const FORM_CONTENT_TYPE: &str = "application/x-www-form-urlencoded";
+const JSON_CONTENT_TYPE: &str = "application/json";
+
+/// 搜索 POST 请求
+#[derive(Debug, PartialEq)]
+struct PostRequest {
+    url: String,
+    body: String,
+    content_type: String,
+}
+
+/// 构建搜索 POST 请求
+///
+/// - 配置了 searchBody：POST 到 searchURL，请求体模板中的占位符按 Content-Type 编码替换
+/// - 未配置：与 Kazumi 一致，把 searchURL 的查询参数拆出来作为表单提交
+fn build_post_request(
+    rule: &Rule,
+    search_url: &str,
+    keyword: &str,
+    page: usize,
+) -> anyhow::Result<PostRequest> {
+    let template = rule.search_body.trim();
+    if template.is_empty() {
+        // 直接使用原始查询串，保留关键词的编码 (如 GBK)
+        let mut uri = url::Url::parse(search_url)?;
+        let body = uri.query().unwrap_or_default().to_string();
+        uri.set_query(None);
+        return Ok(PostRequest {
+            url: uri.to_string(),
+            body,
+            content_type: FORM_CONTENT_TYPE.to_string(),
+        });
+    }
+
+    let content_type = match rule.search_content_type.trim() {
+        "" if template.starts_with('{') || template.starts_with('[') => JSON_CONTENT_TYPE.to_string(),
+        "" => FORM_CONTENT_TYPE.to_string(),
+        ct => ct.to_string(),
+    };
+
+    let keyword = if content_type.contains("json") {
+        // 转义为 JSON 字符串内容 (去掉两侧引号)
+        let quoted = serde_json::to_string(keyword)?;
+        quoted[1..quoted.len() - 1].to_string()
+    } else if content_type.contains("x-www-form-urlencoded") {
+        encode_keyword(keyword, &rule.keyword_encoding)
+    } else {
+        keyword.to_string()
+    };
+
+    Ok(PostRequest {
+        url: search_url.to_string(),
+        body: template
+            .replace("@keyword", &keyword)
+            .replace("@page", &page.to_string()),
+        content_type,
+    })
+}
+
+/// 按规则指定的字符集对关键词做百分号编码 (默认 UTF-8)
+fn encode_keyword(keyword: &str, encoding: &str) -> String {
+    let encoding = encoding.trim();
+    if encoding.is_empty() {
+        return urlencoding::encode(keyword).into_owned();
+    }
+
+    match encoding_rs::Encoding::for_label(encoding.as_bytes()) {
+        Some(encoding) => {
+            let (bytes, _, _) = encoding.encode(keyword);
+            urlencoding::encode_binary(&bytes).into_owned()
+        }
+        None => {
+            warn!("未知的关键词编码 {}，使用 UTF-8", encoding);
+            urlencoding::encode(keyword).into_owned()
+        }
+    }
+}
+
+/// 规则是否配置了章节选择器
+pub fn has_episode_selectors(rule: &Rule) -> bool {
+    !rule.chapter_roads.is_empty() && !rule.chapter_result.is_empty()
+}
+
 /// 获取动漫详情页的章节列表
-async fn fetch_episodes(rule: &Rule, detail_url: &str) -> anyhow::Result<Vec<EpisodeRoad>> {
-    if rule.chapter_roads.is_empty() || rule.chapter_result.is_empty() {
-        return Ok(vec![]);
+/// road 不为空时只解析指定的播放源；debug 模式下无章节时附带解析诊断信息
+pub async fn fetch_episodes(
+    rule: &Rule,
+    detail_url: &str,
+    road: Option<&RoadSelector>,
+    debug: DebugMode,
+) -> anyhow::Result<(Vec<EpisodeRoad>, Option<ParseDiagnostics>)> {
+    if !has_episode_selectors(rule) {
+        return Ok((vec![], None));
     }
 
+    let detail = fetch_detail(rule, detail_url, road, debug, request_timeout(rule, None)).await?;
+    Ok((detail.roads, detail.diagnostics))
+}
+
+/// 详情页解析结果
+struct Detail {
+    roads: Vec<EpisodeRoad>,
+    /// 详情页的最终地址
+    final_url: String,
+    /// 详情页中的磁力/种子链接
+    magnets: Vec<String>,
+    /// 解析诊断 (debug 模式下无章节时)
+    diagnostics: Option<ParseDiagnostics>,
+}
+
+/// 获取详情页并解析章节与磁力链接
+async fn fetch_detail(
+    rule: &Rule,
+    detail_url: &str,
+    road: Option<&RoadSelector>,
+    debug: DebugMode,
+    timeout: Option<Duration>,
+) -> anyhow::Result<Detail> {
     // 获取详情页 HTML
-    let html = get_text(detail_url, Some(&rule.base_url)).await?;
-    
-    // 解析章节
-    parse_episodes(rule, &html, detail_url)
+    let page = fetch_page(rule, detail_url, timeout).await?;
+
+    // 解析章节 (相对链接以最终地址为准)
+    let parsed = parse_episodes(rule, &page.body, &page.final_url, road);
+    let diagnostics = (debug.enabled() && !parsed.as_ref().is_ok_and(|roads| !roads.is_empty()))
+        .then(|| diagnose_episodes(rule, &page, debug));
+    if let (Err(e), Some(diagnostics)) = (&parsed, diagnostics.clone()) {
+        // 诊断模式下解析失败也返回诊断信息
+        debug!("章节解析失败 {}: {}", detail_url, e);
+        return Ok(Detail {
+            roads: Vec::new(),
+            final_url: page.final_url,
+            magnets: Vec::new(),
+            diagnostics: Some(diagnostics),
+        });
+    }
+    let roads = parsed?;
+    let document = Html::parse_document(&page.body);
+    let magnets = extract_magnets(document.root_element(), None, &document_base(&document, &page.final_url));
+    Ok(Detail {
+        roads,
+        final_url: page.final_url,
+        magnets,
+        diagnostics,
+    })
 }
 
-/// 解析章节列表
-fn parse_episodes(rule: &Rule, html: &str, base_url: &str) -> anyhow::Result<Vec<EpisodeRoad>> {
-    let mut roads = Vec::new();
+/// 合并磁力链接到结果中 (去重)
+fn merge_magnets(item: &mut SearchResultItem, magnets: Vec<String>) {
+    if magnets.is_empty() {
+        return;
+    }
+    let existing = item.magnets.get_or_insert_with(Vec::new);
+    for magnet in magnets {
+        if !existing.contains(&magnet) {
+            existing.push(magnet);
+        }
+    }
+}
+
+/// 判断链接是否为磁力链接或种子文件
+fn is_magnet_link(link: &str) -> bool {
+    if link.starts_with("magnet:") {
+        return true;
+    }
+    let path = link.split(['?', '#']).next().unwrap_or_default();
+    path.to_ascii_lowercase().ends_with(".torrent")
+}
+
+/// 提取元素内的磁力/种子链接
+/// 配置了 searchMagnet 时取匹配元素的 href (或文本)，否则自动查找 magnet: / .torrent 链接及文本中的磁力链接
+fn extract_magnets(element: ElementRef, selector: Option<&CompiledSelector>, base_url: &str) -> Vec<String> {
+    let mut magnets: Vec<String> = Vec::new();
+    let mut push = |link: &str| {
+        let link = link.trim().replace("&amp;", "&");
+        if link.is_empty() {
+            return;
+        }
+        let link = if link.starts_with("magnet:") {
+            link
+        } else {
+            normalize_url(&link, base_url)
+        };
+        if !magnets.contains(&link) {
+            magnets.push(link);
+        }
+    };
+
+    if let Some(selector) = selector {
+        for e in selector.select(element) {
+            let link = selector
+                .href(&e)
+                .or_else(|| e.value().attr("value").map(|s| s.to_string()))
+                .unwrap_or_else(|| get_element_text(&e));
+            push(&link);
+        }
+        return magnets;
+    }
+
+    for e in element.select(&MAGNET_SELECTOR) {
+        if let Some(href) = element_href(&e).filter(|href| is_magnet_link(href.trim())) {
+            push(&href);
+        }
+    }
+    for m in RE_MAGNET.find_iter(&element.html()) {
+        push(m.as_str());
+    }
+    magnets
+}
+
+/// 打开播放页并解析真实的媒体地址
+pub async fn resolve_play_url(rule: &Rule, episode_url: &str) -> anyhow::Result<Vec<PlaySource>> {
+    let page = fetch_page(rule, episode_url, request_timeout(rule, None)).await?;
+    let (html, episode_url) = (page.body.as_str(), page.final_url.as_str());
+    if let Some(sources) = script::resolve_play_url(rule, html, episode_url)? {
+        let url_base = document_base(&Html::parse_document(html), episode_url);
+        let mut result: Vec<PlaySource> = Vec::new();
+        for source in sources {
+            let url = normalize_url(source.url.trim(), &url_base);
+            if result.iter().any(|s| s.url == url) {
+                continue;
+            }
+            result.push(PlaySource {
+                kind: source.kind.unwrap_or_else(|| media_kind(&url).to_string()),
+                url,
+                origin: "hook".to_string(),
+            });
+        }
+        return Ok(result);
+    }
+    if rule.use_legacy_parser {
+        return Ok(extract_legacy_play_sources(html, episode_url));
+    }
+    Ok(extract_play_sources(html, episode_url))
+}
+
+/// 旧版解析器 (useLegacyParser)
+///
+/// 与 Kazumi 旧版 webview 解析一致：只看页面中的 iframe，
+/// iframe 地址带 `url=` 参数时取参数中的真实地址，否则返回 iframe 本身
+fn extract_legacy_play_sources(html: &str, page_url: &str) -> Vec<PlaySource> {
+    let document = Html::parse_document(html);
+    let url_base = document_base(&document, page_url);
+    let mut sources: Vec<PlaySource> = Vec::new();
+
+    let Ok(selector) = Selector::parse("iframe[src]") else {
+        return sources;
+    };
+
+    for element in document.select(&selector) {
+        let Some(src) = element.value().attr("src").map(str::trim) else {
+            continue;
+        };
+        if src.is_empty() || src.starts_with("about:") || src.starts_with("javascript:") {
+            continue;
+        }
+
+        let iframe_url = normalize_url(src, &url_base);
+        let embedded = url::Url::parse(&iframe_url).ok().and_then(|u| {
+            u.query_pairs()
+                .find(|(k, v)| k == "url" && v.starts_with("http"))
+                .map(|(_, v)| v.into_owned())
+        });
+
+        let source = match embedded {
+            Some(url) => PlaySource {
+                kind: media_kind(&url).to_string(),
+                url,
+                origin: "legacy".to_string(),
+            },
+            None => PlaySource {
+                url: iframe_url,
+                kind: "iframe".to_string(),
+                origin: "legacy".to_string(),
+            },
+        };
+        if !sources.iter().any(|s| s.url == source.url) {
+            sources.push(source);
+        }
+    }
+
+    sources
+}
+
+/// 从播放页 HTML 中提取媒体地址
+/// 依次检查 video/source 标签、CMS 播放器配置、内联脚本中的 m3u8/mp4 以及 iframe
+fn extract_play_sources(html: &str, page_url: &str) -> Vec<PlaySource> {
     let document = Html::parse_document(html);
+    let url_base = document_base(&document, page_url);
+    let mut sources: Vec<PlaySource> = Vec::new();
+
+    let push = |url: &str, origin: &str, sources: &mut Vec<PlaySource>| {
+        let url = url.trim();
+        if url.is_empty() || url.starts_with("about:") || url.starts_with("javascript:") {
+            return;
+        }
+        let url = normalize_url(url, &url_base);
+        if sources.iter().any(|s| s.url == url) {
+            return;
+        }
+        let kind = if origin == "iframe" {
+            "iframe".to_string()
+        } else {
+            media_kind(&url).to_string()
+        };
+        sources.push(PlaySource {
+            url,
+            kind,
+            origin: origin.to_string(),
+        });
+    };
+
+    // <video src> / <video><source src>
+    if let Ok(selector) = Selector::parse("video[src], video source[src]") {
+        for element in document.select(&selector) {
+            if let Some(src) = element.value().attr("src") {
+                push(src, "video", &mut sources);
+            }
+        }
+    }
+
+    // var player_xxx = {"url": "...", "encrypt": 0}
+    for caps in RE_PLAYER_CONFIG.captures_iter(html) {
+        let Ok(config) = serde_json::from_str::<serde_json::Value>(&caps[1]) else {
+            continue;
+        };
+        let Some(url) = config.get("url").and_then(|u| u.as_str()) else {
+            continue;
+        };
+        let encrypt = config.get("encrypt").and_then(|e| e.as_i64()).unwrap_or(0);
+        if let Some(url) = decode_player_url(url, encrypt) {
+            push(&url, "player", &mut sources);
+        }
+    }
+
+    // 内联脚本中的 m3u8/mp4 地址
+    if let Ok(selector) = Selector::parse("script") {
+        for script in document.select(&selector) {
+            let text: String = script.text().collect();
+            for m in RE_MEDIA_URL.find_iter(&text) {
+                push(&m.as_str().replace("\\/", "/"), "script", &mut sources);
+            }
+        }
+    }
+
+    // <iframe src> (通常是第三方解析播放器)
+    if let Ok(selector) = Selector::parse("iframe[src]") {
+        for element in document.select(&selector) {
+            if let Some(src) = element.value().attr("src") {
+                push(src, "iframe", &mut sources);
+            }
+        }
+    }
+
+    sources
+}
+
+/// 解码 CMS 播放器配置中的地址 (encrypt: 1 = escape, 2 = base64 + escape)
+fn decode_player_url(url: &str, encrypt: i64) -> Option<String> {
+    match encrypt {
+        1 => Some(js_unescape(url)),
+        2 => {
+            let bytes = base64::engine::general_purpose::STANDARD.decode(url).ok()?;
+            Some(js_unescape(&String::from_utf8(bytes).ok()?))
+        }
+        _ => Some(url.to_string()),
+    }
+}
 
-    // 转换 XPath 为 CSS
-    let roads_css = xpath_to_css(&rule.chapter_roads)
-        .map_err(|e| anyhow::anyhow!("播放源 XPath 转换失败: {}", e))?;
-    let result_css = xpath_to_css(&rule.chapter_result)
-        .map_err(|e| anyhow::anyhow!("章节 XPath 转换失败: {}", e))?;
+/// 实现 JavaScript 的 unescape() (%XX 与 %uXXXX)
+fn js_unescape(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
 
-    debug!("播放源 CSS: {}", roads_css.selector);
-    debug!("章节 CSS: {}", result_css.selector);
+    while let Some(pos) = rest.find('%') {
+        output.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+
+        // 用 get 取十六进制部分，% 后紧跟多字节字符时不会切到字符中间
+        let decoded = if rest.starts_with("%u") {
+            rest.get(2..6)
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .and_then(char::from_u32)
+                .map(|c| (c, 6))
+        } else {
+            rest.get(1..3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .map(|b| (b as char, 3))
+        };
+
+        match decoded {
+            Some((c, len)) => {
+                output.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                output.push('%');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
+
+/// 根据 URL 推断媒体类型
+fn media_kind(url: &str) -> &'static str {
+    let path = url.split(['?', '#']).next().unwrap_or(url).to_ascii_lowercase();
+    if path.ends_with(".m3u8") {
+        "m3u8"
+    } else if path.ends_with(".mp4") {
+        "mp4"
+    } else if path.ends_with(".flv") {
+        "flv"
+    } else {
+        "unknown"
+    }
+}
+
+/// 解析章节列表
+fn parse_episodes(
+    rule: &Rule,
+    html: &str,
+    base_url: &str,
+    road: Option<&RoadSelector>,
+) -> anyhow::Result<Vec<EpisodeRoad>> {
+    let mut roads = Vec::new();
+    let document = Html::parse_document(html);
 
-    let roads_selector = Selector::parse(&roads_css.selector)
-        .map_err(|e| anyhow::anyhow!("无效的播放源 CSS 选择器: {:?}", e))?;
-    let result_selector = Selector::parse(&result_css.selector)
-        .map_err(|e| anyhow::anyhow!("无效的章节 CSS 选择器: {:?}", e))?;
+    // 预编译的选择器
+    let selectors = rule_selectors(rule);
+    let EpisodeSelectors {
+        roads: roads_selector,
+        result: result_selector,
+    } = selectors.episodes.as_ref().map_err(|e| anyhow::anyhow!("{}", e))?;
+    let post = selectors.post.as_ref().map_err(|e| anyhow::anyhow!("{}", e))?;
 
-    // 提取 base_url 用于构建完整 URL
-    let url_base = extract_base_url(base_url, &rule.base_url);
+    // 相对链接以 <base href> 或详情页地址为基准解析
+    let url_base = document_base(&document, base_url);
 
     // 查询播放源列表
-    let road_elements: Vec<ElementRef> = document.select(&roads_selector)
-        .enumerate()
-        .filter(|(i, _)| apply_position_filter(*i, &roads_css.position_filter))
-        .map(|(_, e)| e)
-        .collect();
+    let road_elements = roads_selector.select_document(&document);
 
     debug!("找到 {} 个播放源", road_elements.len());
 
     for (index, road_element) in road_elements.iter().enumerate() {
+        // 只解析指定播放源的子树
+        if road.is_some_and(|r| !r.matches(index)) {
+            continue;
+        }
+
         let mut episodes = Vec::new();
 
         // 在播放源内查找章节
-        for ep_element in road_element.select(&result_selector) {
-            let name = get_element_text(&ep_element).trim().to_string();
-            let href = ep_element.value().attr("href").unwrap_or_default().to_string();
-            
+        for ep_element in result_selector.select(*road_element) {
+            let name = apply_steps(&post.episode_name, get_element_text(&ep_element).trim().to_string());
+            let href = Some(rule.post_process.episode_url_attr.trim())
+                .filter(|attr| !attr.is_empty())
+                .or(result_selector.css.attribute.as_deref())
+                .and_then(|attr| ep_element.value().attr(attr))
+                .or_else(|| ep_element.value().attr("href"))
+                .or_else(|| onclick_source(&ep_element, &post.episode_url))
+                .map(|href| apply_steps(&post.episode_url, href.to_string()))
+                .unwrap_or_default();
+
             if name.is_empty() || href.is_empty() {
                 continue;
             }
@@ -131,65 +937,91 @@ fn parse_episodes(rule: &Rule, html: &str, base_url: &str) -> anyhow::Result<Vec
                     None
                 },
                 episodes,
+                aliases: Vec::new(),
             });
         }
     }
 
-    Ok(roads)
+    Ok(dedup_roads(roads))
+}
+
+/// 合并集数链接完全相同的播放源 (部分站点用多个标签页展示同一组集数)
+/// 保留第一个，其余名称记入 aliases
+fn dedup_roads(roads: Vec<EpisodeRoad>) -> Vec<EpisodeRoad> {
+    let mut result: Vec<EpisodeRoad> = Vec::new();
+    for road in roads {
+        let duplicate = result.iter_mut().find(|kept| {
+            kept.episodes.len() == road.episodes.len()
+                && kept.episodes.iter().zip(&road.episodes).all(|(a, b)| a.url == b.url)
+        });
+        match duplicate {
+            Some(kept) => kept.aliases.extend(road.name),
+            None => result.push(road),
+        }
+    }
+    result
 }
 
-/// 解析搜索结果 (兼容 Kazumi 规则)
+/// 解析搜索结果，并从名称中解析季度/集数信息
 fn parse_search_results(rule: &Rule, html: &str) -> anyhow::Result<Vec<SearchResultItem>> {
-    let mut items = Vec::new();
-    let document = Html::parse_document(html);
+    let mut items = extract_search_results(rule, html)?;
+    for item in items.iter_mut() {
+        item.title_info = title::parse(&item.name);
+    }
+    Ok(items)
+}
 
-    // 转换 XPath 为 CSS
-    let list_css = xpath_to_css(&rule.search_list)
-        .map_err(|e| anyhow::anyhow!("列表 XPath 转换失败: {}", e))?;
-    let name_css = xpath_to_css(&rule.search_name)
-        .map_err(|e| anyhow::anyhow!("名称 XPath 转换失败: {}", e))?;
-    let result_css = if rule.search_result.is_empty() {
-        name_css.clone()
-    } else {
-        xpath_to_css(&rule.search_result)
-            .map_err(|e| anyhow::anyhow!("结果 XPath 转换失败: {}", e))?
-    };
+/// 提取搜索结果 (兼容 Kazumi 规则)
+fn extract_search_results(rule: &Rule, html: &str) -> anyhow::Result<Vec<SearchResultItem>> {
+    // 规则脚本优先
+    if let Some(mut items) = script::parse_results(rule, html)? {
+        for item in items.iter_mut() {
+            item.url = normalize_url(&item.url, &rule_base(rule));
+            if let Some(cover) = item.cover.as_mut() {
+                *cover = normalize_url(cover, &rule_base(rule));
+            }
+        }
+        return Ok(items);
+    }
+
+    if rule.rule_type.eq_ignore_ascii_case(RULE_TYPE_JSON) {
+        return parse_json_results(rule, html);
+    }
 
-    debug!("列表 CSS: {}", list_css.selector);
-    debug!("名称 CSS: {}", name_css.selector);
-    debug!("结果 CSS: {}", result_css.selector);
+    let mut items = Vec::new();
+    let document = Html::parse_document(html);
 
-    let list_selector = Selector::parse(&list_css.selector)
-        .map_err(|e| anyhow::anyhow!("无效的列表 CSS 选择器: {:?}", e))?;
-    let name_selector = Selector::parse(&name_css.selector)
-        .map_err(|e| anyhow::anyhow!("无效的名称 CSS 选择器: {:?}", e))?;
-    let result_selector = Selector::parse(&result_css.selector)
-        .map_err(|e| anyhow::anyhow!("无效的结果 CSS 选择器: {:?}", e))?;
+    // 预编译的选择器
+    let selectors = rule_selectors(rule);
+    let SearchSelectors {
+        list: list_selector,
+        name: name_selector,
+        result: result_selector,
+        cover: cover_selector,
+        description: description_selector,
+        status: status_selector,
+        magnet: magnet_selector,
+    } = selectors.search.as_ref().map_err(|e| anyhow::anyhow!("{}", e))?;
+    let post = selectors.post.as_ref().map_err(|e| anyhow::anyhow!("{}", e))?;
+    let url_base = document_base(&document, &rule_base(rule));
 
     // 查询列表元素
-    let list_elements: Vec<ElementRef> = document.select(&list_selector)
-        .enumerate()
-        .filter(|(i, _)| apply_position_filter(*i, &list_css.position_filter))
-        .map(|(_, e)| e)
-        .collect();
+    let list_elements = list_selector.select_document(&document);
 
     debug!("找到 {} 个列表节点", list_elements.len());
 
     for element in list_elements {
         // 在列表项内查找名称
-        let name = element.select(&name_selector)
-            .next()
-            .map(|e| get_element_text(&e).trim().to_string())
+        let name = name_selector.first(element)
+            .map(|e| apply_steps(&post.name, name_selector.text(&e).trim().to_string()))
             .unwrap_or_default();
 
         // 在列表项内查找链接
-        let href = element.select(&result_selector)
-            .next()
+        let href = result_selector.first(element)
             .and_then(|e| {
-                // 尝试获取 href 属性
-                e.value().attr("href")
-                    .or_else(|| e.value().attr("data-href"))
-                    .map(|s| s.to_string())
+                result_selector
+                    .href(&e)
+                    .or_else(|| onclick_source(&e, &post.url).map(|s| s.to_string()))
             })
             .or_else(|| {
                 // 如果没有找到，尝试在元素内查找 a 标签
@@ -198,6 +1030,7 @@ fn parse_search_results(rule: &Rule, html: &str) -> anyhow::Result<Vec<SearchRes
                     .next()
                     .and_then(|a| a.value().attr("href").map(|s| s.to_string()))
             })
+            .map(|href| apply_steps(&post.url, href))
             .unwrap_or_default();
 
         if name.is_empty() || href.is_empty() {
@@ -205,24 +1038,1187 @@ fn parse_search_results(rule: &Rule, html: &str) -> anyhow::Result<Vec<SearchRes
         }
 
         // 构建完整 URL
-        let url = normalize_url(&href, &rule.base_url);
+        let url = normalize_url(&href, &url_base);
 
-        items.push(SearchResultItem {
-            name,
-            url,
-            tags: None,
-            episodes: None,
-        });
-    }
+        // 在列表项内查找封面
+        let cover = cover_selector
+            .as_ref()
+            .and_then(|selector| selector.first(element).and_then(|e| selector.image(&e)))
+            .map(|src| normalize_url(&src, &url_base));
+
+        // 在列表项内查找简介 (合并空白)
+        let description = description_selector
+            .as_ref()
+            .and_then(|selector| selector.first(element).map(|e| collapse_whitespace(&selector.text(&e))))
+            .filter(|s| !s.is_empty());
+
+        // 在列表项内查找更新状态标签
+        let mut tags: Vec<String> = Vec::new();
+        if let Some(selector) = &status_selector {
+            for status in selector.select(element) {
+                let text = collapse_whitespace(&selector.text(&status));
+                if !text.is_empty() && !tags.contains(&text) {
+                    tags.push(text);
+                }
+            }
+        }
+
+        // 在列表项内查找磁力/种子链接
+        let magnets = extract_magnets(element, magnet_selector.as_ref(), &url_base);
+
+        items.push(SearchResultItem {
+            name,
+            url,
+            cover,
+            description,
+            tags: (!tags.is_empty()).then_some(tags),
+            episodes: None,
+            magnets: (!magnets.is_empty()).then_some(magnets),
+            title_info: None,
+            resolved_url: None,
+            resolved_host: None,
+        });
+    }
+
+    Ok(items)
+}
+
+/// 解析 JSON 接口的搜索结果 (type: "json")
+/// searchList 以响应根节点为起点，其余字段以列表项为起点
+fn parse_json_results(rule: &Rule, body: &str) -> anyhow::Result<Vec<SearchResultItem>> {
+    let root: serde_json::Value = serde_json::from_str(body.trim_start_matches('\u{feff}'))
+        .map_err(|e| anyhow::anyhow!("响应不是有效的 JSON: {}", e))?;
+
+    let selectors = rule_selectors(rule);
+    let paths = selectors.json.as_ref().map_err(|e| anyhow::anyhow!("{}", e))?;
+    let post = selectors.post.as_ref().map_err(|e| anyhow::anyhow!("{}", e))?;
+
+    // 列表路径指向单个数组时展开 (如 $.data.list 与 $.data.list[*] 等价)
+    let mut list = paths.list.query(&root).all();
+    if let [serde_json::Value::Array(array)] = list.as_slice() {
+        list = array.iter().collect();
+    }
+    debug!("找到 {} 个 JSON 列表项", list.len());
+
+    let first_text = |path: &serde_json_path::JsonPath, item: &serde_json::Value| {
+        path.query(item).all().into_iter().find_map(json_text)
+    };
+
+    let mut items = Vec::new();
+    for item in list {
+        let name = apply_steps(&post.name, first_text(&paths.name, item).unwrap_or_default());
+        let href = apply_steps(&post.url, first_text(&paths.result, item).unwrap_or_default());
+        if name.is_empty() || href.is_empty() {
+            continue;
+        }
+
+        let mut tags: Vec<String> = Vec::new();
+        if let Some(path) = &paths.status {
+            for text in path.query(item).all().into_iter().filter_map(json_text) {
+                if !tags.contains(&text) {
+                    tags.push(text);
+                }
+            }
+        }
+
+        items.push(SearchResultItem {
+            name,
+            url: normalize_url(&href, &rule_base(rule)),
+            cover: paths
+                .cover
+                .as_ref()
+                .and_then(|path| first_text(path, item))
+                .map(|src| normalize_url(&src, &rule_base(rule))),
+            description: paths
+                .description
+                .as_ref()
+                .and_then(|path| first_text(path, item))
+                .map(|text| collapse_whitespace(&text)),
+            tags: (!tags.is_empty()).then_some(tags),
+            episodes: None,
+            magnets: None,
+            title_info: None,
+            resolved_url: None,
+            resolved_host: None,
+        });
+    }
 
     Ok(items)
 }
 
+/// 编译 JSONPath (允许省略开头的 `$`，如 `title`、`.data.list`、`@.name`)
+fn compile_json_path(path: &str, label: &str) -> anyhow::Result<serde_json_path::JsonPath> {
+    let path = path.trim();
+    let normalized = match path.chars().next() {
+        Some('$') => path.to_string(),
+        Some('@') => format!("${}", &path[1..]),
+        Some('.') | Some('[') => format!("${}", path),
+        Some(_) => format!("$.{}", path),
+        None => anyhow::bail!("{} JSONPath 为空", label),
+    };
+    serde_json_path::JsonPath::parse(&normalized)
+        .map_err(|e| anyhow::anyhow!("{} JSONPath 无效: {}", label, e))
+}
+
+fn compile_optional_json_path(
+    path: &str,
+    label: &str,
+) -> anyhow::Result<Option<serde_json_path::JsonPath>> {
+    if path.trim().is_empty() {
+        return Ok(None);
+    }
+    compile_json_path(path, label).map(Some)
+}
+
+/// JSON 值转文本 (字符串与数字)
+fn json_text(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(s) => Some(s.trim().to_string()).filter(|s| !s.is_empty()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// 缓存的规则数量上限 (超过后清空重建，避免临时规则无限增长)
+const SELECTOR_CACHE_CAPACITY: usize = 1024;
+
+/// 规则预编译选择器缓存 (按选择器内容哈希，规则更新后自动重新编译)
+static SELECTOR_CACHE: LazyLock<RwLock<HashMap<u64, Arc<RuleSelectors>>>> =
+    LazyLock::new(Default::default);
+
+/// 规则的预编译选择器 (编译失败时保存错误信息)
+struct RuleSelectors {
+    search: Result<SearchSelectors, String>,
+    episodes: Result<EpisodeSelectors, String>,
+    /// JSON 规则的 JSONPath (其余规则为 Err)
+    json: Result<JsonPaths, String>,
+    post: Result<PostProcessors, String>,
+}
+
+/// JSON 规则预编译的 JSONPath
+struct JsonPaths {
+    list: serde_json_path::JsonPath,
+    name: serde_json_path::JsonPath,
+    result: serde_json_path::JsonPath,
+    cover: Option<serde_json_path::JsonPath>,
+    description: Option<serde_json_path::JsonPath>,
+    status: Option<serde_json_path::JsonPath>,
+}
+
+impl JsonPaths {
+    fn compile(rule: &Rule) -> anyhow::Result<Self> {
+        Ok(Self {
+            list: compile_json_path(&rule.search_list, "列表")?,
+            name: compile_json_path(&rule.search_name, "名称")?,
+            result: compile_json_path(&rule.search_result, "结果")?,
+            cover: compile_optional_json_path(&rule.search_cover, "封面")?,
+            description: compile_optional_json_path(&rule.search_description, "简介")?,
+            status: compile_optional_json_path(&rule.search_status, "更新状态")?,
+        })
+    }
+}
+
+/// 预编译的字段后处理 (postProcess)
+#[derive(Default)]
+struct PostProcessors {
+    name: Vec<CompiledStep>,
+    url: Vec<CompiledStep>,
+    episode_name: Vec<CompiledStep>,
+    episode_url: Vec<CompiledStep>,
+}
+
+struct CompiledStep {
+    extract: Option<(Regex, String)>,
+    decode: Option<Decoder>,
+    replace: Option<(Regex, String)>,
+}
+
+/// 内置解码器 (用于站点在属性中编码真实链接的情况)
+#[derive(Debug, PartialEq)]
+enum Decoder {
+    Base64,
+    Url,
+    Unescape,
+    /// 按字段路径 (点分隔，数组用下标) 从 JSON 中取值
+    Json(Vec<String>),
+}
+
+impl Decoder {
+    fn parse(name: &str) -> Option<Self> {
+        let name = name.trim();
+        if let Some(path) = name.strip_prefix("json:") {
+            let path = path.trim().trim_start_matches('$').trim_start_matches('.');
+            return Some(Self::Json(
+                path.split('.').filter(|s| !s.is_empty()).map(|s| s.to_string()).collect(),
+            ));
+        }
+        match name.to_ascii_lowercase().as_str() {
+            "base64" => Some(Self::Base64),
+            "url" => Some(Self::Url),
+            "unescape" => Some(Self::Unescape),
+            _ => None,
+        }
+    }
+
+    /// 解码失败时返回 None (保留原值)
+    fn decode(&self, value: &str) -> Option<String> {
+        match self {
+            Self::Base64 => {
+                let value = value.trim().trim_end_matches('=');
+                let bytes = base64::engine::general_purpose::STANDARD_NO_PAD
+                    .decode(value)
+                    .or_else(|_| base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(value))
+                    .ok()?;
+                String::from_utf8(bytes).ok()
+            }
+            Self::Url => urlencoding::decode(value).ok().map(|s| s.into_owned()),
+            Self::Unescape => Some(js_unescape(value)),
+            Self::Json(path) => {
+                let root: serde_json::Value = serde_json::from_str(value.trim()).ok()?;
+                let node = path.iter().try_fold(&root, |node, key| match node {
+                    serde_json::Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+                    _ => node.get(key),
+                })?;
+                json_text(node)
+            }
+        }
+    }
+}
+
+impl PostProcessors {
+    fn compile(rule: &Rule) -> anyhow::Result<Self> {
+        let post = &rule.post_process;
+        Ok(Self {
+            name: compile_steps(&post.name, "name")?,
+            url: compile_steps(&post.url, "url")?,
+            episode_name: compile_steps(&post.episode_name, "episodeName")?,
+            episode_url: compile_steps(&post.episode_url, "episodeUrl")?,
+        })
+    }
+}
+
+fn compile_steps(steps: &[ProcessStep], field: &str) -> anyhow::Result<Vec<CompiledStep>> {
+    let compile = |pattern: &str, replacement: &str| {
+        if pattern.is_empty() {
+            return Ok(None);
+        }
+        Regex::new(pattern)
+            .map(|re| Some((re, replacement.to_string())))
+            .map_err(|e| anyhow::anyhow!("postProcess.{} 正则无效: {}", field, e))
+    };
+    steps
+        .iter()
+        .map(|step| {
+            let decode = match step.decode.trim() {
+                "" => None,
+                name => Some(Decoder::parse(name).ok_or_else(|| {
+                    anyhow::anyhow!("postProcess.{} 未知的解码方式: {}", field, name)
+                })?),
+            };
+            Ok(CompiledStep {
+                extract: compile(&step.extract, &step.template)?,
+                decode,
+                replace: compile(&step.replace, &step.with)?,
+            })
+        })
+        .collect()
+}
+
+/// 依次执行后处理步骤
+fn apply_steps(steps: &[CompiledStep], value: String) -> String {
+    steps.iter().fold(value, |value, step| {
+        let mut value = match &step.extract {
+            Some((re, template)) => match re.captures(&value) {
+                Some(caps) if !template.is_empty() => {
+                    let mut out = String::new();
+                    caps.expand(template, &mut out);
+                    out
+                }
+                Some(caps) => caps
+                    .get(1)
+                    .or_else(|| caps.get(0))
+                    .map(|m| m.as_str().to_string())
+                    .unwrap_or_default(),
+                None => value,
+            },
+            None => value,
+        };
+        if let Some(decoded) = step.decode.as_ref().and_then(|d| d.decode(&value)) {
+            value = decoded;
+        }
+        if let Some((re, with)) = &step.replace {
+            value = re.replace_all(&value, with.as_str()).into_owned();
+        }
+        value.trim().to_string()
+    })
+}
+
+struct SearchSelectors {
+    list: CompiledSelector,
+    name: CompiledSelector,
+    result: CompiledSelector,
+    cover: Option<CompiledSelector>,
+    description: Option<CompiledSelector>,
+    status: Option<CompiledSelector>,
+    magnet: Option<CompiledSelector>,
+}
+
+struct EpisodeSelectors {
+    roads: CompiledSelector,
+    result: CompiledSelector,
+}
+
+impl RuleSelectors {
+    fn compile(rule: &Rule) -> Self {
+        let mode = position_mode(rule);
+        let search = (|| {
+            let list = compile_selector(&rule.search_list, "列表", mode)?;
+            let name = compile_selector(&rule.search_name, "名称", mode)?;
+            let result = if rule.search_result.is_empty() {
+                // 名称选择器的取值属性只用于名称，链接仍取 href
+                let mut result = name.clone();
+                result.css.attribute = None;
+                result
+            } else {
+                compile_selector(&rule.search_result, "结果", mode)?
+            };
+            anyhow::Ok(SearchSelectors {
+                list,
+                name,
+                result,
+                cover: compile_optional_selector(&rule.search_cover, "封面", mode)?,
+                description: compile_optional_selector(&rule.search_description, "简介", mode)?,
+                status: compile_optional_selector(&rule.search_status, "更新状态", mode)?,
+                magnet: compile_optional_selector(&rule.search_magnet, "磁力链接", mode)?,
+            })
+        })();
+
+        let episodes = (|| {
+            anyhow::Ok(EpisodeSelectors {
+                roads: compile_selector(&rule.chapter_roads, "播放源", mode)?,
+                result: compile_selector(&rule.chapter_result, "章节", mode)?,
+            })
+        })();
+
+        let json = if rule.rule_type.eq_ignore_ascii_case(RULE_TYPE_JSON) {
+            JsonPaths::compile(rule).map_err(|e| e.to_string())
+        } else {
+            Err("规则不是 JSON 类型".to_string())
+        };
+
+        Self {
+            search: search.map_err(|e| e.to_string()),
+            episodes: episodes.map_err(|e| e.to_string()),
+            json,
+            post: PostProcessors::compile(rule).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// 规则使用的位置谓词语义
+fn position_mode(rule: &Rule) -> PositionMode {
+    if rule.strict_positions {
+        PositionMode::XPath
+    } else {
+        PositionMode::NthOfType
+    }
+}
+
+/// 规则中与选择器相关字段的指纹
+fn selector_fingerprint(rule: &Rule) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (
+        rule.rule_type.to_ascii_lowercase(),
+        &rule.search_list,
+        &rule.search_name,
+        &rule.search_result,
+        &rule.search_cover,
+        &rule.search_description,
+        &rule.search_status,
+        &rule.search_magnet,
+        &rule.chapter_roads,
+        &rule.chapter_result,
+        &rule.post_process,
+        rule.strict_positions,
+    )
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+/// 规则试运行结果 (POST /admin/rules/test)
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleTestReport {
+    /// 各字段转换后的 CSS 选择器 (JSON 规则为空)
+    pub selectors: BTreeMap<String, CssSelector>,
+    /// 选择器/正则编译错误
+    pub errors: Vec<String>,
+    /// 上游响应状态码
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// 最终落地的地址
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub final_url: Option<String>,
+    /// 到达最终地址前经过的重定向
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub redirects: Vec<String>,
+    /// 请求耗时/毫秒
+    pub fetch_ms: u64,
+    /// 解析耗时/毫秒
+    pub parse_ms: u64,
+    /// 解析出的结果
+    pub items: Vec<SearchResultItem>,
+    /// 请求或解析失败的原因
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 规则校验报告 (POST /rules/validate)
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleValidationReport {
+    /// 没有结构错误、缺失字段、无效选择器且 baseURL 可访问 (未检查时忽略)
+    pub valid: bool,
+    /// 无法按规则结构解析的原因
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema_error: Option<String>,
+    /// 缺失或为空的必填字段
+    pub missing: Vec<String>,
+    /// 选择器问题 (unconvertible 回退为 XPath 求值，不影响 valid)
+    pub selectors: Vec<SelectorIssue>,
+    /// baseURL 可访问性
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<ReachabilityCheck>,
+}
+
+/// baseURL 可访问性检查结果
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReachabilityCheck {
+    pub url: String,
+    pub reachable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    pub elapsed_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 规则中的选择器字段 (规则 JSON 中的字段名与 XPath)
+fn selector_fields(rule: &Rule) -> [(&'static str, &str); 9] {
+    [
+        ("searchList", &rule.search_list),
+        ("searchName", &rule.search_name),
+        ("searchResult", &rule.search_result),
+        ("searchCover", &rule.search_cover),
+        ("searchDescription", &rule.search_description),
+        ("searchStatus", &rule.search_status),
+        ("searchMagnet", &rule.search_magnet),
+        ("chapterRoads", &rule.chapter_roads),
+        ("chapterResult", &rule.chapter_result),
+    ]
+}
+
+/// 规则各选择器字段转换后的 CSS (JSON 规则与转换失败的字段不包含在内)
+fn converted_selectors(rule: &Rule) -> BTreeMap<String, CssSelector> {
+    let mut selectors = BTreeMap::new();
+    if rule.rule_type.eq_ignore_ascii_case(RULE_TYPE_JSON) {
+        return selectors;
+    }
+
+    for (field, xpath) in selector_fields(rule) {
+        if xpath.trim().is_empty() {
+            continue;
+        }
+        if let Ok(css) = xpath_to_css_with(xpath.trim(), position_mode(rule)) {
+            selectors.insert(field.to_string(), css);
+        }
+    }
+    selectors
+}
+
+/// 生成诊断信息的公共部分 (状态码、选择器、编译错误、快照)
+fn diagnostics_base(rule: &Rule, page: &Page, debug: DebugMode) -> ParseDiagnostics {
+    ParseDiagnostics {
+        status: page.status,
+        final_url: page.final_url.clone(),
+        redirects: page.redirects.clone(),
+        body_bytes: page.body.len(),
+        selectors: converted_selectors(rule),
+        stages: Vec::new(),
+        errors: precompile(rule),
+        snapshot: (debug == DebugMode::Snapshot).then(|| truncate_snapshot(&page.body)),
+    }
+}
+
+/// 截断 HTML 快照 (保证在字符边界处截断)
+fn truncate_snapshot(body: &str) -> String {
+    if body.len() <= DEBUG_SNAPSHOT_BYTES {
+        return body.to_string();
+    }
+    let mut end = DEBUG_SNAPSHOT_BYTES;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    body[..end].to_string()
+}
+
+fn stage(stage: &str, count: usize) -> StageCount {
+    StageCount {
+        stage: stage.to_string(),
+        count,
+    }
+}
+
+/// 搜索结果页的解析诊断：列表 → 名称 → 链接 → 结果 各阶段的匹配数量
+fn diagnose_search(rule: &Rule, page: &Page, debug: DebugMode) -> ParseDiagnostics {
+    let mut diagnostics = diagnostics_base(rule, page, debug);
+    if script::compile(rule).ok().flatten().is_some() {
+        diagnostics.stages.push(stage("script", 1));
+    }
+
+    if rule.rule_type.eq_ignore_ascii_case(RULE_TYPE_JSON) {
+        let selectors = rule_selectors(rule);
+        let list = serde_json::from_str::<serde_json::Value>(page.body.trim_start_matches('\u{feff}'))
+            .ok()
+            .zip(selectors.json.as_ref().ok())
+            .map(|(root, paths)| match paths.list.query(&root).all().as_slice() {
+                [serde_json::Value::Array(array)] => array.len(),
+                nodes => nodes.len(),
+            });
+        match list {
+            Some(count) => diagnostics.stages.push(stage("searchList", count)),
+            None => diagnostics.errors.push("响应不是有效的 JSON 或列表路径无效".to_string()),
+        }
+    } else if let Ok(selectors) = &rule_selectors(rule).search {
+        let document = Html::parse_document(&page.body);
+        let list = selectors.list.select_document(&document);
+        let named = list
+            .iter()
+            .filter(|e| {
+                selectors
+                    .name
+                    .first(**e)
+                    .is_some_and(|name| !selectors.name.text(&name).trim().is_empty())
+            })
+            .count();
+        let linked = list
+            .iter()
+            .filter(|e| selectors.result.first(**e).and_then(|r| selectors.result.href(&r)).is_some())
+            .count();
+        diagnostics.stages.push(stage("searchList", list.len()));
+        diagnostics.stages.push(stage("searchName", named));
+        diagnostics.stages.push(stage("searchResult", linked));
+    }
+
+    match parse_search_results(rule, &page.body) {
+        Ok(items) => diagnostics.stages.push(stage("items", items.len())),
+        Err(e) => diagnostics.errors.push(e.to_string()),
+    }
+    diagnostics
+}
+
+/// 详情页的解析诊断：播放源 → 章节 各阶段的匹配数量
+fn diagnose_episodes(rule: &Rule, page: &Page, debug: DebugMode) -> ParseDiagnostics {
+    let mut diagnostics = diagnostics_base(rule, page, debug);
+    if let Ok(selectors) = &rule_selectors(rule).episodes {
+        let document = Html::parse_document(&page.body);
+        let roads = selectors.roads.select_document(&document);
+        let episodes: usize = roads.iter().map(|road| selectors.result.select(*road).len()).sum();
+        diagnostics.stages.push(stage("chapterRoads", roads.len()));
+        diagnostics.stages.push(stage("chapterResult", episodes));
+    }
+    diagnostics
+}
+
+/// 试运行规则：抓取第一页搜索结果并返回解析过程信息，供规则作者调试
+pub async fn test_rule(rule: &Rule, keyword: &str) -> RuleTestReport {
+    let mut report = RuleTestReport {
+        selectors: converted_selectors(rule),
+        errors: precompile(rule),
+        status: None,
+        final_url: None,
+        redirects: Vec::new(),
+        fetch_ms: 0,
+        parse_ms: 0,
+        items: Vec::new(),
+        error: None,
+    };
+
+    let started = std::time::Instant::now();
+    let (page, _) = fetch_search_page(rule, keyword, 1, request_timeout(rule, None), false).await;
+    report.fetch_ms = started.elapsed().as_millis() as u64;
+
+    let page = match page {
+        Ok(page) => page,
+        Err(e) => {
+            report.status = e.downcast_ref::<HttpClientError>().and_then(|e| match e {
+                HttpClientError::BadStatus(status) | HttpClientError::Challenge(status) => Some(*status),
+                HttpClientError::RateLimited(_) => Some(429),
+                _ => None,
+            });
+            report.error = Some(e.to_string());
+            return report;
+        }
+    };
+    report.status = Some(page.status);
+    report.final_url = Some(page.final_url.clone());
+    report.redirects = page.redirects.clone();
+
+    let started = std::time::Instant::now();
+    match parse_search_results(rule, &page.body) {
+        Ok(items) => report.items = items,
+        Err(e) => report.error = Some(e.to_string()),
+    }
+    report.parse_ms = started.elapsed().as_millis() as u64;
+    report
+}
+
+/// 校验提交的规则：结构、必填字段、选择器可转换性，可选检查 baseURL 是否可访问
+pub async fn validate_rule(value: serde_json::Value, check_reachability: bool) -> RuleValidationReport {
+    let mut report = RuleValidationReport {
+        valid: false,
+        schema_error: None,
+        missing: Vec::new(),
+        selectors: Vec::new(),
+        base_url: None,
+    };
+
+    let rule: Rule = match serde_json::from_value(value) {
+        Ok(rule) => rule,
+        Err(e) => {
+            report.schema_error = Some(e.to_string());
+            return report;
+        }
+    };
+
+    report.missing = missing_fields(&rule);
+    report.selectors = validate_selectors(&rule);
+    if check_reachability && !rule.base_url.trim().is_empty() {
+        report.base_url = Some(check_base_url(&rule).await);
+    }
+
+    report.valid = report.missing.is_empty()
+        && report.selectors.iter().all(|i| i.level != "invalid")
+        && report.base_url.as_ref().is_none_or(|c| c.reachable);
+    report
+}
+
+/// 搜索所需但缺失或为空的字段
+/// searchResult 为空时链接取自名称元素，只有 JSON 规则必须填写
+fn missing_fields(rule: &Rule) -> Vec<String> {
+    let mut missing: Vec<String> = [
+        ("name", &rule.name),
+        ("baseURL", &rule.base_url),
+        ("searchURL", &rule.search_url),
+        ("searchList", &rule.search_list),
+        ("searchName", &rule.search_name),
+    ]
+    .into_iter()
+    .filter(|(_, value)| value.trim().is_empty())
+    .map(|(field, _)| field.to_string())
+    .collect();
+
+    if rule.rule_type.eq_ignore_ascii_case(RULE_TYPE_JSON) && rule.search_result.trim().is_empty() {
+        missing.push("searchResult".to_string());
+    }
+
+    if !rule.search_url.trim().is_empty() && !rule.search_url.contains("@keyword") {
+        missing.push("searchURL (@keyword)".to_string());
+    }
+    missing
+}
+
+/// 使用规则的请求配置访问 baseURL
+async fn check_base_url(rule: &Rule) -> ReachabilityCheck {
+    let started = std::time::Instant::now();
+    let config = request_config(rule);
+    // 探测需要访问上游，不使用缓存的响应
+    let request = get_page(&rule.base_url, &config, request_timeout(rule, None));
+    let result = crate::http_cache::bypass(request).await;
+    let elapsed_ms = started.elapsed().as_millis() as u64;
+
+    match result {
+        Ok(page) => ReachabilityCheck {
+            url: rule.base_url.clone(),
+            reachable: true,
+            status: Some(page.status),
+            elapsed_ms,
+            error: None,
+        },
+        Err(e) => ReachabilityCheck {
+            url: rule.base_url.clone(),
+            reachable: false,
+            status: match e {
+                HttpClientError::BadStatus(status) | HttpClientError::Challenge(status) => Some(status),
+                HttpClientError::RateLimited(_) => Some(429),
+                _ => None,
+            },
+            elapsed_ms,
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+/// 获取规则的预编译选择器 (未命中时编译并缓存)
+fn rule_selectors(rule: &Rule) -> Arc<RuleSelectors> {
+    let key = selector_fingerprint(rule);
+    if let Some(selectors) = SELECTOR_CACHE.read().unwrap_or_else(|e| e.into_inner()).get(&key) {
+        return selectors.clone();
+    }
+
+    let selectors = Arc::new(RuleSelectors::compile(rule));
+    let mut cache = SELECTOR_CACHE.write().unwrap_or_else(|e| e.into_inner());
+    if cache.len() >= SELECTOR_CACHE_CAPACITY {
+        cache.clear();
+    }
+    cache.insert(key, selectors.clone());
+    selectors
+}
+
+/// 加载/更新规则时预编译选择器，返回转换失败的错误
+pub fn precompile(rule: &Rule) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    let selectors = rule_selectors(rule);
+    if let Err(e) = &selectors.post {
+        errors.push(e.clone());
+    }
+
+    if rule.rule_type.eq_ignore_ascii_case(RULE_TYPE_JSON) {
+        if let Err(e) = &selectors.json {
+            errors.push(e.clone());
+        }
+        return errors;
+    }
+
+    if let Err(e) = &selectors.search {
+        errors.push(e.clone());
+    }
+    if has_episode_selectors(rule) {
+        if let Err(e) = &selectors.episodes {
+            errors.push(e.clone());
+        }
+    }
+    errors
+}
+
+/// 选择器校验问题
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectorIssue {
+    /// 规则 JSON 中的字段名 (如 searchList)
+    pub field: String,
+    /// 原始 XPath / JSONPath
+    pub selector: String,
+    /// 问题级别: unconvertible (无法转换为 CSS，回退为 XPath 求值) 或 invalid (无法使用)
+    pub level: String,
+    pub message: String,
+}
+
+/// 逐个校验规则的选择器字段 (XPath → CSS 转换与 CSS 解析)
+///
+/// 与 precompile 不同，这里不会在第一个错误处停止，也会报告回退为 XPath 求值的字段；
+/// 必填字段 (列表、名称) 为空视为无效，可选字段为空时跳过
+pub fn validate_selectors(rule: &Rule) -> Vec<SelectorIssue> {
+    let issue = |field: &str, selector: &str, level: &str, message: String| SelectorIssue {
+        field: field.to_string(),
+        selector: selector.to_string(),
+        level: level.to_string(),
+        message,
+    };
+    let required = |field: &str| field == "searchList" || field == "searchName";
+    let mut issues = Vec::new();
+
+    if rule.rule_type.eq_ignore_ascii_case(RULE_TYPE_JSON) {
+        for (field, path) in &selector_fields(rule)[..3] {
+            if path.trim().is_empty() && !required(field) {
+                continue;
+            }
+            if let Err(e) = compile_json_path(path, field) {
+                issues.push(issue(field, path, "invalid", e.to_string()));
+            }
+        }
+        return issues;
+    }
+
+    for (field, xpath) in selector_fields(rule) {
+        let xpath = xpath.trim();
+        if xpath.is_empty() {
+            if required(field) {
+                issues.push(issue(field, xpath, "invalid", "选择器为空".to_string()));
+            }
+            continue;
+        }
+
+        let converted = xpath_to_css_with(xpath, position_mode(rule)).and_then(|css| {
+            Selector::parse(&css.selector)
+                .map(|_| ())
+                .map_err(|e| format!("无效的 CSS 选择器 {}: {}", css.selector, e))
+        });
+        if let Err(reason) = converted {
+            match xpath_eval::validate(xpath) {
+                Ok(()) => issues.push(issue(field, xpath, "unconvertible", reason)),
+                Err(e) => issues.push(issue(
+                    field,
+                    xpath,
+                    "invalid",
+                    format!("{} (XPath 求值也无法解析: {})", reason, e),
+                )),
+            }
+        }
+    }
+    issues
+}
+
+/// 编译后的选择器 (CSS 选择器 + 需要在代码中处理的文本/位置过滤器与轴步骤)
+#[derive(Clone)]
+struct CompiledSelector {
+    css: CssSelector,
+    matcher: Matcher,
+    /// 轴步骤对应的已编译选择器 (与 css.axes 一一对应)
+    axes: Vec<Option<Selector>>,
+}
+
+/// 元素匹配方式
+#[derive(Clone)]
+enum Matcher {
+    Css(Selector),
+    /// 无法转换为 CSS 时直接对原始 XPath 求值
+    XPath(String),
+}
+
+impl CompiledSelector {
+    fn new(css: CssSelector) -> anyhow::Result<Self> {
+        let parse = |selector: &str| {
+            Selector::parse(selector).map_err(|e| anyhow::anyhow!("{:?}", e))
+        };
+        let selector = parse(&css.selector)?;
+        let axes = css
+            .axes
+            .iter()
+            .map(|axis| match axis {
+                AxisStep::FollowingSibling { selector, .. }
+                | AxisStep::PrecedingSibling { selector, .. } => parse(selector).map(Some),
+                AxisStep::Parent { selector } => selector.as_deref().map(parse).transpose(),
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { css, matcher: Matcher::Css(selector), axes })
+    }
+
+    /// XPath 求值回退 (过滤条件均由 XPath 引擎处理，只保留取值属性)
+    fn xpath(xpath: &str) -> Self {
+        Self {
+            css: CssSelector {
+                attribute: attribute_step(xpath),
+                ..Default::default()
+            },
+            matcher: Matcher::XPath(xpath.to_string()),
+            axes: Vec::new(),
+        }
+    }
+
+    /// 在整个文档中查找
+    fn select_document<'a>(&self, document: &'a Html) -> Vec<ElementRef<'a>> {
+        match &self.matcher {
+            Matcher::Css(selector) => self.filter(document.select(selector)),
+            Matcher::XPath(xpath) => xpath_eval::select(xpath, document.root_element()),
+        }
+    }
+
+    /// 在元素内查找
+    fn select<'a>(&self, root: ElementRef<'a>) -> Vec<ElementRef<'a>> {
+        match &self.matcher {
+            Matcher::Css(selector) => self.filter(root.select(selector)),
+            Matcher::XPath(xpath) => xpath_eval::select(xpath, root),
+        }
+    }
+
+    /// 元素内第一个匹配
+    fn first<'a>(&self, root: ElementRef<'a>) -> Option<ElementRef<'a>> {
+        self.select(root).into_iter().next()
+    }
+
+    /// 选择器指定的取值属性 (XPath 以 /@attr 结尾)
+    fn attribute<'a>(&self, element: &ElementRef<'a>) -> Option<&'a str> {
+        self.css.attribute.as_deref().and_then(|attr| element.value().attr(attr))
+    }
+
+    /// 元素的文本 (指定了取值属性时为属性值)
+    fn text(&self, element: &ElementRef) -> String {
+        match &self.css.attribute {
+            Some(_) => self.attribute(element).unwrap_or_default().trim().to_string(),
+            None => get_element_text(element),
+        }
+    }
+
+    /// 元素的链接 (指定了取值属性时为属性值)
+    fn href(&self, element: &ElementRef) -> Option<String> {
+        match &self.css.attribute {
+            Some(_) => self.attribute(element).map(|s| s.to_string()),
+            None => element_href(element),
+        }
+    }
+
+    /// 元素的图片地址 (指定了取值属性时为属性值，避免取到懒加载占位图)
+    fn image(&self, element: &ElementRef) -> Option<String> {
+        match &self.css.attribute {
+            Some(_) => self
+                .attribute(element)
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string()),
+            None => element_image(element),
+        }
+    }
+
+    /// 依次应用文本过滤器、父元素内位置、位置过滤器与轴步骤
+    fn filter<'a>(&self, elements: impl Iterator<Item = ElementRef<'a>>) -> Vec<ElementRef<'a>> {
+        let elements = match &self.css.text_filter {
+            Some(filter) => elements.filter(|e| filter.matches(&get_element_text(e))).collect(),
+            None => elements.collect(),
+        };
+        let elements = match self.css.parent_index {
+            Some(n) => nth_per_parent(elements, n),
+            None => elements,
+        };
+        let elements = apply_position_filter(elements, &self.css.position_filter);
+        self.css
+            .axes
+            .iter()
+            .zip(&self.axes)
+            .fold(elements, |elements, (axis, selector)| walk_axis(elements, axis, selector.as_ref()))
+    }
+}
+
+/// 在同一父元素下按匹配顺序计数，只保留每组的第 n 个 (XPath 位置语义)
+fn nth_per_parent<'a>(elements: Vec<ElementRef<'a>>, n: usize) -> Vec<ElementRef<'a>> {
+    let mut counts = HashMap::new();
+    elements
+        .into_iter()
+        .filter(|e| {
+            let count = counts.entry(e.parent().map(|p| p.id())).or_insert(0);
+            *count += 1;
+            *count == n
+        })
+        .collect()
+}
+
+/// 执行轴步骤：从每个元素出发查找兄弟/父元素 (结果去重，保持首次出现的顺序)
+fn walk_axis<'a>(
+    elements: Vec<ElementRef<'a>>,
+    axis: &AxisStep,
+    selector: Option<&Selector>,
+) -> Vec<ElementRef<'a>> {
+    let mut result: Vec<ElementRef<'a>> = Vec::new();
+    for element in elements {
+        let (nodes, index): (Box<dyn Iterator<Item = _>>, _) = match axis {
+            AxisStep::FollowingSibling { index, .. } => (Box::new(element.next_siblings()), *index),
+            AxisStep::PrecedingSibling { index, .. } => (Box::new(element.prev_siblings()), *index),
+            AxisStep::Parent { .. } => (Box::new(element.parent().into_iter()), Some(1)),
+        };
+        let matched = nodes
+            .filter_map(ElementRef::wrap)
+            .filter(|e| selector.is_none_or(|selector| selector.matches(e)));
+        let found: Vec<ElementRef> = match index {
+            Some(n) => matched.skip(n.saturating_sub(1)).take(1).collect(),
+            None => matched.collect(),
+        };
+        for sibling in found {
+            if !result.iter().any(|e| e.id() == sibling.id()) {
+                result.push(sibling);
+            }
+        }
+    }
+    result
+}
+
+/// 转换并编译选择器 (label 用于错误信息，如 "列表"、"章节")
+/// 无法转换为 CSS 时回退为直接对 XPath 求值
+fn compile_selector(xpath: &str, label: &str, mode: PositionMode) -> anyhow::Result<CompiledSelector> {
+    let converted = xpath_to_css_with(xpath, mode)
+        .map_err(|e| format!("{} XPath 转换失败: {}", label, e))
+        .and_then(|css| {
+            debug!("{} CSS: {}", label, css.selector);
+            CompiledSelector::new(css).map_err(|e| format!("无效的{} CSS 选择器: {}", label, e))
+        });
+
+    match converted {
+        Ok(compiled) => Ok(compiled),
+        Err(reason) => {
+            xpath_eval::validate(xpath.trim())
+                .map_err(|e| anyhow::anyhow!("{} (XPath 求值也无法解析: {})", reason, e))?;
+            debug!("{}，改用 XPath 求值", reason);
+            Ok(CompiledSelector::xpath(xpath.trim()))
+        }
+    }
+}
+
+/// 转换并编译可选选择器 (为空时返回 None)
+fn compile_optional_selector(
+    xpath: &str,
+    label: &str,
+    mode: PositionMode,
+) -> anyhow::Result<Option<CompiledSelector>> {
+    if xpath.trim().is_empty() {
+        return Ok(None);
+    }
+    compile_selector(xpath, label, mode).map(Some)
+}
+
+/// 选择器调试结果
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectDebug {
+    /// 转换后的 CSS 选择器及位置过滤器
+    #[serde(flatten)]
+    pub css: CssSelector,
+    /// 无法转换为 CSS，改用 XPath 求值
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub xpath_fallback: bool,
+    /// 匹配的节点数量 (已应用位置过滤器)
+    pub count: usize,
+    /// 前若干个节点的提取样本
+    pub samples: Vec<SelectSample>,
+}
+
+/// 单个节点的提取样本
+#[derive(Debug, Serialize)]
+pub struct SelectSample {
+    /// 节点文本
+    pub text: String,
+    /// 节点链接 (href / data-href)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub href: Option<String>,
+}
+
+/// 对给定 HTML 执行选择器 (XPath 或 CSS)，返回匹配情况
+/// 与搜索时使用完全相同的转换与提取逻辑，供规则作者调试
+pub fn debug_select(html: &str, xpath: Option<&str>, css: Option<&str>) -> anyhow::Result<SelectDebug> {
+    let compiled = match (xpath, css) {
+        (Some(xpath), _) => compile_selector(xpath, "调试", PositionMode::default())?,
+        (None, Some(css)) => CompiledSelector::new(CssSelector {
+            selector: css.to_string(),
+            ..Default::default()
+        })
+        .map_err(|e| anyhow::anyhow!("无效的 CSS 选择器: {}", e))?,
+        (None, None) => anyhow::bail!("需要提供 xpath 或 css"),
+    };
+
+    let document = Html::parse_document(html);
+    let elements = compiled.select_document(&document);
+
+    let samples = elements
+        .iter()
+        .take(DEBUG_SAMPLE_LIMIT)
+        .map(|e| SelectSample {
+            text: compiled.text(e),
+            href: compiled.href(e),
+        })
+        .collect();
+
+    Ok(SelectDebug {
+        xpath_fallback: matches!(compiled.matcher, Matcher::XPath(_)),
+        css: compiled.css,
+        count: elements.len(),
+        samples,
+    })
+}
+
+/// XPath 转换调试结果
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XPathDebug {
+    /// 转换结果：CSS 选择器、位置/文本过滤器、取值属性与轴步骤
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub css: Option<CssSelector>,
+    /// 无法转换的语法或无效的 CSS
+    pub unsupported: Vec<String>,
+    /// 无法转换时能否回退为 XPath 求值
+    pub xpath_fallback: bool,
+}
+
+/// 只转换不执行，返回 XPath 转换为 CSS 的结果与不支持的语法
+pub fn debug_xpath(xpath: &str, strict_positions: bool) -> XPathDebug {
+    let converted = if strict_positions {
+        xpath_to_css_with(xpath, PositionMode::XPath)
+    } else {
+        xpath_to_css(xpath)
+    };
+
+    let mut unsupported = Vec::new();
+    let css = match converted {
+        Ok(css) => {
+            if let Err(e) = CompiledSelector::new(css.clone()) {
+                unsupported.push(format!("无效的 CSS 选择器: {}", e));
+            }
+            Some(css)
+        }
+        Err(e) => {
+            unsupported.push(e);
+            None
+        }
+    };
+    let xpath_fallback = !unsupported.is_empty() && xpath_eval::validate(xpath.trim()).is_ok();
+
+    XPathDebug {
+        css,
+        unsupported,
+        xpath_fallback,
+    }
+}
+
+/// 获取元素的链接属性 (href，其次 data-href)
+fn element_href(element: &ElementRef) -> Option<String> {
+    element.value().attr("href")
+        .or_else(|| element.value().attr("data-href"))
+        .map(|s| s.to_string())
+}
+
+/// 配置了链接后处理时，以 onclick 属性作为链接来源 (如 `play('123')`)
+fn onclick_source<'a>(element: &ElementRef<'a>, steps: &[CompiledStep]) -> Option<&'a str> {
+    if steps.is_empty() {
+        return None;
+    }
+    element.value().attr("onclick")
+}
+
+/// 获取元素的图片地址 (优先懒加载属性)
+/// 匹配到的元素不是 img 时，在其内部查找第一个 img
+fn element_image(element: &ElementRef) -> Option<String> {
+    const IMAGE_ATTRS: [&str; 4] = ["data-original", "data-src", "data-lazy-src", "src"];
+
+    let image_src = |e: &ElementRef| {
+        IMAGE_ATTRS
+            .iter()
+            .filter_map(|attr| e.value().attr(attr))
+            .map(|s| s.trim())
+            .find(|s| !s.is_empty() && !s.starts_with("data:"))
+            .map(|s| s.to_string())
+    };
+
+    if element.value().name() == "img" {
+        return image_src(element);
+    }
+
+    let img_selector = Selector::parse("img").ok()?;
+    element.select(&img_selector).find_map(|img| image_src(&img))
+}
+
 /// 应用位置过滤器
-fn apply_position_filter(index: usize, filter: &Option<PositionFilter>) -> bool {
+fn apply_position_filter<T>(mut items: Vec<T>, filter: &Option<PositionFilter>) -> Vec<T> {
     match filter {
-        Some(PositionFilter::GreaterThan(n)) => index >= *n,
-        None => true,
+        Some(filter @ PositionFilter::Range { .. }) => items
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| filter.contains(i + 1))
+            .map(|(_, item)| item)
+            .collect(),
+        Some(PositionFilter::LastMinus(n)) => match items.len().checked_sub(n + 1) {
+            Some(index) => vec![items.swap_remove(index)],
+            None => Vec::new(),
+        },
+        None => items,
     }
 }
 
@@ -231,11 +2227,23 @@ fn get_element_text(element: &ElementRef) -> String {
     element.text().collect::<Vec<_>>().join(" ").trim().to_string()
 }
 
+/// 将连续空白 (含换行) 合并为单个空格
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
 /// 规范化 URL
+/// 按 URL 规范 (`Url::join`) 解析相对路径、`../` 与 `//` 协议相对地址，
+/// 绝对地址原样返回；基准地址无法解析时退回简单拼接
 fn normalize_url(href: &str, base_url: &str) -> String {
     if href.starts_with("http://") || href.starts_with("https://") {
-        href.to_string()
-    } else if href.starts_with("//") {
+        return href.to_string();
+    }
+    if let Some(url) = url::Url::parse(base_url).ok().and_then(|base| base.join(href).ok()) {
+        return url.to_string();
+    }
+
+    if href.starts_with("//") {
         format!("https:{}", href)
     } else if href.starts_with("/") {
         format!("{}{}", base_url.trim_end_matches('/'), href)
@@ -244,19 +2252,103 @@ fn normalize_url(href: &str, base_url: &str) -> String {
     }
 }
 
-/// 从详情页 URL 提取基础 URL
-fn extract_base_url(detail_url: &str, rule_base_url: &str) -> String {
-    if let Ok(url) = url::Url::parse(detail_url) {
-        format!("{}://{}", url.scheme(), url.host_str().unwrap_or(""))
-    } else {
-        rule_base_url.trim_end_matches('/').to_string()
-    }
+/// 地址是否属于规则的站点 (http/https，域名与 baseURL 相同或互为子域名，忽略 www. 前缀)
+/// 用于拒绝借规则的请求头访问任意地址
+pub fn belongs_to_rule(rule: &Rule, url: &str) -> bool {
+    let host = |url: &str| {
+        let url = url::Url::parse(url).ok().filter(|u| matches!(u.scheme(), "http" | "https"))?;
+        let host = url.host_str()?.to_ascii_lowercase();
+        Some(host.strip_prefix("www.").map(str::to_string).unwrap_or(host))
+    };
+    let (Some(target), Some(base)) = (host(url), host(&rule.base_url)) else {
+        return false;
+    };
+    let is_subdomain = |a: &str, b: &str| a.strip_suffix(b).is_some_and(|prefix| prefix.ends_with('.'));
+    target == base || is_subdomain(&target, &base) || is_subdomain(&base, &target)
+}
+
+/// 规则的 baseURL 作为目录基准 (保证以 `/` 结尾，使相对路径拼接在其下)
+fn rule_base(rule: &Rule) -> String {
+    format!("{}/", rule.base_url.trim_end_matches('/'))
+}
+
+/// 文档的基准地址: 有 `<base href>` 时以它 (相对 page_url 解析) 为准，否则为 page_url
+fn document_base(document: &Html, page_url: &str) -> String {
+    document
+        .select(&BASE_SELECTOR)
+        .filter_map(|e| e.value().attr("href"))
+        .map(str::trim)
+        .find(|href| !href.is_empty())
+        .map(|href| normalize_url(href, page_url))
+        .unwrap_or_else(|| page_url.to_string())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_retry_policy() {
+        let rule = Rule {
+            retry_attempts: Some(100),
+            retry_backoff_ms: Some(200),
+            ..Default::default()
+        };
+        let policy = retry_policy(&rule);
+        assert_eq!(policy.attempts, MAX_RETRY_ATTEMPTS);
+        assert_eq!(policy.backoff_ms, 200);
+
+        let rule = Rule {
+            retry_attempts: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(retry_policy(&rule).attempts, 1);
+    }
+
+    #[test]
+    fn test_missing_fields() {
+        let rule = Rule {
+            name: "demo".to_string(),
+            base_url: "https://example.com".to_string(),
+            search_url: "https://example.com/search".to_string(),
+            search_list: "//div".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(missing_fields(&rule), ["searchName", "searchURL (@keyword)"]);
+
+        // JSON 规则没有名称元素可以取链接，searchResult 必须填写
+        let rule = Rule {
+            rule_type: RULE_TYPE_JSON.to_string(),
+            search_url: "https://example.com/api?q=@keyword".to_string(),
+            search_name: "title".to_string(),
+            ..rule
+        };
+        assert_eq!(missing_fields(&rule), ["searchResult"]);
+    }
+
+    #[test]
+    fn test_rebase_url() {
+        // 站点跳转到新域名：原站点链接改写到新域名
+        assert_eq!(
+            rebase_url(
+                "https://old.com/video/1?x=1",
+                "https://old.com",
+                "https://new.com:8443/search?wd=a"
+            ),
+            Some("https://new.com:8443/video/1?x=1".to_string())
+        );
+        // 未跳转
+        assert_eq!(
+            rebase_url("https://old.com/video/1", "https://old.com", "https://old.com/search"),
+            None
+        );
+        // 第三方链接保持原样
+        assert_eq!(
+            rebase_url("https://cdn.com/video/1", "https://old.com", "https://new.com/search"),
+            None
+        );
+    }
+
     #[test]
     fn test_normalize_url() {
         assert_eq!(
@@ -273,6 +2365,119 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_document_base() {
+        // 相对路径按页面目录解析，协议相对地址沿用页面协议
+        assert_eq!(
+            normalize_url("../play/1.html", "https://example.com/v/12/index.html"),
+            "https://example.com/v/play/1.html"
+        );
+        assert_eq!(
+            normalize_url("//cdn.example.com/a.jpg", "http://example.com/"),
+            "http://cdn.example.com/a.jpg"
+        );
+
+        let document = Html::parse_document(r#"<html><head><base href="//cdn.example.com/s/"></head></html>"#);
+        let base = document_base(&document, "https://example.com/search?q=1");
+        assert_eq!(base, "https://cdn.example.com/s/");
+        assert_eq!(normalize_url("detail/1", &base), "https://cdn.example.com/s/detail/1");
+
+        let document = Html::parse_document("<html><body></body></html>");
+        assert_eq!(document_base(&document, "https://example.com/a/"), "https://example.com/a/");
+    }
+
+    #[test]
+    fn test_request_config() {
+        let mut rule = Rule {
+            base_url: "https://example.com/".to_string(),
+            ..Default::default()
+        };
+        let config = request_config(&rule);
+        assert_eq!(config.referer.as_deref(), Some("https://example.com/"));
+        assert_eq!(config.user_agent, None);
+        assert_eq!(config.cookie, None);
+
+        rule.referer = "https://www.example.com/search".to_string();
+        rule.user_agent = "Mozilla/5.0 (iPhone)".to_string();
+        rule.headers.insert("X-Requested-With".to_string(), "XMLHttpRequest".to_string());
+        rule.cookies.insert("theme".to_string(), "dark".to_string());
+        rule.cookies.insert("age_verified".to_string(), "1".to_string());
+        let config = request_config(&rule);
+        assert_eq!(config.cookie.as_deref(), Some("age_verified=1; theme=dark"));
+        assert_eq!(config.referer.as_deref(), Some("https://www.example.com/search"));
+        assert_eq!(config.user_agent.as_deref(), Some("Mozilla/5.0 (iPhone)"));
+        assert_eq!(
+            config.headers,
+            vec![("X-Requested-With".to_string(), "XMLHttpRequest".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_request_timeout() {
+        let mut rule = Rule::default();
+        let secs = Duration::from_secs;
+        assert_eq!(request_timeout(&rule, None), None);
+        assert_eq!(request_timeout(&rule, Some(secs(1))), Some(secs(1)));
+        assert_eq!(
+            request_timeout(&rule, Some(secs(u64::MAX))),
+            Some(secs(CONFIG.timeout_seconds))
+        );
+
+        rule.timeout_secs = Some(0);
+        assert_eq!(request_timeout(&rule, None), None);
+        rule.timeout_secs = Some(60);
+        assert_eq!(request_timeout(&rule, None), Some(secs(60)));
+        assert_eq!(request_timeout(&rule, Some(secs(5))), Some(secs(5)));
+    }
+
+    #[test]
+    fn test_encode_keyword() {
+        assert_eq!(encode_keyword("进击", ""), "%E8%BF%9B%E5%87%BB");
+        assert_eq!(encode_keyword("进击", "gbk"), "%BD%F8%BB%F7");
+        assert_eq!(encode_keyword("进击", "GB2312"), "%BD%F8%BB%F7");
+        assert_eq!(encode_keyword("a b", "gbk"), "a%20b");
+        assert_eq!(encode_keyword("进击", "unknown"), "%E8%BF%9B%E5%87%BB");
+    }
+
+    #[test]
+    fn test_build_post_request() {
+        // 无模板：查询参数转表单 (保留端口)
+        let mut rule = Rule {
+            use_post: true,
+            ..Default::default()
+        };
+        let post = build_post_request(
+            &rule,
+            "http://example.com:8080/search.php?wd=%E8%BF%9B%E5%87%BB&submit=",
+            "进击",
+            1,
+        )
+        .unwrap();
+        assert_eq!(post.url, "http://example.com:8080/search.php");
+        assert_eq!(post.body, "wd=%E8%BF%9B%E5%87%BB&submit=");
+        assert_eq!(post.content_type, FORM_CONTENT_TYPE);
+
+        // 表单模板
+        rule.search_body = "searchword=@keyword&page=@page".to_string();
+        let post = build_post_request(&rule, "https://example.com/search", "a b", 2).unwrap();
+        assert_eq!(post.url, "https://example.com/search");
+        assert_eq!(post.body, "searchword=a%20b&page=2");
+        assert_eq!(post.content_type, FORM_CONTENT_TYPE);
+
+        // JSON 模板 (自动识别 Content-Type)
+        rule.search_body = r#"{"kw":"@keyword","page":@page}"#.to_string();
+        let post = build_post_request(&rule, "https://example.com/api", "a\"b", 1).unwrap();
+        assert_eq!(post.body, r#"{"kw":"a\"b","page":1}"#);
+        assert_eq!(post.content_type, JSON_CONTENT_TYPE);
+
+        // 显式 Content-Type
+        rule.search_body = "@keyword".to_string();
+        rule.search_content_type = "text/plain".to_string();
+        let post = build_post_request(&rule, "https://example.com/api", "进击", 1).unwrap();
+        assert_eq!(post.body, "进击");
+        assert_eq!(post.content_type, "text/plain");
+    }
+
     #[test]
     fn test_parse_html_with_css() {
         let html = r#"
@@ -296,6 +2501,578 @@ mod tests {
         assert_eq!(items.len(), 2);
     }
 
+    #[test]
+    fn test_debug_select() {
+        let html = r#"
+        <ul>
+            <li><a href="/a/1">第一集</a></li>
+            <li><a data-href="/a/2">第二集</a></li>
+            <li><a href="/a/3">第三集</a></li>
+        </ul>
+        "#;
+
+        let result = debug_select(html, Some("//ul/li[position() > 1]"), None).unwrap();
+        assert_eq!(result.css.selector, "ul > li");
+        assert_eq!(result.count, 2);
+        assert_eq!(result.samples[0].text, "第二集");
+
+        // 中间路径段的位置条件回退为 XPath 求值
+        let result = debug_select(html, Some("//ul/li[position() > 1]/a"), None).unwrap();
+        assert!(result.xpath_fallback);
+        assert_eq!(result.count, 2);
+        assert_eq!(result.samples[0].text, "第二集");
+        assert_eq!(result.samples[0].href.as_deref(), Some("/a/2"));
+
+        let result = debug_select(html, Some("//ul/li/a[contains(text(), '三')]"), None).unwrap();
+        assert_eq!(result.count, 1);
+        assert_eq!(result.samples[0].href.as_deref(), Some("/a/3"));
+
+        let result = debug_select(html, None, Some("li a[href]")).unwrap();
+        assert_eq!(result.count, 2);
+        assert!(debug_select(html, None, None).is_err());
+    }
+
+    #[test]
+    fn test_sibling_axis_select() {
+        let html = r#"
+        <div>
+            <h3>线路1</h3><p>说明</p><ul><li>1</li></ul><ul><li>2</li></ul>
+            <h3>线路2</h3><ul><li>3</li></ul>
+        </div>
+        "#;
+
+        let result = debug_select(html, Some("//h3/following-sibling::ul[1]"), None).unwrap();
+        assert_eq!(result.count, 2);
+        assert_eq!(result.samples[0].text, "1");
+        assert_eq!(result.samples[1].text, "3");
+
+        // 多个 h3 指向同一个兄弟时只保留一次
+        let result = debug_select(html, Some("//ul/preceding-sibling::h3[1]"), None).unwrap();
+        assert_eq!(result.count, 2);
+
+        let result = debug_select(html, Some("//ul[not(@class='x')]"), None).unwrap();
+        assert_eq!(result.count, 3);
+        assert!(!result.xpath_fallback);
+
+        // 无法转换为 CSS 的表达式回退为 XPath 求值
+        let result = debug_select(html, Some("//ul[li[text()='2']]/preceding-sibling::h3[1]"), None).unwrap();
+        assert!(result.xpath_fallback);
+        assert_eq!(result.count, 1);
+        assert_eq!(result.samples[0].text, "线路1");
+
+        let result = debug_select(html, Some("//li/.."), None).unwrap();
+        assert_eq!(result.count, 3);
+        let result = debug_select(html, Some("//ul/parent::div"), None).unwrap();
+        assert_eq!(result.count, 1);
+    }
+
+    #[test]
+    fn test_debug_xpath() {
+        let result = debug_xpath("//ul/li/a[position() > 1][contains(text(), '集')]/@href", false);
+        let css = result.css.unwrap();
+        assert_eq!(css.selector, "ul > li > a");
+        assert!(css.position_filter.is_some() && css.text_filter.is_some());
+        assert_eq!(css.attribute.as_deref(), Some("href"));
+        assert!(result.unsupported.is_empty());
+        assert!(!result.xpath_fallback);
+
+        let result = debug_xpath("//div[count(a) > 1]", false);
+        assert!(result.css.is_none());
+        assert_eq!(result.unsupported.len(), 1);
+        assert!(result.xpath_fallback);
+
+        assert!(!debug_xpath("//div[", false).xpath_fallback);
+    }
+
+    #[test]
+    fn test_strict_positions() {
+        let html = r#"
+        <ul><li class="ad">广告</li><li class="ep"><a href="/1">1</a></li><li class="ep"><a href="/2">2</a></li></ul>
+        <ul><li class="ep"><a href="/3">3</a></li><li class="ep"><a href="/4">4</a></li></ul>
+        "#;
+        let document = Html::parse_document(html);
+        let texts = |mode: PositionMode| {
+            let selector = compile_selector("//ul/li[@class='ep'][2]", "测试", mode).unwrap();
+            selector.select_document(&document).iter().map(get_element_text).collect::<Vec<_>>()
+        };
+
+        // nth-of-type(2) 在第一个 ul 中命中的是第一个 .ep
+        assert_eq!(texts(PositionMode::NthOfType), vec!["1", "4"]);
+        // XPath 语义：每个 ul 中第 2 个 .ep
+        let rule = Rule {
+            strict_positions: true,
+            ..Default::default()
+        };
+        assert_eq!(texts(position_mode(&rule)), vec!["2", "4"]);
+
+        // 位置谓词在中间路径段时回退为 XPath 求值
+        let selector = compile_selector("//ul/li[@class='ep'][2]/a", "测试", PositionMode::XPath).unwrap();
+        let hrefs: Vec<String> = selector
+            .select_document(&document)
+            .iter()
+            .filter_map(|e| selector.href(e))
+            .collect();
+        assert_eq!(hrefs, vec!["/2", "/4"]);
+    }
+
+    #[test]
+    fn test_parse_search_results_with_extensions() {
+        let html = r#"
+        <div class="list">
+            <div class="item">
+                <a class="thumb" href="/video/1"><img src="data:image/gif;base64,R0lGOD" data-original="/cover/1.jpg"></a>
+                <h3><a href="/video/1">动漫1</a></h3>
+                <p class="desc">简介：
+                    第一部</p>
+                <span class="status">更新至第12集</span><span class="status">HD</span>
+            </div>
+            <div class="item">
+                <h3><a href="/video/2">动漫2</a></h3>
+            </div>
+        </div>
+        "#;
+
+        let rule = Rule {
+            name: "test".to_string(),
+            base_url: "https://example.com".to_string(),
+            search_list: "//div[@class='item']".to_string(),
+            search_name: "//h3/a".to_string(),
+            search_result: "//h3/a".to_string(),
+            search_cover: "//a[@class='thumb']".to_string(),
+            search_description: "//p[@class='desc']".to_string(),
+            search_status: "//span[@class='status']".to_string(),
+            ..Default::default()
+        };
+
+        let items = parse_search_results(&rule, html).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].url, "https://example.com/video/1");
+        assert_eq!(items[0].cover.as_deref(), Some("https://example.com/cover/1.jpg"));
+        assert_eq!(items[0].description.as_deref(), Some("简介： 第一部"));
+        assert_eq!(items[0].tags, Some(vec!["更新至第12集".to_string(), "HD".to_string()]));
+        assert_eq!(items[1].cover, None);
+        assert_eq!(items[1].description, None);
+        assert_eq!(items[1].tags, None);
+    }
+
+    #[test]
+    fn test_attribute_step_selectors() {
+        let html = r#"
+        <div class="item">
+            <img src="/placeholder.gif" data-src="/cover/1.jpg" alt="动漫1">
+            <a href="javascript:;" data-url="/video/1">播放</a>
+        </div>
+        "#;
+
+        let rule = Rule {
+            name: "test".to_string(),
+            base_url: "https://example.com".to_string(),
+            search_list: "//div[@class='item']".to_string(),
+            search_name: "//img/@alt".to_string(),
+            search_result: "//a/@data-url".to_string(),
+            search_cover: "//img/@data-src".to_string(),
+            ..Default::default()
+        };
+
+        let items = parse_search_results(&rule, html).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].name, "动漫1");
+        assert_eq!(items[0].url, "https://example.com/video/1");
+        assert_eq!(items[0].cover.as_deref(), Some("https://example.com/cover/1.jpg"));
+    }
+
+    #[test]
+    fn test_extract_magnets() {
+        let html = r#"
+        <div class="list">
+            <div class="item">
+                <h3><a href="/video/1">动漫1</a></h3>
+                <a href="magnet:?xt=urn:btih:ABCDEF0123&amp;dn=ep1">磁力</a>
+                <a href="/dl/1.torrent">种子</a>
+                <input value="magnet:?xt=urn:btih:FEDCBA9876">
+            </div>
+            <div class="item">
+                <h3><a href="/video/2">动漫2</a></h3>
+                <span class="hash">magnet:?xt=urn:btih:1111</span>
+            </div>
+        </div>
+        "#;
+
+        let mut rule = Rule {
+            base_url: "https://example.com".to_string(),
+            search_list: "//div[@class='item']".to_string(),
+            search_name: "//h3/a".to_string(),
+            ..Default::default()
+        };
+        let items = parse_search_results(&rule, html).unwrap();
+        assert_eq!(
+            items[0].magnets,
+            Some(vec![
+                "magnet:?xt=urn:btih:ABCDEF0123&dn=ep1".to_string(),
+                "https://example.com/dl/1.torrent".to_string(),
+                "magnet:?xt=urn:btih:FEDCBA9876".to_string(),
+            ])
+        );
+
+        // 指定选择器时只取匹配元素
+        rule.search_magnet = "//span[@class='hash']".to_string();
+        let items = parse_search_results(&rule, html).unwrap();
+        assert_eq!(items[0].magnets, None);
+        assert_eq!(items[1].magnets, Some(vec!["magnet:?xt=urn:btih:1111".to_string()]));
+    }
+
+    #[test]
+    fn test_post_process() {
+        let html = r#"
+        <div class="item">
+            <h3><a onclick="play('1024')">【首发】 动漫1 </a></h3>
+        </div>
+        <div class="playlist"><a href="/play/1-1.html?from=list">第01集(HD)</a></div>
+        "#;
+        let rule: Rule = serde_json::from_value(serde_json::json!({
+            "name": "test",
+            "baseURL": "https://example.com",
+            "searchURL": "https://example.com/search?wd=@keyword",
+            "searchList": "//div[@class='item']",
+            "searchName": "//h3/a",
+            "chapterRoads": "//div[@class='playlist']",
+            "chapterResult": ".//a",
+            "postProcess": {
+                "name": [{"replace": "^【[^】]*】"}],
+                "url": [{"extract": r"play\('(\d+)'\)", "template": "/video/$1.html"}],
+                "episodeName": [{"extract": r"第(\d+)集"}],
+                "episodeUrl": [{"replace": r"\?.*$"}]
+            }
+        }))
+        .unwrap();
+
+        let items = parse_search_results(&rule, html).unwrap();
+        assert_eq!(items[0].name, "动漫1");
+        assert_eq!(items[0].url, "https://example.com/video/1024.html");
+
+        let roads = parse_episodes(&rule, html, "https://example.com/video/1024.html", None).unwrap();
+        assert_eq!(roads[0].episodes[0].name, "01");
+        assert_eq!(roads[0].episodes[0].url, "https://example.com/play/1-1.html");
+
+        let bad = Rule {
+            post_process: serde_json::from_value(serde_json::json!({"name": [{"replace": "("}]})).unwrap(),
+            ..Default::default()
+        };
+        assert!(precompile(&bad).iter().any(|e| e.contains("postProcess.name")));
+    }
+
+    #[test]
+    fn test_parse_episodes_single_road() {
+        let html = r#"
+        <div class="playlist"><a href="/play/1-1">第1集</a><a href="/play/1-2">第2集</a></div>
+        <div class="playlist"><a href="/play/2-1">第1集</a></div>
+        "#;
+        let rule = Rule {
+            base_url: "https://www.example.com".to_string(),
+            chapter_roads: "//div[@class='playlist']".to_string(),
+            chapter_result: ".//a".to_string(),
+            ..Default::default()
+        };
+        let page = "https://www.example.com/detail/1";
+
+        assert_eq!(parse_episodes(&rule, html, page, None).unwrap().len(), 2);
+
+        let roads = parse_episodes(&rule, html, page, Some(&RoadSelector::Index(1))).unwrap();
+        assert_eq!(roads.len(), 1);
+        assert_eq!(roads[0].name.as_deref(), Some("线路2"));
+        assert_eq!(roads[0].episodes[0].url, "https://www.example.com/play/2-1");
+
+        let road = RoadSelector::Name("线路1".to_string());
+        let roads = parse_episodes(&rule, html, page, Some(&road)).unwrap();
+        assert_eq!(roads[0].episodes.len(), 2);
+
+        assert!(parse_episodes(&rule, html, page, Some(&RoadSelector::Index(5))).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_decoder_steps() {
+        // data-url = base64('{"data":{"urls":["/play/1.html"]}}')
+        let html = r#"
+        <div class="playlist">
+            <a href="javascript:;" data-url="eyJkYXRhIjp7InVybHMiOlsiL3BsYXkvMS5odG1sIl19fQ==">第1集</a>
+            <a href="javascript:;" data-url="eyJkYXRhIjp7InVybHMiOlsiJTJGcGxheSUyRjIuaHRtbCJdfX0">第2集</a>
+        </div>
+        "#;
+        let rule: Rule = serde_json::from_value(serde_json::json!({
+            "name": "test",
+            "baseURL": "https://example.com",
+            "searchURL": "https://example.com/search?wd=@keyword",
+            "chapterRoads": "//div[@class='playlist']",
+            "chapterResult": ".//a",
+            "postProcess": {
+                "episodeUrlAttr": "data-url",
+                "episodeUrl": [
+                    {"decode": "base64"},
+                    {"decode": "json:data.urls.0"},
+                    {"decode": "url"}
+                ]
+            }
+        }))
+        .unwrap();
+
+        let roads = parse_episodes(&rule, html, "https://example.com/v/1", None).unwrap();
+        let urls: Vec<&str> = roads[0].episodes.iter().map(|e| e.url.as_str()).collect();
+        assert_eq!(urls, vec!["https://example.com/play/1.html", "https://example.com/play/2.html"]);
+
+        assert_eq!(Decoder::Unescape.decode("%u7B2C1%u96C6").as_deref(), Some("第1集"));
+        assert_eq!(Decoder::parse("rot13"), None);
+    }
+
+    #[test]
+    fn test_diagnose_search() {
+        let page = Page {
+            body: r#"<div class="item"><h3>动漫1</h3></div><div class="item"><h3></h3></div>"#.to_string(),
+            final_url: "https://example.com/search".to_string(),
+            status: 200,
+            redirects: Vec::new(),
+        };
+        let rule = Rule {
+            base_url: "https://example.com".to_string(),
+            search_list: "//div[@class='item']".to_string(),
+            search_name: "//h3".to_string(),
+            search_result: "//a".to_string(),
+            ..Default::default()
+        };
+
+        let diagnostics = diagnose_search(&rule, &page, DebugMode::Diagnostics);
+        let counts: Vec<(&str, usize)> = diagnostics
+            .stages
+            .iter()
+            .map(|s| (s.stage.as_str(), s.count))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![("searchList", 2), ("searchName", 1), ("searchResult", 0), ("items", 0)]
+        );
+        assert_eq!(diagnostics.selectors["searchList"].selector, "div.item");
+        assert!(diagnostics.snapshot.is_none());
+
+        let diagnostics = diagnose_search(&rule, &page, DebugMode::Snapshot);
+        assert_eq!(diagnostics.snapshot.as_deref(), Some(page.body.as_str()));
+        assert_eq!(truncate_snapshot(&"芙".repeat(DEBUG_SNAPSHOT_BYTES)).len() % 3, 0);
+    }
+
+    #[test]
+    fn test_dedup_roads() {
+        let html = r#"
+        <div class="playlist"><a href="/play/1">第1集</a><a href="/play/2">第2集</a></div>
+        <div class="playlist"><a href="/play/3">第1集</a></div>
+        <div class="playlist"><a href="/play/1">01</a><a href="/play/2">02</a></div>
+        "#;
+        let rule = Rule {
+            base_url: "https://www.example.com".to_string(),
+            chapter_roads: "//div[@class='playlist']".to_string(),
+            chapter_result: ".//a".to_string(),
+            ..Default::default()
+        };
+
+        let roads = parse_episodes(&rule, html, "https://www.example.com/detail/1", None).unwrap();
+        assert_eq!(roads.len(), 2);
+        assert_eq!(roads[0].name.as_deref(), Some("线路1"));
+        assert_eq!(roads[0].aliases, vec!["线路3".to_string()]);
+        assert_eq!(roads[1].name.as_deref(), Some("线路2"));
+        assert!(roads[1].aliases.is_empty());
+    }
+
+    #[test]
+    fn test_precompile() {
+        let mut rule = Rule {
+            search_list: "//div[@class='item']".to_string(),
+            search_name: ".//h3/a".to_string(),
+            ..Default::default()
+        };
+        assert!(precompile(&rule).is_empty());
+        assert!(Arc::ptr_eq(&rule_selectors(&rule), &rule_selectors(&rule)));
+
+        // 未配置章节选择器时不检查
+        rule.chapter_roads = "//div[@class='playlist'".to_string();
+        assert!(precompile(&rule).is_empty());
+        rule.chapter_result = ".//a".to_string();
+        assert_eq!(precompile(&rule).len(), 1);
+
+        rule.search_name = String::new();
+        assert_eq!(precompile(&rule).len(), 2);
+    }
+
+    #[test]
+    fn test_precompile_json_paths() {
+        let mut rule = Rule {
+            rule_type: RULE_TYPE_JSON.to_string(),
+            search_list: "$.data.list".to_string(),
+            search_name: "title".to_string(),
+            search_result: "url".to_string(),
+            ..Default::default()
+        };
+        assert!(precompile(&rule).is_empty());
+        assert!(rule_selectors(&rule).json.is_ok());
+
+        // 同样的选择器在 HTML 规则中不按 JSONPath 编译
+        rule.rule_type = String::new();
+        assert!(rule_selectors(&rule).json.is_err());
+
+        rule.rule_type = RULE_TYPE_JSON.to_string();
+        rule.search_cover = "$[".to_string();
+        assert_eq!(precompile(&rule).len(), 1);
+    }
+
+    #[test]
+    fn test_validate_selectors() {
+        let mut rule = Rule {
+            search_list: "//div[@class='item']".to_string(),
+            search_name: ".//h3/a".to_string(),
+            ..Default::default()
+        };
+        assert!(validate_selectors(&rule).is_empty());
+
+        rule.search_cover = ".//img[count(@src) > 0]".to_string();
+        rule.chapter_roads = "//div[@class='playlist'".to_string();
+        rule.search_name = String::new();
+        let issues = validate_selectors(&rule);
+        let levels: Vec<_> = issues
+            .iter()
+            .map(|i| (i.field.as_str(), i.level.as_str()))
+            .collect();
+        assert_eq!(
+            levels,
+            vec![
+                ("searchName", "invalid"),
+                ("searchCover", "unconvertible"),
+                ("chapterRoads", "invalid")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_json_results() {
+        let body = r#"{
+            "code": 0,
+            "data": {
+                "list": [
+                    {"title": "进击的巨人", "url": "/detail/1", "pic": "//img.example.com/1.jpg", "status": "完结"},
+                    {"title": "", "url": "/detail/2"},
+                    {"title": "间谍过家家", "url": "https://other.example.com/3", "intro": "  一个\n家庭  "}
+                ]
+            }
+        }"#;
+        let rule = Rule {
+            rule_type: "json".to_string(),
+            base_url: "https://www.example.com".to_string(),
+            search_list: "$.data.list".to_string(),
+            search_name: "title".to_string(),
+            search_result: "@.url".to_string(),
+            search_cover: "$.pic".to_string(),
+            search_description: ".intro".to_string(),
+            search_status: "status".to_string(),
+            ..Default::default()
+        };
+
+        let items = parse_search_results(&rule, body).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].name, "进击的巨人");
+        assert_eq!(items[0].url, "https://www.example.com/detail/1");
+        assert_eq!(items[0].cover.as_deref(), Some("https://img.example.com/1.jpg"));
+        assert_eq!(items[0].tags, Some(vec!["完结".to_string()]));
+        assert_eq!(items[1].url, "https://other.example.com/3");
+        assert_eq!(items[1].description.as_deref(), Some("一个 家庭"));
+
+        // [*] 写法结果相同
+        let rule = Rule {
+            search_list: "$.data.list[*]".to_string(),
+            ..rule
+        };
+        assert_eq!(parse_search_results(&rule, body).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_extract_play_sources() {
+        let html = r#"
+        <html>
+        <body>
+            <video><source src="/media/ep1.mp4"></video>
+            <script>var player_aaaa={"flag":"play","url":"https:\/\/cdn.example.com\/ep1\/index.m3u8","encrypt":0};</script>
+            <script>var backup = "https://backup.example.com/ep1.m3u8?token=1";</script>
+            <iframe src="//player.example.com/?url=ep1"></iframe>
+        </body>
+        </html>
+        "#;
+
+        let sources = extract_play_sources(html, "https://www.example.com/play/1-1.html");
+        let urls: Vec<(&str, &str)> = sources.iter().map(|s| (s.url.as_str(), s.kind.as_str())).collect();
+        assert_eq!(
+            urls,
+            vec![
+                ("https://www.example.com/media/ep1.mp4", "mp4"),
+                ("https://cdn.example.com/ep1/index.m3u8", "m3u8"),
+                ("https://backup.example.com/ep1.m3u8?token=1", "m3u8"),
+                ("https://player.example.com/?url=ep1", "iframe"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_legacy_play_sources() {
+        let html = r#"
+        <html>
+        <body>
+            <video src="/media/ep1.mp4"></video>
+            <iframe src="/jx/?url=https%3A%2F%2Fcdn.example.com%2Fep1.m3u8"></iframe>
+            <iframe src="https://player.example.com/?url=ep1"></iframe>
+        </body>
+        </html>
+        "#;
+
+        let sources = extract_legacy_play_sources(html, "https://www.example.com/play/1-1.html");
+        let urls: Vec<(&str, &str)> = sources.iter().map(|s| (s.url.as_str(), s.kind.as_str())).collect();
+        assert_eq!(
+            urls,
+            vec![
+                ("https://cdn.example.com/ep1.m3u8", "m3u8"),
+                ("https://player.example.com/?url=ep1", "iframe"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decode_player_url() {
+        assert_eq!(
+            decode_player_url("https%3A%2F%2Fa.com%2F%u7B2C1.m3u8", 1).unwrap(),
+            "https://a.com/第1.m3u8"
+        );
+        // base64("https%3A%2F%2Fa.com%2F1.m3u8")
+        assert_eq!(
+            decode_player_url("aHR0cHMlM0ElMkYlMkZhLmNvbSUyRjEubTN1OA==", 2).unwrap(),
+            "https://a.com/1.m3u8"
+        );
+    }
+
+    #[test]
+    fn test_belongs_to_rule() {
+        let rule = Rule {
+            base_url: "https://www.example.com/".to_string(),
+            ..Default::default()
+        };
+        assert!(belongs_to_rule(&rule, "https://www.example.com/detail/1.html"));
+        assert!(belongs_to_rule(&rule, "http://example.com/play/1-1.html"));
+        assert!(belongs_to_rule(&rule, "https://m.example.com/play/1-1.html"));
+        assert!(!belongs_to_rule(&rule, "https://evil.com/?https://www.example.com/"));
+        assert!(!belongs_to_rule(&rule, "https://notexample.com/"));
+        assert!(!belongs_to_rule(&rule, "file:///etc/passwd"));
+        assert!(!belongs_to_rule(&rule, "/detail/1.html"));
+    }
+
+    #[test]
+    fn test_js_unescape_non_ascii() {
+        assert_eq!(js_unescape("%中"), "%中");
+        assert_eq!(js_unescape("%u中文ab"), "%u中文ab");
+        assert_eq!(js_unescape("a%2中"), "a%2中");
+        assert_eq!(js_unescape("%u7B2C%41"), "第A");
+    }
+
     #[test]
     fn test_get_element_text() {
         let html = r#"<div><span>Hello</span> <span>World</span></div>"#;
@@ -0,0 +1,139 @@
+//! 请求 ID 与统一错误响应
+//! 每个请求分配一个 ID (写入日志与响应头)，所有错误以统一结构返回
+
+use crate::http_client::HttpClientError;
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use tracing::{info_span, Instrument};
+
+/// 请求 ID 响应头
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// 中间件：为每个请求分配 ID (沿用客户端传入的 X-Request-Id)，并写入日志 span 与响应头
+pub async fn request_id_middleware(req: Request, next: Next) -> Response {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty() && v.len() <= 64)
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string());
+
+    let span = info_span!("request", id = %request_id);
+    let mut response = REQUEST_ID
+        .scope(request_id.clone(), next.run(req).instrument(span))
+        .await;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// 获取当前请求的 ID (不在请求上下文中时返回 None)
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// 统一错误响应
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+    upstream_status: Option<u16>,
+    /// 上游限流时建议的等待时间/秒 (同时写入 Retry-After 头)
+    retry_after: Option<u64>,
+}
+
+/// 错误响应体
+#[derive(Debug, Serialize)]
+struct ErrorBody {
+    code: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    upstream_status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retry_after: Option<u64>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+            upstream_status: None,
+            retry_after: None,
+        }
+    }
+
+    /// 400 请求参数错误
+    pub fn bad_request(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, code, message)
+    }
+
+    /// 500 服务内部错误
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "internal_error", message)
+    }
+
+    /// 502 上游请求失败，自动从错误链中提取上游状态码
+    /// 上游限流时返回 429 与 Retry-After
+    pub fn upstream(message: impl Into<String>, error: &anyhow::Error) -> Self {
+        let client_error = error.chain().find_map(|e| e.downcast_ref::<HttpClientError>());
+        let message = format!("{}: {}", message.into(), error);
+
+        if let Some(HttpClientError::RateLimited(wait)) = client_error {
+            return Self {
+                status: StatusCode::TOO_MANY_REQUESTS,
+                code: "rate_limited",
+                message,
+                upstream_status: Some(429),
+                retry_after: Some(wait.as_secs().max(1)),
+            };
+        }
+
+        let upstream_status = match client_error {
+            Some(HttpClientError::BadStatus(status) | HttpClientError::Challenge(status)) => Some(*status),
+            _ => None,
+        };
+
+        Self {
+            status: StatusCode::BAD_GATEWAY,
+            code: "upstream_error",
+            message,
+            upstream_status,
+            retry_after: None,
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ErrorBody {
+            code: self.code,
+            message: self.message,
+            request_id: current_request_id(),
+            upstream_status: self.upstream_status,
+            retry_after: self.retry_after,
+        };
+        let mut response = (self.status, Json(body)).into_response();
+        if let Some(secs) = self.retry_after {
+            response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        response
+    }
+}
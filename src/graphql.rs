@@ -0,0 +1,343 @@
+//! GraphQL 接口
+//! 在一次查询中组合规则列表、聚合搜索结果与 Bangumi 条目/章节数据
+
+use crate::bangumi::{self, BangumiSubject};
+use crate::core::search_collect;
+use crate::rules::{get_builtin_rules, select_rules};
+use crate::types::{SearchOptions, StreamResult};
+use async_graphql::http::GraphiQLSource;
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, InputObject, Object, Schema, SimpleObject,
+};
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::response::{Html, IntoResponse};
+use std::time::Duration;
+
+/// GraphQL Schema
+pub type AppSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// 查询的最大嵌套深度
+const MAX_QUERY_DEPTH: usize = 8;
+
+/// 查询的最大复杂度 (普通字段计 1)
+const MAX_QUERY_COMPLEXITY: usize = 500;
+
+/// 一次聚合搜索的复杂度，限制通过别名在一次查询中发起的搜索数
+const SEARCH_COMPLEXITY: usize = 100;
+
+/// 构建 GraphQL Schema
+pub fn build_schema() -> AppSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .limit_depth(MAX_QUERY_DEPTH)
+        .limit_complexity(MAX_QUERY_COMPLEXITY)
+        .finish()
+}
+
+/// 请求携带的 Bangumi token (来自 Authorization 头)
+struct BangumiToken(Option<String>);
+
+/// POST /graphql - 执行 GraphQL 查询
+pub async fn graphql_handler(
+    State(schema): State<AppSchema>,
+    headers: HeaderMap,
+    req: GraphQLRequest,
+) -> GraphQLResponse {
+    let token = headers
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|v| v.to_string());
+
+    schema
+        .execute(req.into_inner().data(BangumiToken(token)))
+        .await
+        .into()
+}
+
+/// GET /graphql - GraphiQL 调试页面
+pub async fn graphiql_handler() -> impl IntoResponse {
+    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+/// 获取本次请求有效的 Bangumi token
+fn bangumi_token<'a>(ctx: &'a Context<'_>) -> Option<&'a str> {
+    let user_token = ctx
+        .data_opt::<BangumiToken>()
+        .and_then(|t| t.0.as_deref());
+    bangumi::get_effective_token(user_token)
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// 所有已加载的规则
+    async fn rules(&self) -> Vec<RuleInfo> {
+        get_builtin_rules()
+            .iter()
+            .map(|r| RuleInfo {
+                name: r.name.clone(),
+                version: r.version.clone(),
+                base_url: r.base_url.clone(),
+                color: r.color.clone(),
+                tags: r.tags.clone(),
+                magic: r.magic,
+            })
+            .collect()
+    }
+
+    /// 使用指定规则聚合搜索
+    #[graphql(complexity = "SEARCH_COMPLEXITY + child_complexity")]
+    async fn search(
+        &self,
+        keyword: String,
+        rules: Vec<String>,
+        #[graphql(default)] options: SearchInput,
+    ) -> Vec<StreamResult> {
+        search_sources(&keyword, &rules, &options.into()).await
+    }
+
+    /// Bangumi 条目详情
+    async fn subject(&self, ctx: &Context<'_>, id: i64) -> async_graphql::Result<Subject> {
+        let subject = bangumi::get_subject_v0(id, bangumi_token(ctx)).await?;
+        Ok(Subject(subject))
+    }
+}
+
+/// 搜索选项
+#[derive(InputObject, Default)]
+struct SearchInput {
+    /// 同时搜索 Bangumi 标题别名
+    #[graphql(default)]
+    expand_aliases: bool,
+    /// 只为前 N 个结果获取集数
+    episode_items: Option<usize>,
+    /// N 个规则有结果后立即返回
+    first: Option<usize>,
+    /// 单次请求超时上限/秒 (0 或为空时不限制)
+    timeout_secs: Option<u64>,
+}
+
+impl From<SearchInput> for SearchOptions {
+    fn from(input: SearchInput) -> Self {
+        Self {
+            expand_aliases: input.expand_aliases,
+            episode_items: input.episode_items,
+            first: input.first,
+            timeout: input
+                .timeout_secs
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs),
+            ..Default::default()
+        }
+    }
+}
+
+/// 使用规则名列表执行搜索
+async fn search_sources(keyword: &str, rules: &[String], options: &SearchOptions) -> Vec<StreamResult> {
+    let names: Vec<&str> = rules.iter().map(|s| s.as_str()).collect();
+    let selected = select_rules(&names);
+    if selected.is_empty() {
+        return Vec::new();
+    }
+    search_collect(keyword, selected, options).await
+}
+
+/// 规则摘要
+#[derive(SimpleObject)]
+struct RuleInfo {
+    name: String,
+    version: String,
+    base_url: String,
+    color: String,
+    tags: Vec<String>,
+    magic: bool,
+}
+
+/// Bangumi 条目
+pub struct Subject(BangumiSubject);
+
+#[Object]
+impl Subject {
+    async fn id(&self) -> i64 {
+        self.0.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn name_cn(&self) -> &str {
+        &self.0.name_cn
+    }
+
+    async fn summary(&self) -> &str {
+        &self.0.summary
+    }
+
+    /// 放送日期
+    async fn air_date(&self) -> &str {
+        &self.0.air_date
+    }
+
+    /// 封面大图
+    async fn image(&self) -> Option<&str> {
+        self.0.images.as_ref().map(|i| i.large.as_str())
+    }
+
+    async fn score(&self) -> Option<f64> {
+        self.0.rating.as_ref().map(|r| r.score).filter(|s| *s > 0.0)
+    }
+
+    async fn rank(&self) -> Option<i32> {
+        self.0.rank.or_else(|| self.0.rating.as_ref().and_then(|r| r.rank))
+    }
+
+    async fn total_episodes(&self) -> Option<i32> {
+        self.0.total_episodes.or(self.0.eps)
+    }
+
+    /// 章节列表 (episode_type: 0=本篇, 1=SP, 2=OP, 3=ED)
+    async fn episodes(
+        &self,
+        ctx: &Context<'_>,
+        episode_type: Option<i32>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+    ) -> async_graphql::Result<Vec<BangumiEpisode>> {
+        let list = bangumi::get_episodes(self.0.id, episode_type, limit, offset, bangumi_token(ctx)).await?;
+        Ok(list
+            .data
+            .into_iter()
+            .map(|e| BangumiEpisode {
+                id: e.id,
+                episode_type: e.episode_type,
+                name: e.name,
+                name_cn: e.name_cn,
+                sort: e.sort,
+                ep: e.ep,
+                airdate: e.airdate,
+                duration: e.duration,
+            })
+            .collect())
+    }
+
+    /// 使用条目名称 (优先中文名) 在指定规则中搜索资源
+    #[graphql(complexity = "SEARCH_COMPLEXITY + child_complexity")]
+    async fn sources(
+        &self,
+        rules: Vec<String>,
+        #[graphql(default)] options: SearchInput,
+    ) -> Vec<StreamResult> {
+        let keyword = if self.0.name_cn.is_empty() {
+            &self.0.name
+        } else {
+            &self.0.name_cn
+        };
+        search_sources(keyword, &rules, &options.into()).await
+    }
+}
+
+/// Bangumi 章节
+#[derive(SimpleObject)]
+struct BangumiEpisode {
+    id: i64,
+    episode_type: i32,
+    name: String,
+    name_cn: String,
+    sort: f64,
+    ep: Option<f64>,
+    airdate: String,
+    duration: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rules_query() {
+        let response = build_schema().execute("{ rules { name baseUrl magic } }").await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        let data = response.data.into_json().unwrap();
+        let names: Vec<&str> = data["rules"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["name"].as_str().unwrap())
+            .collect();
+        let loaded: Vec<String> = get_builtin_rules().iter().map(|r| r.name.clone()).collect();
+        assert!(!names.is_empty());
+        assert_eq!(names, loaded);
+    }
+
+    #[tokio::test]
+    async fn test_search_unknown_rules() {
+        // 规则名都不存在时直接返回空列表，不发起请求
+        let response = build_schema()
+            .execute(
+                r#"{ search(keyword: "test", rules: ["__missing__"], options: { timeoutSecs: 1 }) {
+                    name items { name }
+                } }"#,
+            )
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(response.data.into_json().unwrap(), serde_json::json!({ "search": [] }));
+    }
+
+    #[tokio::test]
+    async fn test_query_depth_limit() {
+        let query = "{ __schema { types { fields { type { ofType { ofType { ofType { ofType { name } } } } } } } } }";
+        let response = build_schema().execute(query).await;
+        assert!(
+            response.errors.iter().any(|e| e.message.contains("nested too deep")),
+            "{:?}",
+            response.errors
+        );
+    }
+
+    #[tokio::test]
+    async fn test_query_complexity_limit() {
+        // 每次搜索计 SEARCH_COMPLEXITY，通过别名发起过多搜索时在执行前拒绝
+        let searches: String = (0..MAX_QUERY_COMPLEXITY / SEARCH_COMPLEXITY + 1)
+            .map(|i| format!(r#"s{}: search(keyword: "x", rules: ["__missing__"]) {{ name }} "#, i))
+            .collect();
+        let response = build_schema().execute(format!("{{ {} }}", searches)).await;
+        assert!(
+            response.errors.iter().any(|e| e.message.contains("too complex")),
+            "{:?}",
+            response.errors
+        );
+
+        let response = build_schema()
+            .execute(r#"{ a: search(keyword: "x", rules: ["__missing__"]) { name } }"#)
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+    }
+
+    #[test]
+    fn test_search_input_options() {
+        let options: SearchOptions = SearchInput {
+            expand_aliases: true,
+            episode_items: Some(3),
+            first: Some(2),
+            timeout_secs: Some(5),
+        }
+        .into();
+        assert!(options.expand_aliases);
+        assert_eq!(options.episode_items, Some(3));
+        assert_eq!(options.first, Some(2));
+        assert_eq!(options.timeout, Some(Duration::from_secs(5)));
+
+        // 0 表示不限制
+        let options: SearchOptions = SearchInput {
+            timeout_secs: Some(0),
+            ..Default::default()
+        }
+        .into();
+        assert_eq!(options.timeout, None);
+    }
+}
@@ -0,0 +1,161 @@
+//! 规则健康检查
+//! 定期使用探测关键词搜索每个规则，记录成功/失败、结果数量与耗时
+
+use crate::config::CONFIG;
+use crate::engine::search_with_rule;
+use crate::rules::get_builtin_rules;
+use crate::types::{Rule, SearchOptions};
+use futures::stream::{self, StreamExt};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// 各规则最近一次健康检查结果 (按规则名)
+static HEALTH: Lazy<RwLock<HashMap<String, RuleHealth>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// 单个规则的健康状态
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleHealth {
+    pub name: String,
+    /// ok (有结果) / empty (无结果) / error (请求或解析失败)
+    pub status: String,
+    pub result_count: usize,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// 最近一次检查时间
+    pub checked_at: String,
+    /// 连续失败次数 (无结果也计为失败)
+    pub consecutive_failures: u32,
+    /// 累计检查次数
+    pub checks: u32,
+    /// 累计失败次数
+    pub failures: u32,
+    /// 连续失败达到 HEALTH_QUARANTINE_THRESHOLD 次，不参与 rules=all 搜索
+    pub degraded: bool,
+}
+
+impl RuleHealth {
+    pub fn is_ok(&self) -> bool {
+        self.status == "ok"
+    }
+}
+
+/// 连续失败次数是否达到隔离阈值 (阈值为 0 时不隔离)
+fn exceeds_threshold(consecutive_failures: u32) -> bool {
+    let threshold = CONFIG.health_quarantine_threshold;
+    threshold > 0 && consecutive_failures >= threshold
+}
+
+/// 规则是否因持续失败被隔离
+pub fn is_degraded(name: &str) -> bool {
+    HEALTH
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(name)
+        .is_some_and(|h| h.degraded)
+}
+
+/// 使用探测关键词检查单个规则并记录结果
+pub async fn check_rule(rule: &Rule) -> RuleHealth {
+    let options = SearchOptions {
+        fetch_episodes: false,
+        ..Default::default()
+    };
+
+    let started = Instant::now();
+    // 探测需要访问上游，不使用缓存的响应
+    let search = search_with_rule(rule, &CONFIG.health_check_keyword, &options, None);
+    let result = crate::http_cache::bypass(search).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    let status = match (&result.error, result.items.is_empty()) {
+        (Some(_), _) => "error",
+        (None, true) => "empty",
+        (None, false) => "ok",
+    };
+
+    let mut health = HEALTH.write().unwrap_or_else(|e| e.into_inner());
+    let previous = health.get(&rule.name);
+    let was_degraded = previous.is_some_and(|p| p.degraded);
+    let failed = status != "ok";
+    let consecutive_failures = match (failed, previous) {
+        (false, _) => 0,
+        (true, Some(p)) => p.consecutive_failures + 1,
+        (true, None) => 1,
+    };
+    let degraded = exceeds_threshold(consecutive_failures);
+    match (was_degraded, degraded) {
+        (false, true) => warn!(
+            "🚧 规则 {} 连续 {} 次健康检查失败，已隔离",
+            rule.name, consecutive_failures
+        ),
+        (true, false) => info!("✅ 规则 {} 健康检查恢复，解除隔离", rule.name),
+        _ => {}
+    }
+
+    let entry = RuleHealth {
+        name: rule.name.clone(),
+        status: status.to_string(),
+        result_count: result.items.len(),
+        latency_ms,
+        error: result.error,
+        checked_at: chrono::Utc::now().to_rfc3339(),
+        consecutive_failures,
+        checks: previous.map_or(0, |p| p.checks) + 1,
+        failures: previous.map_or(0, |p| p.failures) + u32::from(failed),
+        degraded,
+    };
+    health.insert(rule.name.clone(), entry.clone());
+    entry
+}
+
+/// 检查所有启用的规则，返回检查结果
+pub async fn check_all() -> Vec<RuleHealth> {
+    let rules: Vec<Arc<Rule>> = get_builtin_rules();
+    info!("🩺 开始健康检查: {} 个规则", rules.len());
+
+    let results: Vec<RuleHealth> = stream::iter(rules)
+        .map(|rule| async move { check_rule(&rule).await })
+        .buffer_unordered(CONFIG.health_check_concurrency.max(1))
+        .collect()
+        .await;
+
+    let ok = results.iter().filter(|h| h.is_ok()).count();
+    info!("🩺 健康检查完成: {} 正常, {} 异常", ok, results.len() - ok);
+    results
+}
+
+/// 最近一次健康检查结果 (按规则名排序)
+pub fn snapshot() -> Vec<RuleHealth> {
+    let mut results: Vec<RuleHealth> = HEALTH
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .values()
+        .cloned()
+        .collect();
+    results.sort_by(|a, b| a.name.cmp(&b.name));
+    results
+}
+
+/// 启动定时健康检查任务 (HEALTH_CHECK_INTERVAL_MINS 为 0 时不启动)
+pub fn spawn_checker() {
+    let mins = CONFIG.health_check_interval_mins;
+    if mins == 0 {
+        return;
+    }
+
+    info!("🩺 规则健康检查已启用，间隔 {} 分钟", mins);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(mins * 60));
+        loop {
+            interval.tick().await;
+            check_all().await;
+        }
+    });
+}
@@ -0,0 +1,385 @@
+//! HTTP 响应缓存
+//! 按 URL 与请求配置缓存幂等 GET 请求的响应，遵守 Cache-Control / Expires / Vary，
+//! 过期后带上 ETag / Last-Modified 重新验证 (304 时继续使用缓存)，主要用于详情页与 Bangumi 资源
+
+use crate::config::CONFIG;
+use crate::storage::{self, StorageArea};
+use once_cell::sync::Lazy;
+use reqwest::header::{self, HeaderMap};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 内存中最多缓存的响应数
+const MEMORY_ENTRIES: usize = 256;
+/// 内存缓存中响应体的总字节数上限
+const MEMORY_BYTES: usize = 32 * 1024 * 1024;
+
+/// 内存缓存 (按缓存键)
+static MEMORY: Lazy<Mutex<HashMap<String, CachedResponse>>> = Lazy::new(Default::default);
+
+tokio::task_local! {
+    /// 在此范围内的请求不读取缓存 (健康检查、规则校验等探测需要访问上游)
+    static BYPASS: ();
+}
+
+/// 在不读取缓存的范围内执行 (响应仍会写入缓存)
+pub async fn bypass<F: Future>(f: F) -> F::Output {
+    BYPASS.scope((), f).await
+}
+
+fn bypassed() -> bool {
+    BYPASS.try_with(|_| ()).is_ok()
+}
+
+/// 缓存的响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedResponse {
+    pub status: u16,
+    /// 最终落地的地址
+    pub final_url: String,
+    pub content_type: Option<String>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// 过期时间 (Unix 时间戳/秒)
+    pub expires_at: u64,
+    #[serde(skip)]
+    pub body: Vec<u8>,
+}
+
+impl CachedResponse {
+    fn is_fresh(&self, now: u64) -> bool {
+        self.expires_at > now
+    }
+
+    /// 重新验证时附带的条件请求头
+    pub fn validators(&self) -> Vec<(String, String)> {
+        let mut headers = Vec::new();
+        if let Some(etag) = &self.etag {
+            headers.push(("If-None-Match".to_string(), etag.clone()));
+        }
+        if let Some(last_modified) = &self.last_modified {
+            headers.push(("If-Modified-Since".to_string(), last_modified.clone()));
+        }
+        headers
+    }
+}
+
+/// 缓存查询结果
+pub enum Lookup {
+    /// 仍在有效期内，可直接使用
+    Fresh(CachedResponse),
+    /// 已过期但带有校验信息，可发送条件请求
+    Stale(CachedResponse),
+    Miss,
+}
+
+fn enabled() -> bool {
+    matches!(CONFIG.http_cache.as_str(), "memory" | "disk")
+}
+
+fn disk_enabled() -> bool {
+    CONFIG.http_cache == "disk"
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// 响应可缓存的时长/秒；不允许缓存 (no-store、private、Vary: * / Cookie 或既无有效期也无校验信息) 时返回 None
+///
+/// 缓存键已包含请求配置中的全部请求头，只有 Cookie 会随会话 Cookie 存储变化，因此按 Cookie 区分的响应不缓存
+fn freshness(headers: &HeaderMap, now: SystemTime) -> Option<u64> {
+    let varies_by_session = headers
+        .get_all(header::VARY)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .any(|name| name == "*" || name == "cookie");
+    if varies_by_session {
+        return None;
+    }
+
+    let cache_control = headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(",")
+        .to_ascii_lowercase();
+    let mut no_cache = false;
+    let mut max_age = None;
+    let mut shared_max_age = None;
+    for directive in cache_control.split(',').map(str::trim) {
+        match directive.split_once('=') {
+            Some(("max-age", secs)) => max_age = secs.trim_matches('"').parse::<u64>().ok(),
+            Some(("s-maxage", secs)) => shared_max_age = secs.trim_matches('"').parse::<u64>().ok(),
+            _ if directive == "no-store" || directive == "private" => return None,
+            _ if directive == "no-cache" => no_cache = true,
+            _ => {}
+        }
+    }
+
+    let header_str = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    let ttl = if no_cache {
+        0
+    } else if let Some(secs) = shared_max_age.or(max_age) {
+        secs
+    } else if let Some(expires) = header_str(header::EXPIRES) {
+        chrono::DateTime::parse_from_rfc2822(expires)
+            .ok()
+            .and_then(|at| SystemTime::from(at).duration_since(now).ok())
+            .map_or(0, |d| d.as_secs())
+    } else {
+        0
+    };
+    let age = header_str(header::AGE)
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(0);
+    let ttl = ttl.saturating_sub(age);
+
+    let has_validator =
+        headers.contains_key(header::ETAG) || headers.contains_key(header::LAST_MODIFIED);
+    (ttl > 0 || has_validator).then_some(ttl)
+}
+
+/// 缓存键：地址 + 请求配置指纹 (同一地址携带不同请求头、Cookie 或经由不同出口时分别缓存)
+pub fn cache_key(url: &str, fingerprint: &str) -> String {
+    format!("{}#{}", url, hex::encode(&Sha256::digest(fingerprint.as_bytes())[..8]))
+}
+
+/// 缓存文件路径 (按缓存键的 SHA-256)
+fn disk_path(key: &str) -> PathBuf {
+    StorageArea::HttpCache
+        .dir()
+        .join(hex::encode(Sha256::digest(key.as_bytes())))
+}
+
+/// 缓存文件格式: 元数据 JSON 一行 + 响应体
+fn encode(entry: &CachedResponse) -> Vec<u8> {
+    let mut data = serde_json::to_vec(entry).unwrap_or_default();
+    data.push(b'\n');
+    data.extend_from_slice(&entry.body);
+    data
+}
+
+fn decode(data: &[u8]) -> Option<CachedResponse> {
+    let line = data.iter().position(|b| *b == b'\n')?;
+    let mut entry: CachedResponse = serde_json::from_slice(&data[..line]).ok()?;
+    entry.body = data[line + 1..].to_vec();
+    Some(entry)
+}
+
+async fn load(url: &str) -> Option<CachedResponse> {
+    if let Some(entry) = MEMORY.lock().unwrap_or_else(|e| e.into_inner()).get(url) {
+        return Some(entry.clone());
+    }
+    if !disk_enabled() {
+        return None;
+    }
+    let path = disk_path(url);
+    let entry = decode(&tokio::fs::read(&path).await.ok()?)?;
+    storage::touch(&path);
+    remember(url, entry.clone());
+    Some(entry)
+}
+
+/// 放入内存缓存，超出数量或字节上限时淘汰最早过期的条目
+fn remember(url: &str, entry: CachedResponse) {
+    if entry.body.len() > MEMORY_BYTES {
+        return;
+    }
+    let mut memory = MEMORY.lock().unwrap_or_else(|e| e.into_inner());
+    memory.remove(url);
+    let mut bytes: usize = memory.values().map(|e| e.body.len()).sum();
+    while memory.len() >= MEMORY_ENTRIES || bytes + entry.body.len() > MEMORY_BYTES {
+        let Some(oldest) = memory
+            .iter()
+            .min_by_key(|(_, e)| e.expires_at)
+            .map(|(url, _)| url.clone())
+        else {
+            break;
+        };
+        if let Some(removed) = memory.remove(&oldest) {
+            bytes -= removed.body.len();
+        }
+    }
+    memory.insert(url.to_string(), entry);
+}
+
+async fn save(url: &str, entry: CachedResponse) {
+    if disk_enabled() {
+        let path = disk_path(url);
+        if let Err(e) = storage::write_atomic(&path, &encode(&entry)).await {
+            tracing::warn!("写入 HTTP 缓存失败 {}: {}", path.display(), e);
+        }
+    }
+    remember(url, entry);
+}
+
+/// 查询缓存 (在 bypass 范围内总是未命中)
+pub async fn lookup(url: &str) -> Lookup {
+    if !enabled() || bypassed() {
+        return Lookup::Miss;
+    }
+    match load(url).await {
+        Some(entry) if entry.is_fresh(now_secs()) => Lookup::Fresh(entry),
+        Some(entry) if entry.etag.is_some() || entry.last_modified.is_some() => {
+            Lookup::Stale(entry)
+        }
+        _ => Lookup::Miss,
+    }
+}
+
+/// 缓存 200 响应 (按响应头判断是否允许缓存)
+pub async fn store(url: &str, final_url: &str, headers: &HeaderMap, body: &[u8]) {
+    if !enabled() {
+        return;
+    }
+    let Some(ttl) = freshness(headers, SystemTime::now()) else {
+        return;
+    };
+    let header_string = |name| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string())
+    };
+    let entry = CachedResponse {
+        status: 200,
+        final_url: final_url.to_string(),
+        content_type: header_string(header::CONTENT_TYPE),
+        etag: header_string(header::ETAG),
+        last_modified: header_string(header::LAST_MODIFIED),
+        expires_at: now_secs() + ttl,
+        body: body.to_vec(),
+    };
+    save(url, entry).await;
+}
+
+/// 重新验证得到 304：按新的响应头延长有效期，返回缓存的响应
+pub async fn revalidated(
+    url: &str,
+    mut entry: CachedResponse,
+    headers: &HeaderMap,
+) -> CachedResponse {
+    let ttl = freshness(headers, SystemTime::now()).unwrap_or(0);
+    entry.expires_at = now_secs() + ttl;
+    if let Some(etag) = headers.get(header::ETAG).and_then(|v| v.to_str().ok()) {
+        entry.etag = Some(etag.to_string());
+    }
+    save(url, entry.clone()).await;
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.append(*name, value.parse().unwrap());
+        }
+        map
+    }
+
+    #[test]
+    fn test_freshness() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_445_412_480);
+        assert_eq!(
+            freshness(&headers(&[("cache-control", "public, max-age=600")]), now),
+            Some(600)
+        );
+        assert_eq!(
+            freshness(
+                &headers(&[("cache-control", "max-age=600, s-maxage=60"), ("age", "20")]),
+                now
+            ),
+            Some(40)
+        );
+        assert_eq!(
+            freshness(
+                &headers(&[("expires", "Wed, 21 Oct 2015 07:38:00 GMT")]),
+                now
+            ),
+            Some(600)
+        );
+        assert_eq!(
+            freshness(&headers(&[("cache-control", "no-store, max-age=600")]), now),
+            None
+        );
+        assert_eq!(
+            freshness(&headers(&[("cache-control", "private, max-age=600")]), now),
+            None
+        );
+        // 没有有效期时只有带校验信息才缓存 (每次使用前重新验证)
+        assert_eq!(freshness(&headers(&[]), now), None);
+        assert_eq!(freshness(&headers(&[("etag", "\"abc\"")]), now), Some(0));
+        assert_eq!(
+            freshness(
+                &headers(&[
+                    ("cache-control", "no-cache, max-age=600"),
+                    ("etag", "\"abc\"")
+                ]),
+                now
+            ),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_freshness_vary() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_445_412_480);
+        assert_eq!(
+            freshness(&headers(&[("cache-control", "max-age=600"), ("vary", "Accept-Encoding")]), now),
+            Some(600)
+        );
+        assert_eq!(
+            freshness(&headers(&[("cache-control", "max-age=600"), ("vary", "Accept-Encoding, Cookie")]), now),
+            None
+        );
+        assert_eq!(freshness(&headers(&[("etag", "\"abc\""), ("vary", "*")]), now), None);
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let url = "https://example.com/detail/1";
+        assert_eq!(cache_key(url, "a"), cache_key(url, "a"));
+        assert_ne!(cache_key(url, "a"), cache_key(url, "b"));
+        assert!(cache_key(url, "a").starts_with("https://example.com/detail/1#"));
+    }
+
+    #[tokio::test]
+    async fn test_bypass() {
+        assert!(!bypassed());
+        assert!(bypass(async { bypassed() }).await);
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let entry = CachedResponse {
+            status: 200,
+            final_url: "https://example.com/detail/1".to_string(),
+            content_type: Some("text/html; charset=gbk".to_string()),
+            etag: Some("\"abc\"".to_string()),
+            last_modified: None,
+            expires_at: 42,
+            body: b"<html>\n</html>".to_vec(),
+        };
+        let decoded = decode(&encode(&entry)).unwrap();
+        assert_eq!(decoded.final_url, entry.final_url);
+        assert_eq!(decoded.etag, entry.etag);
+        assert_eq!(decoded.body, entry.body);
+    }
+}
@@ -1,27 +1,236 @@
+use crate::bounded;
 use crate::config::CONFIG;
+use crate::http_service::{self, PageRequest};
+use crate::stats;
 use once_cell::sync::Lazy;
-use reqwest::{Client, Response};
+use reqwest::cookie::{CookieStore, Jar};
+use reqwest::header::HeaderMap;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::Deserialize;
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::time::Duration;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tower::ServiceExt;
 
-/// 创建 HTTP 客户端
-fn build_client(timeout_secs: u64) -> Client {
-    Client::builder()
+/// 客户端用途
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ClientKind {
+    /// API 请求：自动跟随重定向，不保存 Cookie
+    Api,
+    /// 抓取规则站点：使用共享的会话 Cookie 存储，
+    /// 不自动跟随重定向 (由 send_following 跟随并记录重定向链)
+    Scraper,
+    /// 经反代前缀重试：同样由 send_following 跟随重定向，但不使用会话 Cookie
+    /// (所有站点都在反代域名下，保存的 Cookie 会被发往其它站点)
+    Proxied,
+}
+
+/// 创建 HTTP 客户端 (proxy 不为空时所有请求经由该出站代理；域名按 DNS_HOSTS / DOH_URL 解析)
+/// insecure 为 true 时不校验证书，只用于证书有问题且显式放行的站点
+fn build_client(timeout_secs: u64, proxy: Option<&str>, insecure: bool, kind: ClientKind) -> Client {
+    let mut builder = Client::builder()
         .timeout(Duration::from_secs(timeout_secs))
         .user_agent(&CONFIG.user_agent)
         .gzip(true)
         .brotli(true)
-        .danger_accept_invalid_certs(true) // 某些站点证书有问题
-        .build()
-        .expect("Failed to create HTTP client")
+        .zstd(true)
+        .deflate(true)
+        .danger_accept_invalid_certs(insecure);
+    builder = match kind {
+        ClientKind::Api => builder.redirect(redirect_policy()),
+        ClientKind::Scraper => builder
+            .cookie_provider(COOKIE_JAR.clone())
+            .redirect(reqwest::redirect::Policy::none()),
+        ClientKind::Proxied => builder.redirect(reqwest::redirect::Policy::none()),
+    };
+    for (host, ips) in &CONFIG.dns_hosts {
+        let addrs: Vec<SocketAddr> = ips.iter().map(|ip| SocketAddr::new(*ip, 0)).collect();
+        builder = builder.resolve_to_addrs(host, &addrs);
+    }
+    if let Some(resolver) = crate::dns::resolver() {
+        builder = builder.dns_resolver(resolver);
+    }
+    if CONFIG.host_max_concurrency > 0 {
+        builder = builder.pool_max_idle_per_host(CONFIG.host_max_concurrency);
+    }
+    if let Some(proxy) = proxy {
+        match reqwest::Proxy::all(proxy) {
+            Ok(proxy) => builder = builder.proxy(proxy),
+            Err(e) => tracing::warn!("出站代理 {} 无效，改为直连: {}", proxy, e),
+        }
+    }
+    builder.build().expect("Failed to create HTTP client")
+}
+
+/// 配置的出站代理 (OUTBOUND_PROXY)
+fn outbound_proxy() -> Option<&'static str> {
+    Some(CONFIG.outbound_proxy.as_str()).filter(|proxy| !proxy.is_empty())
+}
+
+/// 所有请求都使用的出站代理 (OUTBOUND_PROXY_SCOPE=all)
+fn global_proxy() -> Option<&'static str> {
+    outbound_proxy().filter(|_| CONFIG.outbound_proxy_scope != "magic")
 }
 
-/// 全局 HTTP 客户端
-pub static HTTP_CLIENT: Lazy<Client> = Lazy::new(|| build_client(CONFIG.timeout_seconds));
+/// 会话 Cookie 存储 (按域名)，首个请求下发的 Cookie 会在后续详情/播放页请求中带上
+/// 只挂在抓取规则站点的客户端上，Bangumi、GitHub 等 API 请求不共享
+pub static COOKIE_JAR: Lazy<Arc<Jar>> = Lazy::new(|| Arc::new(Jar::default()));
+
+/// 全局 HTTP 客户端 (校验证书，不保存 Cookie)
+pub static HTTP_CLIENT: Lazy<Client> =
+    Lazy::new(|| build_client(CONFIG.timeout_seconds, global_proxy(), false, ClientKind::Api));
+
+/// 抓取规则站点的客户端 (校验证书，共享会话 Cookie)
+static SCRAPER_CLIENT: Lazy<Client> =
+    Lazy::new(|| build_client(CONFIG.timeout_seconds, global_proxy(), false, ClientKind::Scraper));
+
+/// 用于反代前缀重试的 HTTP 客户端 (更长超时，不保存 Cookie)
+static RETRY_CLIENT: Lazy<Client> =
+    Lazy::new(|| build_client(CONFIG.retry_timeout_seconds, global_proxy(), false, ClientKind::Proxied));
+
+/// 不校验证书的客户端 (只用于放行的站点)
+static INSECURE_CLIENT: Lazy<Client> =
+    Lazy::new(|| build_client(CONFIG.timeout_seconds, global_proxy(), true, ClientKind::Scraper));
+
+/// 请求使用的出站代理地址 (不使用代理时为 None)
+pub fn proxy_for(config: &RequestConfig) -> Option<&'static str> {
+    global_proxy().or_else(|| outbound_proxy().filter(|_| config.outbound_proxy))
+}
+
+/// 出站代理仅供部分规则使用时的代理地址 (OUTBOUND_PROXY_SCOPE=magic)
+fn scoped_proxy() -> Option<&'static str> {
+    outbound_proxy().filter(|_| global_proxy().is_none())
+}
+
+/// 只供部分规则使用的出站代理客户端 (OUTBOUND_PROXY_SCOPE=magic)
+static OUTBOUND_CLIENT: Lazy<Option<Client>> =
+    Lazy::new(|| scoped_proxy().map(|proxy| build_client(CONFIG.timeout_seconds, Some(proxy), false, ClientKind::Scraper)));
+
+/// 不校验证书的出站代理客户端
+static OUTBOUND_INSECURE_CLIENT: Lazy<Option<Client>> =
+    Lazy::new(|| scoped_proxy().map(|proxy| build_client(CONFIG.timeout_seconds, Some(proxy), true, ClientKind::Scraper)));
+
+/// 请求使用的客户端 (需要出站代理的规则使用代理客户端，放行的站点不校验证书)
+pub fn client_for(url: &str, config: &RequestConfig) -> &'static Client {
+    let insecure = accepts_invalid_certs(url, config);
+    if config.outbound_proxy && scoped_proxy().is_some() {
+        let client = if insecure { &OUTBOUND_INSECURE_CLIENT } else { &OUTBOUND_CLIENT };
+        if let Some(client) = client.as_ref() {
+            return client;
+        }
+    }
+    if insecure {
+        &INSECURE_CLIENT
+    } else {
+        &SCRAPER_CLIENT
+    }
+}
+
+/// 是否跳过证书校验：规则开启 insecureTls，或域名在 INSECURE_TLS_HOSTS 中 (含子域名)
+pub fn accepts_invalid_certs(url: &str, config: &RequestConfig) -> bool {
+    if config.insecure_tls {
+        return true;
+    }
+    let Some(host) = host_of(url) else {
+        return false;
+    };
+    CONFIG.insecure_tls_hosts.iter().any(|allowed| host_matches(&host, allowed))
+}
+
+/// 域名与放行条目相同，或为其子域名
+fn host_matches(host: &str, allowed: &str) -> bool {
+    host == allowed
+        || host
+            .strip_suffix(allowed)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// 请求是否经由出站代理 (此时不再使用反代前缀重试)
+fn via_outbound_proxy(config: &RequestConfig) -> bool {
+    proxy_for(config).is_some()
+}
+
+/// 单次请求的附加配置 (通常由规则生成)
+#[derive(Debug, Clone, Default)]
+pub struct RequestConfig {
+    /// Referer 头
+    pub referer: Option<String>,
+    /// 覆盖全局 User-Agent
+    pub user_agent: Option<String>,
+    /// Cookie 头
+    pub cookie: Option<String>,
+    /// 额外请求头
+    pub headers: Vec<(String, String)>,
+    /// 经由出站代理请求 (OUTBOUND_PROXY_SCOPE=magic 时生效)
+    pub outbound_proxy: bool,
+    /// 对冲请求：直连超过该时间未完成时并行发起反代请求
+    pub hedge_delay: Option<Duration>,
+    /// 不校验该站点的证书 (证书过期、自签名等)
+    pub insecure_tls: bool,
+    /// 发起请求的规则名 (用于出站请求统计)
+    pub rule: Option<String>,
+    /// 只允许访问公网地址 (目标由调用方提供的媒体中转请求)，重定向的每一跳都会检查
+    pub public_only: bool,
+}
+
+impl RequestConfig {
+    /// 将配置应用到请求上
+    pub fn apply(&self, mut req: RequestBuilder) -> RequestBuilder {
+        if let Some(referer) = &self.referer {
+            req = req.header("Referer", referer);
+        }
+        if let Some(user_agent) = &self.user_agent {
+            req = req.header("User-Agent", user_agent);
+        }
+        if let Some(cookie) = &self.cookie {
+            req = req.header("Cookie", cookie);
+        }
+        for (name, value) in &self.headers {
+            req = req.header(name.as_str(), value.as_str());
+        }
+        req
+    }
+
+    /// 重定向到下一跳时重新设置配置的请求头：先去掉所有由配置设置的请求头
+    /// (Range 除外，它是中转请求自身的范围)，to_origin 为 true (仍在原域名) 时
+    /// 按下一跳地址重新合并会话 Cookie 后设置 (30x 响应下发的 Cookie 也会带上)
+    fn reapply_headers(&self, headers: &mut HeaderMap, url: &str, to_origin: bool) {
+        use reqwest::header::{HeaderName, HeaderValue, AUTHORIZATION, COOKIE, RANGE, REFERER, USER_AGENT};
+
+        for name in [REFERER, USER_AGENT, COOKIE, AUTHORIZATION] {
+            headers.remove(name);
+        }
+        for (name, _) in &self.headers {
+            if !name.eq_ignore_ascii_case(RANGE.as_str()) {
+                headers.remove(name.as_str());
+            }
+        }
+        if !to_origin {
+            return;
+        }
 
-/// 用于重试的 HTTP 客户端 (更长超时)
-static RETRY_CLIENT: Lazy<Client> = Lazy::new(|| build_client(CONFIG.retry_timeout_seconds));
+        let config = prepare_config(url, self);
+        let fixed = [
+            (REFERER, config.referer.as_deref()),
+            (USER_AGENT, config.user_agent.as_deref()),
+            (COOKIE, config.cookie.as_deref()),
+        ];
+        let extra = config
+            .headers
+            .iter()
+            .filter_map(|(name, value)| Some((HeaderName::from_bytes(name.as_bytes()).ok()?, Some(value.as_str()))));
+        for (name, value) in fixed.into_iter().chain(extra) {
+            if let Some(value) = value.and_then(|v| HeaderValue::from_str(v).ok()) {
+                headers.insert(name, value);
+            }
+        }
+    }
+}
 
 #[derive(Debug, Error)]
 pub enum HttpClientError {
@@ -31,6 +240,27 @@ pub enum HttpClientError {
     RequestFailed(String),
     #[error("响应异常状态码: {0}")]
     BadStatus(u16),
+    #[error("触发 Cloudflare 验证: {0}")]
+    Challenge(u16),
+    #[error("站点 {0} 连续请求失败，暂时停用")]
+    CircuitOpen(String),
+    #[error("站点限流，请在 {} 秒后重试", .0.as_secs().max(1))]
+    RateLimited(Duration),
+    #[error("重定向异常: {0}")]
+    Redirect(String),
+    #[error("不允许访问内网地址: {0}")]
+    PrivateAddress(String),
+}
+
+/// 发送请求失败对应的错误
+fn send_error(e: reqwest::Error) -> HttpClientError {
+    if e.is_timeout() {
+        HttpClientError::Timeout
+    } else if e.is_redirect() {
+        HttpClientError::Redirect(e.to_string())
+    } else {
+        HttpClientError::RequestFailed(e.to_string())
+    }
 }
 
 /// 判断是否应该使用反代重试
@@ -42,173 +272,1375 @@ fn should_retry(error: &HttpClientError) -> bool {
     )
 }
 
+/// 判断错误是否为临时性故障 (超时、连接失败、5xx 等)，可稍后重试
+/// 限流 (429) 需等待 Retry-After，不立即重试
+pub fn is_transient(error: &HttpClientError) -> bool {
+    should_use_proxy(error)
+}
+
+/// 检查响应状态码，区分 Cloudflare 验证页与普通错误
+/// 304 只会出现在带校验信息的条件请求 (缓存重新验证) 中，交给调用方处理
+fn check_status(response: Response) -> Result<Response, HttpClientError> {
+    if response.status().is_success() || response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(response);
+    }
+    Err(status_error(response.status().as_u16(), response.headers()))
+}
+
+/// 非成功状态码对应的错误 (限流、Cloudflare 验证页或普通错误)
+pub fn status_error(status: u16, headers: &HeaderMap) -> HttpClientError {
+    if status == StatusCode::TOO_MANY_REQUESTS.as_u16() {
+        return HttpClientError::RateLimited(retry_after(headers));
+    }
+
+    let mitigated = headers
+        .get("cf-mitigated")
+        .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"challenge"));
+    let from_cloudflare = headers
+        .get(reqwest::header::SERVER)
+        .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"cloudflare"));
+
+    if mitigated || (from_cloudflare && matches!(status, 403 | 503)) {
+        HttpClientError::Challenge(status)
+    } else {
+        HttpClientError::BadStatus(status)
+    }
+}
+
+/// 判断错误是否应该走反代重试
+fn should_use_proxy(error: &HttpClientError) -> bool {
+    match error {
+        HttpClientError::BadStatus(status) | HttpClientError::Challenge(status) => {
+            should_retry_status(*status)
+        }
+        _ => should_retry(error),
+    }
+}
+
 /// 判断状态码是否应该重试
 fn should_retry_status(status: u16) -> bool {
-    // 403, 404, 500+ 等可能是反爬，尝试反代
-    matches!(status, 403 | 429 | 500..=599)
+    // 403, 500+ 等可能是反爬，尝试反代
+    // 429 不走反代：换出口继续请求往往会被封禁得更久
+    matches!(status, 403 | 500..=599)
 }
 
-/// GET 请求 (内部实现)
-async fn get_internal(client: &Client, url: &str, referer: Option<&str>) -> Result<Response, HttpClientError> {
-    let mut req = client.get(url);
-    
-    if let Some(ref_url) = referer {
-        req = req.header("Referer", ref_url);
+/// 覆盖客户端的默认超时 (规则或搜索请求指定时)
+fn with_timeout(req: RequestBuilder, timeout: Option<Duration>) -> RequestBuilder {
+    match timeout {
+        Some(timeout) => req.timeout(timeout),
+        None => req,
     }
-    
+}
+
+/// GET 请求 (内部实现)
+async fn get_internal(
+    client: &Client,
+    url: &str,
+    config: &RequestConfig,
+    timeout: Option<Duration>,
+) -> Result<Response, HttpClientError> {
+    let mut req = with_timeout(prepare_config(url, config).apply(client.get(url)), timeout);
+
     req = req
         .header("Accept-Language", "zh-CN,zh;q=0.9,en;q=0.8")
         .header("Connection", "keep-alive");
 
-    let response = req.send().await.map_err(|e| {
-        if e.is_timeout() {
-            HttpClientError::Timeout
-        } else {
-            HttpClientError::RequestFailed(e.to_string())
-        }
-    })?;
+    let response = send_following(client, req.build().map_err(send_error)?, config).await?;
+
+    check_status(response)
+}
 
-    if !response.status().is_success() {
-        return Err(HttpClientError::BadStatus(response.status().as_u16()));
+/// GET 请求 (自动重试反代；timeout 为空时使用客户端的默认超时)
+pub async fn get(
+    url: &str,
+    config: &RequestConfig,
+    timeout: Option<Duration>,
+) -> Result<Response, HttpClientError> {
+    http_service::response_service()
+        .oneshot(PageRequest::get(url, config, timeout))
+        .await
+}
+
+/// 对冲请求的等待时间 (规则开启对冲且可以使用反代时)
+fn hedge_delay(config: &RequestConfig) -> Option<Duration> {
+    config
+        .hedge_delay
+        .filter(|_| !CONFIG.proxy_prefix.is_empty() && !via_outbound_proxy(config))
+}
+
+/// 对冲请求：先发起直连，delay 后仍未完成时并行发起反代请求，
+/// 取先成功的响应并取消另一个 (直连返回无需反代的错误时直接返回)
+async fn hedge<T>(
+    direct: impl Future<Output = Result<T, HttpClientError>>,
+    proxied: impl Future<Output = Result<T, HttpClientError>>,
+    delay: Duration,
+) -> Result<T, HttpClientError> {
+    let proxied = async {
+        tokio::time::sleep(delay).await;
+        proxied.await
+    };
+    tokio::pin!(direct, proxied);
+
+    tokio::select! {
+        result = &mut direct => match result {
+            Err(e) if should_use_proxy(&e) => proxied.await,
+            result => result,
+        },
+        result = &mut proxied => match result {
+            Ok(response) => Ok(response),
+            Err(_) => direct.await,
+        },
     }
+}
 
-    Ok(response)
+/// 发送单个请求：反代前缀地址使用反代重试客户端，其余按规则选择客户端直连
+pub async fn send(req: &PageRequest) -> Result<Response, HttpClientError> {
+    let client = if req.proxied {
+        &RETRY_CLIENT
+    } else {
+        client_for(&req.url, &req.config)
+    };
+    match &req.body {
+        None => get_internal(client, &req.url, &req.config, req.timeout).await,
+        Some((body, content_type)) => {
+            post_internal(client, &req.url, body, content_type, &req.config, req.timeout).await
+        }
+    }
 }
 
-/// GET 请求 (自动重试反代)
-pub async fn get(url: &str, referer: Option<&str>) -> Result<Response, HttpClientError> {
-    // 第一次尝试直连
-    match get_internal(&HTTP_CLIENT, url, referer).await {
-        Ok(resp) => Ok(resp),
-        Err(e) => {
-            // 网络问题或反爬状态码，尝试反代
-            let should_use_proxy = match &e {
-                HttpClientError::BadStatus(status) => should_retry_status(*status),
-                _ => should_retry(&e),
-            };
+/// 直连失败时依次尝试 Cloudflare 求解 (仅 GET) 与反代 (开启对冲时直连与反代竞速)
+/// send 发送单个请求 (服务栈的下一层)
+pub async fn with_fallback<T, F, Fut>(req: PageRequest, send: F) -> Result<T, HttpClientError>
+where
+    F: Fn(PageRequest) -> Fut,
+    Fut: Future<Output = Result<T, HttpClientError>>,
+{
+    if req.config.public_only {
+        ensure_public(&req.url).await?;
+    }
+    let rule = req.config.rule.as_deref();
+    let proxied = || PageRequest {
+        url: proxied_url(&CONFIG.proxy_prefix, &req.url),
+        proxied: true,
+        ..req.clone()
+    };
+
+    if let Some(delay) = hedge_delay(&req.config) {
+        return hedge(
+            send(req.clone()),
+            async {
+                stats::record_proxy_fallback(&req.url, rule);
+                send(proxied()).await
+            },
+            delay,
+        )
+        .await;
+    }
+
+    // 第一次尝试直连 (带上已缓存的 Cloudflare 凭据)
+    let error = match send(req.clone()).await {
+        Ok(resp) => return Ok(resp),
+        Err(e) => e,
+    };
 
-            if should_use_proxy {
-                let proxy_url = format!("{}{}", CONFIG.proxy_prefix, url);
-                tracing::debug!("使用反代重试: {}", url);
-                get_internal(&RETRY_CLIENT, &proxy_url, referer).await
-            } else {
-                Err(e)
+    // Cloudflare 验证：交给求解服务，拿到凭据后重试
+    if let HttpClientError::Challenge(_) = error {
+        if req.body.is_none() && !CONFIG.flaresolverr_url.is_empty() {
+            match solve_challenge(&req.url).await {
+                Ok(()) => {
+                    stats::record_retry(&req.url, rule);
+                    return send(req.clone()).await;
+                }
+                Err(e) => tracing::warn!("Cloudflare 验证求解失败 {}: {}", req.url, e),
             }
         }
     }
+
+    // 网络问题或反爬状态码，尝试反代 (已经由出站代理时不再重试)
+    if should_use_proxy(&error) && !via_outbound_proxy(&req.config) {
+        tracing::debug!("使用反代重试: {}", req.url);
+        stats::record_proxy_fallback(&req.url, rule);
+        send(proxied()).await
+    } else {
+        Err(error)
+    }
 }
 
-/// GET 请求并返回文本
-pub async fn get_text(url: &str, referer: Option<&str>) -> Result<String, HttpClientError> {
-    let response = get(url, referer).await?;
-    response
-        .text()
+/// 抓取的页面 (记录重定向/反代之后的最终地址)
+#[derive(Debug, Clone)]
+pub struct Page {
+    pub body: String,
+    /// 最终落地的地址 (经反代时为反代目标地址)
+    pub final_url: String,
+    /// HTTP 状态码
+    pub status: u16,
+    /// 到达最终地址前经过的重定向 (按顺序，不含最终地址)
+    pub redirects: Vec<String>,
+}
+
+/// 反代前缀模板的占位符
+static RE_PROXY_PLACEHOLDER: Lazy<regex::Regex> =
+    Lazy::new(|| regex::Regex::new(r"\{(url|url_encoded|host|path)\}").unwrap());
+
+/// PROXY_PREFIX 为模板时用于还原目标地址的正则
+static PROXY_PATTERN: Lazy<Option<regex::Regex>> = Lazy::new(|| proxy_pattern(&CONFIG.proxy_prefix));
+
+/// 生成反代地址：不含占位符时直接拼接 (prefix + url)，否则按模板替换
+/// {url} 原地址、{url_encoded} 编码后的原地址、{host} 域名 (含端口)、{path} 路径与查询参数 (不含开头的 /)
+pub fn proxied_url(prefix: &str, url: &str) -> String {
+    if !RE_PROXY_PLACEHOLDER.is_match(prefix) {
+        return format!("{}{}", prefix, url);
+    }
+    let parsed = url::Url::parse(url).ok();
+    RE_PROXY_PLACEHOLDER
+        .replace_all(prefix, |caps: &regex::Captures| match &caps[1] {
+            "url" => url.to_string(),
+            "url_encoded" => urlencoding::encode(url).into_owned(),
+            "host" => parsed.as_ref().map_or(String::new(), |u| {
+                let host = u.host_str().unwrap_or_default();
+                match u.port() {
+                    Some(port) => format!("{}:{}", host, port),
+                    None => host.to_string(),
+                }
+            }),
+            _ => parsed.as_ref().map_or(String::new(), |u| {
+                let path = u.path().trim_start_matches('/');
+                match u.query() {
+                    Some(query) => format!("{}?{}", path, query),
+                    None => path.to_string(),
+                }
+            }),
+        })
+        .into_owned()
+}
+
+/// 将反代前缀模板转换为还原目标地址的正则 (不含占位符时返回 None)
+fn proxy_pattern(prefix: &str) -> Option<regex::Regex> {
+    if !RE_PROXY_PLACEHOLDER.is_match(prefix) {
+        return None;
+    }
+    let mut pattern = String::from("^");
+    let mut last = 0;
+    let mut seen = Vec::new();
+    for caps in RE_PROXY_PLACEHOLDER.captures_iter(prefix) {
+        let m = caps.get(0).unwrap();
+        pattern.push_str(&regex::escape(&prefix[last..m.start()]));
+        let name = caps.get(1).unwrap().as_str();
+        let group = match name {
+            "url" => ".+",
+            "url_encoded" => "[^&#]+",
+            "host" => "[^/?#]+",
+            _ => ".*",
+        };
+        // 同一占位符出现多次时只捕获第一次
+        if seen.contains(&name) {
+            pattern.push_str(&format!("(?:{})", group));
+        } else {
+            pattern.push_str(&format!("(?P<{}>{})", name, group));
+            seen.push(name);
+        }
+        last = m.end();
+    }
+    pattern.push_str(&regex::escape(&prefix[last..]));
+    pattern.push('$');
+    regex::Regex::new(&pattern).ok()
+}
+
+/// 按反代前缀还原目标地址 (不是反代地址时返回 None)
+/// 模板只含 {host} / {path} 时无法得知原地址的协议，按 https 还原
+fn unproxy(prefix: &str, pattern: Option<&regex::Regex>, url: &str) -> Option<String> {
+    let Some(pattern) = pattern else {
+        return url
+            .strip_prefix(prefix)
+            .filter(|_| !prefix.is_empty())
+            .map(|s| s.to_string());
+    };
+    let caps = pattern.captures(url)?;
+    if let Some(m) = caps.name("url") {
+        return Some(m.as_str().to_string());
+    }
+    if let Some(m) = caps.name("url_encoded") {
+        return urlencoding::decode(m.as_str()).ok().map(|s| s.into_owned());
+    }
+    let host = caps.name("host")?.as_str();
+    let path = caps.name("path").map_or("", |m| m.as_str());
+    Some(format!("https://{}/{}", host, path))
+}
+
+/// 去掉反代前缀，得到实际访问的站点地址
+pub fn unproxied_url(url: &str) -> String {
+    unproxy(&CONFIG.proxy_prefix, PROXY_PATTERN.as_ref(), url).unwrap_or_else(|| url.to_string())
+}
+
+/// GET 请求并返回页面 (按 Cache-Control 缓存，过期后带上 ETag / Last-Modified 重新验证)
+pub async fn get_page(
+    url: &str,
+    config: &RequestConfig,
+    timeout: Option<Duration>,
+) -> Result<Page, HttpClientError> {
+    let fetched = http_service::page_service()
+        .oneshot(PageRequest::get(url, config, timeout))
+        .await?;
+    Ok(fetched.into_page())
+}
+
+/// 响应体中用于嗅探 <meta charset> 的最大字节数
+const CHARSET_SNIFF_BYTES: usize = 4096;
+
+/// <meta charset="gbk"> / <meta http-equiv="Content-Type" content="text/html; charset=gb2312">
+static RE_META_CHARSET: Lazy<regex::bytes::Regex> = Lazy::new(|| {
+    regex::bytes::Regex::new(r#"(?i)<meta[^>]+charset\s*=\s*["']?\s*([a-z0-9_:.\-]+)"#).unwrap()
+});
+
+/// 读取二进制响应体，超过 MAX_RESPONSE_MB 时返回错误 (截断的二进制数据没有意义)
+pub async fn read_bytes(response: Response) -> Result<Vec<u8>, HttpClientError> {
+    let (bytes, truncated) = read_limited(response).await?;
+    if truncated {
+        return Err(HttpClientError::RequestFailed(format!(
+            "响应体超过 {} MB",
+            CONFIG.max_response_mb
+        )));
+    }
+    Ok(bytes)
+}
+
+/// 读取响应体，最多 MAX_RESPONSE_MB，返回数据与是否被截断
+pub async fn read_limited(mut response: Response) -> Result<(Vec<u8>, bool), HttpClientError> {
+    let limit = response_limit();
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
         .await
-        .map_err(|e| HttpClientError::RequestFailed(e.to_string()))
+        .map_err(|e| HttpClientError::RequestFailed(e.to_string()))?
+    {
+        if !append_limited(&mut bytes, &chunk, limit) {
+            return Ok((bytes, true));
+        }
+    }
+    Ok((bytes, false))
+}
+
+/// 响应体字节数上限 (MAX_RESPONSE_MB)
+pub fn response_limit() -> usize {
+    match CONFIG.max_response_mb {
+        0 => usize::MAX,
+        mb => mb.saturating_mul(1024 * 1024),
+    }
+}
+
+/// 追加数据块，超出上限的部分丢弃；返回 false 表示已达到上限
+pub fn append_limited(buf: &mut Vec<u8>, chunk: &[u8], limit: usize) -> bool {
+    let remaining = limit.saturating_sub(buf.len());
+    if chunk.len() > remaining {
+        buf.extend_from_slice(&chunk[..remaining]);
+        return false;
+    }
+    buf.extend_from_slice(chunk);
+    true
+}
+
+/// 检测编码并解码：BOM → Content-Type charset → <meta> → UTF-8
+pub fn decode_body(bytes: &[u8], content_type: Option<&str>) -> String {
+    if let Some((encoding, _)) = encoding_rs::Encoding::for_bom(bytes) {
+        return encoding.decode_with_bom_removal(bytes).0.into_owned();
+    }
+
+    let from_header = content_type.and_then(|ct| {
+        ct.split(';')
+            .filter_map(|part| part.trim().split_once('='))
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case("charset"))
+            .map(|(_, value)| value.trim().trim_matches('"').to_string())
+    });
+    let from_meta = || {
+        let head = &bytes[..bytes.len().min(CHARSET_SNIFF_BYTES)];
+        RE_META_CHARSET
+            .captures(head)
+            .map(|caps| String::from_utf8_lossy(&caps[1]).into_owned())
+    };
+
+    let encoding = from_header
+        .or_else(from_meta)
+        .and_then(|label| encoding_rs::Encoding::for_label(label.as_bytes()))
+        .unwrap_or(encoding_rs::UTF_8);
+
+    encoding.decode_without_bom_handling(bytes).0.into_owned()
 }
 
 /// GET 请求并返回 JSON
 #[allow(dead_code)]
 pub async fn get_json<T: serde::de::DeserializeOwned>(
     url: &str,
-    referer: Option<&str>,
+    config: &RequestConfig,
 ) -> Result<T, HttpClientError> {
-    let response = get(url, referer).await?;
+    let response = get(url, config, None).await?;
     response
         .json()
         .await
         .map_err(|e| HttpClientError::RequestFailed(e.to_string()))
 }
 
-/// POST 请求 (Form body) 内部实现
-async fn post_form_internal(
+/// POST 请求内部实现 (body 已按 content_type 编码)
+async fn post_internal(
     client: &Client,
     url: &str,
-    form: &HashMap<String, String>,
-    referer: Option<&str>,
+    body: &str,
+    content_type: &str,
+    config: &RequestConfig,
+    timeout: Option<Duration>,
 ) -> Result<Response, HttpClientError> {
-    let mut req = client.post(url).form(form);
-
-    if let Some(ref_url) = referer {
-        req = req.header("Referer", ref_url);
-    }
+    let mut req = prepare_config(url, config).apply(
+        client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, content_type)
+            .body(body.to_string()),
+    );
+    req = with_timeout(req, timeout);
 
     req = req
         .header("Accept-Language", "zh-CN,zh;q=0.9,en;q=0.8")
         .header("Connection", "keep-alive");
 
-    let response = req.send().await.map_err(|e| {
-        if e.is_timeout() {
-            HttpClientError::Timeout
-        } else {
-            HttpClientError::RequestFailed(e.to_string())
+    let response = send_following(client, req.build().map_err(send_error)?, config).await?;
+
+    check_status(response)
+}
+
+/// POST 请求 (JSON body)
+#[allow(dead_code)]
+pub async fn post_json<T: serde::Serialize>(
+    url: &str,
+    body: &T,
+    config: &RequestConfig,
+) -> Result<Response, HttpClientError> {
+    let req = config.apply(HTTP_CLIENT.post(url).json(body));
+
+    let response = req.send().await.map_err(send_error)?;
+
+    check_status(response)
+}
+
+// ============================================================================
+// 重定向
+// ============================================================================
+
+/// 同一地址在一条重定向链中最多出现的次数 (设置 Cookie 后跳回原地址是常见做法，再多视为循环)
+const MAX_REDIRECT_REPEATS: usize = 2;
+
+/// 响应经过的重定向 (按顺序，不含最终地址)，由 send_following 写入响应的 extensions
+#[derive(Debug, Clone, Default)]
+struct RedirectChain(Vec<String>);
+
+/// 是否跟随重定向：超过 MAX_REDIRECTS 或出现循环时报错 (避免在循环中耗尽超时)，
+/// 开启 REDIRECT_SAME_HOST 时不跟随跳转到其它域名 (返回 false)
+pub fn check_redirect(previous: &[String], first_host: Option<&str>, next: &url::Url) -> Result<bool, String> {
+    let next_str = next.as_str();
+    if previous.iter().filter(|url| *url == next_str).count() >= MAX_REDIRECT_REPEATS {
+        return Err(format!("重定向循环: {} -> {}", previous.join(" -> "), next));
+    }
+    if previous.len() > CONFIG.max_redirects {
+        return Err(format!("重定向超过 {} 次: {}", CONFIG.max_redirects, previous.join(" -> ")));
+    }
+    if CONFIG.redirect_same_host && first_host != next.host_str() {
+        tracing::debug!("不跟随跨域名重定向: {} -> {}", previous.join(" -> "), next);
+        return Ok(false);
+    }
+    tracing::debug!("重定向: {} -> {}", previous.last().map_or("", |s| s.as_str()), next);
+    Ok(true)
+}
+
+/// 自动跟随重定向的客户端 (API 请求) 使用的策略，规则与 send_following 相同
+fn redirect_policy() -> reqwest::redirect::Policy {
+    reqwest::redirect::Policy::custom(|attempt| {
+        let previous: Vec<String> = attempt.previous().iter().map(|url| url.to_string()).collect();
+        let first_host = attempt.previous().first().and_then(|url| url.host_str());
+        match check_redirect(&previous, first_host, attempt.url()) {
+            Ok(true) => attempt.follow(),
+            Ok(false) => attempt.stop(),
+            Err(message) => attempt.error(message),
+        }
+    })
+}
+
+/// 发送请求并逐跳跟随重定向 (抓取客户端不自动跟随)，经过的地址记录在响应上，
+/// 每个请求各自记录，不会与同时落到同一地址的其他请求混淆
+/// 每一跳按 config 重新设置请求头，config.public_only 为 true 时拒绝跳转到内网地址
+pub async fn send_following(
+    client: &Client,
+    request: reqwest::Request,
+    config: &RequestConfig,
+) -> Result<Response, HttpClientError> {
+    let first_host = request.url().host_str().map(str::to_string);
+    let mut previous: Vec<String> = Vec::new();
+    let mut request = request;
+    loop {
+        let next_request = request.try_clone();
+        let mut response = client.execute(request).await.map_err(send_error)?;
+        let status = response.status().as_u16();
+        let location = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .filter(|_| matches!(status, 301 | 302 | 303 | 307 | 308))
+            .and_then(|v| v.to_str().ok())
+            .and_then(|location| response.url().join(location).ok());
+        let (Some(next), Some(mut next_request)) = (location, next_request) else {
+            response.extensions_mut().insert(RedirectChain(previous));
+            return Ok(response);
+        };
+
+        previous.push(response.url().to_string());
+        match check_redirect(&previous, first_host.as_deref(), &next) {
+            Ok(true) => {}
+            Ok(false) => {
+                previous.pop();
+                response.extensions_mut().insert(RedirectChain(previous));
+                return Ok(response);
+            }
+            Err(message) => return Err(HttpClientError::Redirect(message)),
+        }
+        if config.public_only {
+            ensure_public(next.as_str()).await?;
         }
-    })?;
 
-    if !response.status().is_success() {
-        return Err(HttpClientError::BadStatus(response.status().as_u16()));
+        // 301/302/303 之后改为不带 body 的 GET (与浏览器一致)，307/308 保持原请求
+        if matches!(status, 301..=303) && next_request.method() != reqwest::Method::HEAD {
+            *next_request.method_mut() = reqwest::Method::GET;
+            *next_request.body_mut() = None;
+            next_request.headers_mut().remove(reqwest::header::CONTENT_TYPE);
+            next_request.headers_mut().remove(reqwest::header::CONTENT_LENGTH);
+        }
+        // 规则的请求头只发往原域名：跳转到其它域名时全部去掉 (该域名的会话 Cookie 由 Cookie 存储附加)
+        let to_origin = next.host_str() == first_host.as_deref();
+        config.reapply_headers(next_request.headers_mut(), next.as_str(), to_origin);
+        *next_request.url_mut() = next;
+        request = next_request;
     }
+}
 
-    Ok(response)
+/// 响应到达最终地址前经过的重定向 (去掉反代前缀)
+pub fn redirects_of(response: &Response) -> Vec<String> {
+    response
+        .extensions()
+        .get::<RedirectChain>()
+        .map(|chain| chain.0.iter().map(|url| unproxied_url(url)).collect())
+        .unwrap_or_default()
 }
 
-/// POST 请求 (Form body) 并返回文本 (自动重试反代)
-pub async fn post_form_text(
-    url: &str,
-    form: &HashMap<String, String>,
-    referer: Option<&str>,
-) -> Result<String, HttpClientError> {
-    // 第一次尝试直连
-    match post_form_internal(&HTTP_CLIENT, url, form, referer).await {
-        Ok(resp) => resp
-            .text()
-            .await
-            .map_err(|e| HttpClientError::RequestFailed(e.to_string())),
-        Err(e) => {
-            // 网络问题或反爬状态码，尝试反代
-            let should_use_proxy = match &e {
-                HttpClientError::BadStatus(status) => should_retry_status(*status),
-                _ => should_retry(&e),
-            };
-
-            if should_use_proxy {
-                let proxy_url = format!("{}{}", CONFIG.proxy_prefix, url);
-                tracing::debug!("使用反代重试 POST: {}", url);
-                let resp = post_form_internal(&RETRY_CLIENT, &proxy_url, form, referer).await?;
-                resp.text()
-                    .await
-                    .map_err(|e| HttpClientError::RequestFailed(e.to_string()))
-            } else {
-                Err(e)
+// ============================================================================
+// 内网地址限制
+// ============================================================================
+
+/// 是否为公网地址 (回环、私有、链路本地、运营商级 NAT、保留地址等返回 false)
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                || a >= 240
+                || (a == 100 && (64..128).contains(&b))
+                || (a == 198 && matches!(b, 18 | 19)))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(v4));
             }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
         }
     }
 }
 
-/// POST 请求 (JSON body)
-#[allow(dead_code)]
-pub async fn post_json<T: serde::Serialize>(
+/// 确认地址的域名解析到公网地址 (按客户端相同的方式解析)，否则返回 PrivateAddress
+pub async fn ensure_public(url: &str) -> Result<(), HttpClientError> {
+    let parsed = url::Url::parse(url).map_err(|e| HttpClientError::RequestFailed(e.to_string()))?;
+    let host = parsed.host_str().unwrap_or_default().to_string();
+    let ips = match parsed.host() {
+        Some(url::Host::Ipv4(ip)) => vec![IpAddr::V4(ip)],
+        Some(url::Host::Ipv6(ip)) => vec![IpAddr::V6(ip)],
+        Some(url::Host::Domain(domain)) => crate::dns::lookup(domain)
+            .await
+            .map_err(|e| HttpClientError::RequestFailed(format!("解析 {} 失败: {}", domain, e)))?,
+        None => Vec::new(),
+    };
+    if ips.is_empty() || !ips.into_iter().all(is_public_ip) {
+        return Err(HttpClientError::PrivateAddress(host));
+    }
+    Ok(())
+}
+
+// ============================================================================
+// 熔断 (按域名)
+// ============================================================================
+
+/// 按域名记录的熔断、并发与限流状态最多保存的域名数
+/// (图片中转等请求的目标由调用方提供，不限制会随域名数无限增长)
+const MAX_TRACKED_HOSTS: usize = 1024;
+
+/// 各域名的熔断状态
+static CIRCUITS: Lazy<Mutex<CircuitBreaker>> = Lazy::new(|| {
+    Mutex::new(CircuitBreaker::new(
+        CONFIG.circuit_failure_threshold,
+        Duration::from_secs(CONFIG.circuit_cooldown_secs),
+    ))
+});
+
+/// 连续失败达到阈值后熔断，冷却期内直接拒绝该域名的请求
+/// 冷却结束后放行请求试探：成功则恢复，失败则重新熔断
+#[derive(Debug)]
+struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    hosts: HashMap<String, Circuit>,
+}
+
+#[derive(Debug, Default)]
+struct Circuit {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            hosts: HashMap::new(),
+        }
+    }
+
+    /// 是否允许请求该域名
+    fn allow(&self, host: &str, now: Instant) -> bool {
+        self.hosts
+            .get(host)
+            .and_then(|c| c.open_until)
+            .is_none_or(|until| now >= until)
+    }
+
+    fn record_success(&mut self, host: &str) {
+        self.hosts.remove(host);
+    }
+
+    /// 记录一次失败，返回是否因此熔断
+    fn record_failure(&mut self, host: &str, now: Instant) -> bool {
+        if self.threshold == 0 {
+            return false;
+        }
+        // 已满时只保留正在熔断的域名
+        bounded::make_room(&mut self.hosts, host, MAX_TRACKED_HOSTS, |c| {
+            c.open_until.is_some_and(|until| until > now)
+        });
+        let circuit = self.hosts.entry(host.to_string()).or_default();
+        circuit.failures += 1;
+        if circuit.failures >= self.threshold {
+            circuit.open_until = Some(now + self.cooldown);
+            return true;
+        }
+        false
+    }
+}
+
+/// 判断错误是否说明站点不可用 (超时、连接失败、5xx)
+/// 403 等反爬状态码与限流说明站点仍在线，不计入
+fn is_host_failure(error: &HttpClientError) -> bool {
+    match error {
+        HttpClientError::Timeout | HttpClientError::RequestFailed(_) => true,
+        HttpClientError::BadStatus(status) => *status >= 500,
+        _ => false,
+    }
+}
+
+/// 在熔断保护下执行请求
+pub async fn with_circuit<T>(
     url: &str,
-    body: &T,
-    referer: Option<&str>,
-) -> Result<Response, HttpClientError> {
-    let mut req = HTTP_CLIENT.post(url).json(body);
+    request: impl Future<Output = Result<T, HttpClientError>>,
+) -> Result<T, HttpClientError> {
+    let Some(host) = host_of(url) else {
+        return request.await;
+    };
+    if let Some(wait) = rate_limit_remaining(&host, Instant::now()) {
+        return Err(HttpClientError::RateLimited(wait));
+    }
 
-    if let Some(ref_url) = referer {
-        req = req.header("Referer", ref_url);
+    let allowed = CIRCUITS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .allow(&host, Instant::now());
+    if !allowed {
+        return Err(HttpClientError::CircuitOpen(host));
     }
 
-    let response = req.send().await.map_err(|e| {
-        if e.is_timeout() {
-            HttpClientError::Timeout
-        } else {
-            HttpClientError::RequestFailed(e.to_string())
+    let result = request.await;
+    if let Err(HttpClientError::RateLimited(wait)) = &result {
+        record_rate_limit(&host, *wait);
+    }
+    let mut circuits = CIRCUITS.lock().unwrap_or_else(|e| e.into_inner());
+    match &result {
+        Ok(_) => circuits.record_success(&host),
+        Err(e) if is_host_failure(e) => {
+            if circuits.record_failure(&host, Instant::now()) {
+                tracing::warn!("站点 {} 连续请求失败，暂停 {:?}", host, circuits.cooldown);
+            }
         }
-    })?;
+        Err(_) => {}
+    }
+    result
+}
+
+// ============================================================================
+// 并发限制 (按域名)
+// ============================================================================
+
+/// 各域名的并发名额
+static HOST_LIMITS: Lazy<Mutex<HashMap<String, Arc<Semaphore>>>> = Lazy::new(Default::default);
+
+/// 获取域名的并发信号量 (首次使用时创建)
+fn host_semaphore(host: &str, limit: usize) -> Arc<Semaphore> {
+    let mut limits = HOST_LIMITS.lock().unwrap_or_else(|e| e.into_inner());
+    // 已满时只保留仍有请求持有名额的信号量
+    bounded::make_room(&mut limits, host, MAX_TRACKED_HOSTS, |semaphore| {
+        Arc::strong_count(semaphore) > 1
+    });
+    limits
+        .entry(host.to_string())
+        .or_insert_with(|| Arc::new(Semaphore::new(limit)))
+        .clone()
+}
+
+/// 等待域名的并发名额 (未限制时返回 None)
+async fn acquire_host(host: &str) -> Option<OwnedSemaphorePermit> {
+    if CONFIG.host_max_concurrency == 0 {
+        return None;
+    }
+    host_semaphore(host, CONFIG.host_max_concurrency)
+        .acquire_owned()
+        .await
+        .ok()
+}
+
+/// 在域名并发限制下执行请求，避免一次聚合搜索同时向同一站点发起大量连接而触发 WAF 封禁
+pub async fn with_host_limit<T>(url: &str, request: impl Future<Output = T>) -> T {
+    let _permit = match host_of(url) {
+        Some(host) => acquire_host(&host).await,
+        None => None,
+    };
+    request.await
+}
+
+// ============================================================================
+// 限流退避 (按域名)
+// ============================================================================
 
-    if !response.status().is_success() {
-        return Err(HttpClientError::BadStatus(response.status().as_u16()));
+/// 429 未带 Retry-After 时的默认退避时间
+const RATE_LIMIT_DEFAULT: Duration = Duration::from_secs(30);
+
+/// 退避时间上限 (避免异常的 Retry-After 让站点长期不可用)
+const RATE_LIMIT_MAX: Duration = Duration::from_secs(10 * 60);
+
+/// 各域名限流解除的时间
+static RATE_LIMITS: Lazy<Mutex<HashMap<String, Instant>>> = Lazy::new(Default::default);
+
+/// 解析 Retry-After (秒数或 HTTP 日期)
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
     }
+    let at = SystemTime::from(chrono::DateTime::parse_from_rfc2822(value).ok()?);
+    Some(at.duration_since(now).unwrap_or_default())
+}
+
+/// 429 响应需要等待的时间
+fn retry_after(headers: &HeaderMap) -> Duration {
+    headers
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| parse_retry_after(v, SystemTime::now()))
+        .unwrap_or(RATE_LIMIT_DEFAULT)
+        .min(RATE_LIMIT_MAX)
+}
+
+/// 域名仍处于限流退避期时返回剩余等待时间
+fn rate_limit_remaining(host: &str, now: Instant) -> Option<Duration> {
+    let mut limits = RATE_LIMITS.lock().unwrap_or_else(|e| e.into_inner());
+    let until = *limits.get(host)?;
+    if until > now {
+        Some(until - now)
+    } else {
+        limits.remove(host);
+        None
+    }
+}
+
+/// 记录域名被限流，退避期内不再向其发送请求
+fn record_rate_limit(host: &str, wait: Duration) {
+    let now = Instant::now();
+    let mut limits = RATE_LIMITS.lock().unwrap_or_else(|e| e.into_inner());
+    bounded::make_room(&mut limits, host, MAX_TRACKED_HOSTS, |until| *until > now);
+    limits.insert(host.to_string(), now + wait);
+    tracing::warn!("站点 {} 限流，{} 秒内不再请求", host, wait.as_secs());
+}
+
+/// 发送请求并遵守按域名的限流退避 (用于直接使用 HTTP_CLIENT 的请求，如 Bangumi API)
+/// 退避期内不发送请求；收到 429 时记录 Retry-After 并返回 RateLimited，其它响应原样返回
+pub async fn send_with_backoff(req: RequestBuilder) -> Result<Response, HttpClientError> {
+    let (client, request) = req.build_split();
+    let request = request.map_err(|e| HttpClientError::RequestFailed(e.to_string()))?;
+    let host = request.url().host_str().map(|h| h.to_ascii_lowercase());
+    if let Some(wait) = host
+        .as_deref()
+        .and_then(|h| rate_limit_remaining(h, Instant::now()))
+    {
+        return Err(HttpClientError::RateLimited(wait));
+    }
+
+    let _permit = match &host {
+        Some(host) => acquire_host(host).await,
+        None => None,
+    };
+    let url = request.url().to_string();
+    let started = Instant::now();
+    let result = client.execute(request).await.map_err(send_error);
+    let failed = match &result {
+        Ok(r) => r.status().is_client_error() || r.status().is_server_error(),
+        Err(_) => true,
+    };
+    stats::record_request(&url, None, started.elapsed(), failed);
+    let response = result?;
+    if response.status() != StatusCode::TOO_MANY_REQUESTS {
+        return Ok(response);
+    }
+    let wait = retry_after(response.headers());
+    if let Some(host) = &host {
+        record_rate_limit(host, wait);
+    }
+    Err(HttpClientError::RateLimited(wait))
+}
+
+// ============================================================================
+// Cloudflare 验证 (FlareSolverr)
+// ============================================================================
+
+/// 未返回过期时间时凭据的默认有效期
+const CLEARANCE_DEFAULT_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// Cloudflare 验证通过后的凭据 (cf_clearance 与 User-Agent 绑定，需一起使用)
+#[derive(Debug, Clone)]
+struct Clearance {
+    cookie: String,
+    user_agent: String,
+    expires_at: Instant,
+}
+
+/// 按域名缓存的凭据
+static CLEARANCES: Lazy<RwLock<HashMap<String, Clearance>>> = Lazy::new(Default::default);
+
+#[derive(Debug, Deserialize)]
+struct SolverResponse {
+    status: String,
+    #[serde(default)]
+    message: String,
+    solution: Option<SolverSolution>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SolverSolution {
+    #[serde(default)]
+    cookies: Vec<SolverCookie>,
+    #[serde(default)]
+    user_agent: String,
+}
 
-    Ok(response)
+#[derive(Debug, Deserialize)]
+struct SolverCookie {
+    name: String,
+    value: String,
+    /// Unix 时间戳 (秒)，会话 Cookie 为 -1
+    #[serde(default)]
+    expires: Option<f64>,
+}
+
+pub fn host_of(url: &str) -> Option<String> {
+    url::Url::parse(url).ok()?.host_str().map(|h| h.to_ascii_lowercase())
+}
+
+/// 会话 Cookie 存储中该地址的 Cookie
+pub fn session_cookie(url: &str) -> Option<String> {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|u| COOKIE_JAR.cookies(&u))
+        .and_then(|v| v.to_str().ok().map(|v| v.to_string()))
+}
+
+/// 合并该域名的 Cloudflare 凭据与会话 Cookie
+/// 显式设置 Cookie 头时 reqwest 不会再附加 Cookie 存储中的内容，因此这里手动合并
+fn prepare_config<'a>(url: &str, config: &'a RequestConfig) -> Cow<'a, RequestConfig> {
+    let clearance = host_of(url).and_then(|host| {
+        let clearances = CLEARANCES.read().unwrap_or_else(|e| e.into_inner());
+        clearances
+            .get(&host)
+            .filter(|c| c.expires_at > Instant::now())
+            .cloned()
+    });
+    if config.cookie.is_none() && clearance.is_none() {
+        return Cow::Borrowed(config);
+    }
+
+    let session = session_cookie(url);
+
+    let mut config = config.clone();
+    let cookie = [session, config.cookie.take(), clearance.as_ref().map(|c| c.cookie.clone())]
+        .into_iter()
+        .flatten()
+        .filter(|c| !c.is_empty())
+        .collect::<Vec<_>>()
+        .join("; ");
+    config.cookie = Some(cookie).filter(|c| !c.is_empty());
+
+    if let Some(user_agent) = clearance.map(|c| c.user_agent).filter(|ua| !ua.is_empty()) {
+        config.user_agent = Some(user_agent);
+    }
+    Cow::Owned(config)
+}
+
+/// 通过 FlareSolverr 完成验证并缓存该域名的凭据
+async fn solve_challenge(url: &str) -> anyhow::Result<()> {
+    let host = host_of(url).ok_or_else(|| anyhow::anyhow!("无效的 URL"))?;
+    let endpoint = format!("{}/v1", CONFIG.flaresolverr_url.trim_end_matches('/'));
+    let timeout = Duration::from_secs(CONFIG.flaresolverr_timeout_secs);
+    tracing::info!("🛡️ 使用 FlareSolverr 通过 Cloudflare 验证: {}", host);
+
+    let response: SolverResponse = HTTP_CLIENT
+        .post(&endpoint)
+        .timeout(timeout + Duration::from_secs(10))
+        .json(&serde_json::json!({
+            "cmd": "request.get",
+            "url": url,
+            "maxTimeout": timeout.as_millis() as u64,
+        }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let solution = match response.solution {
+        Some(solution) if response.status == "ok" => solution,
+        _ => anyhow::bail!("{}", response.message),
+    };
+    if solution.cookies.is_empty() {
+        anyhow::bail!("未返回 Cookie");
+    }
+
+    // 以 cf_clearance 的过期时间为准
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let expires_at = solution
+        .cookies
+        .iter()
+        .find(|c| c.name == "cf_clearance")
+        .and_then(|c| c.expires)
+        .filter(|&ts| ts > now.as_secs_f64())
+        .map(|ts| Instant::now() + Duration::from_secs_f64(ts - now.as_secs_f64()))
+        .unwrap_or_else(|| Instant::now() + CLEARANCE_DEFAULT_TTL);
+
+    let cookie = solution
+        .cookies
+        .iter()
+        .map(|c| format!("{}={}", c.name, c.value))
+        .collect::<Vec<_>>()
+        .join("; ");
+
+    CLEARANCES
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(
+            host,
+            Clearance {
+                cookie,
+                user_agent: solution.user_agent,
+                expires_at,
+            },
+        );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proxied_url() {
+        let url = "https://example.com:8443/search?q=a&p=1";
+        assert_eq!(
+            proxied_url("https://rp.example.net/?target=", url),
+            "https://rp.example.net/?target=https://example.com:8443/search?q=a&p=1"
+        );
+        assert_eq!(
+            proxied_url("https://rp.example.net/?url={url_encoded}&ua=1", url),
+            "https://rp.example.net/?url=https%3A%2F%2Fexample.com%3A8443%2Fsearch%3Fq%3Da%26p%3D1&ua=1"
+        );
+        assert_eq!(
+            proxied_url("https://mirror.example.net/https/{host}/{path}", url),
+            "https://mirror.example.net/https/example.com:8443/search?q=a&p=1"
+        );
+    }
+
+    #[test]
+    fn test_unproxy() {
+        let url = "https://example.com/search?q=a&p=1";
+        for prefix in [
+            "https://rp.example.net/?target=",
+            "https://rp.example.net/{url}",
+            "https://rp.example.net/?url={url_encoded}&ua=1",
+            "https://mirror.example.net/https/{host}/{path}",
+        ] {
+            let pattern = proxy_pattern(prefix);
+            let proxied = proxied_url(prefix, url);
+            assert_eq!(unproxy(prefix, pattern.as_ref(), &proxied).as_deref(), Some(url), "{}", prefix);
+            assert_eq!(unproxy(prefix, pattern.as_ref(), "https://other.example.org/"), None, "{}", prefix);
+        }
+        assert_eq!(unproxy("", None, url), None);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_445_412_480);
+        assert_eq!(parse_retry_after("120", now), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        // 已过去的时间不再等待
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn test_host_matches() {
+        assert!(host_matches("example.com", "example.com"));
+        assert!(host_matches("www.example.com", "example.com"));
+        assert!(!host_matches("badexample.com", "example.com"));
+        assert!(!host_matches("example.com.cn", "example.com"));
+    }
+
+    #[test]
+    fn test_host_semaphore() {
+        let semaphore = host_semaphore("busy.test", 2);
+        let _first = semaphore.clone().try_acquire_owned().unwrap();
+        let _second = host_semaphore("busy.test", 2).try_acquire_owned().unwrap();
+        assert!(host_semaphore("busy.test", 2).try_acquire_owned().is_err());
+        assert!(host_semaphore("idle.test", 2).try_acquire_owned().is_ok());
+    }
+
+    #[test]
+    fn test_rate_limit_backoff() {
+        record_rate_limit("limited.test", Duration::from_secs(60));
+        let remaining = rate_limit_remaining("limited.test", Instant::now()).unwrap();
+        assert!(remaining > Duration::from_secs(55));
+        assert_eq!(rate_limit_remaining("limited.test", Instant::now() + Duration::from_secs(61)), None);
+        assert_eq!(rate_limit_remaining("limited.test", Instant::now()), None);
+        assert_eq!(rate_limit_remaining("other.test", Instant::now()), None);
+    }
+
+    #[test]
+    fn test_append_limited() {
+        let mut buf = Vec::new();
+        assert!(append_limited(&mut buf, b"hello", 8));
+        assert!(append_limited(&mut buf, b"abc", 8));
+        assert!(!append_limited(&mut buf, b"xyz", 8));
+        assert_eq!(buf, b"helloabc");
+    }
+
+    #[tokio::test]
+    async fn test_send_following_records_chain() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // /a -> /b -> /c，POST 经 303 后改为 GET
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]);
+                let head = if request.starts_with("POST /a") {
+                    "HTTP/1.1 303 See Other\r\nLocation: /b\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+                } else if request.starts_with("GET /b") {
+                    "HTTP/1.1 302 Found\r\nLocation: /c\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+                } else {
+                    let body = request.split_whitespace().take(2).collect::<Vec<_>>().join(" ");
+                    format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body)
+                };
+                let _ = socket.write_all(head.as_bytes()).await;
+            }
+        });
+
+        let client = build_client(5, None, false, ClientKind::Scraper);
+        let request = client.post(format!("http://{}/a", addr)).body("x=1").build().unwrap();
+        let response = send_following(&client, request, &RequestConfig::default()).await.unwrap();
+        assert_eq!(response.url().path(), "/c");
+        assert_eq!(
+            redirects_of(&response),
+            vec![format!("http://{}/a", addr), format!("http://{}/b", addr)]
+        );
+        assert_eq!(response.text().await.unwrap(), "GET /c");
+    }
+
+    #[test]
+    fn test_is_public_ip() {
+        for ip in ["93.184.216.34", "2606:2800:220:1:248:1893:25c8:1946"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.0.0.1",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_following_public_only() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let _ = socket.read(&mut buf).await;
+                let head = "HTTP/1.1 302 Found\r\nLocation: http://169.254.169.254/latest\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+                let _ = socket.write_all(head.as_bytes()).await;
+            }
+        });
+
+        // 跳转到内网地址的重定向在连接前被拒绝
+        let client = build_client(5, None, false, ClientKind::Scraper);
+        let request = client.get(format!("http://{}/a", addr)).build().unwrap();
+        let config = RequestConfig {
+            public_only: true,
+            ..Default::default()
+        };
+        let error = send_following(&client, request, &config).await.unwrap_err();
+        assert!(matches!(error, HttpClientError::PrivateAddress(host) if host == "169.254.169.254"));
+        assert!(matches!(
+            ensure_public(&format!("http://{}/a", addr)).await,
+            Err(HttpClientError::PrivateAddress(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_send_following_headers() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // /a 下发 Cookie 并跳到 /b，/b 跳到另一域名 (localhost) 的 /c，记录每一跳收到的规则请求头
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = received.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = [0u8; 2048];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]).to_ascii_lowercase();
+                let headers: Vec<String> = request
+                    .lines()
+                    .filter(|line| ["cookie:", "referer:", "x-token:"].iter().any(|h| line.starts_with(h)))
+                    .map(str::to_string)
+                    .collect();
+                log.lock().unwrap().push(headers);
+                let head = if request.starts_with("get /a") {
+                    "HTTP/1.1 302 Found\r\nSet-Cookie: session=fresh; Path=/\r\nLocation: /b\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+                } else if request.starts_with("get /b") {
+                    format!(
+                        "HTTP/1.1 302 Found\r\nLocation: http://localhost:{}/c\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                        addr.port()
+                    )
+                } else {
+                    "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+                };
+                let _ = socket.write_all(head.as_bytes()).await;
+            }
+        });
+
+        let config = RequestConfig {
+            referer: Some("http://origin.example/".to_string()),
+            cookie: Some("token=rule".to_string()),
+            headers: vec![("X-Token".to_string(), "secret".to_string())],
+            ..Default::default()
+        };
+        let client = build_client(5, None, false, ClientKind::Scraper);
+        let url = format!("http://{}/a", addr);
+        let request = prepare_config(&url, &config).apply(client.get(&url)).build().unwrap();
+        let response = send_following(&client, request, &config).await.unwrap();
+        assert_eq!(response.url().host_str(), Some("localhost"));
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 3);
+        // 同域名的 /b 带上规则请求头，以及 /a 的 30x 下发的 Cookie
+        assert!(received[1].contains(&"cookie: session=fresh; token=rule".to_string()));
+        assert!(received[1].contains(&"referer: http://origin.example/".to_string()));
+        assert!(received[1].contains(&"x-token: secret".to_string()));
+        // 其它域名的 /c 不带任何规则请求头
+        assert!(received[2].is_empty(), "{:?}", received[2]);
+    }
+
+    #[tokio::test]
+    async fn test_decompression() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // 手工构造的 "hello"：zstd 帧 (单个未压缩块) 与 zlib 包装的 deflate 存储块
+        let zstd: &[u8] = b"\x28\xb5\x2f\xfd\x20\x05\x29\x00\x00hello";
+        let deflate: &[u8] = b"\x78\x01\x01\x05\x00\xfa\xffhello\x06\x2c\x02\x15";
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]);
+                let (encoding, body) = if request.starts_with("GET /zstd") {
+                    ("zstd", zstd)
+                } else {
+                    ("deflate", deflate)
+                };
+                let head = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Encoding: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    encoding,
+                    body.len()
+                );
+                let _ = socket.write_all(head.as_bytes()).await;
+                let _ = socket.write_all(body).await;
+            }
+        });
+
+        let client = build_client(5, None, false, ClientKind::Api);
+        for encoding in ["zstd", "deflate"] {
+            let response = client
+                .get(format!("http://{}/{}", addr, encoding))
+                .send()
+                .await
+                .unwrap();
+            let (bytes, truncated) = read_limited(response).await.unwrap();
+            assert!(!truncated);
+            assert_eq!(decode_body(&bytes, Some("text/plain; charset=utf-8")), "hello", "{}", encoding);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hedge() {
+        let delayed = |ms: u64, result: Result<u32, HttpClientError>| async move {
+            tokio::time::sleep(Duration::from_millis(ms)).await;
+            result
+        };
+        let delay = Duration::from_millis(10);
+
+        // 直连慢于反代：反代胜出
+        let result = hedge(delayed(500, Ok(1)), delayed(0, Ok(2)), delay).await;
+        assert_eq!(result.unwrap(), 2);
+        // 直连在对冲前完成：不等待反代
+        let result = hedge(delayed(0, Ok(1)), delayed(0, Ok(2)), delay).await;
+        assert_eq!(result.unwrap(), 1);
+        // 反代失败时等待直连
+        let result = hedge(delayed(50, Ok(1)), delayed(0, Err(HttpClientError::Timeout)), delay).await;
+        assert_eq!(result.unwrap(), 1);
+        // 直连返回无需反代的错误 (404) 时直接返回
+        let result = hedge(delayed(0, Err(HttpClientError::BadStatus(404))), delayed(0, Ok(2)), delay).await;
+        assert!(matches!(result, Err(HttpClientError::BadStatus(404))));
+    }
+
+    #[test]
+    fn test_circuit_breaker() {
+        let mut breaker = CircuitBreaker::new(3, Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert!(!breaker.record_failure("a.com", now));
+        assert!(!breaker.record_failure("a.com", now));
+        assert!(breaker.allow("a.com", now));
+        assert!(breaker.record_failure("a.com", now));
+        assert!(!breaker.allow("a.com", now));
+        assert!(breaker.allow("b.com", now));
+
+        // 冷却结束后放行试探，再次失败立即熔断
+        let later = now + Duration::from_secs(61);
+        assert!(breaker.allow("a.com", later));
+        assert!(breaker.record_failure("a.com", later));
+        assert!(!breaker.allow("a.com", later));
+
+        // 成功后恢复
+        breaker.record_success("a.com");
+        assert!(breaker.allow("a.com", later));
+
+        // 已满时清除未熔断的域名，正在熔断的保留
+        for _ in 0..3 {
+            breaker.record_failure("a.com", later);
+        }
+        for i in 0..MAX_TRACKED_HOSTS {
+            breaker.record_failure(&format!("h{}.com", i), later);
+        }
+        assert!(breaker.hosts.len() <= MAX_TRACKED_HOSTS);
+        assert!(!breaker.allow("a.com", later));
+
+        // 阈值为 0 时不熔断
+        let mut disabled = CircuitBreaker::new(0, Duration::from_secs(60));
+        for _ in 0..10 {
+            assert!(!disabled.record_failure("a.com", now));
+        }
+        assert!(disabled.allow("a.com", now));
+    }
+
+    #[test]
+    fn test_decode_body() {
+        // "进击的巨人" (GBK)
+        let gbk = b"\xbd\xf8\xbb\xf7\xb5\xc4\xbe\xde\xc8\xcb";
+        assert_eq!(decode_body(gbk, Some("text/html; charset=GBK")), "进击的巨人");
+
+        let mut html = b"<html><head><meta http-equiv=\"Content-Type\" content=\"text/html; charset=gb2312\"></head><body>".to_vec();
+        html.extend_from_slice(gbk);
+        assert!(decode_body(&html, Some("text/html")).ends_with("<body>进击的巨人"));
+
+        let mut html = b"<meta charset='big5'>".to_vec();
+        html.extend_from_slice(b"\xb6\x69\xc0\xbb"); // "進擊" (Big5)
+        assert_eq!(decode_body(&html, None), "<meta charset='big5'>進擊");
+
+        assert_eq!(decode_body("进击".as_bytes(), None), "进击");
+        assert_eq!(decode_body(b"\xef\xbb\xbf\xe8\xbf\x9b", Some("text/html; charset=gbk")), "进");
+    }
+
+    #[test]
+    fn test_prepare_config_merges_session_cookies() {
+        let url = "https://cookie-jar.example.com/search";
+        let config = RequestConfig::default();
+        assert!(matches!(prepare_config(url, &config), Cow::Borrowed(_)));
+
+        COOKIE_JAR.add_cookie_str("PHPSESSID=abc; Path=/", &url::Url::parse(url).unwrap());
+        let config = RequestConfig {
+            cookie: Some("theme=dark".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            prepare_config(url, &config).cookie.as_deref(),
+            Some("PHPSESSID=abc; theme=dark")
+        );
+    }
 }
@@ -0,0 +1,752 @@
+//! 出站请求服务栈 (tower)
+//! 缓存、重试、域名并发限制、熔断与限流退避、反代回退等功能各自实现为一层 Layer，
+//! get_page 等请求函数只负责组装服务栈，新增的横切功能加一层即可
+
+use crate::config::CONFIG;
+use crate::http_cache::{self, CachedResponse, Lookup};
+use crate::http_client::{self, HttpClientError, Page, RequestConfig};
+use crate::stats;
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use reqwest::Response;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tower::retry::{Policy, RetryLayer};
+use tower::{Layer, Service, ServiceBuilder, ServiceExt};
+
+/// 单次重试退避时间上限
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(10);
+
+/// 服务栈各层返回的 future
+pub type BoxFuture<T> = Pin<Box<dyn Future<Output = Result<T, HttpClientError>> + Send>>;
+
+/// 出站请求
+#[derive(Debug, Clone)]
+pub struct PageRequest {
+    pub url: String,
+    /// POST 请求的 body 与 Content-Type (为空时为 GET 请求)
+    pub body: Option<(String, String)>,
+    pub config: RequestConfig,
+    /// 为空时使用客户端的默认超时
+    pub timeout: Option<Duration>,
+    /// 临时故障时的重试策略 (默认不重试)
+    pub retry: RetryPolicy,
+    /// 已发起的次数 (重试时克隆的请求共享同一计数，调用方可据此得知实际尝试次数)
+    pub attempts: Arc<AtomicU32>,
+    /// 地址已改写为反代前缀地址 (反代回退层发起，使用反代重试客户端)
+    pub proxied: bool,
+}
+
+impl PageRequest {
+    pub fn get(url: &str, config: &RequestConfig, timeout: Option<Duration>) -> Self {
+        Self {
+            url: url.to_string(),
+            body: None,
+            config: config.clone(),
+            timeout,
+            retry: RetryPolicy::default(),
+            attempts: Arc::new(AtomicU32::new(1)),
+            proxied: false,
+        }
+    }
+
+    pub fn post(
+        url: &str,
+        body: &str,
+        content_type: &str,
+        config: &RequestConfig,
+        timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            url: url.to_string(),
+            body: Some((body.to_string(), content_type.to_string())),
+            config: config.clone(),
+            timeout,
+            retry: RetryPolicy::default(),
+            attempts: Arc::new(AtomicU32::new(1)),
+            proxied: false,
+        }
+    }
+
+    /// 临时故障时按 policy 退避重试
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// 已发起的次数 (包括重试)
+    pub fn attempts(&self) -> u32 {
+        self.attempts.load(Ordering::Relaxed)
+    }
+}
+
+/// 已读取响应体的响应 (尚未解码)
+#[derive(Debug, Clone)]
+pub struct Fetched {
+    /// 最终落地的地址 (经反代时为反代目标地址)
+    pub final_url: String,
+    pub status: u16,
+    /// 到达最终地址前经过的重定向
+    pub redirects: Vec<String>,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+    /// 响应体是否超过 MAX_RESPONSE_MB 被截断
+    pub truncated: bool,
+}
+
+impl Fetched {
+    fn from_cache(entry: CachedResponse) -> Self {
+        let mut headers = HeaderMap::new();
+        if let Some(value) = entry
+            .content_type
+            .as_deref()
+            .and_then(|v| HeaderValue::from_str(v).ok())
+        {
+            headers.insert(header::CONTENT_TYPE, value);
+        }
+        Self {
+            final_url: entry.final_url,
+            status: entry.status,
+            redirects: Vec::new(),
+            headers,
+            body: entry.body,
+            truncated: false,
+        }
+    }
+
+    async fn read(url: &str, response: Response) -> Result<Self, HttpClientError> {
+        let final_url = http_client::unproxied_url(response.url().as_str());
+        let redirects = http_client::redirects_of(&response);
+        let status = response.status().as_u16();
+        let headers = response.headers().clone();
+        let (body, truncated) = http_client::read_limited(response).await?;
+        if truncated {
+            tracing::warn!("响应体超过 {} MB，已截断: {}", CONFIG.max_response_mb, url);
+        }
+        Ok(Self {
+            final_url,
+            status,
+            redirects,
+            headers,
+            body,
+            truncated,
+        })
+    }
+
+    /// 按实际编码将响应体转换为 UTF-8 页面
+    pub fn into_page(self) -> Page {
+        let content_type = self
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok());
+        Page {
+            body: http_client::decode_body(&self.body, content_type),
+            final_url: self.final_url,
+            status: self.status,
+            redirects: self.redirects,
+        }
+    }
+}
+
+/// 页面请求的服务栈：缓存 → 重试 → 域名并发限制 → 读取响应体 → 熔断/限流 → 统计 → 反代回退 → 发送
+/// (读取响应体期间连接仍被占用，因此并发名额在读取完成后才释放；重试等待期间不占用并发名额)
+pub fn page_service(
+) -> impl Service<PageRequest, Response = Fetched, Error = HttpClientError, Future = BoxFuture<Fetched>>
+{
+    ServiceBuilder::new()
+        .layer(CacheLayer)
+        .layer(RetryLayer::new(TransientRetry))
+        .layer(HostLimitLayer)
+        .layer(ReadBodyLayer)
+        .layer(CircuitLayer)
+        .layer(MetricsLayer)
+        .layer(FallbackLayer)
+        .service(Fetch)
+}
+
+/// 返回原始响应的服务栈，由调用方自行读取响应体 (如图片代理)
+pub fn response_service(
+) -> impl Service<PageRequest, Response = Response, Error = HttpClientError, Future = BoxFuture<Response>>
+{
+    ServiceBuilder::new()
+        .layer(HostLimitLayer)
+        .layer(CircuitLayer)
+        .layer(MetricsLayer)
+        .layer(FallbackLayer)
+        .service(Fetch)
+}
+
+// ============================================================================
+// 发送
+// ============================================================================
+
+/// 发送单个请求 (不做任何回退)
+#[derive(Debug, Clone, Copy)]
+pub struct Fetch;
+
+impl Service<PageRequest> for Fetch {
+    type Response = Response;
+    type Error = HttpClientError;
+    type Future = BoxFuture<Response>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: PageRequest) -> Self::Future {
+        Box::pin(async move { http_client::send(&req).await })
+    }
+}
+
+// ============================================================================
+// 反代回退
+// ============================================================================
+
+/// 直连失败时的回退：Cloudflare 验证页交给求解服务后重试，网络问题或反爬状态码改用反代前缀重试
+/// (开启对冲时直连与反代竞速)
+#[derive(Debug, Clone, Copy)]
+pub struct FallbackLayer;
+
+impl<S> Layer<S> for FallbackLayer {
+    type Service = Fallback<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Fallback { inner }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Fallback<S> {
+    inner: S,
+}
+
+impl<S> Service<PageRequest> for Fallback<S>
+where
+    S: Service<PageRequest, Error = HttpClientError> + Clone + Send + Sync + 'static,
+    S::Future: Send,
+    S::Response: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = HttpClientError;
+    type Future = BoxFuture<S::Response>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: PageRequest) -> Self::Future {
+        let inner = self.inner.clone();
+        Box::pin(http_client::with_fallback(req, move |req| inner.clone().oneshot(req)))
+    }
+}
+
+// ============================================================================
+// 重试
+// ============================================================================
+
+/// 重试策略 (由请求方按规则设置，默认只请求一次)
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// 最多尝试的次数 (包括第一次)
+    pub attempts: u32,
+    pub backoff_ms: u64,
+    pub jitter_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 1,
+            backoff_ms: 0,
+            jitter_ms: 0,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 第 retry 次重试前的等待时间 (指数退避，不含抖动)
+    fn backoff(&self, retry: u32) -> Duration {
+        let factor = 1u64 << (retry.saturating_sub(1)).min(16);
+        Duration::from_millis(self.backoff_ms.saturating_mul(factor)).min(MAX_RETRY_BACKOFF)
+    }
+
+    /// 随机抖动，避免多个请求同时重试
+    fn jitter(&self) -> Duration {
+        if self.jitter_ms == 0 {
+            return Duration::ZERO;
+        }
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.subsec_nanos() as u64)
+            .unwrap_or(0);
+        Duration::from_millis(nanos % (self.jitter_ms + 1))
+    }
+}
+
+/// 临时故障 (超时、连接失败、5xx 等) 时按请求的重试策略退避重试
+#[derive(Debug, Clone, Copy)]
+pub struct TransientRetry;
+
+impl<T> Policy<PageRequest, T, HttpClientError> for TransientRetry {
+    type Future = tokio::time::Sleep;
+
+    fn retry(
+        &mut self,
+        req: &mut PageRequest,
+        result: &mut Result<T, HttpClientError>,
+    ) -> Option<Self::Future> {
+        let Err(error) = result else {
+            return None;
+        };
+        let attempt = req.attempts();
+        if !http_client::is_transient(error) || attempt >= req.retry.attempts {
+            return None;
+        }
+
+        let delay = req.retry.backoff(attempt) + req.retry.jitter();
+        tracing::debug!("第 {} 次请求失败，{:?} 后重试 {}: {}", attempt, delay, req.url, error);
+        stats::record_retry(&req.url, req.config.rule.as_deref());
+        req.attempts.fetch_add(1, Ordering::Relaxed);
+        Some(tokio::time::sleep(delay))
+    }
+
+    fn clone_request(&mut self, req: &PageRequest) -> Option<PageRequest> {
+        (req.retry.attempts > 1).then(|| req.clone())
+    }
+}
+
+// ============================================================================
+// 读取响应体
+// ============================================================================
+
+/// 读取响应体 (最多 MAX_RESPONSE_MB)
+#[derive(Debug, Clone, Copy)]
+pub struct ReadBodyLayer;
+
+impl<S> Layer<S> for ReadBodyLayer {
+    type Service = ReadBody<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ReadBody { inner }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReadBody<S> {
+    inner: S,
+}
+
+impl<S> Service<PageRequest> for ReadBody<S>
+where
+    S: Service<PageRequest, Response = Response, Error = HttpClientError> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Response = Fetched;
+    type Error = HttpClientError;
+    type Future = BoxFuture<Fetched>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: PageRequest) -> Self::Future {
+        let inner = self.inner.clone();
+        Box::pin(async move {
+            let url = req.url.clone();
+            let response = inner.oneshot(req).await?;
+            Fetched::read(&url, response).await
+        })
+    }
+}
+
+// ============================================================================
+// 响应缓存
+// ============================================================================
+
+/// 按 Cache-Control 缓存 GET 响应，过期后带上 ETag / Last-Modified 重新验证
+#[derive(Debug, Clone, Copy)]
+pub struct CacheLayer;
+
+impl<S> Layer<S> for CacheLayer {
+    type Service = Cache<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Cache { inner }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cache<S> {
+    inner: S,
+}
+
+impl<S> Service<PageRequest> for Cache<S>
+where
+    S: Service<PageRequest, Response = Fetched, Error = HttpClientError> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Response = Fetched;
+    type Error = HttpClientError;
+    type Future = BoxFuture<Fetched>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, mut req: PageRequest) -> Self::Future {
+        let inner = self.inner.clone();
+        Box::pin(async move {
+            // 只缓存幂等的 GET 请求
+            if req.body.is_some() {
+                return inner.oneshot(req).await;
+            }
+
+            // 键中包含请求配置，先于附加条件请求头计算
+            let url = cache_key(&req);
+            let stale = match http_cache::lookup(&url).await {
+                Lookup::Fresh(entry) => return Ok(Fetched::from_cache(entry)),
+                Lookup::Stale(entry) => {
+                    req.config.headers.extend(entry.validators());
+                    Some(entry)
+                }
+                Lookup::Miss => None,
+            };
+
+            let fetched = inner.oneshot(req).await?;
+            if let (304, Some(entry)) = (fetched.status, stale) {
+                let entry = http_cache::revalidated(&url, entry, &fetched.headers).await;
+                return Ok(Fetched::from_cache(entry));
+            }
+            if fetched.status == 200 && !fetched.truncated {
+                http_cache::store(&url, &fetched.final_url, &fetched.headers, &fetched.body).await;
+            }
+            Ok(fetched)
+        })
+    }
+}
+
+/// 请求的缓存键 (地址 + 影响响应内容的请求配置与该地址的会话 Cookie)
+fn cache_key(req: &PageRequest) -> String {
+    let config = &req.config;
+    let mut fingerprint = format!(
+        "referer={:?}\nua={:?}\ncookie={:?}\nsession={:?}\nproxy={}\ninsecure={}",
+        config.referer,
+        config.user_agent,
+        config.cookie,
+        http_client::session_cookie(&req.url),
+        config.outbound_proxy,
+        config.insecure_tls
+    );
+    let mut headers = config.headers.clone();
+    headers.sort();
+    for (name, value) in headers {
+        fingerprint.push_str(&format!("\n{}: {}", name.to_ascii_lowercase(), value));
+    }
+    http_cache::cache_key(&req.url, &fingerprint)
+}
+
+// ============================================================================
+// 域名并发限制
+// ============================================================================
+
+/// 同一域名同时进行的请求数不超过 HOST_MAX_CONCURRENCY
+#[derive(Debug, Clone, Copy)]
+pub struct HostLimitLayer;
+
+impl<S> Layer<S> for HostLimitLayer {
+    type Service = HostLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HostLimit { inner }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HostLimit<S> {
+    inner: S,
+}
+
+impl<S> Service<PageRequest> for HostLimit<S>
+where
+    S: Service<PageRequest, Error = HttpClientError> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Response: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = HttpClientError;
+    type Future = BoxFuture<S::Response>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: PageRequest) -> Self::Future {
+        let inner = self.inner.clone();
+        Box::pin(async move {
+            let url = req.url.clone();
+            http_client::with_host_limit(&url, inner.oneshot(req)).await
+        })
+    }
+}
+
+// ============================================================================
+// 熔断与限流退避
+// ============================================================================
+
+/// 站点连续失败时熔断，429 后按 Retry-After 退避
+#[derive(Debug, Clone, Copy)]
+pub struct CircuitLayer;
+
+impl<S> Layer<S> for CircuitLayer {
+    type Service = Circuit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Circuit { inner }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Circuit<S> {
+    inner: S,
+}
+
+impl<S> Service<PageRequest> for Circuit<S>
+where
+    S: Service<PageRequest, Error = HttpClientError> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Response: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = HttpClientError;
+    type Future = BoxFuture<S::Response>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: PageRequest) -> Self::Future {
+        let inner = self.inner.clone();
+        Box::pin(async move {
+            let url = req.url.clone();
+            http_client::with_circuit(&url, inner.oneshot(req)).await
+        })
+    }
+}
+
+// ============================================================================
+// 出站请求统计
+// ============================================================================
+
+/// 按域名与规则统计发往上游的请求数、失败数与耗时
+#[derive(Debug, Clone, Copy)]
+pub struct MetricsLayer;
+
+impl<S> Layer<S> for MetricsLayer {
+    type Service = Metrics<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Metrics { inner }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Metrics<S> {
+    inner: S,
+}
+
+impl<S> Service<PageRequest> for Metrics<S>
+where
+    S: Service<PageRequest, Error = HttpClientError> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Response: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = HttpClientError;
+    type Future = BoxFuture<S::Response>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: PageRequest) -> Self::Future {
+        let inner = self.inner.clone();
+        Box::pin(async move {
+            let url = req.url.clone();
+            let rule = req.config.rule.clone();
+            let started = Instant::now();
+            let result = inner.oneshot(req).await;
+            stats::record_request(&url, rule.as_deref(), started.elapsed(), result.is_err());
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cached_page() {
+        let entry = CachedResponse {
+            status: 200,
+            final_url: "https://example.com/detail/1".to_string(),
+            content_type: Some("text/html; charset=GBK".to_string()),
+            etag: Some("\"abc\"".to_string()),
+            last_modified: None,
+            expires_at: 42,
+            // "进击的巨人" (GBK)
+            body: b"\xbd\xf8\xbb\xf7\xb5\xc4\xbe\xde\xc8\xcb".to_vec(),
+        };
+        let page = Fetched::from_cache(entry).into_page();
+        assert_eq!(page.body, "进击的巨人");
+        assert_eq!(page.final_url, "https://example.com/detail/1");
+        assert_eq!(page.status, 200);
+        assert!(page.redirects.is_empty());
+    }
+
+    #[test]
+    fn test_cache_key_includes_config() {
+        let plain = PageRequest::get("https://example.com/", &RequestConfig::default(), None);
+        let with_cookie = PageRequest::get(
+            "https://example.com/",
+            &RequestConfig {
+                cookie: Some("age_verified=1".to_string()),
+                ..Default::default()
+            },
+            None,
+        );
+        let mut with_rule = plain.clone();
+        with_rule.config.rule = Some("AGE".to_string());
+        assert_ne!(cache_key(&plain), cache_key(&with_cookie));
+        // 规则名只用于统计，不影响响应内容
+        assert_eq!(cache_key(&plain), cache_key(&with_rule));
+    }
+
+    #[test]
+    fn test_cache_key_includes_session_cookie() {
+        use reqwest::cookie::CookieStore;
+
+        // 站点下发会话 Cookie 后 (如登录、年龄确认) 不再命中之前未带 Cookie 时的缓存
+        let url = "https://session.example.com/search?q=1";
+        let req = PageRequest::get(url, &RequestConfig::default(), None);
+        let before = cache_key(&req);
+        let value = HeaderValue::from_static("sid=abc; Path=/");
+        http_client::COOKIE_JAR.set_cookies(&mut std::iter::once(&value), &url::Url::parse(url).unwrap());
+        assert_ne!(before, cache_key(&req));
+        // 其它站点的 Cookie 不影响
+        let other = PageRequest::get("https://other.example.com/", &RequestConfig::default(), None);
+        assert!(http_client::session_cookie(&other.url).is_none());
+    }
+
+    #[test]
+    fn test_retry_backoff() {
+        let policy = RetryPolicy {
+            attempts: 3,
+            backoff_ms: 200,
+            jitter_ms: 0,
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(200));
+        assert_eq!(policy.backoff(2), Duration::from_millis(400));
+        assert_eq!(policy.backoff(3), Duration::from_millis(800));
+        assert_eq!(policy.backoff(30), MAX_RETRY_BACKOFF);
+        assert_eq!(policy.jitter(), Duration::ZERO);
+    }
+
+    /// 前 failures 次返回 error，之后成功的服务
+    #[derive(Clone)]
+    struct Flaky {
+        calls: Arc<AtomicU32>,
+        failures: u32,
+        error: fn() -> HttpClientError,
+    }
+
+    impl Service<PageRequest> for Flaky {
+        type Response = u32;
+        type Error = HttpClientError;
+        type Future = BoxFuture<u32>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _: PageRequest) -> Self::Future {
+            let call = self.calls.fetch_add(1, Ordering::Relaxed) + 1;
+            let result = if call <= self.failures { Err((self.error)()) } else { Ok(call) };
+            Box::pin(async move { result })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transient_retry() {
+        let policy = RetryPolicy {
+            attempts: 3,
+            backoff_ms: 1,
+            jitter_ms: 0,
+        };
+        let service = |failures, error| {
+            let calls = Arc::new(AtomicU32::new(0));
+            let service = ServiceBuilder::new()
+                .layer(RetryLayer::new(TransientRetry))
+                .service(Flaky {
+                    calls: calls.clone(),
+                    failures,
+                    error,
+                });
+            (service, calls)
+        };
+        let request = || PageRequest::get("https://retry.example.com/", &RequestConfig::default(), None);
+
+        // 临时故障重试到成功，请求上记录实际尝试次数
+        let (retrying, _) = service(2, || HttpClientError::Timeout);
+        let req = request().with_retry(policy);
+        let attempts = req.attempts.clone();
+        assert_eq!(retrying.oneshot(req).await.unwrap(), 3);
+        assert_eq!(attempts.load(Ordering::Relaxed), 3);
+
+        // 超过尝试次数后返回最后一次的错误
+        let (retrying, calls) = service(5, || HttpClientError::Timeout);
+        assert!(retrying.oneshot(request().with_retry(policy)).await.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+
+        // 非临时故障 (如 404、429) 不重试
+        let (retrying, calls) = service(1, || HttpClientError::BadStatus(404));
+        assert!(retrying.oneshot(request().with_retry(policy)).await.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        // 未设置重试策略时只请求一次
+        let (retrying, calls) = service(1, || HttpClientError::Timeout);
+        assert!(retrying.oneshot(request()).await.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_to_proxy() {
+        let (service, calls) = {
+            let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let log = calls.clone();
+            let service = ServiceBuilder::new()
+                .layer(FallbackLayer)
+                .service(tower::service_fn(move |req: PageRequest| {
+                    log.lock().unwrap().push((req.url.clone(), req.proxied));
+                    let result = if req.proxied { Ok(req.url) } else { Err(HttpClientError::BadStatus(403)) };
+                    async move { result }
+                }));
+            (service, calls)
+        };
+
+        // 直连被拒绝 (403) 时改用反代前缀地址重试
+        let url = "https://blocked.example.com/search";
+        let result = service
+            .oneshot(PageRequest::get(url, &RequestConfig::default(), None))
+            .await
+            .unwrap();
+        assert_eq!(result, http_client::proxied_url(&CONFIG.proxy_prefix, url));
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0], (url.to_string(), false));
+        assert!(calls[1].1);
+    }
+}
@@ -0,0 +1,234 @@
+//! 浏览器 TLS 指纹模拟 (impersonate 特性)
+//! 部分站点无视 User-Agent、直接按 TLS 指纹 (ClientHello) 拦截 reqwest，
+//! 规则设置 impersonate 后改用 wreq 以浏览器的指纹发起请求
+
+use crate::config::CONFIG;
+use crate::http_client::{self, HttpClientError, Page, RequestConfig};
+use crate::http_service::{BoxFuture, CircuitLayer, HostLimitLayer, MetricsLayer, PageRequest, TransientRetry};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower::retry::RetryLayer;
+use tower::{Service, ServiceBuilder, ServiceExt};
+use wreq::header::{CONTENT_TYPE, LOCATION};
+use wreq::{Client, RequestBuilder, Response, Url};
+use wreq_util::Emulation;
+
+/// 客户端的区分条件：指纹、出站代理、是否跳过证书校验
+type ClientKey = (String, Option<&'static str>, bool);
+
+/// 已创建的客户端
+static CLIENTS: Lazy<Mutex<HashMap<ClientKey, Client>>> = Lazy::new(Default::default);
+
+/// 规则可选的浏览器指纹
+fn emulation(profile: &str) -> Option<Emulation> {
+    match profile.trim().to_ascii_lowercase().as_str() {
+        "chrome" => Some(Emulation::Chrome137),
+        "edge" => Some(Emulation::Edge134),
+        "firefox" => Some(Emulation::Firefox139),
+        "safari" => Some(Emulation::Safari18_5),
+        _ => None,
+    }
+}
+
+/// 获取指纹对应的客户端 (首次使用时创建)
+fn client(url: &str, profile: &str, config: &RequestConfig) -> Result<Client, HttpClientError> {
+    let emulation = emulation(profile)
+        .ok_or_else(|| HttpClientError::RequestFailed(format!("不支持的浏览器指纹: {}", profile)))?;
+    let proxy = http_client::proxy_for(config);
+    let insecure = http_client::accepts_invalid_certs(url, config);
+    let key = (profile.trim().to_ascii_lowercase(), proxy, insecure);
+
+    let mut clients = CLIENTS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(client) = clients.get(&key) {
+        return Ok(client.clone());
+    }
+
+    // 不覆盖 User-Agent：指纹模拟会带上与之匹配的浏览器 UA
+    // 不自动跟随重定向，由 send_following 逐跳跟随并检查
+    let mut builder = Client::builder()
+        .emulation(emulation)
+        .timeout(Duration::from_secs(CONFIG.timeout_seconds))
+        .cookie_store(true)
+        .redirect(wreq::redirect::Policy::none())
+        .cert_verification(!insecure);
+    if let Some(proxy) = proxy {
+        let proxy = wreq::Proxy::all(proxy).map_err(|e| HttpClientError::RequestFailed(e.to_string()))?;
+        builder = builder.proxy(proxy);
+    }
+    let client = builder
+        .build()
+        .map_err(|e| HttpClientError::RequestFailed(e.to_string()))?;
+    clients.insert(key, client.clone());
+    Ok(client)
+}
+
+/// 将规则的请求配置应用到请求上 (只有规则显式指定时才覆盖 User-Agent)
+fn apply(config: &RequestConfig, mut req: RequestBuilder) -> RequestBuilder {
+    if let Some(referer) = &config.referer {
+        req = req.header("Referer", referer);
+    }
+    if let Some(user_agent) = &config.user_agent {
+        req = req.header("User-Agent", user_agent);
+    }
+    if let Some(cookie) = &config.cookie {
+        req = req.header("Cookie", cookie);
+    }
+    for (name, value) in &config.headers {
+        req = req.header(name.as_str(), value.as_str());
+    }
+    req
+}
+
+fn send_error(e: wreq::Error) -> HttpClientError {
+    if e.is_timeout() {
+        HttpClientError::Timeout
+    } else {
+        HttpClientError::RequestFailed(e.to_string())
+    }
+}
+
+/// 发送请求并逐跳跟随重定向，返回响应与经过的地址
+/// 规则的请求头只发往原域名，跳转到其它域名时不再携带 (与 http_client::send_following 一致)
+async fn send_following(client: &Client, req: &PageRequest) -> Result<(Response, Vec<String>), HttpClientError> {
+    let first_host = Url::parse(&req.url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string));
+    let mut url = req.url.clone();
+    let mut body = req.body.clone();
+    let mut previous: Vec<String> = Vec::new();
+    loop {
+        let builder = match &body {
+            None => client.get(url.as_str()),
+            Some((body, content_type)) => client
+                .post(url.as_str())
+                .header(CONTENT_TYPE, content_type.as_str())
+                .body(body.clone()),
+        };
+        let to_origin = Url::parse(&url).ok().and_then(|u| u.host_str().map(str::to_string)) == first_host;
+        let builder = if to_origin { apply(&req.config, builder) } else { builder };
+        let builder = match req.timeout {
+            Some(timeout) => builder.timeout(timeout),
+            None => builder,
+        };
+
+        let response = builder.send().await.map_err(send_error)?;
+        let status = response.status().as_u16();
+        let next = response
+            .headers()
+            .get(LOCATION)
+            .filter(|_| matches!(status, 301 | 302 | 303 | 307 | 308))
+            .and_then(|v| v.to_str().ok())
+            .and_then(|location| response.url().join(location).ok());
+        let Some(next) = next else {
+            return Ok((response, previous));
+        };
+
+        previous.push(response.url().to_string());
+        match http_client::check_redirect(&previous, first_host.as_deref(), &next) {
+            Ok(true) => {}
+            Ok(false) => {
+                previous.pop();
+                return Ok((response, previous));
+            }
+            Err(message) => return Err(HttpClientError::Redirect(message)),
+        }
+        if req.config.public_only {
+            http_client::ensure_public(next.as_str()).await?;
+        }
+        // 301/302/303 之后改为不带 body 的 GET (与浏览器一致)
+        if matches!(status, 301..=303) {
+            body = None;
+        }
+        url = next.to_string();
+    }
+}
+
+/// 发送请求并读取页面 (响应体超过 MAX_RESPONSE_MB 时截断)
+async fn fetch(client: Client, req: PageRequest) -> Result<Page, HttpClientError> {
+    if req.config.public_only {
+        http_client::ensure_public(&req.url).await?;
+    }
+    let (response, redirects) = send_following(&client, &req).await?;
+
+    let status = response.status().as_u16();
+    if !response.status().is_success() {
+        return Err(http_client::status_error(status, response.headers()));
+    }
+    Ok(Page {
+        final_url: response.url().to_string(),
+        status,
+        redirects,
+        body: read_text(response).await?,
+    })
+}
+
+async fn read_text(mut response: Response) -> Result<String, HttpClientError> {
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+
+    let limit = http_client::response_limit();
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| HttpClientError::RequestFailed(e.to_string()))?
+    {
+        if !http_client::append_limited(&mut bytes, &chunk, limit) {
+            tracing::warn!("响应体超过 {} MB，已截断: {}", CONFIG.max_response_mb, response.url());
+            break;
+        }
+    }
+    Ok(http_client::decode_body(&bytes, content_type.as_deref()))
+}
+
+/// 以浏览器指纹发送请求并读取页面
+#[derive(Clone)]
+struct Fetch {
+    client: Client,
+}
+
+impl Service<PageRequest> for Fetch {
+    type Response = Page;
+    type Error = HttpClientError;
+    type Future = BoxFuture<Page>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: PageRequest) -> Self::Future {
+        Box::pin(fetch(self.client.clone(), req))
+    }
+}
+
+/// 同样按请求的重试策略重试、受熔断、限流与域名并发限制并计入出站统计
+/// (不经过响应缓存，也不使用反代前缀与 Cloudflare 求解：两者都会换回 reqwest 的指纹)
+pub async fn fetch_page(req: PageRequest, profile: &str) -> Result<Page, HttpClientError> {
+    let client = client(&req.url, profile, &req.config)?;
+    ServiceBuilder::new()
+        .layer(RetryLayer::new(TransientRetry))
+        .layer(HostLimitLayer)
+        .layer(CircuitLayer)
+        .layer(MetricsLayer)
+        .service(Fetch { client })
+        .oneshot(req)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emulation() {
+        assert!(emulation("chrome").is_some());
+        assert!(emulation(" Safari ").is_some());
+        assert!(emulation("netscape").is_none());
+    }
+}
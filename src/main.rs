@@ -1,37 +1,61 @@
+mod auth;
 mod bangumi;
+mod bounded;
 mod config;
 mod core;
+mod dns;
 mod engine;
+mod error;
+mod graphql;
+mod health;
+mod http_cache;
 mod http_client;
+mod http_service;
+#[cfg(feature = "impersonate")]
+mod impersonate;
+mod proxy;
 mod rules;
+mod script;
+mod stats;
+mod storage;
+mod title;
 mod types;
 mod updater;
+#[cfg(feature = "webview")]
+mod webview;
+mod xpath_eval;
 mod xpath_to_css;
 
 use config::CONFIG;
 
 use axum::{
-    body::Body,
-    extract::{Multipart, Path, Request},
-    http::{header, HeaderMap, Method, StatusCode},
+    body::{Body, Bytes},
+    extract::{rejection::JsonRejection, Multipart, Path, Query, Request},
+    http::{header, HeaderMap, HeaderName, Method, StatusCode},
+    middleware,
     response::{Html, IntoResponse, Response},
     routing::{any, get, post},
     Json, Router,
 };
 use futures::StreamExt;
+use serde::Deserialize;
 use serde_json::json;
 use std::net::SocketAddr;
+use tower_http::compression::predicate::{DefaultPredicate, NotForContentType, Predicate};
+use tower_http::compression::CompressionLayer;
 use tower_http::cors::{Any, CorsLayer};
 use tracing::{info, Level};
 use tracing_subscriber::FmtSubscriber;
 
 use crate::core::search_stream_with_rules;
-use crate::rules::get_builtin_rules;
+use crate::error::ApiError;
+use crate::rules::{get_builtin_rules, get_rule_by_name};
+use crate::types::{DebugMode, RoadSelector, Rule, SearchOptions, SearchResultItem};
 
 #[tokio::main]
 async fn main() {
     // 初始化日志
-    let _subscriber = FmtSubscriber::builder()
+    FmtSubscriber::builder()
         .with_max_level(Level::INFO)
         .with_target(false)
         .with_thread_ids(false)
@@ -42,8 +66,13 @@ async fn main() {
     // CORS 配置
     let cors = CorsLayer::new()
         .allow_origin(Any)
-        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
-        .allow_headers([header::CONTENT_TYPE]);
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE, Method::OPTIONS])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::AUTHORIZATION,
+            HeaderName::from_static(error::REQUEST_ID_HEADER),
+        ])
+        .expose_headers([HeaderName::from_static(error::REQUEST_ID_HEADER)]);
 
     // 检查是否需要拉取规则（本地无规则或设置了 AUTO_UPDATE）
     let need_update = !updater::has_local_rules() 
@@ -53,22 +82,92 @@ async fn main() {
         info!("📡 正在拉取规则...");
         let result = updater::update_rules().await;
         info!(
-            "📦 更新完成: {} 新增, {} 更新, {} 失败",
-            result.added, result.updated, result.failed
+            "📦 更新完成: {} 新增, {} 更新, {} 删除, {} 失败",
+            result.added, result.updated, result.removed, result.failed
         );
     }
 
+    // 生成 rules/index.json，使 rules/ 目录可作为 Kazumi 规则仓库
+    rules::refresh_index();
+
+    // 后台存储清理
+    storage::spawn_janitor();
+
+    // 规则定时更新
+    updater::spawn_scheduler();
+
+    // 规则文件变动时热重载
+    rules::spawn_watcher();
+
+    // 规则定时健康检查
+    health::spawn_checker();
+
+    // 规则使用统计定期写入磁盘
+    stats::spawn_flusher();
+
+    // 管理路由 (需要 ADMIN_TOKEN)
+    let admin = Router::new()
+        .route("/storage", get(storage_handler))
+        .route("/storage/cleanup", post(storage_cleanup_handler))
+        .route("/update", post(update_handler))
+        // 检查更新会请求 GitHub API，消耗速率配额，只对管理员开放
+        .route("/update/check", get(update_check_handler))
+        .route("/update/{name}", post(update_rule_handler))
+        .route("/rules/rollback", post(rollback_handler))
+        .route("/rules/health/check", post(health_check_handler))
+        .route("/rules/import", post(import_rule_handler))
+        // 试运行会按提交的规则请求任意地址并返回上游内容，只对管理员开放
+        .route("/rules/test", post(rule_test_handler))
+        .route(
+            "/rules/{name}",
+            post(create_rule_handler)
+                .put(replace_rule_handler)
+                .patch(patch_rule_handler)
+                .delete(delete_rule_handler),
+        )
+        .route_layer(middleware::from_fn(auth::require_admin));
+
     // 路由
     let app = Router::new()
         // 核心路由
         .route("/", get(index_handler))
         .route("/api", post(search_handler))
+        .route("/api/detail", post(detail_handler))
+        .route("/api/resolve", post(resolve_handler))
+        .route("/proxy/stream", get(stream_proxy_handler))
+        .route("/proxy/image", get(image_proxy_handler))
         .route("/info", get(api_info_handler))
         .route("/rules", get(rules_handler))
-        .route("/update", get(update_handler))
+        .route("/rules/validation", get(rules_validation_handler))
+        .route("/rules/export", get(rules_export_handler))
+        .route("/rules/health", get(rules_health_handler))
+        .route("/rules/index.json", get(rules_index_handler))
+        .route("/rules/{name}", get(rule_detail_handler))
+        .route("/stats", get(stats_handler))
+        .route("/stats/upstream", get(upstream_stats_handler))
+        // 已废弃：重定向到 POST /admin/update
+        .route("/update", any(legacy_update_handler))
+        .route("/webhooks/github", post(github_webhook_handler))
         .route("/health", get(health_handler))
+        // GraphQL (聚合搜索 + Bangumi 元数据)
+        .route("/graphql", get(graphql::graphiql_handler).post(graphql::graphql_handler))
+        .with_state(graphql::build_schema())
+        // 规则调试
+        .route("/debug/select", post(debug_select_handler))
+        .route("/debug/xpath", post(debug_xpath_handler))
+        .route("/rules/validate", post(rule_validate_handler))
+        .nest("/admin", admin)
+        // Bangumi 扩展接口
+        .route("/bangumi/users/{username}/heatmap", get(bangumi_heatmap_handler))
         // Bangumi API 通用代理 (透传到 api.bgm.tv，自动添加 CORS)
         .route("/bgm/{*path}", any(bangumi_proxy_handler))
+        .layer(middleware::from_fn(error::request_id_middleware))
+        // gzip/br 压缩 (跳过 text/event-stream 避免缓冲导致 SSE 无法实时推送，跳过中转的媒体分片)
+        .layer(CompressionLayer::new().compress_when(
+            DefaultPredicate::new()
+                .and(NotForContentType::const_new("video/"))
+                .and(NotForContentType::const_new("application/octet-stream")),
+        ))
         .layer(cors);
 
     // 启动服务器
@@ -95,11 +194,45 @@ async fn api_info_handler() -> impl IntoResponse {
         "endpoints": {
             "core": {
                 "GET /": "搜索页面",
-                "POST /api": "搜索动漫 (FormData: anime=关键词, rules=规则名1,规则名2)",
+                "POST /api": "搜索动漫 (FormData: anime=关键词, rules=规则名1,规则名2 (all=所有未被隔离的规则), aliases=1 使用 Bangumi 别名扩展, episodes=0 不获取集数, episode_items=N 只为前 N 个结果获取集数, pages=N 抓取页数, first=N 快速模式 (N 个规则有结果后立即结束), timeout=N 单次请求超时上限/秒, debug=1 无结果时附带解析诊断 (debug=2 另附 HTML 快照，需要管理令牌))",
+                "POST /api/detail": "获取单个结果的集数列表 (JSON: rule=规则名, url=详情页链接 或 item=搜索结果, road=只获取指定播放源 (序号或名称), debug=1/2 无章节时附带解析诊断 (需要管理令牌))",
+                "POST /api/resolve": "解析播放页中的真实媒体地址 (JSON: rule=规则名, url=播放页链接)",
+                "GET /proxy/stream": "HLS 播放列表/分片中转 (url=媒体地址, rule=规则名)",
+                "GET /proxy/image": "封面图片中转，带磁盘缓存 (url=图片地址, rule=规则名)",
                 "GET /rules": "获取所有规则列表",
-                "GET /update": "从 KazumiRules 更新规则",
+                "GET /rules/validation": "列出选择器无法转换为 CSS (回退为 XPath 求值) 或无效的规则",
+                "GET /rules/{name}": "获取单个规则的完整定义、来源与最后更新时间 (headers 与 cookies 需要管理令牌)",
+                "GET /rules/health": "规则健康检查结果 (探测关键词的搜索状态、结果数量、耗时与连续失败次数)",
+                "GET /rules/export": "导出当前安装的全部规则 (JSON 数组，兼容 Kazumi 规则格式；headers 与 cookies 需要管理令牌)",
+                "ANY /update": "已废弃，308 重定向到 POST /admin/update",
+                "POST /webhooks/github": "GitHub Webhook，规则仓库推送时触发更新 (需要配置 GITHUB_WEBHOOK_SECRET)",
                 "GET /health": "健康检查"
             },
+            "admin": {
+                "note": "需要 Authorization: Bearer <ADMIN_TOKEN>",
+                "POST /admin/update": "从 KazumiRules 更新规则 (ref=分支/tag/SHA 更新到指定版本)",
+                "GET /admin/update/check": "检查上游是否有规则更新 (不下载规则)",
+                "POST /admin/update/{name}": "只更新单个规则 (校验后热重载该规则)",
+                "POST /admin/rules/rollback": "恢复更新前备份的规则集并热重载",
+                "POST /admin/rules/health/check": "立即对所有启用的规则执行健康检查",
+                "POST /admin/rules/import": "从 URL 导入规则 (JSON: url, overwrite=是否覆盖同名规则)",
+                "POST /admin/rules/test": "试运行规则 (JSON: rule=完整规则, keyword=关键词)，返回转换后的选择器、状态码、耗时与解析结果",
+                "POST /admin/rules/{name}": "创建本地规则 (JSON: 完整规则，name 需与路径一致)",
+                "PUT /admin/rules/{name}": "修改本地规则",
+                "PATCH /admin/rules/{name}": "修改规则的本地设置 (JSON: enabled=是否启用, priority=优先级，越大越靠前)",
+                "DELETE /admin/rules/{name}": "删除本地规则",
+                "GET /admin/storage": "磁盘缓存占用情况",
+                "POST /admin/storage/cleanup": "立即按容量上限清理缓存"
+            },
+            "graphql": {
+                "GET /graphql": "GraphiQL 调试页面",
+                "POST /graphql": "GraphQL 查询 (规则、聚合搜索、Bangumi 条目与章节)"
+            },
+            "debug": {
+                "POST /debug/select": "对提供的 HTML 执行选择器 (JSON: html, xpath 或 css)",
+                "POST /debug/xpath": "只转换不执行，返回 XPath 对应的 CSS、位置/文本过滤器与不支持的语法 (JSON: xpath, strictPositions)",
+                "POST /rules/validate": "校验规则 (JSON: 完整规则)，返回结构错误、缺失字段、选择器问题与 baseURL 可访问性 (需要管理令牌，reachability=false 跳过访问)"
+            },
             "bangumi_proxy": {
                 "ANY /bgm/*": "Bangumi API 通用代理 (透传到 api.bgm.tv，自动添加 CORS)",
                 "example": "GET /bgm/v0/subjects/328609 → https://api.bgm.tv/v0/subjects/328609"
@@ -113,10 +246,11 @@ async fn api_info_handler() -> impl IntoResponse {
 }
 
 /// POST / - 动漫搜索处理器 (SSE 流式响应)
-async fn search_handler(mut multipart: Multipart) -> Response {
+async fn search_handler(headers: HeaderMap, mut multipart: Multipart) -> Response {
     // 解析 FormData
     let mut keyword: Option<String> = None;
     let mut rule_names: Option<String> = None;
+    let mut options = SearchOptions::default();
 
     while let Ok(Some(field)) = multipart.next_field().await {
         match field.name() {
@@ -130,50 +264,77 @@ async fn search_handler(mut multipart: Multipart) -> Response {
                     rule_names = Some(text.trim().to_string());
                 }
             }
+            Some("aliases") => {
+                if let Ok(text) = field.text().await {
+                    options.expand_aliases = text.trim() == "1";
+                }
+            }
+            Some("episodes") => {
+                if let Ok(text) = field.text().await {
+                    options.fetch_episodes = text.trim() != "0";
+                }
+            }
+            Some("pages") => {
+                if let Ok(text) = field.text().await {
+                    options.pages = text.trim().parse().unwrap_or(1);
+                }
+            }
+            Some("episode_items") => {
+                if let Ok(text) = field.text().await {
+                    options.episode_items = text.trim().parse().ok();
+                }
+            }
+            Some("first") => {
+                if let Ok(text) = field.text().await {
+                    options.first = text.trim().parse().ok();
+                }
+            }
+            Some("timeout") => {
+                if let Ok(text) = field.text().await {
+                    options.timeout = text
+                        .trim()
+                        .parse()
+                        .ok()
+                        .filter(|secs| *secs > 0)
+                        .map(std::time::Duration::from_secs);
+                }
+            }
+            Some("debug") => {
+                if let Ok(text) = field.text().await {
+                    options.debug = DebugMode::from_level(text.trim().parse().unwrap_or(0));
+                }
+            }
             _ => {}
         }
     }
 
     let keyword = match keyword {
         Some(k) if !k.is_empty() => k,
-        _ => {
-            return (
-                StatusCode::BAD_REQUEST,
-                [(header::CONTENT_TYPE, "application/json")],
-                Json(json!({"error": "Anime name is required"})),
-            )
-                .into_response();
-        }
+        _ => return ApiError::bad_request("missing_keyword", "Anime name is required").into_response(),
     };
 
+    if let Err(e) = check_debug_access(options.debug, &headers) {
+        return e.into_response();
+    }
+
     // 筛选规则
-    let all_rules = get_builtin_rules();
     let selected_rules: Vec<_> = match rule_names {
         Some(names) if !names.is_empty() => {
             let name_list: Vec<&str> = names.split(',').map(|s| s.trim()).collect();
-            all_rules
-                .into_iter()
-                .filter(|r| name_list.contains(&r.name.as_str()))
-                .collect()
+            rules::select_rules(&name_list)
         }
         _ => {
             // 如果没有指定规则，返回错误
-            return (
-                StatusCode::BAD_REQUEST,
-                [(header::CONTENT_TYPE, "application/json")],
-                Json(json!({"error": "Rules are required. Use 'rules' field to specify rule names (comma separated)"})),
+            return ApiError::bad_request(
+                "missing_rules",
+                "Rules are required. Use 'rules' field to specify rule names (comma separated)",
             )
-                .into_response();
+            .into_response();
         }
     };
 
     if selected_rules.is_empty() {
-        return (
-            StatusCode::BAD_REQUEST,
-            [(header::CONTENT_TYPE, "application/json")],
-            Json(json!({"error": "No matching rules found"})),
-        )
-            .into_response();
+        return ApiError::bad_request("no_matching_rules", "No matching rules found").into_response();
     }
 
     info!(
@@ -187,10 +348,10 @@ async fn search_handler(mut multipart: Multipart) -> Response {
     );
 
     // 创建 SSE 流
-    let stream = search_stream_with_rules(keyword, selected_rules);
+    let stream = search_stream_with_rules(keyword, selected_rules, options);
 
     // 将流转换为字节流
-    let body = Body::from_stream(stream.map(|s| Ok::<_, std::convert::Infallible>(s)));
+    let body = Body::from_stream(stream.map(Ok::<_, std::convert::Infallible>));
 
     Response::builder()
         .status(StatusCode::OK)
@@ -202,26 +363,326 @@ async fn search_handler(mut multipart: Multipart) -> Response {
         .unwrap()
 }
 
-/// 获取规则列表
+/// 详情请求：url 与 item 二选一
+#[derive(Debug, Deserialize)]
+struct DetailRequest {
+    rule: String,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    item: Option<SearchResultItem>,
+    /// 只获取指定播放源 (序号从 0 开始，或名称如 "线路2")
+    #[serde(default)]
+    road: Option<RoadSelector>,
+    /// 无章节时返回诊断信息 (1 = 诊断，2 = 另附 HTML 快照；需要管理令牌)
+    #[serde(default)]
+    debug: u8,
+}
+
+/// debug 模式会暴露上游页面内容，仅允许携带管理令牌的请求使用
+fn check_debug_access(debug: DebugMode, headers: &HeaderMap) -> Result<(), ApiError> {
+    if debug.enabled() && !auth::is_admin(headers) {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "debug_forbidden",
+            "debug 模式需要管理令牌",
+        ));
+    }
+    Ok(())
+}
+
+/// POST /api/detail - 获取单个搜索结果的集数列表
+async fn detail_handler(
+    headers: HeaderMap,
+    req: Result<Json<DetailRequest>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let Json(req) = req.map_err(|e| ApiError::bad_request("invalid_request", e.body_text()))?;
+    let debug = DebugMode::from_level(req.debug);
+    check_debug_access(debug, &headers)?;
+
+    let url = req
+        .url
+        .or(req.item.map(|item| item.url))
+        .filter(|u| !u.is_empty())
+        .ok_or_else(|| ApiError::bad_request("missing_url", "url or item is required"))?;
+
+    let rule = get_rule_by_name(&req.rule).ok_or_else(|| {
+        ApiError::new(StatusCode::NOT_FOUND, "rule_not_found", format!("Rule not found: {}", req.rule))
+    })?;
+    check_rule_url(&rule, &url)?;
+
+    if !engine::has_episode_selectors(&rule) {
+        return Err(ApiError::bad_request(
+            "no_episode_selectors",
+            format!("Rule {} does not define chapterRoads/chapterResult", rule.name),
+        ));
+    }
+
+    let (roads, diagnostics) = engine::fetch_episodes(&rule, &url, req.road.as_ref(), debug)
+        .await
+        .map_err(|e| ApiError::upstream("获取章节失败", &e))?;
+
+    if let Some(diagnostics) = diagnostics {
+        return Ok(Json(json!({
+            "name": rule.name,
+            "url": url,
+            "episodes": roads,
+            "diagnostics": diagnostics
+        })));
+    }
+
+    if roads.is_empty() {
+        if let Some(road) = &req.road {
+            return Err(ApiError::new(
+                StatusCode::NOT_FOUND,
+                "road_not_found",
+                format!("Road not found: {:?}", road),
+            ));
+        }
+    }
+
+    Ok(Json(json!({
+        "name": rule.name,
+        "url": url,
+        "episodes": roads
+    })))
+}
+
+/// 播放地址解析请求
+#[derive(Debug, Deserialize)]
+struct ResolveRequest {
+    rule: String,
+    url: String,
+}
+
+/// POST /api/resolve - 解析播放页中的真实媒体地址
+async fn resolve_handler(
+    req: Result<Json<ResolveRequest>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let Json(req) = req.map_err(|e| ApiError::bad_request("invalid_request", e.body_text()))?;
+
+    let rule = get_rule_by_name(&req.rule).ok_or_else(|| {
+        ApiError::new(StatusCode::NOT_FOUND, "rule_not_found", format!("Rule not found: {}", req.rule))
+    })?;
+    check_rule_url(&rule, &req.url)?;
+
+    let sources = engine::resolve_play_url(&rule, &req.url)
+        .await
+        .map_err(|e| ApiError::upstream("解析播放地址失败", &e))?;
+
+    // 优先直接可播放的媒体，其次 iframe
+    let best = sources
+        .iter()
+        .find(|s| s.kind != "iframe" && s.kind != "unknown")
+        .or_else(|| sources.first());
+
+    Ok(Json(json!({
+        "name": rule.name,
+        "url": req.url,
+        "best": best,
+        "sources": sources
+    })))
+}
+
+/// 拒绝不属于规则站点的地址 (否则会带着规则的请求头与 Cookie 访问任意地址)
+fn check_rule_url(rule: &Rule, url: &str) -> Result<(), ApiError> {
+    if engine::belongs_to_rule(rule, url) {
+        return Ok(());
+    }
+    Err(ApiError::bad_request(
+        "url_not_in_rule",
+        format!("URL host does not match rule {} baseURL: {}", rule.name, url),
+    ))
+}
+
+/// 媒体中转参数
+#[derive(Debug, Deserialize)]
+struct StreamProxyQuery {
+    url: String,
+    #[serde(default)]
+    rule: Option<String>,
+    /// 改写播放列表时附加的地址签名
+    #[serde(default)]
+    sig: Option<String>,
+}
+
+/// GET /proxy/stream - 使用规则的 Referer/UA 中转 HLS 播放列表与分片
+async fn stream_proxy_handler(
+    Query(query): Query<StreamProxyQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let rule = match query.rule.as_deref() {
+        Some(name) => Some(get_rule_by_name(name).ok_or_else(|| {
+            ApiError::new(StatusCode::NOT_FOUND, "rule_not_found", format!("Rule not found: {}", name))
+        })?),
+        None => None,
+    };
+
+    proxy::relay_stream(&query.url, rule.as_deref(), query.sig.as_deref(), &headers).await
+}
+
+/// GET /proxy/image - 使用规则的 Referer/UA 中转封面图片 (磁盘缓存)
+async fn image_proxy_handler(Query(query): Query<StreamProxyQuery>) -> Result<Response, ApiError> {
+    let rule = match query.rule.as_deref() {
+        Some(name) => Some(get_rule_by_name(name).ok_or_else(|| {
+            ApiError::new(StatusCode::NOT_FOUND, "rule_not_found", format!("Rule not found: {}", name))
+        })?),
+        None => None,
+    };
+
+    proxy::relay_image(&query.url, rule.as_deref()).await
+}
+
+/// 获取规则列表 (包括已禁用的规则)
 async fn rules_handler() -> impl IntoResponse {
-    let rules = get_builtin_rules();
-    let rule_info: Vec<_> = rules
+    let rules = rules::get_all_rules();
+    let mut rule_info: Vec<_> = rules
         .iter()
         .map(|r| {
+            let source = rules::rule_source(&r.name);
             json!({
                 "name": r.name,
                 "version": r.version,
                 "baseUrl": r.base_url,
                 "color": r.color,
                 "tags": r.tags,
-                "magic": r.magic
+                "magic": r.magic,
+                "enabled": rules::is_enabled(&r.name),
+                "priority": rules::priority(&r.name),
+                "degraded": health::is_degraded(&r.name),
+                "stats": stats::usage(&r.name),
+                "source": source.as_ref().map(|s| s.source.as_str()),
+                "updatedAt": source.as_ref().and_then(|s| s.updated_at.as_deref())
             })
         })
         .collect();
+    // 加载失败的规则文件 (如内容与完整性清单不一致) 同样列出，便于发现
+    rule_info.extend(rules::rejected_files().into_iter().map(|(file, reason)| {
+        json!({
+            "name": file,
+            "enabled": false,
+            "rejected": reason
+        })
+    }));
 
     Json(rule_info)
 }
 
+/// GET /rules/{name} - 单个规则的完整定义 (未携带管理令牌时不含 headers 与 cookies)
+async fn rule_detail_handler(
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<Json<rules::RuleDetail>, ApiError> {
+    let not_found = || {
+        ApiError::new(
+            StatusCode::NOT_FOUND,
+            "rule_not_found",
+            format!("规则不存在: {}", name),
+        )
+    };
+    if rules::find_rule(&name).is_none() {
+        return Err(not_found());
+    }
+
+    let lookup = name.clone();
+    let mut detail = tokio::task::spawn_blocking(move || rules::rule_detail(&lookup))
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?
+        .ok_or_else(not_found)?;
+    if !auth::is_admin(&headers) {
+        rules::redact_credentials(&mut detail.rule);
+    }
+    Ok(Json(detail))
+}
+
+/// 汇总健康检查结果
+fn health_report(results: Vec<health::RuleHealth>) -> serde_json::Value {
+    let ok = results.iter().filter(|h| h.is_ok()).count();
+    json!({
+        "keyword": CONFIG.health_check_keyword,
+        "checked": results.len(),
+        "ok": ok,
+        "failing": results.len() - ok,
+        "rules": results
+    })
+}
+
+/// GET /rules/health - 最近一次规则健康检查结果
+async fn rules_health_handler() -> Json<serde_json::Value> {
+    Json(health_report(health::snapshot()))
+}
+
+/// POST /admin/rules/health/check - 立即执行健康检查
+async fn health_check_handler() -> Json<serde_json::Value> {
+    health::check_all().await;
+    Json(health_report(health::snapshot()))
+}
+
+/// GET /rules/index.json - Kazumi 兼容的规则索引
+async fn rules_index_handler() -> Json<Vec<rules::IndexEntry>> {
+    Json(rules::rule_index())
+}
+
+/// GET /stats - 各规则的使用统计 (按选用次数排序)
+async fn stats_handler() -> Json<Vec<stats::RuleUsage>> {
+    Json(stats::all_usage())
+}
+
+/// GET /stats/upstream - 出站请求统计 (按站点域名与规则)
+async fn upstream_stats_handler() -> Json<stats::UpstreamReport> {
+    Json(stats::upstream_usage())
+}
+
+/// GET /rules/export - 导出全部规则为 JSON 数组 (未携带管理令牌时不含 headers 与 cookies)
+async fn rules_export_handler(headers: HeaderMap) -> Result<Response, ApiError> {
+    let mut rules = tokio::task::spawn_blocking(rules::export_rules)
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?;
+    if !auth::is_admin(&headers) {
+        rules.iter_mut().for_each(rules::redact_credentials);
+    }
+
+    let filename = format!("rules-{}.json", chrono::Utc::now().format("%Y%m%d"));
+    Ok((
+        [(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", filename),
+        )],
+        Json(rules),
+    )
+        .into_response())
+}
+
+/// GET /rules/validation - 列出选择器无法转换或无效的规则
+async fn rules_validation_handler() -> impl IntoResponse {
+    let rules = get_builtin_rules();
+    let mut invalid = 0;
+    let mut unconvertible = 0;
+    let reports: Vec<_> = rules
+        .iter()
+        .filter_map(|r| {
+            let issues = engine::validate_selectors(r);
+            if issues.is_empty() {
+                return None;
+            }
+            if issues.iter().any(|i| i.level == "invalid") {
+                invalid += 1;
+            } else {
+                unconvertible += 1;
+            }
+            Some(json!({ "name": r.name, "issues": issues }))
+        })
+        .collect();
+
+    Json(json!({
+        "total": rules.len(),
+        "valid": rules.len() - reports.len(),
+        "unconvertible": unconvertible,
+        "invalid": invalid,
+        "rules": reports
+    }))
+}
+
 /// 健康检查
 async fn health_handler() -> impl IntoResponse {
     Json(json!({
@@ -230,18 +691,484 @@ async fn health_handler() -> impl IntoResponse {
     }))
 }
 
-/// GET /update - 从 KazumiRules 更新规则
-async fn update_handler() -> impl IntoResponse {
-    info!("📡 手动触发规则更新...");
-    let result = updater::update_rules().await;
-    Json(json!({
+/// 规则更新参数
+#[derive(Debug, Deserialize)]
+struct UpdateQuery {
+    /// 更新到指定版本 (分支、tag 或 commit SHA)，默认跟随 RULES_COMMIT 或分支
+    #[serde(rename = "ref")]
+    git_ref: Option<String>,
+}
+
+/// POST /admin/update - 从 KazumiRules 更新规则
+async fn update_handler(
+    Query(query): Query<UpdateQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let git_ref = query.git_ref.as_deref().unwrap_or(CONFIG.rules_ref());
+    if !updater::is_valid_ref(git_ref) {
+        return Err(ApiError::bad_request("invalid_ref", format!("无效的版本: {}", git_ref)));
+    }
+
+    info!("📡 手动触发规则更新 ({})...", git_ref);
+    let result = updater::update_rules_at(git_ref).await;
+    Ok(Json(json!({
         "success": true,
         "total": result.total,
         "added": result.added,
         "updated": result.updated,
+        "removed": result.removed,
         "failed": result.failed,
         "details": result.details
-    }))
+    })))
+}
+
+/// POST /admin/update/{name} - 只更新单个规则
+async fn update_rule_handler(Path(name): Path<String>) -> Result<Json<serde_json::Value>, ApiError> {
+    if !updater::is_valid_rule_name(&name) {
+        return Err(ApiError::bad_request("invalid_rule_name", format!("无效的规则名: {}", name)));
+    }
+    if rules::custom_rule_names().contains(&name) {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "rule_overridden",
+            format!("规则 {} 已通过管理接口在本地覆盖，删除本地规则后才能从上游更新", name),
+        ));
+    }
+
+    info!("📡 手动触发规则更新: {}", name);
+    let detail = updater::update_rule(&name)
+        .await
+        .map_err(|e| ApiError::upstream(format!("更新规则 {} 失败", name), &e))?;
+
+    Ok(Json(json!({ "success": true, "detail": detail })))
+}
+
+/// POST /admin/rules/rollback - 恢复上一次更新前的规则集
+async fn rollback_handler() -> Result<Json<serde_json::Value>, ApiError> {
+    let result = tokio::task::spawn_blocking(updater::rollback_rules)
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?
+        .map_err(|e| ApiError::new(StatusCode::CONFLICT, "rollback_failed", e.to_string()))?;
+
+    Ok(Json(json!({ "success": true, "rollback": result })))
+}
+
+/// 校验提交的规则 JSON，返回规则与格式化后的文件内容
+fn parse_submitted_rule(
+    name: &str,
+    body: Result<Json<serde_json::Value>, JsonRejection>,
+) -> Result<(Rule, String), ApiError> {
+    if !updater::is_valid_rule_name(name) {
+        return Err(ApiError::bad_request(
+            "invalid_rule_name",
+            format!("无效的规则名: {}", name),
+        ));
+    }
+
+    let Json(value) = body.map_err(|e| ApiError::bad_request("invalid_request", e.body_text()))?;
+    let content =
+        serde_json::to_string_pretty(&value).map_err(|e| ApiError::internal(e.to_string()))?;
+    let rule: Rule = serde_json::from_value(value).map_err(|e| {
+        ApiError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "invalid_rule",
+            format!("规则格式无效: {}", e),
+        )
+    })?;
+
+    if rule.name != name {
+        return Err(ApiError::bad_request(
+            "rule_name_mismatch",
+            format!("规则名 {} 与路径 {} 不一致", rule.name, name),
+        ));
+    }
+
+    Ok((rule, content))
+}
+
+/// 保存规则并返回选择器校验结果
+async fn save_submitted_rule(rule: Rule, content: String) -> Result<serde_json::Value, ApiError> {
+    let name = rule.name.clone();
+    let issues = engine::validate_selectors(&rule);
+    tokio::task::spawn_blocking(move || rules::save_custom_rule(rule, &content))
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?
+        .map_err(|e| ApiError::internal(format!("保存规则失败: {}", e)))?;
+
+    Ok(json!({ "success": true, "name": name, "issues": issues }))
+}
+
+/// POST /admin/rules/{name} - 创建本地规则
+async fn create_rule_handler(
+    Path(name): Path<String>,
+    body: Result<Json<serde_json::Value>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    let (rule, content) = parse_submitted_rule(&name, body)?;
+    if rules::rule_file_exists(&name) {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "rule_exists",
+            format!("规则已存在: {}", name),
+        ));
+    }
+
+    info!("➕ 创建规则: {}", name);
+    let body = save_submitted_rule(rule, content).await?;
+    Ok((StatusCode::CREATED, Json(body)).into_response())
+}
+
+/// PUT /admin/rules/{name} - 修改本地规则
+async fn replace_rule_handler(
+    Path(name): Path<String>,
+    body: Result<Json<serde_json::Value>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let (rule, content) = parse_submitted_rule(&name, body)?;
+    if !rules::rule_file_exists(&name) {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "rule_not_found",
+            format!("规则不存在: {}", name),
+        ));
+    }
+
+    info!("✏️ 修改规则: {}", name);
+    save_submitted_rule(rule, content).await.map(Json)
+}
+
+/// 规则本地设置修改请求
+#[derive(Debug, Deserialize)]
+struct PatchRuleRequest {
+    enabled: Option<bool>,
+    priority: Option<i32>,
+}
+
+/// PATCH /admin/rules/{name} - 修改规则的本地设置 (更新规则时保留)
+async fn patch_rule_handler(
+    Path(name): Path<String>,
+    req: Result<Json<PatchRuleRequest>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let Json(req) = req.map_err(|e| ApiError::bad_request("invalid_request", e.body_text()))?;
+    if rules::find_rule(&name).is_none() {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "rule_not_found",
+            format!("规则不存在: {}", name),
+        ));
+    }
+
+    if let Some(enabled) = req.enabled {
+        rules::set_enabled(&name, enabled)
+            .map_err(|e| ApiError::internal(format!("保存规则设置失败: {}", e)))?;
+    }
+    if let Some(priority) = req.priority {
+        rules::set_priority(&name, priority)
+            .map_err(|e| ApiError::internal(format!("保存规则设置失败: {}", e)))?;
+    }
+
+    Ok(Json(json!({
+        "success": true,
+        "name": name,
+        "enabled": rules::is_enabled(&name),
+        "priority": rules::priority(&name)
+    })))
+}
+
+/// DELETE /admin/rules/{name} - 删除本地规则
+async fn delete_rule_handler(
+    Path(name): Path<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    if !updater::is_valid_rule_name(&name) || !rules::rule_file_exists(&name) {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "rule_not_found",
+            format!("规则不存在: {}", name),
+        ));
+    }
+
+    rules::delete_custom_rule(&name)
+        .map_err(|e| ApiError::internal(format!("删除规则失败: {}", e)))?;
+    Ok(Json(json!({ "success": true, "name": name })))
+}
+
+/// 规则导入请求
+#[derive(Debug, Deserialize)]
+struct ImportRuleRequest {
+    url: String,
+    #[serde(default)]
+    overwrite: bool,
+}
+
+/// POST /admin/rules/import - 从 URL 下载、校验并安装规则
+async fn import_rule_handler(
+    req: Result<Json<ImportRuleRequest>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    let Json(req) = req.map_err(|e| ApiError::bad_request("invalid_request", e.body_text()))?;
+    let url = url::Url::parse(req.url.trim())
+        .ok()
+        .filter(|u| matches!(u.scheme(), "http" | "https"))
+        .ok_or_else(|| ApiError::bad_request("invalid_url", "url 必须是 http(s) 地址"))?;
+
+    info!("📥 导入规则: {}", url);
+    let value = updater::fetch_rule_json(url.as_str())
+        .await
+        .map_err(|e| ApiError::upstream("下载规则失败", &e))?;
+
+    // 校验未通过时返回完整报告
+    let report = engine::validate_rule(value.clone(), false).await;
+    if !report.valid {
+        return Ok((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({ "success": false, "code": "invalid_rule", "report": report })),
+        )
+            .into_response());
+    }
+
+    let name = value
+        .get("name")
+        .and_then(|n| n.as_str())
+        .unwrap_or_default()
+        .to_string();
+    let (rule, content) = parse_submitted_rule(&name, Ok(Json(value)))?;
+    if !req.overwrite && rules::rule_file_exists(&name) {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "rule_exists",
+            format!("规则已存在: {} (设置 overwrite=true 覆盖)", name),
+        ));
+    }
+
+    let mut body = save_submitted_rule(rule, content).await?;
+    body["source"] = json!(url.as_str());
+    Ok(Json(body).into_response())
+}
+
+/// /update - 已废弃的更新入口，308 重定向到 /admin/update (保留请求方法)
+async fn legacy_update_handler() -> Response {
+    (
+        StatusCode::PERMANENT_REDIRECT,
+        [
+            (header::LOCATION, "/admin/update"),
+            (HeaderName::from_static("deprecation"), "true"),
+            (header::CACHE_CONTROL, "no-store"),
+        ],
+    )
+        .into_response()
+}
+
+/// GET /admin/update/check - 检查上游是否有规则更新 (不下载规则)
+async fn update_check_handler() -> Result<Json<updater::UpdateCheck>, ApiError> {
+    updater::check_updates()
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::upstream("检查规则更新失败", &e))
+}
+
+/// GitHub push 事件 (只取用到的字段)
+#[derive(Debug, Deserialize)]
+struct GitHubPushEvent {
+    #[serde(rename = "ref")]
+    git_ref: String,
+    repository: GitHubRepository,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitHubRepository {
+    full_name: String,
+}
+
+/// POST /webhooks/github - 规则仓库推送时触发规则更新
+async fn github_webhook_handler(headers: HeaderMap, body: Bytes) -> Result<Response, ApiError> {
+    if CONFIG.github_webhook_secret.is_empty() {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "webhook_disabled",
+            "Webhook 未启用 (未配置 GITHUB_WEBHOOK_SECRET)",
+        ));
+    }
+
+    let signature = headers
+        .get("X-Hub-Signature-256")
+        .and_then(|v| v.to_str().ok());
+    if !auth::verify_github_signature(&body, signature) {
+        return Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "invalid_signature",
+            "Webhook 签名无效",
+        ));
+    }
+
+    let event = headers
+        .get("X-GitHub-Event")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if event != "push" {
+        // ping 等其他事件只确认收到
+        return Ok(Json(json!({ "success": true, "event": event, "triggered": false })).into_response());
+    }
+
+    let push: GitHubPushEvent = serde_json::from_slice(&body)
+        .map_err(|e| ApiError::bad_request("invalid_payload", e.to_string()))?;
+
+    if !CONFIG.rules_commit.is_empty() {
+        return Ok(Json(json!({
+            "success": true,
+            "event": event,
+            "triggered": false,
+            "reason": format!("规则已固定在 {}", CONFIG.rules_commit)
+        }))
+        .into_response());
+    }
+
+    let expected_ref = format!("refs/heads/{}", CONFIG.rules_branch);
+    if !push.repository.full_name.eq_ignore_ascii_case(&CONFIG.rules_repo) || push.git_ref != expected_ref {
+        return Ok(Json(json!({
+            "success": true,
+            "event": event,
+            "triggered": false,
+            "reason": format!("忽略 {} 的 {}", push.repository.full_name, push.git_ref)
+        }))
+        .into_response());
+    }
+
+    // GitHub 要求 10 秒内响应，更新在后台执行 (与其他更新互斥，依次进行)
+    info!("📡 收到 {} 推送，触发规则更新...", push.repository.full_name);
+    tokio::spawn(updater::update_rules());
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({ "success": true, "event": event, "triggered": true })),
+    )
+        .into_response())
+}
+
+/// 选择器调试请求
+#[derive(Debug, Deserialize)]
+struct DebugSelectRequest {
+    html: String,
+    #[serde(default)]
+    xpath: Option<String>,
+    #[serde(default)]
+    css: Option<String>,
+}
+
+/// POST /debug/select - 对提供的 HTML 执行 XPath/CSS 选择器
+async fn debug_select_handler(
+    req: Result<Json<DebugSelectRequest>, JsonRejection>,
+) -> Result<Json<engine::SelectDebug>, ApiError> {
+    let Json(req) = req.map_err(|e| ApiError::bad_request("invalid_request", e.body_text()))?;
+
+    engine::debug_select(&req.html, req.xpath.as_deref(), req.css.as_deref())
+        .map(Json)
+        .map_err(|e| ApiError::bad_request("invalid_selector", e.to_string()))
+}
+
+/// XPath 转换调试请求
+#[derive(Debug, Deserialize)]
+struct DebugXPathRequest {
+    xpath: String,
+    #[serde(default, alias = "strictPositions")]
+    strict_positions: bool,
+}
+
+/// POST /debug/xpath - 查看 XPath 转换为 CSS 的结果
+async fn debug_xpath_handler(
+    req: Result<Json<DebugXPathRequest>, JsonRejection>,
+) -> Result<Json<engine::XPathDebug>, ApiError> {
+    let Json(req) = req.map_err(|e| ApiError::bad_request("invalid_request", e.body_text()))?;
+
+    if req.xpath.trim().is_empty() {
+        return Err(ApiError::bad_request("missing_xpath", "xpath is required"));
+    }
+
+    Ok(Json(engine::debug_xpath(&req.xpath, req.strict_positions)))
+}
+
+/// 规则试运行请求
+#[derive(Debug, Deserialize)]
+struct RuleTestRequest {
+    rule: Rule,
+    keyword: String,
+}
+
+/// POST /admin/rules/test - 使用提交的规则试运行一次搜索
+async fn rule_test_handler(
+    req: Result<Json<RuleTestRequest>, JsonRejection>,
+) -> Result<Json<engine::RuleTestReport>, ApiError> {
+    let Json(req) = req.map_err(|e| ApiError::bad_request("invalid_request", e.body_text()))?;
+
+    let keyword = req.keyword.trim();
+    if keyword.is_empty() {
+        return Err(ApiError::bad_request("missing_keyword", "keyword is required"));
+    }
+
+    Ok(Json(engine::test_rule(&req.rule, keyword).await))
+}
+
+/// 规则校验参数
+#[derive(Debug, Deserialize)]
+struct RuleValidateQuery {
+    /// 是否访问 baseURL 检查可访问性 (默认检查)
+    reachability: Option<bool>,
+}
+
+/// POST /rules/validate - 校验规则 JSON
+/// 访问 baseURL 会向任意地址发起请求，只有携带管理令牌时才检查可访问性
+async fn rule_validate_handler(
+    headers: HeaderMap,
+    Query(query): Query<RuleValidateQuery>,
+    req: Result<Json<serde_json::Value>, JsonRejection>,
+) -> Result<Json<engine::RuleValidationReport>, ApiError> {
+    let Json(value) = req.map_err(|e| ApiError::bad_request("invalid_request", e.body_text()))?;
+    let reachability = auth::is_admin(&headers) && query.reachability.unwrap_or(true);
+    Ok(Json(engine::validate_rule(value, reachability).await))
+}
+
+/// GET /admin/storage - 各缓存区域的磁盘占用
+async fn storage_handler() -> Result<Json<serde_json::Value>, ApiError> {
+    let areas = tokio::task::spawn_blocking(storage::usage)
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?;
+
+    Ok(Json(json!({
+        "totalBytes": areas.iter().map(|a| a.bytes).sum::<u64>(),
+        "areas": areas
+    })))
+}
+
+/// POST /admin/storage/cleanup - 立即执行容量清理
+async fn storage_cleanup_handler() -> Result<Json<serde_json::Value>, ApiError> {
+    let reports = tokio::task::spawn_blocking(storage::enforce_limits)
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?;
+
+    Ok(Json(json!({ "success": true, "areas": reports })))
+}
+
+// ============================================================================
+// Bangumi 扩展接口
+// ============================================================================
+
+/// 热力图查询参数
+#[derive(Debug, Deserialize)]
+struct HeatmapQuery {
+    subject_type: Option<i32>,
+}
+
+/// GET /bangumi/users/{username}/heatmap - 用户收藏活动热力图
+async fn bangumi_heatmap_handler(
+    Path(username): Path<String>,
+    Query(query): Query<HeatmapQuery>,
+    headers: HeaderMap,
+) -> Result<Json<bangumi::Heatmap>, ApiError> {
+    let user_token = headers
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let token = bangumi::get_effective_token(user_token);
+
+    bangumi::get_user_heatmap(&username, query.subject_type, token)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::upstream("获取热力图失败", &e))
 }
 
 // ============================================================================
@@ -284,10 +1211,8 @@ async fn bangumi_proxy_handler(
     let body_bytes = match axum::body::to_bytes(req.into_body(), 10 * 1024 * 1024).await {
         Ok(bytes) => bytes,
         Err(e) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(json!({"error": format!("Failed to read request body: {}", e)})),
-            ).into_response();
+            return ApiError::bad_request("invalid_request", format!("Failed to read request body: {}", e))
+                .into_response();
         }
     };
 
@@ -299,10 +1224,7 @@ async fn bangumi_proxy_handler(
     let response = match request_builder.send().await {
         Ok(resp) => resp,
         Err(e) => {
-            return (
-                StatusCode::BAD_GATEWAY,
-                Json(json!({"error": format!("Proxy request failed: {}", e)})),
-            ).into_response();
+            return ApiError::upstream("Proxy request failed", &e.into()).into_response();
         }
     };
 
@@ -318,11 +1240,7 @@ async fn bangumi_proxy_handler(
     let response_body = match response.bytes().await {
         Ok(bytes) => bytes,
         Err(e) => {
-            return (
-                StatusCode::BAD_GATEWAY,
-                Json(json!({"error": format!("Failed to read response: {}", e)})),
-            )
-                .into_response();
+            return ApiError::upstream("Failed to read response", &e.into()).into_response();
         }
     };
     
@@ -0,0 +1,478 @@
+//! 媒体中转代理
+//! 使用规则对应的 Referer/User-Agent 请求 HLS 播放列表与分片、封面图片，绕过防盗链
+//! 目标地址由调用方提供：只访问公网地址，规则的 Cookie 与自定义请求头只发往规则自己的站点
+
+use crate::config::CONFIG;
+use crate::engine::{belongs_to_rule, request_config, request_timeout};
+use crate::error::ApiError;
+use crate::http_client::{self, HttpClientError, RequestConfig};
+use crate::http_service::{self, PageRequest};
+use crate::storage::{self, StorageArea};
+use crate::types::Rule;
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, StatusCode},
+    response::Response,
+};
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tower::ServiceExt;
+use url::Url;
+
+/// 中转代理路径
+const STREAM_PROXY_PATH: &str = "/proxy/stream";
+
+/// 需要透传给客户端的响应头
+const PASSTHROUGH_HEADERS: [header::HeaderName; 5] = [
+    header::CONTENT_TYPE,
+    header::CONTENT_LENGTH,
+    header::CONTENT_RANGE,
+    header::ACCEPT_RANGES,
+    header::CACHE_CONTROL,
+];
+
+/// HLS 播放列表的类型
+const PLAYLIST_CONTENT_TYPES: [&str; 4] = [
+    "application/vnd.apple.mpegurl",
+    "application/x-mpegurl",
+    "audio/mpegurl",
+    "audio/x-mpegurl",
+];
+
+/// 通用二进制类型 (TS 分片与密钥常用，只对播放列表中的地址放行)
+const OCTET_STREAM_CONTENT_TYPES: [&str; 2] = ["application/octet-stream", "binary/octet-stream"];
+
+/// 是否为可中转的播放列表或媒体类型 (必须声明类型)
+fn is_stream_content_type(content_type: &str, from_playlist: bool) -> bool {
+    let mime = content_type.split(';').next().unwrap_or("").trim();
+    mime.starts_with("video/")
+        || mime.starts_with("audio/")
+        || PLAYLIST_CONTENT_TYPES.contains(&mime)
+        || (from_playlist && OCTET_STREAM_CONTENT_TYPES.contains(&mime))
+}
+
+/// 播放列表地址签名的密钥 (每次启动随机生成，重启后客户端重新拉取播放列表即可)
+static SIGNING_KEY: Lazy<Vec<u8>> = Lazy::new(|| {
+    [uuid::Uuid::new_v4(), uuid::Uuid::new_v4()]
+        .iter()
+        .flat_map(|id| id.as_bytes().to_vec())
+        .collect()
+});
+
+fn signature_mac(url: &str, rule: Option<&str>, credentials: bool) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&SIGNING_KEY).expect("HMAC accepts any key length");
+    mac.update(format!("{}\n{}\n{}", url, rule.unwrap_or(""), credentials).as_bytes());
+    mac
+}
+
+/// 改写播放列表时为地址签名，证明地址来自本代理改写的播放列表
+/// credentials 记录该播放列表是否携带了规则凭据，分片请求沿用同样的凭据
+fn playlist_signature(url: &str, rule: Option<&str>, credentials: bool) -> String {
+    hex::encode(signature_mac(url, rule, credentials).finalize().into_bytes())
+}
+
+/// 校验地址签名，有效时返回播放列表是否携带了规则凭据
+fn verify_signature(url: &str, rule: Option<&str>, signature: &str) -> Option<bool> {
+    let signature = hex::decode(signature).ok()?;
+    [true, false]
+        .into_iter()
+        .find(|credentials| signature_mac(url, rule, *credentials).verify_slice(&signature).is_ok())
+}
+
+/// 中转请求的配置：trusted (目标属于规则站点，或来自携带凭据的播放列表) 时使用规则的完整配置，
+/// 否则只带 Referer/UA，规则的 Cookie、自定义请求头与证书放行不会发往其它站点
+fn relay_config(rule: &Rule, trusted: bool) -> RequestConfig {
+    let config = request_config(rule);
+    let mut config = if trusted {
+        config
+    } else {
+        RequestConfig {
+            referer: config.referer,
+            user_agent: config.user_agent,
+            outbound_proxy: config.outbound_proxy,
+            rule: config.rule,
+            ..Default::default()
+        }
+    };
+    config.public_only = true;
+    config
+}
+
+/// 中转请求失败对应的错误 (目标为内网地址时返回 403)
+fn relay_error(message: &str, error: HttpClientError) -> ApiError {
+    match error {
+        HttpClientError::PrivateAddress(host) => ApiError::new(
+            StatusCode::FORBIDDEN,
+            "private_address",
+            format!("{}: {} resolves to a private address", message, host),
+        ),
+        error => ApiError::upstream(message, &error.into()),
+    }
+}
+
+/// 中转 HLS 播放列表或媒体分片
+/// 播放列表中的地址会被改写为经过本代理的签名地址，其余内容以流的形式原样转发
+pub async fn relay_stream(
+    url: &str,
+    rule: Option<&Rule>,
+    signature: Option<&str>,
+    headers: &HeaderMap,
+) -> Result<Response, ApiError> {
+    let target = Url::parse(url)
+        .ok()
+        .filter(|u| matches!(u.scheme(), "http" | "https"))
+        .ok_or_else(|| ApiError::bad_request("invalid_url", format!("Invalid stream url: {}", url)))?;
+
+    let rule_name = rule.map(|r| r.name.as_str());
+    let from_playlist = signature.and_then(|sig| verify_signature(target.as_str(), rule_name, sig));
+    let trusted = from_playlist == Some(true) || rule.is_some_and(|r| belongs_to_rule(r, target.as_str()));
+    let mut config = match rule {
+        Some(rule) => relay_config(rule, trusted),
+        None => RequestConfig {
+            public_only: true,
+            ..Default::default()
+        },
+    };
+    if let Some(range) = headers.get(header::RANGE).and_then(|v| v.to_str().ok()) {
+        config.headers.push((header::RANGE.to_string(), range.to_string()));
+    }
+
+    // 经服务栈发送 (熔断、域名并发限制与统计)，重定向逐跳跟随并检查目标地址
+    let timeout = rule.and_then(|rule| request_timeout(rule, None));
+    let response = http_service::response_service()
+        .oneshot(PageRequest::get(target.as_str(), &config, timeout))
+        .await
+        .map_err(|e| relay_error("Stream request failed", e))?;
+    let status = StatusCode::from_u16(response.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    // 只中转播放列表与音视频，避免被当作通用代理返回任意网页
+    if !is_stream_content_type(&content_type, from_playlist.is_some()) {
+        return Err(ApiError::new(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "not_a_stream",
+            format!("Upstream returned {}", if content_type.is_empty() { "no content type" } else { &content_type }),
+        ));
+    }
+    let is_playlist = content_type.contains("mpegurl") || target.path().ends_with(".m3u8");
+
+    let mut builder = Response::builder()
+        .status(status)
+        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff");
+
+    if is_playlist {
+        // 经反代请求时以反代目标地址为基准解析相对地址
+        let final_url = Url::parse(&http_client::unproxied_url(response.url().as_str()))
+            .unwrap_or_else(|_| target.clone());
+        // 播放列表需要整体读入改写，同样受 MAX_RESPONSE_MB 限制
+        let body = http_client::read_bytes(response)
+            .await
+            .map_err(|e| ApiError::upstream("Failed to read playlist", &e.into()))?;
+        let text = String::from_utf8_lossy(&body);
+        let rewritten = rewrite_playlist(&text, &final_url, rule, trusted);
+
+        return builder
+            .header(header::CONTENT_TYPE, "application/vnd.apple.mpegurl")
+            .header(header::CACHE_CONTROL, "no-cache")
+            .body(Body::from(rewritten))
+            .map_err(|e| ApiError::internal(e.to_string()));
+    }
+
+    for name in PASSTHROUGH_HEADERS {
+        if let Some(value) = response.headers().get(&name) {
+            builder = builder.header(name, value);
+        }
+    }
+
+    builder
+        .body(Body::from_stream(response.bytes_stream()))
+        .map_err(|e| ApiError::internal(e.to_string()))
+}
+
+/// 中转封面图片 (带磁盘缓存)
+/// 使用规则的 Referer/UA 请求 (图片属于规则站点时才带上规则凭据)，未指定规则时以图片所在站点作为 Referer
+pub async fn relay_image(url: &str, rule: Option<&Rule>) -> Result<Response, ApiError> {
+    let target = Url::parse(url)
+        .ok()
+        .filter(|u| matches!(u.scheme(), "http" | "https"))
+        .ok_or_else(|| ApiError::bad_request("invalid_url", format!("Invalid image url: {}", url)))?;
+
+    let path = image_cache_path(target.as_str());
+    if let Ok(data) = tokio::fs::read(&path).await {
+        if let Some((fetched_at, content_type, body)) = decode_cached_image(&data) {
+            if now_secs().saturating_sub(fetched_at) < CONFIG.image_cache_ttl_secs && is_raster_image(content_type) {
+                storage::touch(&path);
+                return image_response(content_type, body.to_vec(), "HIT");
+            }
+        }
+    }
+
+    let config = match rule {
+        Some(rule) => relay_config(rule, belongs_to_rule(rule, target.as_str())),
+        None => RequestConfig {
+            referer: Some(target.origin().ascii_serialization() + "/"),
+            public_only: true,
+            ..Default::default()
+        },
+    };
+    let timeout = rule.and_then(|rule| request_timeout(rule, None));
+    let response = http_client::get(target.as_str(), &config, timeout)
+        .await
+        .map_err(|e| relay_error("Image request failed", e))?;
+
+    // 只中转图片，避免被当作通用代理；SVG 可以携带脚本，同样拒绝
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+    if !is_raster_image(&content_type) {
+        return Err(ApiError::new(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "not_an_image",
+            format!("Upstream returned {}", if content_type.is_empty() { "no content type" } else { &content_type }),
+        ));
+    }
+
+    let body = http_client::read_bytes(response)
+        .await
+        .map_err(|e| ApiError::upstream("Failed to read image", &e.into()))?;
+
+    let data = encode_cached_image(now_secs(), &content_type, &body);
+    if let Err(e) = storage::write_atomic(&path, &data).await {
+        tracing::warn!("写入图片缓存失败 {}: {}", path.display(), e);
+    }
+
+    image_response(&content_type, body, "MISS")
+}
+
+/// 是否为可中转的位图类型 (image/*，不含 SVG)
+fn is_raster_image(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    mime.starts_with("image/") && !mime.starts_with("image/svg")
+}
+
+fn image_response(content_type: &str, body: Vec<u8>, cache_status: &str) -> Result<Response, ApiError> {
+    Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .header(header::CONTENT_SECURITY_POLICY, "default-src 'none'; sandbox")
+        .header(
+            header::CACHE_CONTROL,
+            format!("public, max-age={}, immutable", CONFIG.image_cache_ttl_secs),
+        )
+        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+        .header("X-Cache", cache_status)
+        .body(Body::from(body))
+        .map_err(|e| ApiError::internal(e.to_string()))
+}
+
+/// 图片缓存文件路径 (按地址的 SHA-256，跨版本稳定且不会碰撞)
+fn image_cache_path(url: &str) -> PathBuf {
+    StorageArea::ImageCache.dir().join(hex::encode(Sha256::digest(url.as_bytes())))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// 缓存文件格式: "<抓取时间戳>\n<Content-Type>\n" + 图片数据
+fn encode_cached_image(fetched_at: u64, content_type: &str, body: &[u8]) -> Vec<u8> {
+    let mut data = format!("{}\n{}\n", fetched_at, content_type).into_bytes();
+    data.extend_from_slice(body);
+    data
+}
+
+fn decode_cached_image(data: &[u8]) -> Option<(u64, &str, &[u8])> {
+    let first = data.iter().position(|b| *b == b'\n')?;
+    let second = first + 1 + data[first + 1..].iter().position(|b| *b == b'\n')?;
+    let fetched_at = std::str::from_utf8(&data[..first]).ok()?.parse().ok()?;
+    let content_type = std::str::from_utf8(&data[first + 1..second]).ok()?;
+    Some((fetched_at, content_type, &data[second + 1..]))
+}
+
+/// 改写 m3u8 播放列表：分片/子列表地址与 URI="..." 属性都指向本代理
+/// credentials 为播放列表本身是否携带了规则凭据
+fn rewrite_playlist(playlist: &str, base: &Url, rule: Option<&Rule>, credentials: bool) -> String {
+    let mut output = String::with_capacity(playlist.len() * 2);
+
+    for line in playlist.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            output.push_str(line);
+        } else if trimmed.starts_with('#') {
+            output.push_str(&rewrite_uri_attributes(trimmed, base, rule, credentials));
+        } else {
+            output.push_str(&proxied_url(trimmed, base, rule, credentials));
+        }
+        output.push('\n');
+    }
+
+    output
+}
+
+/// 改写标签中的 URI="..." 属性 (#EXT-X-KEY, #EXT-X-MAP, #EXT-X-MEDIA 等)
+fn rewrite_uri_attributes(line: &str, base: &Url, rule: Option<&Rule>, credentials: bool) -> String {
+    let Some(start) = line.find("URI=\"") else {
+        return line.to_string();
+    };
+    let value_start = start + 5;
+    let Some(len) = line[value_start..].find('"') else {
+        return line.to_string();
+    };
+    let value_end = value_start + len;
+
+    format!(
+        "{}{}{}",
+        &line[..value_start],
+        proxied_url(&line[value_start..value_end], base, rule, credentials),
+        &line[value_end..]
+    )
+}
+
+/// 将 (相对) 地址解析为绝对地址并包装为带签名的代理地址
+/// 只有属于规则站点的地址沿用播放列表的凭据，第三方 CDN 等地址签为不携带凭据
+fn proxied_url(uri: &str, base: &Url, rule: Option<&Rule>, credentials: bool) -> String {
+    let absolute = base.join(uri).map(|u| u.to_string()).unwrap_or_else(|_| uri.to_string());
+    let credentials = credentials && rule.is_some_and(|r| belongs_to_rule(r, &absolute));
+    let rule = rule.map(|r| r.name.as_str());
+    let mut proxied = format!("{}?url={}", STREAM_PROXY_PATH, urlencoding::encode(&absolute));
+    if let Some(rule) = rule {
+        proxied.push_str(&format!("&rule={}", urlencoding::encode(rule)));
+    }
+    proxied.push_str(&format!("&sig={}", playlist_signature(&absolute, rule, credentials)));
+    proxied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cached_image_roundtrip() {
+        let data = encode_cached_image(1700000000, "image/webp", b"RIFF\n\x00WEBP");
+        assert_eq!(
+            decode_cached_image(&data),
+            Some((1700000000, "image/webp", &b"RIFF\n\x00WEBP"[..]))
+        );
+        assert_eq!(decode_cached_image(b"broken"), None);
+    }
+
+    #[test]
+    fn test_raster_image() {
+        assert!(is_raster_image("image/webp"));
+        assert!(is_raster_image("Image/JPEG; charset=binary"));
+        assert!(!is_raster_image("image/svg+xml"));
+        assert!(!is_raster_image("text/html"));
+    }
+
+    #[test]
+    fn test_stream_content_type() {
+        assert!(is_stream_content_type("application/vnd.apple.mpegurl; charset=utf-8", false));
+        assert!(is_stream_content_type("video/mp2t", false));
+        assert!(!is_stream_content_type("application/octet-stream", false));
+        assert!(is_stream_content_type("application/octet-stream", true));
+        assert!(!is_stream_content_type("", true));
+        assert!(!is_stream_content_type("text/html; charset=utf-8", true));
+        assert!(!is_stream_content_type("image/svg+xml", false));
+    }
+
+    #[test]
+    fn test_playlist_signature() {
+        let url = "https://cdn.example.com/hls/seg0.ts";
+        let signature = playlist_signature(url, Some("AGE"), true);
+        assert_eq!(verify_signature(url, Some("AGE"), &signature), Some(true));
+        assert_eq!(
+            verify_signature(url, Some("AGE"), &playlist_signature(url, Some("AGE"), false)),
+            Some(false)
+        );
+        assert_eq!(verify_signature(url, Some("OTHER"), &signature), None);
+        assert_eq!(verify_signature("https://evil.example/", Some("AGE"), &signature), None);
+        assert_eq!(verify_signature(url, Some("AGE"), "not-hex"), None);
+    }
+
+    #[test]
+    fn test_relay_config() {
+        let rule = Rule {
+            name: "AGE".to_string(),
+            base_url: "https://www.example.com/".to_string(),
+            cookies: [("session".to_string(), "secret".to_string())].into_iter().collect(),
+            headers: [("X-Token".to_string(), "secret".to_string())].into_iter().collect(),
+            insecure_tls: true,
+            ..Default::default()
+        };
+
+        let trusted = relay_config(&rule, true);
+        assert!(trusted.cookie.is_some() && !trusted.headers.is_empty() && trusted.insecure_tls);
+        assert!(trusted.public_only);
+
+        // 其它站点只带 Referer/UA
+        let untrusted = relay_config(&rule, false);
+        assert_eq!(untrusted.referer.as_deref(), Some("https://www.example.com/"));
+        assert!(untrusted.cookie.is_none() && untrusted.headers.is_empty() && !untrusted.insecure_tls);
+        assert!(untrusted.public_only);
+    }
+
+    #[test]
+    fn test_rewrite_playlist() {
+        let playlist = "#EXTM3U\n#EXT-X-KEY:METHOD=AES-128,URI=\"key.key\"\n#EXTINF:10,\nseg0.ts\n#EXTINF:10,\nhttps://cdn2.example.com/seg1.ts\n#EXT-X-ENDLIST\n";
+        let base = Url::parse("https://cdn.example.com/hls/index.m3u8").unwrap();
+        let rule = Rule {
+            name: "AGE".to_string(),
+            base_url: "https://cdn.example.com/".to_string(),
+            ..Default::default()
+        };
+
+        let rewritten = rewrite_playlist(playlist, &base, Some(&rule), false);
+        let lines: Vec<&str> = rewritten.lines().collect();
+        let sig = |url: &str| playlist_signature(url, Some("AGE"), false);
+
+        assert_eq!(lines[0], "#EXTM3U");
+        assert_eq!(
+            lines[1],
+            format!(
+                "#EXT-X-KEY:METHOD=AES-128,URI=\"/proxy/stream?url=https%3A%2F%2Fcdn.example.com%2Fhls%2Fkey.key&rule=AGE&sig={}\"",
+                sig("https://cdn.example.com/hls/key.key")
+            )
+        );
+        assert_eq!(
+            lines[3],
+            format!(
+                "/proxy/stream?url=https%3A%2F%2Fcdn.example.com%2Fhls%2Fseg0.ts&rule=AGE&sig={}",
+                sig("https://cdn.example.com/hls/seg0.ts")
+            )
+        );
+        assert_eq!(
+            lines[5],
+            format!(
+                "/proxy/stream?url=https%3A%2F%2Fcdn2.example.com%2Fseg1.ts&rule=AGE&sig={}",
+                sig("https://cdn2.example.com/seg1.ts")
+            )
+        );
+        assert_eq!(lines[6], "#EXT-X-ENDLIST");
+
+        // 携带凭据的播放列表：规则站点的地址沿用凭据，第三方地址签为不携带凭据
+        let rewritten = rewrite_playlist(playlist, &base, Some(&rule), true);
+        let lines: Vec<&str> = rewritten.lines().collect();
+        let credentials = |line: &str| {
+            let query = line.split('?').nth(1).unwrap();
+            let params: std::collections::HashMap<_, _> = url::form_urlencoded::parse(query.as_bytes()).collect();
+            verify_signature(&params["url"], Some("AGE"), &params["sig"])
+        };
+        assert_eq!(credentials(lines[3]), Some(true));
+        assert_eq!(credentials(lines[5]), Some(false));
+    }
+}
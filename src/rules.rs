@@ -1,61 +1,669 @@
 //! 规则管理器
 //! 从 rules/ 目录读取 JSON 规则文件，兼容 Kazumi 规则格式
 
+use crate::config::CONFIG;
 use crate::types::Rule;
+use notify::Watcher;
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
-use std::path::Path;
-use std::sync::Arc;
-use tracing::{info, warn};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{error, info, warn};
 
 /// 规则目录路径
 const RULES_DIR: &str = "rules";
+/// 通过管理接口创建的本地规则名单 (规则更新时不会被当作上游已删除的规则归档)
+const CUSTOM_RULES_FILE: &str = "rules/.custom_rules.json";
+/// 规则的本地设置 (启用状态等，规则更新时保留)
+const SETTINGS_FILE: &str = "rules/.rule_settings.json";
+/// Kazumi 规则索引 (描述本地安装的规则，使 rules/ 目录可作为规则仓库)
+const INDEX_FILE: &str = "rules/index.json";
+/// 从规则仓库下载的规则内容 SHA-256 (规则名 → 摘要)，加载时校验
+const INTEGRITY_FILE: &str = "rules/.integrity.json";
 
-/// 全局规则列表
-static RULES: Lazy<Vec<Arc<Rule>>> = Lazy::new(load_all_rules);
+/// 全局规则列表 (规则更新后热重载)
+static RULES: Lazy<RwLock<Vec<Arc<Rule>>>> = Lazy::new(|| RwLock::new(load_all_rules()));
 
-/// 获取所有规则
+/// 规则名 → 实际加载的规则文件 (同名规则以来源优先级高的为准)
+static RULE_FILES: Lazy<RwLock<HashMap<String, LoadedFile>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// 加载失败的规则文件 (文件名 → 原因)，在 /rules 中列出
+static REJECTED_FILES: Lazy<RwLock<BTreeMap<String, String>>> =
+    Lazy::new(|| RwLock::new(BTreeMap::new()));
+
+/// 规则的本地设置 (按规则名)
+static SETTINGS: Lazy<RwLock<HashMap<String, RuleSettings>>> =
+    Lazy::new(|| RwLock::new(load_settings()));
+
+/// 单个规则的本地设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleSettings {
+    /// 是否启用 (禁用的规则不参与搜索，规则文件保留)
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// 优先级 (越大越靠前，决定规则列表与搜索分发的顺序)
+    #[serde(default)]
+    pub priority: i32,
+}
+
+/// 规则来源 (按优先级从低到高排列，同名规则以高者为准)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum RuleOrigin {
+    /// 从规则仓库下载 (记录在完整性清单中)
+    Repo,
+    /// 随部署附带或手动放入 rules/ 的规则
+    Builtin,
+    /// 通过管理接口创建或覆盖的规则
+    Local,
+}
+
+impl RuleOrigin {
+    fn source(self) -> String {
+        match self {
+            RuleOrigin::Repo => CONFIG.rules_repo.clone(),
+            RuleOrigin::Builtin => "builtin".to_string(),
+            RuleOrigin::Local => "local".to_string(),
+        }
+    }
+}
+
+/// 实际加载的规则文件及其来源
+#[derive(Debug, Clone)]
+struct LoadedFile {
+    path: PathBuf,
+    origin: RuleOrigin,
+}
+
+/// 规则来源与规则文件最后修改时间 (GET /rules)
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleSource {
+    /// "local" (通过管理接口创建)、"builtin" (随部署附带或手动放入) 或规则仓库 owner/repo
+    pub source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Default for RuleSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            priority: 0,
+        }
+    }
+}
+
+fn rules() -> std::sync::RwLockReadGuard<'static, Vec<Arc<Rule>>> {
+    RULES.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn settings() -> std::sync::RwLockReadGuard<'static, HashMap<String, RuleSettings>> {
+    SETTINGS.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 获取所有启用的规则 (按优先级排序)
 pub fn get_builtin_rules() -> Vec<Arc<Rule>> {
-    RULES.clone()
+    sort_by_priority(rules().iter().filter(|r| is_enabled(&r.name)).cloned().collect())
+}
+
+/// 获取所有已加载的规则 (包括已禁用的，按优先级排序)
+pub fn get_all_rules() -> Vec<Arc<Rule>> {
+    sort_by_priority(rules().clone())
+}
+
+/// 按名称查找单个启用的规则
+pub fn get_rule_by_name(name: &str) -> Option<Arc<Rule>> {
+    find_rule(name).filter(|r| is_enabled(&r.name))
+}
+
+/// 按名称查找单个规则 (包括已禁用的)
+pub fn find_rule(name: &str) -> Option<Arc<Rule>> {
+    rules().iter().find(|r| r.name == name).cloned()
+}
+
+/// 按名称筛选启用的规则 (按优先级排序)
+pub fn get_rules_by_names(names: &[&str]) -> Vec<Arc<Rule>> {
+    sort_by_priority(
+        rules()
+            .iter()
+            .filter(|r| names.contains(&r.name.as_str()) && is_enabled(&r.name))
+            .cloned()
+            .collect(),
+    )
+}
+
+/// 解析搜索请求中的规则列表："all" 表示所有启用且未被健康检查隔离的规则，
+/// 其他情况按名称筛选 (显式指定的规则即使被隔离也会使用)
+pub fn select_rules(names: &[&str]) -> Vec<Arc<Rule>> {
+    if let [name] = names {
+        if name.eq_ignore_ascii_case("all") {
+            return get_builtin_rules()
+                .into_iter()
+                .filter(|r| !crate::health::is_degraded(&r.name))
+                .collect();
+        }
+    }
+    get_rules_by_names(names)
+}
+
+/// 按优先级从高到低排序，同优先级保持名称顺序
+fn sort_by_priority(mut rules: Vec<Arc<Rule>>) -> Vec<Arc<Rule>> {
+    let settings = settings();
+    rules.sort_by_key(|r| std::cmp::Reverse(settings.get(&r.name).map_or(0, |s| s.priority)));
+    rules
+}
+
+/// 规则是否启用 (没有本地设置时默认启用)
+pub fn is_enabled(name: &str) -> bool {
+    settings().get(name).is_none_or(|s| s.enabled)
+}
+
+/// 规则的优先级 (没有本地设置时为 0)
+pub fn priority(name: &str) -> i32 {
+    settings().get(name).map_or(0, |s| s.priority)
+}
+
+/// 启用或禁用规则并保存到本地设置
+pub fn set_enabled(name: &str, enabled: bool) -> anyhow::Result<()> {
+    update_settings(name, |s| s.enabled = enabled)?;
+    info!("{} 规则: {}", if enabled { "✅ 启用" } else { "⛔ 禁用" }, name);
+    Ok(())
+}
+
+/// 设置规则优先级并保存到本地设置
+pub fn set_priority(name: &str, priority: i32) -> anyhow::Result<()> {
+    update_settings(name, |s| s.priority = priority)?;
+    info!("🔢 规则 {} 优先级: {}", name, priority);
+    Ok(())
+}
+
+/// 修改单个规则的本地设置并写入文件
+fn update_settings(name: &str, update: impl FnOnce(&mut RuleSettings)) -> anyhow::Result<()> {
+    let mut settings = SETTINGS.write().unwrap_or_else(|e| e.into_inner());
+    update(settings.entry(name.to_string()).or_default());
+    fs::create_dir_all(RULES_DIR)?;
+    fs::write(SETTINGS_FILE, serde_json::to_string_pretty(&*settings)?)?;
+    Ok(())
+}
+
+fn load_settings() -> HashMap<String, RuleSettings> {
+    fs::read_to_string(SETTINGS_FILE)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// 规则内容的 SHA-256 (十六进制)
+pub fn content_digest(content: &[u8]) -> String {
+    hex::encode(Sha256::digest(content))
+}
+
+fn load_integrity() -> HashMap<String, String> {
+    fs::read_to_string(INTEGRITY_FILE)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// 修改完整性清单 (规则更新、回滚与本地保存时调用)
+pub fn update_integrity(update: impl FnOnce(&mut HashMap<String, String>)) -> anyhow::Result<()> {
+    let mut manifest = load_integrity();
+    update(&mut manifest);
+    fs::create_dir_all(RULES_DIR)?;
+    fs::write(INTEGRITY_FILE, serde_json::to_string_pretty(&manifest)?)?;
+    Ok(())
+}
+
+/// 是否为规则文件 (.json，排除 index.json 与以 . 开头的元数据文件)
+pub fn is_rule_file(path: &Path) -> bool {
+    let filename = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    filename.ends_with(".json") && filename != "index.json" && !filename.starts_with('.')
+}
+
+/// 重新读取 rules/ 目录并替换内存中的规则列表，返回规则数量
+///
+/// 正在进行的搜索持有旧规则的 Arc，不受影响
+pub fn reload_rules() -> usize {
+    let rules = load_all_rules();
+    let count = rules.len();
+    *RULES.write().unwrap_or_else(|e| e.into_inner()) = rules;
+    info!("🔁 已重新加载 {} 个规则", count);
+    refresh_index();
+    count
+}
+
+/// Kazumi 兼容的规则索引条目 (rules/index.json)
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexEntry {
+    pub name: String,
+    pub version: String,
+    pub use_native_player: bool,
+    pub author: String,
+    /// 规则文件最后修改时间 (毫秒时间戳)
+    pub last_update: u64,
+}
+
+/// 根据已加载的规则生成索引 (包括已禁用的规则，按名称排序)
+pub fn rule_index() -> Vec<IndexEntry> {
+    get_all_rules()
+        .iter()
+        .map(|rule| {
+            let path = loaded_file(&rule.name)
+                .map(|file| file.path)
+                .unwrap_or_else(|| rule_path(&rule.name));
+            // author 不在 Rule 结构中，从原始 JSON 读取
+            let author = fs::read_to_string(&path)
+                .ok()
+                .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok())
+                .and_then(|v| v["author"].as_str().map(str::to_string))
+                .unwrap_or_default();
+            let last_update = fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_millis() as u64);
+            IndexEntry {
+                name: rule.name.clone(),
+                version: rule.version.clone(),
+                use_native_player: rule.use_native_player,
+                author,
+                last_update,
+            }
+        })
+        .collect()
+}
+
+fn write_index() -> anyhow::Result<usize> {
+    let index = rule_index();
+    fs::create_dir_all(RULES_DIR)?;
+    fs::write(INDEX_FILE, serde_json::to_string_pretty(&index)?)?;
+    Ok(index.len())
+}
+
+/// 重新生成 rules/index.json (规则加载、更新、本地修改后调用)
+pub fn refresh_index() {
+    if let Err(e) = write_index() {
+        warn!("生成规则索引失败: {}", e);
+    }
+}
+
+/// 规则文件及其修改时间
+fn rule_files_snapshot() -> HashMap<PathBuf, SystemTime> {
+    fs::read_dir(RULES_DIR)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|e| {
+            let path = e.path();
+            let is_rule = is_rule_file(&path);
+            let modified = e.metadata().ok().filter(|m| m.is_file())?.modified().ok()?;
+            is_rule.then_some((path, modified))
+        })
+        .collect()
+}
+
+/// 启动规则目录监视任务 (notify)：规则文件新增、修改或删除时热重载，
+/// 编辑器保存等操作产生的一连串事件在 RULES_WATCH_DEBOUNCE_MS 内合并为一次重载 (为 0 时不启动)
+pub fn spawn_watcher() {
+    let debounce = Duration::from_millis(CONFIG.rules_watch_debounce_ms);
+    if debounce.is_zero() {
+        return;
+    }
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+        // 读取文件产生的访问事件不算变动 (重载本身会读取规则文件)
+        Ok(event) if !matches!(event.kind, notify::EventKind::Access(_)) => {
+            let paths: Vec<PathBuf> = event.paths.into_iter().filter(|p| is_rule_file(p)).collect();
+            if !paths.is_empty() {
+                let _ = tx.send(paths);
+            }
+        }
+        Ok(_) => {}
+        Err(e) => warn!("规则目录监视出错: {}", e),
+    });
+    let mut watcher = match watcher {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            warn!("无法监视规则目录，文件变动不会自动热重载: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = watcher.watch(Path::new(RULES_DIR), notify::RecursiveMode::NonRecursive) {
+        warn!("无法监视规则目录 {}，文件变动不会自动热重载: {}", RULES_DIR, e);
+        return;
+    }
+
+    tokio::spawn(async move {
+        // watcher 被丢弃时停止监视，由任务持有
+        let _watcher = watcher;
+        while let Some(paths) = rx.recv().await {
+            let mut changed: BTreeSet<PathBuf> = paths.into_iter().collect();
+            while let Ok(Some(paths)) = tokio::time::timeout(debounce, rx.recv()).await {
+                changed.extend(paths);
+            }
+            for path in &changed {
+                info!("📝 规则文件变动: {}", path.display());
+            }
+
+            if let Err(e) = tokio::task::spawn_blocking(reload_rules).await {
+                warn!("重新加载规则失败: {}", e);
+            }
+        }
+    });
+}
+
+/// 替换 (或新增) 内存中的单个规则，不重新读取其他规则 (规则文件为 rules/<name>.json)
+pub fn reload_rule(rule: Rule) {
+    info!("🔁 重新加载规则: {} v{}", rule.name, rule.version);
+    prepare_rule(&rule);
+
+    let origin = file_origin(&rule.name, &custom_rule_names(), &load_integrity());
+    RULE_FILES.write().unwrap_or_else(|e| e.into_inner()).insert(
+        rule.name.clone(),
+        LoadedFile {
+            path: rule_path(&rule.name),
+            origin,
+        },
+    );
+
+    let rule = Arc::new(rule);
+    let mut rules = RULES.write().unwrap_or_else(|e| e.into_inner());
+    match rules.binary_search_by(|r| r.name.cmp(&rule.name)) {
+        Ok(index) => rules[index] = rule,
+        Err(index) => rules.insert(index, rule),
+    }
+}
+
+/// 从内存中移除规则
+pub fn remove_rule(name: &str) {
+    RULES
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|r| r.name != name);
+    RULE_FILES
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(name);
+}
+
+/// 根据文件名判断规则文件的来源
+fn file_origin(stem: &str, custom: &[String], integrity: &HashMap<String, String>) -> RuleOrigin {
+    if custom.iter().any(|n| n == stem) {
+        RuleOrigin::Local
+    } else if integrity.contains_key(stem) {
+        RuleOrigin::Repo
+    } else {
+        RuleOrigin::Builtin
+    }
+}
+
+fn loaded_file(name: &str) -> Option<LoadedFile> {
+    RULE_FILES
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(name)
+        .cloned()
+}
+
+/// 规则是否由 rules/<name>.json 以外的文件提供 (例如手动放入的同名覆盖规则)
+pub fn is_shadowed(name: &str) -> bool {
+    loaded_file(name).is_some_and(|file| file.path != rule_path(name))
+}
+
+fn modified_at(path: &Path) -> Option<String> {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339())
+}
+
+/// 规则来源与最后修改时间 (未加载的规则返回 None)
+pub fn rule_source(name: &str) -> Option<RuleSource> {
+    let file = loaded_file(name)?;
+    Some(RuleSource {
+        source: file.origin.source(),
+        updated_at: modified_at(&file.path),
+    })
+}
+
+/// 本地规则文件路径
+fn rule_path(name: &str) -> PathBuf {
+    Path::new(RULES_DIR).join(format!("{}.json", name))
+}
+
+/// 检查本地是否存在该规则文件
+pub fn rule_file_exists(name: &str) -> bool {
+    rule_path(name).exists()
+}
+
+/// 通过管理接口创建的本地规则名单
+pub fn custom_rule_names() -> Vec<String> {
+    fs::read_to_string(CUSTOM_RULES_FILE)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn save_custom_rule_names(names: &[String]) -> anyhow::Result<()> {
+    fs::write(CUSTOM_RULES_FILE, serde_json::to_string_pretty(names)?)?;
+    Ok(())
+}
+
+/// 保存本地规则文件并热重载该规则
+pub fn save_custom_rule(rule: Rule, content: &str) -> anyhow::Result<()> {
+    fs::create_dir_all(RULES_DIR)?;
+    fs::write(rule_path(&rule.name), content)?;
+    // 本地保存的内容不再对应上游文件，不参与完整性校验
+    update_integrity(|manifest| {
+        manifest.remove(&rule.name);
+    })?;
+
+    let mut names = custom_rule_names();
+    if !names.contains(&rule.name) {
+        names.push(rule.name.clone());
+        save_custom_rule_names(&names)?;
+    }
+
+    reload_rule(rule);
+    refresh_index();
+    Ok(())
+}
+
+/// 删除本地规则文件并从内存中移除
+pub fn delete_custom_rule(name: &str) -> anyhow::Result<()> {
+    fs::remove_file(rule_path(name))?;
+    update_integrity(|manifest| {
+        manifest.remove(name);
+    })?;
+
+    let mut names = custom_rule_names();
+    if let Some(index) = names.iter().position(|n| n == name) {
+        names.remove(index);
+        save_custom_rule_names(&names)?;
+    }
+
+    remove_rule(name);
+    refresh_index();
+    info!("🗑️ 已删除规则: {}", name);
+    Ok(())
+}
+
+/// 规则的原始定义与文件信息 (GET /rules/{name})
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleDetail {
+    pub name: String,
+    /// 来源："local" (通过管理接口创建)、"builtin" (随部署附带或手动放入) 或规则仓库 owner/repo
+    pub source: String,
+    /// 规则文件最后修改时间
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+    pub enabled: bool,
+    pub priority: i32,
+    /// 规则文件中的原始 JSON
+    pub rule: serde_json::Value,
+}
+
+/// 读取规则的原始 JSON 与文件信息 (文件名与规则名不一致时按规则名查找)
+pub fn rule_detail(name: &str) -> Option<RuleDetail> {
+    let read = |path: &Path| -> Option<serde_json::Value> {
+        let value: serde_json::Value = serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+        (value["name"].as_str() == Some(name)).then_some(value)
+    };
+
+    // 优先读取实际加载的文件 (可能是同名的本地覆盖规则)
+    let path = loaded_file(name)
+        .map(|file| file.path)
+        .unwrap_or_else(|| rule_path(name));
+    let rule = read(&path).or_else(|| {
+        rule_files_snapshot()
+            .into_keys()
+            .find_map(|path| read(&path))
+    })?;
+
+    let RuleSource { source, updated_at } = rule_source(name).unwrap_or_else(|| RuleSource {
+        source: file_origin(name, &custom_rule_names(), &load_integrity()).source(),
+        updated_at: modified_at(&path),
+    });
+
+    Some(RuleDetail {
+        name: name.to_string(),
+        source,
+        updated_at,
+        enabled: is_enabled(name),
+        priority: priority(name),
+        rule,
+    })
+}
+
+/// 导出 rules/ 目录下的所有规则 (保留原始 JSON 字段，按名称排序)
+pub fn export_rules() -> Vec<serde_json::Value> {
+    let mut rules: Vec<serde_json::Value> = fs::read_dir(RULES_DIR)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|path| path.is_file() && is_rule_file(path))
+        .filter_map(|path| {
+            let value: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+            // 只导出能正常加载的规则
+            serde_json::from_value::<Rule>(value.clone()).ok()?;
+            Some(value)
+        })
+        .collect();
+
+    rules.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+    rules
+}
+
+/// 移除规则 JSON 中可能含有凭据的字段 (自定义请求头与 Cookie)，用于未携带管理令牌的调用方
+pub fn redact_credentials(rule: &mut serde_json::Value) {
+    if let Some(object) = rule.as_object_mut() {
+        object.remove("headers");
+        object.remove("cookies");
+    }
+}
+
+/// 预编译选择器并记录无效或需要 XPath 求值的选择器
+fn prepare_rule(rule: &Rule) {
+    for error in crate::engine::precompile(rule) {
+        warn!("⚠️ 规则 {} 选择器无效: {}", rule.name, error);
+    }
+    for issue in crate::engine::validate_selectors(rule)
+        .iter()
+        .filter(|i| i.level == "unconvertible")
+    {
+        info!(
+            "规则 {} 的 {} 无法转换为 CSS，将使用 XPath 求值: {}",
+            rule.name, issue.field, issue.message
+        );
+    }
 }
 
 /// 从 rules/ 目录加载所有规则
+///
+/// 多个文件声明同名规则时按来源优先级取舍：本地 (管理接口) > 附带/手动放入 > 规则仓库
 fn load_all_rules() -> Vec<Arc<Rule>> {
-    let mut rules = Vec::new();
     let rules_path = Path::new(RULES_DIR);
 
     if !rules_path.exists() {
         warn!("规则目录 {} 不存在，请创建并添加规则文件", RULES_DIR);
-        return rules;
-    }
-
-    // 读取目录中的所有 JSON 文件
-    match fs::read_dir(rules_path) {
-        Ok(entries) => {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                // 跳过 index.json (Kazumi 索引文件)
-                let filename = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
-                if filename == "index.json" {
-                    continue;
-                }
-                if path.extension().map(|e| e == "json").unwrap_or(false) {
-                    match load_rule_from_file(&path) {
-                        Ok(rule) => {
-                            info!("📦 加载规则: {} v{}", rule.name, rule.version);
-                            rules.push(Arc::new(rule));
-                        }
-                        Err(e) => {
-                            warn!("⚠️ 加载规则失败 {}: {}", path.display(), e);
-                        }
-                    }
-                }
-            }
-        }
+        return Vec::new();
+    }
+
+    let integrity = load_integrity();
+    let custom = custom_rule_names();
+
+    // 读取目录中的所有 JSON 文件 (按文件名排序，保证同名规则的取舍稳定)
+    let mut paths: Vec<PathBuf> = match fs::read_dir(rules_path) {
+        Ok(entries) => entries.flatten().map(|e| e.path()).collect(),
         Err(e) => {
             warn!("读取规则目录失败: {}", e);
+            Vec::new()
+        }
+    };
+    paths.sort();
+
+    let mut loaded: HashMap<String, (Rule, LoadedFile)> = HashMap::new();
+    let mut rejected = BTreeMap::new();
+    // 跳过 index.json (Kazumi 索引文件) 与元数据文件
+    for path in paths.into_iter().filter(|path| is_rule_file(path)) {
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+        let rule = match load_rule_from_file(&path, integrity.get(stem).map(String::as_str)) {
+            Ok(rule) => rule,
+            Err(e) => {
+                // 内容被改动的下载规则可能是篡改，需要运维处理
+                if e.is::<IntegrityMismatch>() {
+                    error!("❌ 拒绝加载规则 {}: {}", path.display(), e);
+                } else {
+                    warn!("⚠️ 加载规则失败 {}: {}", path.display(), e);
+                }
+                rejected.insert(stem.to_string(), e.to_string());
+                continue;
+            }
+        };
+
+        let origin = file_origin(stem, &custom, &integrity);
+        if let Some((_, existing)) = loaded.get(&rule.name) {
+            let (kept, ignored) = if origin > existing.origin {
+                (&path, &existing.path)
+            } else {
+                (&existing.path, &path)
+            };
+            warn!(
+                "⚠️ 规则 {} 在多个文件中定义，使用 {}，忽略 {}",
+                rule.name,
+                kept.display(),
+                ignored.display()
+            );
+            if origin <= existing.origin {
+                continue;
+            }
         }
+        loaded.insert(rule.name.clone(), (rule, LoadedFile { path, origin }));
+    }
+
+    let mut rules = Vec::with_capacity(loaded.len());
+    let mut files = HashMap::with_capacity(loaded.len());
+    for (name, (rule, file)) in loaded {
+        info!("📦 加载规则: {} v{}", rule.name, rule.version);
+        prepare_rule(&rule);
+        rules.push(Arc::new(rule));
+        files.insert(name, file);
     }
+    *RULE_FILES.write().unwrap_or_else(|e| e.into_inner()) = files;
+    *REJECTED_FILES.write().unwrap_or_else(|e| e.into_inner()) = rejected;
 
     // 按名称排序
     rules.sort_by(|a, b| a.name.cmp(&b.name));
@@ -63,9 +671,22 @@ fn load_all_rules() -> Vec<Arc<Rule>> {
     rules
 }
 
-/// 从 JSON 文件加载单个规则
-fn load_rule_from_file(path: &Path) -> anyhow::Result<Rule> {
+/// 规则文件内容与完整性清单不一致
+#[derive(Debug, thiserror::Error)]
+#[error("内容与下载时记录的 SHA-256 不一致，拒绝加载 (手动修改过的规则请从 {INTEGRITY_FILE} 中删除对应条目)")]
+struct IntegrityMismatch;
+
+/// 加载失败的规则文件 (文件名 → 原因)
+pub fn rejected_files() -> BTreeMap<String, String> {
+    REJECTED_FILES.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// 从 JSON 文件加载单个规则 (有下载时记录的摘要则先校验内容)
+fn load_rule_from_file(path: &Path, expected_digest: Option<&str>) -> anyhow::Result<Rule> {
     let content = fs::read_to_string(path)?;
+    if expected_digest.is_some_and(|expected| content_digest(content.as_bytes()) != expected) {
+        return Err(IntegrityMismatch.into());
+    }
     let rule: Rule = serde_json::from_str(&content)?;
     Ok(rule)
 }
@@ -0,0 +1,313 @@
+//! 规则脚本扩展 (rhai)
+//! 选择器无法表达的逻辑 (解密地址、拼接 API 请求等) 可以在规则的 `script` 字段中用 rhai 编写，
+//! 引擎在对应阶段调用以下函数 (均为可选)：
+//!
+//! - `transform_search_url(url, keyword, page)` → 新的搜索 URL
+//! - `parse_results(html)` → `[#{ name, url, cover?, description?, tags? }]`，替代选择器解析
+//! - `resolve_play_url(html, url)` → `["https://...m3u8"]` 或 `[#{ url, kind? }]`
+//!
+//! 脚本运行在沙箱中：没有文件/网络访问，并限制运算次数与数据大小
+
+use crate::types::{Rule, SearchResultItem};
+use base64::Engine as _;
+use regex::Regex;
+use rhai::{Array, Dynamic, Engine, Scope, AST};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, LazyLock, RwLock};
+
+/// 单次调用最大运算次数 (防止死循环)
+const MAX_OPERATIONS: u64 = 2_000_000;
+
+/// 沙箱脚本引擎
+static ENGINE: LazyLock<Engine> = LazyLock::new(build_engine);
+
+/// 已编译的脚本 (按源码哈希缓存)
+static AST_CACHE: LazyLock<RwLock<HashMap<u64, Arc<AST>>>> = LazyLock::new(Default::default);
+
+fn build_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(32)
+        .set_max_expr_depths(64, 32)
+        .set_max_string_size(16 * 1024 * 1024)
+        .set_max_array_size(10_000)
+        .set_max_map_size(10_000);
+
+    engine.on_print(|text| tracing::debug!("[script] {}", text));
+    engine.on_debug(|text, _, pos| tracing::debug!("[script] {:?} {}", pos, text));
+
+    engine.register_fn("url_encode", |s: &str| urlencoding::encode(s).into_owned());
+    engine.register_fn("url_decode", |s: &str| {
+        urlencoding::decode(s).map(|s| s.into_owned()).unwrap_or_else(|_| s.to_string())
+    });
+    engine.register_fn("base64_decode", |s: &str| {
+        base64::engine::general_purpose::STANDARD
+            .decode(s.trim())
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .unwrap_or_default()
+    });
+    engine.register_fn("base64_encode", |s: &str| {
+        base64::engine::general_purpose::STANDARD.encode(s)
+    });
+    // 返回所有匹配的捕获组: [[整体, 组1, 组2...], ...]
+    engine.register_fn("regex_captures", |text: &str, pattern: &str| -> Array {
+        let Ok(re) = Regex::new(pattern) else {
+            return Array::new();
+        };
+        re.captures_iter(text)
+            .map(|caps| {
+                let groups: Array = caps
+                    .iter()
+                    .map(|m| m.map(|m| m.as_str()).unwrap_or_default().into())
+                    .collect();
+                Dynamic::from_array(groups)
+            })
+            .collect()
+    });
+    engine.register_fn("regex_replace", |text: &str, pattern: &str, replacement: &str| {
+        match Regex::new(pattern) {
+            Ok(re) => re.replace_all(text, replacement).into_owned(),
+            Err(_) => text.to_string(),
+        }
+    });
+    engine.register_fn("parse_json", |text: &str| -> Dynamic {
+        serde_json::from_str::<serde_json::Value>(text)
+            .ok()
+            .and_then(|value| rhai::serde::to_dynamic(value).ok())
+            .unwrap_or(Dynamic::UNIT)
+    });
+
+    engine
+}
+
+/// 编译规则脚本 (未配置脚本时返回 None)
+pub fn compile(rule: &Rule) -> anyhow::Result<Option<Arc<AST>>> {
+    let source = rule.script.trim();
+    if source.is_empty() {
+        return Ok(None);
+    }
+
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    let key = hasher.finish();
+
+    if let Some(ast) = AST_CACHE.read().unwrap_or_else(|e| e.into_inner()).get(&key) {
+        return Ok(Some(ast.clone()));
+    }
+
+    let ast = Arc::new(
+        ENGINE
+            .compile(source)
+            .map_err(|e| anyhow::anyhow!("规则脚本编译失败: {}", e))?,
+    );
+    AST_CACHE
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(key, ast.clone());
+    Ok(Some(ast))
+}
+
+/// 调用脚本中的函数 (函数未定义时返回 None)
+fn call(rule: &Rule, name: &str, args: impl rhai::FuncArgs) -> anyhow::Result<Option<Dynamic>> {
+    let Some(ast) = compile(rule)? else {
+        return Ok(None);
+    };
+    if !ast.iter_functions().any(|f| f.name == name) {
+        return Ok(None);
+    }
+
+    let result = ENGINE
+        .call_fn::<Dynamic>(&mut Scope::new(), &ast, name, args)
+        .map_err(|e| anyhow::anyhow!("规则脚本 {} 执行失败: {}", name, e))?;
+    Ok(Some(result))
+}
+
+/// 钩子：改写搜索 URL
+pub fn transform_search_url(
+    rule: &Rule,
+    url: &str,
+    keyword: &str,
+    page: usize,
+) -> anyhow::Result<Option<String>> {
+    let Some(result) = call(
+        rule,
+        "transform_search_url",
+        (url.to_string(), keyword.to_string(), page as i64),
+    )?
+    else {
+        return Ok(None);
+    };
+    result
+        .into_string()
+        .map(Some)
+        .map_err(|t| anyhow::anyhow!("transform_search_url 应返回字符串，实际为 {}", t))
+}
+
+/// 脚本返回的搜索结果
+#[derive(Debug, Deserialize)]
+struct ScriptItem {
+    name: String,
+    url: String,
+    #[serde(default)]
+    cover: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+/// 钩子：解析搜索结果页 (链接未做补全，由调用方处理)
+pub fn parse_results(rule: &Rule, html: &str) -> anyhow::Result<Option<Vec<SearchResultItem>>> {
+    let Some(result) = call(rule, "parse_results", (html.to_string(),))? else {
+        return Ok(None);
+    };
+    let items: Vec<ScriptItem> = rhai::serde::from_dynamic(&result)
+        .map_err(|e| anyhow::anyhow!("parse_results 返回值无效: {}", e))?;
+
+    Ok(Some(
+        items
+            .into_iter()
+            .filter(|item| !item.name.trim().is_empty() && !item.url.trim().is_empty())
+            .map(|item| SearchResultItem {
+                name: item.name.trim().to_string(),
+                url: item.url.trim().to_string(),
+                cover: item.cover.filter(|s| !s.is_empty()),
+                description: item.description.filter(|s| !s.is_empty()),
+                tags: (!item.tags.is_empty()).then_some(item.tags),
+                episodes: None,
+                magnets: None,
+                title_info: None,
+                resolved_url: None,
+                resolved_host: None,
+            })
+            .collect(),
+    ))
+}
+
+/// 脚本返回的媒体地址 (字符串或 #{ url, kind })
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ScriptSource {
+    Url(String),
+    Source {
+        url: String,
+        #[serde(default)]
+        kind: Option<String>,
+    },
+}
+
+/// 脚本解析出的媒体地址 (kind 为空时由调用方按扩展名判断)
+#[derive(Debug, PartialEq)]
+pub struct HookSource {
+    pub url: String,
+    pub kind: Option<String>,
+}
+
+/// 钩子：从播放页解析媒体地址
+pub fn resolve_play_url(rule: &Rule, html: &str, url: &str) -> anyhow::Result<Option<Vec<HookSource>>> {
+    let Some(result) = call(rule, "resolve_play_url", (html.to_string(), url.to_string()))? else {
+        return Ok(None);
+    };
+    let sources: Vec<ScriptSource> = rhai::serde::from_dynamic(&result)
+        .map_err(|e| anyhow::anyhow!("resolve_play_url 返回值无效: {}", e))?;
+
+    Ok(Some(
+        sources
+            .into_iter()
+            .map(|source| match source {
+                ScriptSource::Url(url) => HookSource { url, kind: None },
+                ScriptSource::Source { url, kind } => HookSource { url, kind },
+            })
+            .filter(|source| !source.url.trim().is_empty())
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule_with_script(script: &str) -> Rule {
+        Rule {
+            script: script.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_script_hooks() {
+        let rule = rule_with_script(
+            r#"
+            fn transform_search_url(url, keyword, page) {
+                url + "&p=" + page
+            }
+
+            fn parse_results(html) {
+                let items = [];
+                for caps in regex_captures(html, "<a href=\"([^\"]+)\">([^<]+)</a>") {
+                    items.push(#{ name: caps[2], url: caps[1], tags: ["TV"] });
+                }
+                items
+            }
+
+            fn resolve_play_url(html, url) {
+                let data = parse_json(html);
+                [base64_decode(data.url), #{ url: data.backup, kind: "mp4" }]
+            }
+            "#,
+        );
+
+        assert_eq!(
+            transform_search_url(&rule, "https://a.com/s?wd=x", "x", 2).unwrap(),
+            Some("https://a.com/s?wd=x&p=2".to_string())
+        );
+
+        let items = parse_results(&rule, r#"<a href="/v/1">进击的巨人</a><a href="/v/2">  </a>"#)
+            .unwrap()
+            .unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].name, "进击的巨人");
+        assert_eq!(items[0].url, "/v/1");
+        assert_eq!(items[0].tags, Some(vec!["TV".to_string()]));
+
+        let sources = resolve_play_url(
+            &rule,
+            r#"{"url": "aHR0cHM6Ly9hLmNvbS8xLm0zdTg=", "backup": "https://b.com/1"}"#,
+            "https://a.com/play/1",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            sources,
+            vec![
+                HookSource {
+                    url: "https://a.com/1.m3u8".to_string(),
+                    kind: None,
+                },
+                HookSource {
+                    url: "https://b.com/1".to_string(),
+                    kind: Some("mp4".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_script_sandbox() {
+        // 未定义的钩子返回 None
+        let rule = rule_with_script("fn parse_results(html) { [] }");
+        assert_eq!(transform_search_url(&rule, "u", "k", 1).unwrap(), None);
+
+        // 死循环被运算次数限制打断
+        let rule = rule_with_script("fn transform_search_url(url, keyword, page) { loop {} }");
+        assert!(transform_search_url(&rule, "u", "k", 1).is_err());
+
+        // 语法错误
+        let rule = rule_with_script("fn parse_results(html) {");
+        assert!(compile(&rule).is_err());
+    }
+}
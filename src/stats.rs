@@ -0,0 +1,306 @@
+//! 规则使用统计
+//! 记录每个规则被选用的次数、有结果/无结果/出错次数与平均耗时，定期写入磁盘；
+//! 另外按站点域名与规则统计出站请求 (请求数、失败、重试、反代回退与耗时)，用于区分慢在本机还是上游
+
+use crate::config::CONFIG;
+use crate::http_client;
+use crate::types::PlatformSearchResult;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::warn;
+
+/// 统计写入磁盘的间隔
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 各规则的累计统计 (按规则名)
+static STATS: Lazy<Mutex<HashMap<String, RuleStats>>> = Lazy::new(|| Mutex::new(load()));
+
+/// 是否有尚未写入磁盘的变动
+static DIRTY: AtomicBool = AtomicBool::new(false);
+
+/// 单个规则的累计统计
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleStats {
+    /// 被搜索选用的次数
+    #[serde(default)]
+    pub selected: u64,
+    /// 返回非空结果的次数
+    #[serde(default)]
+    pub hits: u64,
+    /// 无结果的次数
+    #[serde(default)]
+    pub empty: u64,
+    /// 出错的次数
+    #[serde(default)]
+    pub errors: u64,
+    /// 已完成搜索的累计耗时/毫秒
+    #[serde(default)]
+    pub total_latency_ms: u64,
+}
+
+impl RuleStats {
+    fn completed(&self) -> u64 {
+        self.hits + self.empty + self.errors
+    }
+}
+
+/// 对外输出的统计 (附带平均耗时与成功率)
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleUsage {
+    pub name: String,
+    #[serde(flatten)]
+    pub stats: RuleStats,
+    /// 平均耗时/毫秒
+    pub avg_latency_ms: u64,
+    /// 返回非空结果的比例 (没有完成的搜索时为 0)
+    pub success_rate: f64,
+}
+
+impl RuleUsage {
+    fn new(name: &str, stats: &RuleStats) -> Self {
+        let completed = stats.completed();
+        Self {
+            name: name.to_string(),
+            stats: stats.clone(),
+            avg_latency_ms: stats.total_latency_ms.checked_div(completed).unwrap_or(0),
+            success_rate: if completed == 0 {
+                0.0
+            } else {
+                stats.hits as f64 / completed as f64
+            },
+        }
+    }
+}
+
+fn stats_path() -> PathBuf {
+    PathBuf::from(&CONFIG.cache_dir).join("rule_stats.json")
+}
+
+fn load() -> HashMap<String, RuleStats> {
+    fs::read_to_string(stats_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn update(name: &str, f: impl FnOnce(&mut RuleStats)) {
+    let mut stats = STATS.lock().unwrap_or_else(|e| e.into_inner());
+    f(stats.entry(name.to_string()).or_default());
+    DIRTY.store(true, Ordering::Relaxed);
+}
+
+/// 记录规则被搜索选用
+pub fn record_selected(name: &str) {
+    update(name, |s| s.selected += 1);
+}
+
+/// 记录规则搜索完成的结果与耗时
+pub fn record_result(name: &str, result: &PlatformSearchResult, latency: Duration) {
+    update(name, |s| {
+        if result.error.is_some() {
+            s.errors += 1;
+        } else if result.items.is_empty() {
+            s.empty += 1;
+        } else {
+            s.hits += 1;
+        }
+        s.total_latency_ms += latency.as_millis() as u64;
+    });
+}
+
+/// 单个规则的统计
+pub fn usage(name: &str) -> RuleUsage {
+    let stats = STATS.lock().unwrap_or_else(|e| e.into_inner());
+    RuleUsage::new(name, &stats.get(name).cloned().unwrap_or_default())
+}
+
+/// 所有规则的统计 (按选用次数从多到少排序)
+pub fn all_usage() -> Vec<RuleUsage> {
+    let stats = STATS.lock().unwrap_or_else(|e| e.into_inner());
+    let mut usage: Vec<RuleUsage> = stats
+        .iter()
+        .map(|(name, stats)| RuleUsage::new(name, stats))
+        .collect();
+    usage.sort_by(|a, b| {
+        b.stats
+            .selected
+            .cmp(&a.stats.selected)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    usage
+}
+
+fn save() -> anyhow::Result<()> {
+    let content = {
+        let stats = STATS.lock().unwrap_or_else(|e| e.into_inner());
+        serde_json::to_string(&*stats)?
+    };
+    fs::create_dir_all(&CONFIG.cache_dir)?;
+    fs::write(stats_path(), content)?;
+    Ok(())
+}
+
+/// 将统计写入磁盘 (没有变动时跳过)
+fn flush() {
+    if !DIRTY.swap(false, Ordering::Relaxed) {
+        return;
+    }
+    if let Err(e) = save() {
+        warn!("保存规则统计失败: {}", e);
+        DIRTY.store(true, Ordering::Relaxed);
+    }
+}
+
+/// 启动定期写入统计的后台任务
+pub fn spawn_flusher() {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = tokio::task::spawn_blocking(flush).await {
+                warn!("规则统计写入任务异常: {}", e);
+            }
+        }
+    });
+}
+
+// ============================================================================
+// 出站请求统计 (按站点域名与规则，只保存在内存中)
+// ============================================================================
+
+/// 各域名的出站请求统计
+static UPSTREAM_HOSTS: Lazy<Mutex<HashMap<String, UpstreamStats>>> = Lazy::new(Default::default);
+
+/// 统计的域名数上限 (图片中转等请求的目标由调用方提供)
+const MAX_UPSTREAM_HOSTS: usize = 1024;
+
+/// 各规则的出站请求统计
+static UPSTREAM_RULES: Lazy<Mutex<HashMap<String, UpstreamStats>>> = Lazy::new(Default::default);
+
+/// 出站请求的累计统计
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpstreamStats {
+    /// 发往上游的请求数 (不含缓存命中与熔断/限流拒绝的请求)
+    pub requests: u64,
+    /// 失败的请求数 (超时、连接失败与异常状态码)
+    pub errors: u64,
+    /// 失败后重新发起的次数 (Cloudflare 求解后重试与规则的重试策略)
+    pub retries: u64,
+    /// 改用反代请求的次数 (含对冲请求)
+    pub proxy_fallbacks: u64,
+    /// 累计耗时/毫秒
+    pub total_latency_ms: u64,
+    /// 最大耗时/毫秒
+    pub max_latency_ms: u64,
+}
+
+/// 对外输出的出站请求统计 (附带平均耗时与失败率)
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpstreamUsage {
+    /// 域名或规则名
+    pub name: String,
+    #[serde(flatten)]
+    pub stats: UpstreamStats,
+    /// 平均耗时/毫秒
+    pub avg_latency_ms: u64,
+    /// 失败请求的比例 (没有请求时为 0)
+    pub error_rate: f64,
+}
+
+impl UpstreamUsage {
+    fn new(name: &str, stats: &UpstreamStats) -> Self {
+        Self {
+            name: name.to_string(),
+            stats: stats.clone(),
+            avg_latency_ms: stats.total_latency_ms.checked_div(stats.requests).unwrap_or(0),
+            error_rate: if stats.requests == 0 {
+                0.0
+            } else {
+                stats.errors as f64 / stats.requests as f64
+            },
+        }
+    }
+}
+
+/// 出站请求统计报告
+#[derive(Debug, Clone, Serialize)]
+pub struct UpstreamReport {
+    /// 按域名 (按请求数从多到少排序)
+    pub hosts: Vec<UpstreamUsage>,
+    /// 按发起请求的规则 (按请求数从多到少排序)
+    pub rules: Vec<UpstreamUsage>,
+}
+
+fn update_upstream(url: &str, rule: Option<&str>, f: impl Fn(&mut UpstreamStats)) {
+    if let Some(host) = http_client::host_of(url) {
+        let mut hosts = UPSTREAM_HOSTS.lock().unwrap_or_else(|e| e.into_inner());
+        // 已满时去掉请求数最少的域名，保留主要站点的统计
+        if hosts.len() >= MAX_UPSTREAM_HOSTS && !hosts.contains_key(&host) {
+            let least = hosts.iter().min_by_key(|(_, s)| s.requests).map(|(h, _)| h.clone());
+            if let Some(least) = least {
+                hosts.remove(&least);
+            }
+        }
+        f(hosts.entry(host).or_default());
+    }
+    if let Some(rule) = rule {
+        let mut rules = UPSTREAM_RULES.lock().unwrap_or_else(|e| e.into_inner());
+        f(rules.entry(rule.to_string()).or_default());
+    }
+}
+
+/// 记录一次出站请求的结果与耗时
+pub fn record_request(url: &str, rule: Option<&str>, latency: Duration, failed: bool) {
+    let latency_ms = latency.as_millis() as u64;
+    update_upstream(url, rule, |s| {
+        s.requests += 1;
+        if failed {
+            s.errors += 1;
+        }
+        s.total_latency_ms += latency_ms;
+        s.max_latency_ms = s.max_latency_ms.max(latency_ms);
+    });
+}
+
+/// 记录失败后的重试
+pub fn record_retry(url: &str, rule: Option<&str>) {
+    update_upstream(url, rule, |s| s.retries += 1);
+}
+
+/// 记录改用反代请求
+pub fn record_proxy_fallback(url: &str, rule: Option<&str>) {
+    update_upstream(url, rule, |s| s.proxy_fallbacks += 1);
+}
+
+fn sorted_usage(stats: &Mutex<HashMap<String, UpstreamStats>>) -> Vec<UpstreamUsage> {
+    let stats = stats.lock().unwrap_or_else(|e| e.into_inner());
+    let mut usage: Vec<UpstreamUsage> = stats
+        .iter()
+        .map(|(name, stats)| UpstreamUsage::new(name, stats))
+        .collect();
+    usage.sort_by(|a, b| {
+        b.stats
+            .requests
+            .cmp(&a.stats.requests)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    usage
+}
+
+/// 出站请求统计 (按域名与规则)
+pub fn upstream_usage() -> UpstreamReport {
+    UpstreamReport {
+        hosts: sorted_usage(&UPSTREAM_HOSTS),
+        rules: sorted_usage(&UPSTREAM_RULES),
+    }
+}
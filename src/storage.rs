@@ -0,0 +1,261 @@
+//! 磁盘存储管理
+//! 统一管理各类缓存目录，按配置上限进行 LRU 淘汰，防止长时间运行后占满磁盘
+
+use crate::config::CONFIG;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, info, warn};
+
+/// 受管理的存储区域
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageArea {
+    /// HTTP 页面缓存
+    HttpCache,
+    /// 图片缓存
+    ImageCache,
+    /// 规则快照 (每个子目录为一个完整快照)
+    RuleSnapshots,
+    /// 任务结果
+    JobResults,
+}
+
+impl StorageArea {
+    pub const ALL: [StorageArea; 4] = [
+        StorageArea::HttpCache,
+        StorageArea::ImageCache,
+        StorageArea::RuleSnapshots,
+        StorageArea::JobResults,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            StorageArea::HttpCache => "http_cache",
+            StorageArea::ImageCache => "image_cache",
+            StorageArea::RuleSnapshots => "rule_snapshots",
+            StorageArea::JobResults => "job_results",
+        }
+    }
+
+    /// 区域所在目录
+    pub fn dir(&self) -> PathBuf {
+        match self {
+            StorageArea::HttpCache => Path::new(&CONFIG.cache_dir).join("http"),
+            StorageArea::ImageCache => Path::new(&CONFIG.cache_dir).join("images"),
+            StorageArea::RuleSnapshots => Path::new("rules").join(".backup"),
+            StorageArea::JobResults => Path::new(&CONFIG.cache_dir).join("jobs"),
+        }
+    }
+
+    /// 容量上限 (字节，0 = 不限制)
+    pub fn limit_bytes(&self) -> u64 {
+        let mb = match self {
+            StorageArea::HttpCache => CONFIG.http_cache_max_mb,
+            StorageArea::ImageCache => CONFIG.image_cache_max_mb,
+            StorageArea::RuleSnapshots => CONFIG.rule_snapshot_max_mb,
+            StorageArea::JobResults => CONFIG.job_results_max_mb,
+        };
+        mb * 1024 * 1024
+    }
+
+    /// 是否以顶层条目 (整个快照目录) 为淘汰单位
+    fn evicts_whole_entries(&self) -> bool {
+        matches!(self, StorageArea::RuleSnapshots)
+    }
+}
+
+/// 单个区域的占用情况
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AreaUsage {
+    pub name: &'static str,
+    pub path: String,
+    pub bytes: u64,
+    pub files: usize,
+    /// 容量上限 (字节，0 = 不限制)
+    pub limit_bytes: u64,
+}
+
+/// 单个区域的清理结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EvictionReport {
+    pub name: &'static str,
+    pub removed_entries: usize,
+    pub freed_bytes: u64,
+}
+
+/// 可淘汰的条目 (文件或快照目录)
+struct Entry {
+    path: PathBuf,
+    bytes: u64,
+    files: usize,
+    last_used: SystemTime,
+}
+
+/// 标记缓存文件被使用 (更新修改时间，作为 LRU 依据)
+pub fn touch(path: &Path) {
+    if let Ok(file) = fs::File::options().append(true).open(path) {
+        let _ = file.set_modified(SystemTime::now());
+    }
+}
+
+/// 先写临时文件再重命名，避免并发请求读到写了一半的缓存文件
+pub async fn write_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    let tmp = path.with_extension("tmp");
+    tokio::fs::write(&tmp, data).await?;
+    tokio::fs::rename(&tmp, path).await
+}
+
+/// 统计所有区域的占用
+pub fn usage() -> Vec<AreaUsage> {
+    StorageArea::ALL
+        .iter()
+        .map(|area| {
+            let entries = collect_entries(*area);
+            AreaUsage {
+                name: area.name(),
+                path: area.dir().display().to_string(),
+                bytes: entries.iter().map(|e| e.bytes).sum(),
+                files: entries.iter().map(|e| e.files).sum(),
+                limit_bytes: area.limit_bytes(),
+            }
+        })
+        .collect()
+}
+
+/// 对所有区域执行容量限制，按最近使用时间从旧到新淘汰
+pub fn enforce_limits() -> Vec<EvictionReport> {
+    StorageArea::ALL.iter().map(|area| enforce_area(*area)).collect()
+}
+
+fn enforce_area(area: StorageArea) -> EvictionReport {
+    let mut report = EvictionReport {
+        name: area.name(),
+        removed_entries: 0,
+        freed_bytes: 0,
+    };
+
+    let limit = area.limit_bytes();
+    if limit == 0 {
+        return report;
+    }
+
+    let mut entries = collect_entries(area);
+    let mut total: u64 = entries.iter().map(|e| e.bytes).sum();
+    if total <= limit {
+        return report;
+    }
+
+    entries.sort_by_key(|e| e.last_used);
+    for entry in entries {
+        if total <= limit {
+            break;
+        }
+
+        let removed = if entry.path.is_dir() {
+            fs::remove_dir_all(&entry.path)
+        } else {
+            fs::remove_file(&entry.path)
+        };
+
+        match removed {
+            Ok(()) => {
+                debug!("🧹 淘汰 {}: {}", area.name(), entry.path.display());
+                total -= entry.bytes;
+                report.removed_entries += 1;
+                report.freed_bytes += entry.bytes;
+            }
+            Err(e) => warn!("删除 {} 失败: {}", entry.path.display(), e),
+        }
+    }
+
+    report
+}
+
+/// 收集区域内的可淘汰条目
+fn collect_entries(area: StorageArea) -> Vec<Entry> {
+    let dir = area.dir();
+    let Ok(read_dir) = fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+
+    if area.evicts_whole_entries() {
+        read_dir
+            .flatten()
+            .map(|e| {
+                let path = e.path();
+                let mut files = Vec::new();
+                walk_files(&path, &mut files);
+                Entry {
+                    bytes: files.iter().map(|f| f.bytes).sum(),
+                    files: files.len(),
+                    last_used: files
+                        .iter()
+                        .map(|f| f.last_used)
+                        .max()
+                        .unwrap_or(SystemTime::UNIX_EPOCH),
+                    path,
+                }
+            })
+            .collect()
+    } else {
+        let mut files = Vec::new();
+        walk_files(&dir, &mut files);
+        files
+    }
+}
+
+/// 递归收集目录下的所有文件
+fn walk_files(path: &Path, out: &mut Vec<Entry>) {
+    let Ok(metadata) = fs::metadata(path) else {
+        return;
+    };
+
+    if metadata.is_file() {
+        out.push(Entry {
+            path: path.to_path_buf(),
+            bytes: metadata.len(),
+            files: 1,
+            last_used: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+        });
+    } else if metadata.is_dir() {
+        if let Ok(read_dir) = fs::read_dir(path) {
+            for entry in read_dir.flatten() {
+                walk_files(&entry.path(), out);
+            }
+        }
+    }
+}
+
+/// 启动后台清理任务
+pub fn spawn_janitor() {
+    let interval_secs = CONFIG.storage_janitor_interval_secs.max(1);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+        loop {
+            interval.tick().await;
+            let reports = match tokio::task::spawn_blocking(enforce_limits).await {
+                Ok(reports) => reports,
+                Err(e) => {
+                    warn!("存储清理任务异常: {}", e);
+                    continue;
+                }
+            };
+
+            for report in reports.iter().filter(|r| r.removed_entries > 0) {
+                info!(
+                    "🧹 {} 清理 {} 项，释放 {} KB",
+                    report.name,
+                    report.removed_entries,
+                    report.freed_bytes / 1024
+                );
+            }
+        }
+    });
+}
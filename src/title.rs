@@ -0,0 +1,147 @@
+//! 标题解析
+//! 从搜索结果名称中提取季度、集数、分段与类型 (如 "第二季"、"S2"、"剧场版")，
+//! 便于客户端分组与过滤
+
+use crate::types::TitleInfo;
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// 中文或阿拉伯数字
+const NUM: &str = r"[0-9零〇一二两三四五六七八九十百]+";
+
+/// 季度: 第二季 / 第2期 / Season 2 / 2nd Season / S2 (S2E05)
+static RE_SEASON: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"(?i)第\s*({NUM})\s*[季期]|season\s*(\d+)|(\d+)(?:st|nd|rd|th)\s+season|(?:^|[^a-z])s(\d{{1,2}})(?:e\d+|[^a-z0-9]|$)"
+    ))
+    .unwrap()
+});
+
+/// 集数: 第12集 / 第12话 / S2E05 / EP05
+static RE_EPISODE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"(?i)第\s*({NUM})\s*[集话話]|s\d{{1,2}}e(\d+)|(?:^|[^a-z])ep?\.?\s*(\d+)(?:[^0-9]|$)"
+    ))
+    .unwrap()
+});
+
+/// 分段: 第2部分 / 第二部 / Part 2 / Part.2 / 第2クール
+static RE_PART: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"(?i)第\s*({NUM})\s*(?:部分|部|クール)|part\.?\s*(\d+)"
+    ))
+    .unwrap()
+});
+
+/// 类型关键词 (按顺序匹配，先匹配到的优先)
+static KINDS: LazyLock<Vec<(Regex, &'static str)>> = LazyLock::new(|| {
+    [
+        (r"(?i)剧场版|劇場版|电影版|movie|the\s+movie", "movie"),
+        (r"(?i)\b(?:ova|oad)\b", "ova"),
+        (r"(?i)特别篇|特別篇|番外|\bsp\b|special", "special"),
+    ]
+    .into_iter()
+    .map(|(pattern, kind)| (Regex::new(pattern).unwrap(), kind))
+    .collect()
+});
+
+/// 解析标题，没有识别出任何信息时返回 None
+pub fn parse(name: &str) -> Option<TitleInfo> {
+    let info = TitleInfo {
+        season: first_number(&RE_SEASON, name),
+        episode: first_number(&RE_EPISODE, name),
+        part: first_number(&RE_PART, name),
+        kind: KINDS
+            .iter()
+            .find(|(re, _)| re.is_match(name))
+            .map(|(_, kind)| kind.to_string()),
+    };
+
+    (info.season.is_some() || info.episode.is_some() || info.part.is_some() || info.kind.is_some())
+        .then_some(info)
+}
+
+/// 取第一个匹配中第一个非空捕获组的数字
+fn first_number(re: &Regex, text: &str) -> Option<u32> {
+    re.captures_iter(text)
+        .find_map(|caps| caps.iter().skip(1).flatten().next().map(|m| m.as_str().to_string()))
+        .and_then(|s| parse_number(&s))
+}
+
+/// 解析阿拉伯数字或中文数字 (如 "12"、"二"、"十二"、"二十三")
+fn parse_number(text: &str) -> Option<u32> {
+    if let Ok(n) = text.parse() {
+        return Some(n);
+    }
+
+    let digit = |c: char| match c {
+        '零' | '〇' => Some(0),
+        '一' => Some(1),
+        '二' | '两' => Some(2),
+        '三' => Some(3),
+        '四' => Some(4),
+        '五' => Some(5),
+        '六' => Some(6),
+        '七' => Some(7),
+        '八' => Some(8),
+        '九' => Some(9),
+        _ => None,
+    };
+
+    let mut total = 0;
+    let mut current = 0;
+    for c in text.chars() {
+        match c {
+            '十' => {
+                total += current.max(1) * 10;
+                current = 0;
+            }
+            '百' => {
+                total += current.max(1) * 100;
+                current = 0;
+            }
+            c => current = digit(c)?,
+        }
+    }
+    Some(total + current).filter(|n| *n > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("12"), Some(12));
+        assert_eq!(parse_number("二"), Some(2));
+        assert_eq!(parse_number("十"), Some(10));
+        assert_eq!(parse_number("十二"), Some(12));
+        assert_eq!(parse_number("二十三"), Some(23));
+        assert_eq!(parse_number("一百零五"), Some(105));
+        assert_eq!(parse_number("第"), None);
+    }
+
+    #[test]
+    fn test_parse_title() {
+        let info = parse("进击的巨人 第二季").unwrap();
+        assert_eq!(info.season, Some(2));
+        assert_eq!(info.kind, None);
+
+        let info = parse("Spy x Family S2E05").unwrap();
+        assert_eq!((info.season, info.episode), (Some(2), Some(5)));
+
+        let info = parse("Mushoku Tensei 2nd Season Part 2").unwrap();
+        assert_eq!((info.season, info.part), (Some(2), Some(2)));
+
+        let info = parse("咒术回战 第2期 第二部分 第12话").unwrap();
+        assert_eq!((info.season, info.part, info.episode), (Some(2), Some(2), Some(12)));
+
+        let info = parse("名侦探柯南 剧场版").unwrap();
+        assert_eq!(info.kind.as_deref(), Some("movie"));
+
+        assert_eq!(parse("OVA").unwrap().kind.as_deref(), Some("ova"));
+        assert!(parse("葬送的芙莉莲").is_none());
+        // 普通单词中的 s/ep 不应误判
+        assert!(parse("Steins;Gate").is_none());
+    }
+}
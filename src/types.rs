@@ -1,4 +1,8 @@
+use crate::xpath_to_css::CssSelector;
+use async_graphql::SimpleObject;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
 
 /// Kazumi 风格的规则定义
 /// 完全兼容 Kazumi 规则格式: https://github.com/Predidit/KazumiRules
@@ -8,7 +12,7 @@ pub struct Rule {
     #[serde(default = "default_api")]
     pub api: String,
 
-    /// 类型 (anime)
+    /// 类型 (anime；"json" 表示搜索接口返回 JSON，选择器使用 JSONPath)
     #[serde(rename = "type", default = "default_type")]
     pub rule_type: String,
 
@@ -23,7 +27,7 @@ pub struct Rule {
     #[serde(default, alias = "muliSources")]
     pub muli_sources: bool,
 
-    /// 是否使用 webview
+    /// 是否使用 webview (启用 webview 特性时通过无头浏览器渲染页面)
     #[serde(default, alias = "useWebview")]
     pub use_webview: bool,
 
@@ -35,7 +39,7 @@ pub struct Rule {
     #[serde(default, alias = "usePost")]
     pub use_post: bool,
 
-    /// 是否使用旧版解析器
+    /// 是否使用旧版解析器 (播放页只解析 iframe 及其 url= 参数)
     #[serde(default, alias = "useLegacyParser")]
     pub use_legacy_parser: bool,
 
@@ -51,10 +55,23 @@ pub struct Rule {
     #[serde(alias = "baseURL")]
     pub base_url: String,
 
-    /// 搜索 URL (使用 @keyword 作为占位符)
+    /// 搜索 URL (使用 @keyword 作为占位符，可选 @page 作为页码占位符)
     #[serde(alias = "searchURL")]
     pub search_url: String,
 
+    /// 关键词编码 (可选，扩展字段；如 "gbk"，为空时使用 UTF-8)
+    #[serde(default, alias = "keywordEncoding")]
+    pub keyword_encoding: String,
+
+    /// POST 请求体模板 (可选，扩展字段；支持 @keyword / @page 占位符)
+    /// 为空时沿用 Kazumi 行为：将 searchURL 的查询参数作为表单提交
+    #[serde(default, alias = "searchBody")]
+    pub search_body: String,
+
+    /// POST 请求体 Content-Type (可选，扩展字段；为空时按请求体自动判断)
+    #[serde(default, alias = "searchContentType")]
+    pub search_content_type: String,
+
     /// 搜索结果列表选择器 (CSS/XPath)
     #[serde(default, alias = "searchList")]
     pub search_list: String,
@@ -67,6 +84,22 @@ pub struct Rule {
     #[serde(default, alias = "searchResult")]
     pub search_result: String,
 
+    /// 搜索结果封面图片选择器 (可选，扩展字段)
+    #[serde(default, alias = "searchCover")]
+    pub search_cover: String,
+
+    /// 搜索结果简介选择器 (可选，扩展字段)
+    #[serde(default, alias = "searchDescription")]
+    pub search_description: String,
+
+    /// 搜索结果更新状态选择器 (如 "更新至第12集"，可选，扩展字段)
+    #[serde(default, alias = "searchStatus")]
+    pub search_status: String,
+
+    /// 磁力/种子链接选择器 (可选，扩展字段；为空时自动查找 magnet: 与 .torrent 链接)
+    #[serde(default, alias = "searchMagnet")]
+    pub search_magnet: String,
+
     /// 章节列表选择器
     #[serde(default, alias = "chapterRoads")]
     pub chapter_roads: String,
@@ -92,12 +125,103 @@ pub struct Rule {
     /// 是否需要魔法
     #[serde(default)]
     pub magic: bool,
+
+    /// 是否经由出站代理请求 (可选，扩展字段；OUTBOUND_PROXY_SCOPE=magic 时 magic 规则默认使用)
+    #[serde(default, alias = "outboundProxy")]
+    pub outbound_proxy: bool,
+
+    /// 自定义请求头 (如 X-Requested-With)，搜索、详情、播放页请求均会携带
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+
+    /// 固定 Cookie (如年龄确认、主题设置)，搜索、详情、播放页请求均会携带
+    #[serde(default)]
+    pub cookies: BTreeMap<String, String>,
+
+    /// rhai 脚本钩子 (transform_search_url / parse_results / resolve_play_url)
+    #[serde(default)]
+    pub script: String,
+
+    /// 搜索请求最多尝试次数 (可选，扩展字段；为空时使用 RETRY_ATTEMPTS)
+    #[serde(default, alias = "retryAttempts")]
+    pub retry_attempts: Option<u32>,
+
+    /// 重试退避基准时间/毫秒 (可选，扩展字段；为空时使用 RETRY_BACKOFF_MS)
+    #[serde(default, alias = "retryBackoffMs")]
+    pub retry_backoff_ms: Option<u64>,
+
+    /// 请求超时/秒 (可选，扩展字段；为空时使用 TIMEOUT_SECONDS)
+    #[serde(default, alias = "timeoutSecs")]
+    pub timeout_secs: Option<u64>,
+
+    /// 对冲请求等待时间/毫秒 (可选，扩展字段；直连超过该时间未完成时并行请求反代，取先返回者)
+    #[serde(default, alias = "hedgeDelayMs")]
+    pub hedge_delay_ms: Option<u64>,
+
+    /// 不校验该站点的证书 (可选，扩展字段；只用于证书过期、自签名等站点)
+    #[serde(default, alias = "insecureTls")]
+    pub insecure_tls: bool,
+
+    /// 模拟的浏览器 TLS 指纹: chrome / edge / firefox / safari (可选，扩展字段；启用 impersonate 特性时生效)
+    #[serde(default)]
+    pub impersonate: String,
+
+    /// 字段正则后处理 (可选，扩展字段)
+    #[serde(default, alias = "postProcess")]
+    pub post_process: PostProcess,
+
+    /// 按 XPath 语义解析位置谓词 `[n]` (可选，扩展字段；默认转换为 nth-of-type)
+    #[serde(default, alias = "strictPositions")]
+    pub strict_positions: bool,
+}
+
+/// 选择器提取后的字段后处理 (每个字段按顺序执行多个步骤)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Hash)]
+pub struct PostProcess {
+    /// 结果名称
+    #[serde(default)]
+    pub name: Vec<ProcessStep>,
+    /// 结果链接 (选中元素没有 href 时取 onclick 属性)
+    #[serde(default)]
+    pub url: Vec<ProcessStep>,
+    /// 章节名称
+    #[serde(default, alias = "episodeName")]
+    pub episode_name: Vec<ProcessStep>,
+    /// 章节链接 (选中元素没有 href 时取 onclick 属性)
+    #[serde(default, alias = "episodeUrl")]
+    pub episode_url: Vec<ProcessStep>,
+    /// 章节链接的来源属性 (如 "data-url"，为空时使用 href)
+    #[serde(default, alias = "episodeUrlAttr")]
+    pub episode_url_attr: String,
+}
+
+/// 单个后处理步骤：依次执行提取、解码、替换，未配置的部分跳过
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Hash)]
+pub struct ProcessStep {
+    /// 提取正则：取第一个匹配 (不匹配时保留原值)
+    #[serde(default)]
+    pub extract: String,
+    /// 提取结果模板 (可用 $1 / ${name} 引用捕获组)，为空时取第 1 个捕获组，无捕获组时取整个匹配
+    #[serde(default)]
+    pub template: String,
+    /// 解码: base64 / url (百分号解码) / unescape (JS unescape) / json:字段路径 (如 json:data.url)
+    #[serde(default)]
+    pub decode: String,
+    /// 替换正则：所有匹配替换为 with
+    #[serde(default)]
+    pub replace: String,
+    /// 替换内容 (可用 $1 引用捕获组)
+    #[serde(default)]
+    pub with: String,
 }
 
 fn default_api() -> String {
     "1".to_string()
 }
 
+/// JSON 接口规则类型
+pub const RULE_TYPE_JSON: &str = "json";
+
 fn default_type() -> String {
     "anime".to_string()
 }
@@ -130,46 +254,105 @@ impl Default for Rule {
             user_agent: String::new(),
             base_url: String::new(),
             search_url: String::new(),
+            keyword_encoding: String::new(),
+            search_body: String::new(),
+            search_content_type: String::new(),
             search_list: String::new(),
             search_name: String::new(),
             search_result: String::new(),
+            search_cover: String::new(),
+            search_description: String::new(),
+            search_status: String::new(),
+            search_magnet: String::new(),
             chapter_roads: String::new(),
             chapter_result: String::new(),
             referer: String::new(),
             color: default_color(),
             tags: vec![],
             magic: false,
+            outbound_proxy: false,
+            headers: BTreeMap::new(),
+            cookies: BTreeMap::new(),
+            script: String::new(),
+            retry_attempts: None,
+            retry_backoff_ms: None,
+            timeout_secs: None,
+            hedge_delay_ms: None,
+            insecure_tls: false,
+            impersonate: String::new(),
+            post_process: PostProcess::default(),
+            strict_positions: false,
         }
     }
 }
 
 /// 单个搜索结果
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+#[serde(rename_all = "camelCase")]
 pub struct SearchResultItem {
     /// 动漫名称
     pub name: String,
     /// 资源链接
     pub url: String,
-    /// 可选标签 (如：集数、画质等)
+    /// 封面图片
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cover: Option<String>,
+    /// 简介
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// 可选标签 (如：更新状态、集数、画质等)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tags: Option<Vec<String>>,
     /// 集数列表 (播放源 -> 集数列表)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub episodes: Option<Vec<EpisodeRoad>>,
+    /// 磁力链接与种子地址 (BT 类站点)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub magnets: Option<Vec<String>>,
+    /// 从名称解析出的季度/集数/分段信息
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title_info: Option<TitleInfo>,
+    /// 重定向/反代后实际落地的地址 (与 url 不同时返回，站点换域名时应优先使用)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved_url: Option<String>,
+    /// 实际落地的站点主机名
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved_host: Option<String>,
+}
+
+/// 标题解析结果 (如 "第二季"、"S2E05"、"剧场版")
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, SimpleObject)]
+pub struct TitleInfo {
+    /// 季度
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub season: Option<u32>,
+    /// 集数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub episode: Option<u32>,
+    /// 分段 (如 "第2部分"、"Part 2")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub part: Option<u32>,
+    /// 类型: movie (剧场版) / ova / special (特别篇)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
 }
 
 /// 播放源 (一个动漫可能有多个播放源)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
 pub struct EpisodeRoad {
     /// 播放源名称 (如: "线路1", "备用线路")
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
     /// 该播放源下的集数列表
     pub episodes: Vec<Episode>,
+    /// 集数完全相同而被合并的其他播放源名称
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
 }
 
 /// 单集信息
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+#[graphql(name = "SourceEpisode")]
 pub struct Episode {
     /// 集数名称 (如: "第1集", "01")
     pub name: String,
@@ -177,8 +360,43 @@ pub struct Episode {
     pub url: String,
 }
 
+/// 指定播放源：序号 (从 0 开始) 或名称 (如 "线路2")
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum RoadSelector {
+    Index(usize),
+    Name(String),
+}
+
+impl RoadSelector {
+    /// 是否选中第 index 个播放源
+    pub fn matches(&self, index: usize) -> bool {
+        match self {
+            Self::Index(i) => *i == index,
+            Self::Name(name) => {
+                let name = name.trim();
+                name.strip_prefix("线路")
+                    .unwrap_or(name)
+                    .parse::<usize>()
+                    .is_ok_and(|n| n == index + 1)
+            }
+        }
+    }
+}
+
+/// 从播放页解析出的媒体地址
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlaySource {
+    /// 媒体地址
+    pub url: String,
+    /// 媒体类型: m3u8, mp4, flv, iframe, unknown
+    pub kind: String,
+    /// 来源: video (video/source 标签), player (播放器配置), script (内联脚本), iframe, legacy (旧版解析器), hook (规则脚本)
+    pub origin: String,
+}
+
 /// 平台搜索的返回值
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PlatformSearchResult {
     /// 搜索结果列表
     pub items: Vec<SearchResultItem>,
@@ -187,6 +405,47 @@ pub struct PlatformSearchResult {
     /// 错误信息
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    /// 搜索请求尝试次数 (含重试)
+    #[serde(default)]
+    pub attempts: u32,
+    /// 解析诊断信息 (debug 模式下无结果时返回)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diagnostics: Option<ParseDiagnostics>,
+    /// 站点限流时建议的等待时间/秒
+    #[serde(rename = "retryAfter", default, skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<u64>,
+}
+
+/// 解析诊断信息：用于排查规则无结果的原因
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParseDiagnostics {
+    /// 上游响应状态码
+    pub status: u16,
+    /// 最终落地的地址
+    pub final_url: String,
+    /// 到达最终地址前经过的重定向
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redirects: Vec<String>,
+    /// 响应体大小 (字节)
+    pub body_bytes: usize,
+    /// 各字段转换后的 CSS 选择器
+    pub selectors: BTreeMap<String, CssSelector>,
+    /// 各阶段匹配数量 (按解析顺序)
+    pub stages: Vec<StageCount>,
+    /// 选择器编译或解析错误
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    /// 截断的 HTML 快照 (debug=2)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<String>,
+}
+
+/// 单个解析阶段的匹配数量
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StageCount {
+    pub stage: String,
+    pub count: usize,
 }
 
 impl PlatformSearchResult {
@@ -195,6 +454,9 @@ impl PlatformSearchResult {
             items: Vec::new(),
             count: -1,
             error: Some(message),
+            attempts: 1,
+            diagnostics: None,
+            retry_after: None,
         }
     }
 
@@ -204,16 +466,70 @@ impl PlatformSearchResult {
             items,
             count,
             error: None,
+            attempts: 1,
+            diagnostics: None,
+            retry_after: None,
         }
     }
+
+    pub fn with_attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts;
+        self
+    }
+}
+
+/// 搜索选项 (由请求参数解析)
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    /// 通过 Bangumi 解析标题别名，并使用所有别名搜索
+    pub expand_aliases: bool,
+    /// 是否获取每个结果的集数列表 (关闭后可通过 /api/detail 按需加载)
+    pub fetch_episodes: bool,
+    /// 抓取的结果页数 (仅对 searchURL 含 @page 的规则生效)
+    pub pages: usize,
+    /// 为前 N 个结果获取集数 (None 时使用服务端上限 MAX_EPISODE_ITEMS)
+    pub episode_items: Option<usize>,
+    /// 无结果时附带解析诊断信息 (需要管理令牌)
+    pub debug: DebugMode,
+    /// 快速模式：N 个规则返回非空结果后立即结束搜索并取消其余规则
+    pub first: Option<usize>,
+    /// 单次请求的超时上限 (规则或全局超时更长时以此为准)
+    pub timeout: Option<Duration>,
+}
+
+/// 调试输出级别 (debug 参数：1 = 诊断信息，2 = 另附截断的 HTML 快照)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DebugMode {
+    #[default]
+    Off,
+    Diagnostics,
+    Snapshot,
+}
+
+impl DebugMode {
+    pub fn from_level(level: u8) -> Self {
+        match level {
+            0 => Self::Off,
+            1 => Self::Diagnostics,
+            _ => Self::Snapshot,
+        }
+    }
+
+    pub fn enabled(self) -> bool {
+        self != Self::Off
+    }
 }
 
-impl Default for PlatformSearchResult {
+impl Default for SearchOptions {
     fn default() -> Self {
         Self {
-            items: Vec::new(),
-            count: 0,
-            error: None,
+            expand_aliases: false,
+            fetch_episodes: true,
+            pages: 1,
+            episode_items: None,
+            debug: DebugMode::Off,
+            first: None,
+            timeout: None,
         }
     }
 }
@@ -228,7 +544,8 @@ pub struct StreamProgress {
 }
 
 /// SSE 流中的单个结果
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+#[graphql(name = "SourceResult")]
 pub struct StreamResult {
     /// 平台名称
     pub name: String,
@@ -241,14 +558,31 @@ pub struct StreamResult {
     /// 错误信息
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    /// 搜索请求尝试次数 (含重试)
+    #[serde(default)]
+    pub attempts: u32,
+    /// 规则优先级 (越大越靠前，客户端可据此排列陆续到达的结果)
+    #[serde(default)]
+    pub priority: i32,
+    /// 解析诊断信息 (debug 模式下无结果时返回)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[graphql(skip)]
+    pub diagnostics: Option<ParseDiagnostics>,
+    /// 站点限流时建议的等待时间/秒
+    #[serde(rename = "retryAfter", default, skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<u64>,
 }
 
 /// SSE 事件数据
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum StreamEvent {
-    /// 初始事件，包含总数
-    Init { total: usize },
+    /// 初始事件，包含总数 (启用别名扩展时附带实际使用的关键词)
+    Init {
+        total: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        keywords: Option<Vec<String>>,
+    },
     /// 进度更新 (无结果)
     Progress { progress: StreamProgress },
     /// 进度更新 + 结果
@@ -256,6 +590,57 @@ pub enum StreamEvent {
         progress: StreamProgress,
         result: StreamResult,
     },
+    /// 规则尚未完成时的阶段性结果 (之后会被该规则的 result 事件覆盖)
+    Partial { partial: StreamResult },
     /// 完成信号
     Done { done: bool },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_result_item_field_names() {
+        let item = SearchResultItem {
+            name: "葬送的芙莉莲".to_string(),
+            url: "https://old.example.com/v/1".to_string(),
+            cover: None,
+            description: None,
+            tags: None,
+            episodes: None,
+            magnets: None,
+            title_info: None,
+            resolved_url: Some("https://new.example.com/v/1".to_string()),
+            resolved_host: Some("new.example.com".to_string()),
+        };
+
+        let value = serde_json::to_value(&item).unwrap();
+        assert_eq!(value["resolvedUrl"], "https://new.example.com/v/1");
+        assert_eq!(value["resolvedHost"], "new.example.com");
+        assert!(value.get("resolved_url").is_none());
+    }
+
+    #[test]
+    fn test_title_info_field_name() {
+        let item = SearchResultItem {
+            name: "进击的巨人 第二季".to_string(),
+            url: "https://www.example.com/v/2".to_string(),
+            cover: None,
+            description: None,
+            tags: None,
+            episodes: None,
+            magnets: None,
+            title_info: Some(TitleInfo {
+                season: Some(2),
+                ..Default::default()
+            }),
+            resolved_url: None,
+            resolved_host: None,
+        };
+
+        let value = serde_json::to_value(&item).unwrap();
+        assert_eq!(value["titleInfo"], serde_json::json!({ "season": 2 }));
+        assert!(value.get("title_info").is_none());
+    }
+}
@@ -2,72 +2,220 @@
 //! 通过 GitHub API 检测 KazumiRules 仓库变动并同步规则
 
 use crate::config::CONFIG;
-use crate::http_client::HTTP_CLIENT;
+use crate::engine::validate_selectors;
+use crate::http_client::{proxied_url, HTTP_CLIENT};
+use crate::storage::StorageArea;
+use crate::types::Rule;
+use once_cell::sync::Lazy;
+use reqwest::{header, StatusCode};
 use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use std::time::{Duration, SystemTime};
 use tracing::{debug, info, warn};
 
 /// 规则目录
 const RULES_DIR: &str = "rules";
 /// 存储上次 commit SHA 的文件
 const LAST_COMMIT_FILE: &str = "rules/.last_commit";
+/// 上游已删除规则的归档目录
+const ARCHIVE_DIR: &str = "rules/.removed";
+/// GitHub API 条件请求缓存 (ETag/Last-Modified 与响应内容)
+const GITHUB_CACHE_FILE: &str = "rules/.github_cache.json";
+
+/// 规则更新锁：定时更新、Webhook、手动更新与回滚依次执行，避免同时改写规则目录与清单
+static UPDATE_LOCK: Lazy<tokio::sync::Mutex<()>> = Lazy::new(|| tokio::sync::Mutex::new(()));
+
+/// GitHub 请求的线路
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Route {
+    /// 直连失败时改用 GITHUB_PROXY
+    Fallback,
+    /// 只直连 GitHub (校验下载内容的文件列表优先直连获取)
+    Direct,
+    /// 只经由 GITHUB_PROXY (直连已经失败时)
+    Proxy,
+}
 
-/// 带代理重试的 GET 请求
-async fn get_with_retry(url: &str) -> anyhow::Result<reqwest::Response> {
-    // 第一次直接请求
-    let result = HTTP_CLIENT
-        .get(url)
-        .header("Accept", "application/vnd.github.v3+json")
-        .header("User-Agent", "anime-search-api")
-        .send()
-        .await;
+/// 带代理重试的 GET 请求 (304 视为成功，交由调用方使用缓存)
+async fn get_with_retry(
+    url: &str,
+    headers: &[(&str, String)],
+    route: Route,
+) -> anyhow::Result<reqwest::Response> {
+    if route == Route::Proxy {
+        return get_via_proxy(url, headers).await;
+    }
+    let allow_proxy = route == Route::Fallback;
+
+    // 第一次直接请求 (令牌只发给 GitHub，不经过代理)
+    let mut req = github_request(url, headers);
+    if !CONFIG.github_token.is_empty() {
+        req = req.bearer_auth(&CONFIG.github_token);
+    }
+    let result = req.send().await;
 
     match result {
-        Ok(resp) if resp.status().is_success() => Ok(resp),
+        Ok(resp) if is_ok_status(resp.status()) => Ok(resp),
+        Ok(resp) if !allow_proxy => anyhow::bail!("HTTP {}", resp.status()),
+        Err(e) if !allow_proxy => Err(e.into()),
         Ok(resp) => {
             // 状态码错误，尝试代理
             let status = resp.status();
             debug!("请求失败 ({}), 尝试代理: {}", status, url);
-            get_via_proxy(url).await
+            get_via_proxy(url, headers).await
         }
         Err(e) => {
             // 网络错误，尝试代理
             debug!("请求失败 ({}), 尝试代理: {}", e, url);
-            get_via_proxy(url).await
+            get_via_proxy(url, headers).await
         }
     }
 }
 
 /// 通过代理请求
-async fn get_via_proxy(url: &str) -> anyhow::Result<reqwest::Response> {
-    let proxy_url = format!("{}{}", CONFIG.github_proxy, url);
+async fn get_via_proxy(
+    url: &str,
+    headers: &[(&str, String)],
+) -> anyhow::Result<reqwest::Response> {
+    let proxy_url = proxied_url(&CONFIG.github_proxy, url);
     debug!("使用代理: {}", proxy_url);
 
-    let response = HTTP_CLIENT
-        .get(&proxy_url)
-        .header("Accept", "application/vnd.github.v3+json")
-        .header("User-Agent", "anime-search-api")
-        .send()
-        .await?;
+    let response = github_request(&proxy_url, headers).send().await?;
 
-    if !response.status().is_success() {
+    if !is_ok_status(response.status()) {
         anyhow::bail!("代理请求失败: HTTP {}", response.status());
     }
 
     Ok(response)
 }
 
+/// 构造 GitHub 请求 (附带额外请求头)
+fn github_request(url: &str, headers: &[(&str, String)]) -> reqwest::RequestBuilder {
+    let mut req = HTTP_CLIENT
+        .get(url)
+        .header("Accept", "application/vnd.github.v3+json")
+        .header("User-Agent", "anime-search-api");
+    for (name, value) in headers {
+        req = req.header(*name, value.as_str());
+    }
+    req
+}
+
+fn is_ok_status(status: StatusCode) -> bool {
+    status.is_success() || status == StatusCode::NOT_MODIFIED
+}
+
+/// 条件请求缓存项 (按 URL 保存上次响应的校验头与内容)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedResponse {
+    #[serde(default)]
+    etag: Option<String>,
+    #[serde(default)]
+    last_modified: Option<String>,
+    body: String,
+}
+
+fn read_github_cache() -> HashMap<String, CachedResponse> {
+    fs::read_to_string(GITHUB_CACHE_FILE)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn save_github_cache(cache: &HashMap<String, CachedResponse>) -> anyhow::Result<()> {
+    let _ = fs::create_dir_all(RULES_DIR);
+    fs::write(GITHUB_CACHE_FILE, serde_json::to_string(cache)?)?;
+    Ok(())
+}
+
+/// 带 ETag/Last-Modified 的条件 GET，304 时返回缓存内容
+///
+/// GitHub 对 304 响应不计入 API 速率限制
+async fn get_cached(url: &str, route: Route) -> anyhow::Result<String> {
+    let mut cache = read_github_cache();
+
+    let mut headers = Vec::new();
+    if let Some(cached) = cache.get(url) {
+        if let Some(etag) = &cached.etag {
+            headers.push(("If-None-Match", etag.clone()));
+        }
+        if let Some(last_modified) = &cached.last_modified {
+            headers.push(("If-Modified-Since", last_modified.clone()));
+        }
+    }
+
+    let response = get_with_retry(url, &headers, route).await?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        if let Some(cached) = cache.remove(url) {
+            debug!("未变动 (304): {}", url);
+            return Ok(cached.body);
+        }
+        anyhow::bail!("收到 304 但本地无缓存: {}", url);
+    }
+
+    let header_value = |name: header::HeaderName| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string())
+    };
+    let etag = header_value(header::ETAG);
+    let last_modified = header_value(header::LAST_MODIFIED);
+    let body = response.text().await?;
+
+    if etag.is_some() || last_modified.is_some() {
+        cache.insert(
+            url.to_string(),
+            CachedResponse {
+                etag,
+                last_modified,
+                body: body.clone(),
+            },
+        );
+        if let Err(e) = save_github_cache(&cache) {
+            warn!("保存 GitHub 缓存失败: {}", e);
+        }
+    }
+
+    Ok(body)
+}
+
 /// GitHub Commit 响应
 #[derive(Debug, Deserialize)]
 struct GitHubCommit {
     sha: String,
 }
 
+/// GitHub Compare 响应 (两个 commit 之间变动的文件)
+#[derive(Debug, Deserialize)]
+struct GitHubCompare {
+    /// ahead / behind / diverged / identical
+    status: String,
+    #[serde(default)]
+    files: Vec<GitHubChangedFile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitHubChangedFile {
+    filename: String,
+    status: String,
+    #[serde(default)]
+    previous_filename: Option<String>,
+}
+
+/// Compare API 单次最多返回的文件数，达到时列表可能被截断
+const COMPARE_FILES_LIMIT: usize = 300;
+
 /// GitHub Contents 响应 (文件列表)
 #[derive(Debug, Deserialize)]
 struct GitHubContent {
     name: String,
+    /// Git blob 哈希 (SHA-1)
+    sha: String,
     #[serde(rename = "type")]
     content_type: String,
 }
@@ -78,6 +226,7 @@ pub struct UpdateResult {
     pub total: usize,
     pub updated: usize,
     pub added: usize,
+    pub removed: usize,
     pub failed: usize,
     pub details: Vec<UpdateDetail>,
 }
@@ -85,7 +234,7 @@ pub struct UpdateResult {
 #[derive(Debug, Clone, Serialize)]
 pub struct UpdateDetail {
     pub name: String,
-    pub action: String, // "added", "updated", "failed"
+    pub action: String, // "added", "updated", "removed", "failed"
     pub message: String,
 }
 
@@ -99,11 +248,7 @@ pub fn has_local_rules() -> bool {
     match fs::read_dir(rules_path) {
         Ok(entries) => entries
             .flatten()
-            .any(|e| {
-                let name = e.file_name();
-                let name = name.to_string_lossy();
-                name.ends_with(".json") && name != "index.json"
-            }),
+            .any(|e| crate::rules::is_rule_file(&e.path())),
         Err(_) => false,
     }
 }
@@ -120,37 +265,141 @@ fn save_last_commit(sha: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
-/// 获取仓库最新 commit SHA
-async fn fetch_latest_commit() -> anyhow::Result<String> {
-    let url = CONFIG.github_api_commits();
-    let response = get_with_retry(&url).await?;
-    let commit: GitHubCommit = response.json().await?;
+/// 获取指定版本 (分支、tag 或 SHA) 对应的 commit SHA
+async fn fetch_latest_commit(git_ref: &str) -> anyhow::Result<String> {
+    let url = CONFIG.github_api_commits(git_ref);
+    let commit: GitHubCommit = serde_json::from_str(&get_cached(&url, Route::Fallback).await?)?;
     Ok(commit.sha)
 }
 
-/// 获取仓库中的所有规则文件名
-async fn fetch_rule_files() -> anyhow::Result<Vec<String>> {
-    let url = CONFIG.github_api_contents();
-    let response = get_with_retry(&url).await?;
-    let contents: Vec<GitHubContent> = response.json().await?;
+/// 上游规则文件列表
+struct RuleFiles {
+    /// 规则名 → Git blob 哈希
+    blobs: HashMap<String, String>,
+    /// 列表是否直连 GitHub 获取 (经由代理获取时哈希同样来自代理，只能发现传输损坏，不能发现代理篡改)
+    verified: bool,
+}
+
+/// 文件列表经由代理获取时的提示
+const UNVERIFIED_MESSAGE: &str = "文件列表经由 GITHUB_PROXY 获取，无法校验规则是否被代理篡改";
+
+/// 获取仓库指定版本中的所有规则文件
+///
+/// blob 哈希用于校验经由代理下载的内容，因此文件列表优先直连 GitHub 获取；
+/// 直连失败时 (只能经由 GITHUB_PROXY 访问 GitHub 的部署) 改用代理，并记录完整性无法保证
+async fn fetch_rule_files(git_ref: &str) -> anyhow::Result<RuleFiles> {
+    let url = CONFIG.github_api_contents(git_ref);
+    let (body, verified) = match get_cached(&url, Route::Direct).await {
+        Ok(body) => (body, true),
+        Err(e) => {
+            warn!(
+                "直连 GitHub 获取规则列表失败 ({})，改用 GITHUB_PROXY：文件哈希同样经由代理获取，无法校验规则是否被代理篡改",
+                e
+            );
+            (get_cached(&url, Route::Proxy).await?, false)
+        }
+    };
+    let contents: Vec<GitHubContent> = serde_json::from_str(&body)?;
 
     // 过滤出 .json 文件，排除 index.json
-    let rule_files: Vec<String> = contents
+    let rule_files: HashMap<String, String> = contents
         .into_iter()
         .filter(|c| {
             c.content_type == "file" && c.name.ends_with(".json") && c.name != "index.json"
         })
-        .map(|c| c.name.trim_end_matches(".json").to_string())
+        .map(|c| (c.name.trim_end_matches(".json").to_string(), c.sha))
         .collect();
 
-    Ok(rule_files)
+    Ok(RuleFiles {
+        blobs: rule_files,
+        verified,
+    })
+}
+
+/// 从仓库中的文件路径提取规则名 (只取根目录下的 .json，排除 index.json 与元数据文件)
+fn rule_name_from_path(path: &str) -> Option<String> {
+    if path.contains('/') || !crate::rules::is_rule_file(Path::new(path)) {
+        return None;
+    }
+    path.strip_suffix(".json").map(|name| name.to_string())
+}
+
+/// 两个 commit 之间变动的规则
+struct RuleChanges {
+    /// 新增或修改的规则
+    changed: Vec<String>,
+    /// 上游已删除的规则
+    removed: Vec<String>,
+}
+
+/// 通过 Compare API 获取两个 commit 之间变动的规则
+///
+/// 变动文件过多 (列表可能被截断) 时返回 None，由调用方回退为全量更新
+async fn fetch_changed_rules(base: &str, head: &str) -> anyhow::Result<Option<RuleChanges>> {
+    let url = CONFIG.github_api_compare(base, head);
+    let response = get_with_retry(&url, &[], Route::Fallback).await?;
+    let compare: GitHubCompare = response.json().await?;
+
+    // 回退到旧版本或分叉时文件列表不完整
+    if !matches!(compare.status.as_str(), "ahead" | "identical")
+        || compare.files.len() >= COMPARE_FILES_LIMIT
+    {
+        return Ok(None);
+    }
+
+    let mut changes = RuleChanges {
+        changed: Vec::new(),
+        removed: Vec::new(),
+    };
+    for file in compare.files {
+        if file.status == "renamed" {
+            if let Some(previous) = file
+                .previous_filename
+                .as_deref()
+                .and_then(rule_name_from_path)
+            {
+                changes.removed.push(previous);
+            }
+        }
+        let Some(name) = rule_name_from_path(&file.filename) else {
+            continue;
+        };
+        if file.status == "removed" {
+            changes.removed.push(name);
+        } else {
+            changes.changed.push(name);
+        }
+    }
+
+    Ok(Some(changes))
+}
+
+/// 计算内容的 Git blob 哈希 (与 GitHub Contents API 返回的 sha 一致)
+fn git_blob_sha(content: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(format!("blob {}\0", content.len()));
+    hasher.update(content);
+    hex::encode(hasher.finalize())
 }
 
-/// 下载单个规则
-async fn download_rule(name: &str) -> anyhow::Result<String> {
-    let url = format!("{}{}.json", CONFIG.github_raw_base(), name);
-    let response = get_with_retry(&url).await?;
-    let content = response.text().await?;
+/// 下载指定版本的单个规则，并校验内容与上游 blob 哈希一致 (防止镜像或代理篡改)
+async fn download_rule(name: &str, git_ref: &str, blob_sha: &str) -> anyhow::Result<String> {
+    let url = format!("{}{}.json", CONFIG.rules_raw_base(git_ref), name);
+    let response = if CONFIG.has_raw_mirror() {
+        // 镜像直接请求：不携带 GitHub 令牌，也不走 GitHub 代理
+        let response = HTTP_CLIENT.get(&url).send().await?;
+        if !response.status().is_success() {
+            anyhow::bail!("镜像请求失败: HTTP {}", response.status());
+        }
+        response
+    } else {
+        get_with_retry(&url, &[], Route::Fallback).await?
+    };
+    let bytes = response.bytes().await?;
+    if git_blob_sha(&bytes) != blob_sha {
+        anyhow::bail!("内容与上游 blob 哈希不一致，可能被篡改");
+    }
+    let content = String::from_utf8(bytes.to_vec())?;
 
     // 验证 JSON 格式
     serde_json::from_str::<serde_json::Value>(&content)?;
@@ -158,6 +407,17 @@ async fn download_rule(name: &str) -> anyhow::Result<String> {
     Ok(content)
 }
 
+/// 从任意地址下载规则 JSON (用于导入分享的规则)
+pub async fn fetch_rule_json(url: &str) -> anyhow::Result<serde_json::Value> {
+    let response = HTTP_CLIENT.get(url).send().await?;
+    if !response.status().is_success() {
+        anyhow::bail!("下载失败: HTTP {}", response.status());
+    }
+
+    let bytes = crate::http_client::read_bytes(response).await?;
+    serde_json::from_slice(&bytes).map_err(|e| anyhow::anyhow!("不是有效的 JSON: {}", e))
+}
+
 /// 保存规则到本地
 fn save_rule(name: &str, content: &str) -> anyhow::Result<()> {
     let _ = fs::create_dir_all(RULES_DIR);
@@ -166,17 +426,179 @@ fn save_rule(name: &str, content: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// 校验下载的规则选择器并记录日志，返回更新详情中的说明
+fn validate_rule(name: &str, content: &str) -> String {
+    let rule: Rule = match serde_json::from_str(content) {
+        Ok(rule) => rule,
+        Err(e) => {
+            warn!("⚠️ 规则 {} 格式无效: {}", name, e);
+            return format!("规则格式无效: {}", e);
+        }
+    };
+
+    let issues = validate_selectors(&rule);
+    let invalid = issues.iter().filter(|i| i.level == "invalid").count();
+    for issue in &issues {
+        warn!("⚠️ 规则 {} 的 {} 选择器有问题: {}", name, issue.field, issue.message);
+    }
+
+    match (invalid, issues.len() - invalid) {
+        (0, 0) => "ok".to_string(),
+        (0, unconvertible) => format!("ok ({} 个选择器使用 XPath 求值)", unconvertible),
+        (invalid, _) => format!("ok ({} 个选择器无效)", invalid),
+    }
+}
+
 /// 检查本地是否存在该规则
 fn rule_exists(name: &str) -> bool {
     Path::new(RULES_DIR).join(format!("{}.json", name)).exists()
 }
 
-/// 检测变动并更新规则
+/// 本地所有规则名 (不含 index.json)
+fn local_rule_names() -> Vec<String> {
+    fs::read_dir(RULES_DIR)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|e| e.path().is_file())
+                .filter_map(|e| rule_name_from_path(&e.file_name().to_string_lossy()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 将本地规则文件移动到归档目录 (不再加载，需要时可手动恢复)
+fn archive_rule(name: &str) -> anyhow::Result<()> {
+    fs::create_dir_all(ARCHIVE_DIR)?;
+    let filename = format!("{}.json", name);
+    fs::rename(
+        Path::new(RULES_DIR).join(&filename),
+        Path::new(ARCHIVE_DIR).join(&filename),
+    )?;
+    crate::rules::update_integrity(|manifest| {
+        manifest.remove(name);
+    })?;
+    Ok(())
+}
+
+/// 回滚结果
+#[derive(Debug, Clone, Serialize)]
+pub struct RollbackResult {
+    /// 恢复到的 commit SHA (备份时本地无 commit 记录则为 "local")
+    pub commit: String,
+    pub restored: usize,
+    pub removed: usize,
+}
+
+/// 更新前备份当前规则集到 rules/.backup/<sha>/ (由存储清理按 RULE_SNAPSHOT_MAX_MB 淘汰旧备份)
+fn backup_rules(commit: Option<&str>) -> anyhow::Result<usize> {
+    let names = local_rule_names();
+    if names.is_empty() {
+        return Ok(0);
+    }
+
+    // 已有同一 commit 的备份时保留 (上次更新有失败时本地可能已是新旧混合)
+    let dir = StorageArea::RuleSnapshots.dir().join(commit.unwrap_or("local"));
+    if dir.exists() {
+        return Ok(0);
+    }
+    fs::create_dir_all(&dir)?;
+
+    for name in &names {
+        let filename = format!("{}.json", name);
+        fs::copy(Path::new(RULES_DIR).join(&filename), dir.join(&filename))?;
+    }
+    Ok(names.len())
+}
+
+/// 恢复最近一次备份的规则集并热重载，恢复后删除该备份 (再次回滚会继续恢复更早的备份)
+/// 同步执行 (在 spawn_blocking 中调用)，与规则更新互斥
+pub fn rollback_rules() -> anyhow::Result<RollbackResult> {
+    let _guard = UPDATE_LOCK.blocking_lock();
+    let backup = fs::read_dir(StorageArea::RuleSnapshots.dir())
+        .ok()
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|e| e.path().is_dir())
+        .max_by_key(|e| {
+            e.metadata()
+                .and_then(|m| m.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH)
+        })
+        .ok_or_else(|| anyhow::anyhow!("没有可回滚的规则备份"))?;
+
+    let backup_dir = backup.path();
+    let commit = backup.file_name().to_string_lossy().into_owned();
+    let backup_names: Vec<String> = fs::read_dir(&backup_dir)?
+        .flatten()
+        .filter_map(|e| rule_name_from_path(&e.file_name().to_string_lossy()))
+        .collect();
+    if backup_names.is_empty() {
+        anyhow::bail!("备份 {} 为空", commit);
+    }
+
+    // 删除备份中不存在的规则，再覆盖恢复 (通过管理接口保存的本地规则不受回滚影响)
+    let mut result = RollbackResult {
+        commit: commit.clone(),
+        restored: 0,
+        removed: 0,
+    };
+    let custom = crate::rules::custom_rule_names();
+    let mut digests = HashMap::new();
+    for name in local_rule_names() {
+        if !backup_names.contains(&name) && !custom.contains(&name) {
+            fs::remove_file(Path::new(RULES_DIR).join(format!("{}.json", name)))?;
+            result.removed += 1;
+        }
+    }
+    for name in backup_names.iter().filter(|name| !custom.contains(name)) {
+        let filename = format!("{}.json", name);
+        let content = fs::read(backup_dir.join(&filename))?;
+        fs::write(Path::new(RULES_DIR).join(&filename), &content)?;
+        digests.insert(name.clone(), crate::rules::content_digest(&content));
+        result.restored += 1;
+    }
+    // 恢复的内容即为备份时的内容，以此重建完整性清单
+    crate::rules::update_integrity(|manifest| *manifest = digests)?;
+
+    if commit == "local" {
+        let _ = fs::remove_file(LAST_COMMIT_FILE);
+    } else {
+        save_last_commit(&commit)?;
+    }
+    fs::remove_dir_all(&backup_dir)?;
+
+    crate::rules::reload_rules();
+    info!(
+        "⏪ 已回滚到 {}: 恢复 {} 个规则，删除 {} 个规则",
+        commit, result.restored, result.removed
+    );
+    Ok(result)
+}
+
+/// 检测变动并更新规则 (跟随 RULES_COMMIT 或分支最新提交)
 pub async fn update_rules() -> UpdateResult {
+    update_rules_at(CONFIG.rules_ref()).await
+}
+
+/// 检查版本名是否只包含分支/tag/SHA 中允许的字符 (避免拼接进 URL 时被篡改)
+pub fn is_valid_ref(git_ref: &str) -> bool {
+    !git_ref.is_empty()
+        && !git_ref.contains("..")
+        && git_ref
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '/'))
+}
+
+/// 将规则更新到指定版本 (分支、tag 或 commit SHA)
+pub async fn update_rules_at(git_ref: &str) -> UpdateResult {
+    let _guard = UPDATE_LOCK.lock().await;
     let mut result = UpdateResult {
         total: 0,
         updated: 0,
         added: 0,
+        removed: 0,
         failed: 0,
         details: Vec::new(),
     };
@@ -187,8 +609,8 @@ pub async fn update_rules() -> UpdateResult {
         info!("📦 本地无规则文件，立即拉取...");
     }
 
-    // 获取最新 commit SHA
-    let latest_commit = match fetch_latest_commit().await {
+    // 获取目标版本的 commit SHA
+    let latest_commit = match fetch_latest_commit(git_ref).await {
         Ok(sha) => sha,
         Err(e) => {
             warn!("获取最新 commit 失败: {}", e);
@@ -218,9 +640,29 @@ pub async fn update_rules() -> UpdateResult {
         &latest_commit[..7]
     );
 
-    // 获取规则文件列表
-    let rule_files = match fetch_rule_files().await {
-        Ok(files) => files,
+    // 有上次的 commit 时只下载变动的规则，否则 (或比较失败时) 全量下载
+    let changes = match last_commit.as_deref().filter(|_| !force_update) {
+        Some(base) => match fetch_changed_rules(base, &latest_commit).await {
+            Ok(changes) => changes,
+            Err(e) => {
+                warn!("比较 commit 失败，改为全量更新: {}", e);
+                None
+            }
+        },
+        None => None,
+    };
+
+    // 上游规则文件及其 blob 哈希 (用于校验下载的内容)
+    let blobs = match fetch_rule_files(&latest_commit).await {
+        Ok(files) if files.verified => files.blobs,
+        Ok(files) => {
+            result.details.push(UpdateDetail {
+                name: "contents".to_string(),
+                action: "unverified".to_string(),
+                message: UNVERIFIED_MESSAGE.to_string(),
+            });
+            files.blobs
+        }
         Err(e) => {
             warn!("获取规则列表失败: {}", e);
             result.details.push(UpdateDetail {
@@ -232,14 +674,92 @@ pub async fn update_rules() -> UpdateResult {
         }
     };
 
+    // 通过管理接口覆盖的规则保留本地版本，不下载也不归档
+    let custom = crate::rules::custom_rule_names();
+    let (rule_files, removed_files): (Vec<String>, Vec<String>) = match changes {
+        Some(changes) => {
+            info!(
+                "📡 {} 个规则有变动，{} 个规则被删除",
+                changes.changed.len(),
+                changes.removed.len()
+            );
+            (changes.changed, changes.removed)
+        }
+        None => {
+            info!("📡 发现 {} 个规则文件", blobs.len());
+            // 本地有而上游没有的规则视为已删除
+            let orphans = local_rule_names()
+                .into_iter()
+                .filter(|name| !blobs.contains_key(name))
+                .collect();
+            let mut files: Vec<String> = blobs.keys().cloned().collect();
+            files.sort();
+            (files, orphans)
+        }
+    };
+    let (overridden, rule_files): (Vec<String>, Vec<String>) =
+        rule_files.into_iter().partition(|name| custom.contains(name));
+    let removed_files: Vec<String> = removed_files
+        .into_iter()
+        .filter(|name| !custom.contains(name))
+        .collect();
+    for name in overridden {
+        debug!("📌 规则 {} 已被本地覆盖，跳过", name);
+    }
+
     result.total = rule_files.len();
-    info!("📡 发现 {} 个规则文件", rule_files.len());
+
+    // 备份当前规则集，供 /admin/rules/rollback 恢复
+    match backup_rules(last_commit.as_deref()) {
+        Ok(0) => {}
+        Ok(count) => debug!("💾 已备份 {} 个规则", count),
+        Err(e) => warn!("备份规则失败: {}", e),
+    }
+
+    // 归档上游已删除的规则
+    for name in removed_files {
+        if !rule_exists(&name) {
+            continue;
+        }
+        match archive_rule(&name) {
+            Ok(()) => {
+                result.removed += 1;
+                info!("➖ 规则 {} 已在上游删除，移至 {}", name, ARCHIVE_DIR);
+                result.details.push(UpdateDetail {
+                    name,
+                    action: "removed".to_string(),
+                    message: format!("上游已删除，已移至 {}", ARCHIVE_DIR),
+                });
+            }
+            Err(e) => {
+                warn!("归档规则 {} 失败: {}", name, e);
+                result.failed += 1;
+                result.details.push(UpdateDetail {
+                    name,
+                    action: "failed".to_string(),
+                    message: format!("归档失败: {}", e),
+                });
+            }
+        }
+    }
 
     // 下载并保存每个规则
+    let mut digests = HashMap::new();
     for name in rule_files {
         let is_new = !rule_exists(&name);
 
-        match download_rule(&name).await {
+        let Some(blob_sha) = blobs.get(&name) else {
+            warn!("规则 {} 不在上游文件列表中", name);
+            result.failed += 1;
+            result.details.push(UpdateDetail {
+                name,
+                action: "failed".to_string(),
+                message: "上游文件列表中不存在，无法校验内容".to_string(),
+            });
+            continue;
+        };
+
+        match download_rule(&name, &latest_commit, blob_sha).await {
             Ok(content) => {
                 if let Err(e) = save_rule(&name, &content) {
                     warn!("保存规则 {} 失败: {}", name, e);
@@ -250,6 +770,10 @@ pub async fn update_rules() -> UpdateResult {
                         message: format!("保存失败: {}", e),
                     });
                 } else {
+                    digests.insert(
+                        name.clone(),
+                        crate::rules::content_digest(content.as_bytes()),
+                    );
                     if is_new {
                         result.added += 1;
                         debug!("➕ 新增规则: {}", name);
@@ -260,7 +784,7 @@ pub async fn update_rules() -> UpdateResult {
                     result.details.push(UpdateDetail {
                         name: name.clone(),
                         action: if is_new { "added" } else { "updated" }.to_string(),
-                        message: "ok".to_string(),
+                        message: validate_rule(&name, &content),
                     });
                 }
             }
@@ -276,31 +800,195 @@ pub async fn update_rules() -> UpdateResult {
         }
     }
 
-    // 保存当前 commit SHA
-    if let Err(e) = save_last_commit(&latest_commit) {
+    // 记录下载内容的摘要，加载时校验
+    if !digests.is_empty() {
+        if let Err(e) = crate::rules::update_integrity(|manifest| manifest.extend(digests)) {
+            warn!("保存规则完整性清单失败: {}", e);
+        }
+    }
+
+    // 保存当前 commit SHA (有失败时保留旧的，下次比较仍会包含失败的规则)
+    if result.failed > 0 {
+        warn!("有 {} 个规则更新失败，下次检查时重试", result.failed);
+    } else if let Err(e) = save_last_commit(&latest_commit) {
         warn!("保存 commit SHA 失败: {}", e);
     }
 
+    // 热重载内存中的规则
+    if result.added + result.updated + result.removed > 0 {
+        crate::rules::reload_rules();
+    }
+
     info!(
-        "✅ 更新完成: {} 新增, {} 更新, {} 失败",
-        result.added, result.updated, result.failed
+        "✅ 更新完成: {} 新增, {} 更新, {} 删除, {} 失败",
+        result.added, result.updated, result.removed, result.failed
     );
 
     result
 }
 
-/// 检查是否需要更新（仅检查，不执行更新）
-#[allow(dead_code)]
-pub async fn check_for_updates() -> bool {
-    if !has_local_rules() {
-        return true;
+/// 检查规则名是否可以安全地用作文件名
+pub fn is_valid_rule_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "index"
+        && !name.starts_with('.')
+        && !name.contains(['/', '\\', '\0'])
+}
+
+/// 更新单个规则：下载、校验后保存，并只热重载该规则
+///
+/// 格式无效的规则不会覆盖本地文件
+pub async fn update_rule(name: &str) -> anyhow::Result<UpdateDetail> {
+    let _guard = UPDATE_LOCK.lock().await;
+    if crate::rules::custom_rule_names().iter().any(|n| n == name) {
+        anyhow::bail!("规则 {} 已通过管理接口在本地覆盖，删除本地规则后才能从上游更新", name);
+    }
+
+    let is_new = !rule_exists(name);
+    let git_ref = CONFIG.rules_ref();
+    let files = fetch_rule_files(git_ref).await?;
+    let blob_sha = files
+        .blobs
+        .get(name)
+        .ok_or_else(|| anyhow::anyhow!("上游不存在规则: {}", name))?;
+    let content = download_rule(name, git_ref, blob_sha).await?;
+    let rule: Rule = serde_json::from_str(&content)
+        .map_err(|e| anyhow::anyhow!("规则格式无效: {}", e))?;
+
+    save_rule(name, &content)?;
+    crate::rules::update_integrity(|manifest| {
+        manifest.insert(name.to_string(), crate::rules::content_digest(content.as_bytes()));
+    })?;
+    let mut message = validate_rule(name, &content);
+    if !files.verified {
+        message = format!("{} ({})", message, UNVERIFIED_MESSAGE);
+    }
+    // 同名规则由其他文件覆盖时只更新上游文件，内存中仍使用覆盖的规则
+    if crate::rules::is_shadowed(name) {
+        info!("规则 {} 由其他文件覆盖，保留当前加载的规则", name);
+    } else {
+        crate::rules::reload_rule(rule);
+    }
+    crate::rules::refresh_index();
+
+    info!("✅ 规则 {} 已{}", name, if is_new { "新增" } else { "更新" });
+    Ok(UpdateDetail {
+        name: name.to_string(),
+        action: if is_new { "added" } else { "updated" }.to_string(),
+        message,
+    })
+}
+
+/// 启动定时更新任务 (UPDATE_INTERVAL_HOURS 为 0 时不启动)
+pub fn spawn_scheduler() {
+    let hours = CONFIG.update_interval_hours;
+    if hours == 0 {
+        return;
     }
 
-    match fetch_latest_commit().await {
-        Ok(latest) => {
-            let last = read_last_commit();
-            last.as_ref() != Some(&latest)
+    let period = Duration::from_secs(hours * 3600);
+    info!("⏰ 规则定时更新已启用，间隔 {} 小时", hours);
+    tokio::spawn(async move {
+        // 启动时已按需更新过，首次在一个间隔后执行
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        loop {
+            interval.tick().await;
+            info!("📡 定时更新规则...");
+            update_rules().await;
         }
-        Err(_) => false,
+    });
+}
+
+/// 更新检查结果 (只比较，不下载规则)
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateCheck {
+    /// 本地记录的 commit SHA
+    pub current: Option<String>,
+    /// 上游最新 commit SHA
+    pub latest: String,
+    pub up_to_date: bool,
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// 本地规则文件的 Git blob 哈希
+fn local_blob_sha(name: &str) -> Option<String> {
+    fs::read(Path::new(RULES_DIR).join(format!("{}.json", name)))
+        .ok()
+        .map(|content| git_blob_sha(&content))
+}
+
+/// 比较本地记录的 commit 与上游，列出更新时会新增、更新和删除的规则
+///
+/// 有上次的 commit 时使用 Compare API，否则按文件列表的 blob 哈希与本地文件比较
+pub async fn check_updates() -> anyhow::Result<UpdateCheck> {
+    let latest = fetch_latest_commit(CONFIG.rules_ref()).await?;
+    let current = read_last_commit().filter(|_| has_local_rules());
+    let mut check = UpdateCheck {
+        up_to_date: current.as_ref() == Some(&latest),
+        current,
+        latest,
+        added: Vec::new(),
+        updated: Vec::new(),
+        removed: Vec::new(),
+    };
+    if check.up_to_date {
+        return Ok(check);
     }
-}
\ No newline at end of file
+
+    let changes = match check.current.as_deref() {
+        Some(base) => fetch_changed_rules(base, &check.latest).await?,
+        None => None,
+    };
+    let (changed, removed) = match changes {
+        Some(changes) => (changes.changed, changes.removed),
+        None => {
+            let blobs = fetch_rule_files(&check.latest).await?.blobs;
+            let removed = local_rule_names()
+                .into_iter()
+                .filter(|name| !blobs.contains_key(name))
+                .collect();
+            let changed = blobs
+                .into_iter()
+                .filter(|(name, sha)| local_blob_sha(name).as_ref() != Some(sha))
+                .map(|(name, _)| name)
+                .collect();
+            (changed, removed)
+        }
+    };
+
+    // 通过管理接口覆盖的规则更新时会跳过
+    let custom = crate::rules::custom_rule_names();
+    for name in changed.into_iter().filter(|name| !custom.contains(name)) {
+        if rule_exists(&name) {
+            check.updated.push(name);
+        } else {
+            check.added.push(name);
+        }
+    }
+    check.removed = removed
+        .into_iter()
+        .filter(|name| rule_exists(name) && !custom.contains(name))
+        .collect();
+    check.added.sort();
+    check.updated.sort();
+    check.removed.sort();
+
+    Ok(check)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn git_blob_sha_matches_git_hash_object() {
+        assert_eq!(
+            git_blob_sha(b"hello world\n"),
+            "3b18e512dba79e4c8300dd08aeb37f8e728b8dad"
+        );
+        assert_eq!(git_blob_sha(b""), "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391");
+    }
+}
@@ -0,0 +1,97 @@
+//! 无头浏览器渲染 (webview 特性)
+//! 用于 useWebview 规则：站点通过 JavaScript 渲染搜索结果，需要执行脚本后再解析
+
+use crate::config::CONFIG;
+use crate::http_client::{Page, RequestConfig};
+use anyhow::Context;
+use chromiumoxide::browser::{Browser, BrowserConfig};
+use chromiumoxide::cdp::browser_protocol::network::{Headers, SetExtraHttpHeadersParams};
+use chromiumoxide::cdp::browser_protocol::page::NavigateParams;
+use futures::StreamExt;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+
+/// 全局浏览器实例 (首次使用时启动)
+static BROWSER: OnceCell<Browser> = OnceCell::const_new();
+
+/// 启动 Chromium 并在后台驱动 CDP 连接
+async fn launch() -> anyhow::Result<Browser> {
+    let mut builder = BrowserConfig::builder()
+        .no_sandbox()
+        .request_timeout(Duration::from_secs(CONFIG.timeout_seconds))
+        .arg("--disable-gpu")
+        .arg("--mute-audio")
+        .arg(format!("--user-agent={}", CONFIG.user_agent));
+    if !CONFIG.chrome_path.is_empty() {
+        builder = builder.chrome_executable(&CONFIG.chrome_path);
+    }
+    let config = builder.build().map_err(|e| anyhow::anyhow!(e))?;
+
+    let (browser, mut handler) = Browser::launch(config)
+        .await
+        .context("启动 Chromium 失败")?;
+
+    tokio::spawn(async move {
+        while let Some(event) = handler.next().await {
+            if let Err(e) = event {
+                tracing::debug!("Chromium 事件错误: {}", e);
+            }
+        }
+        tracing::warn!("Chromium 连接已断开");
+    });
+
+    tracing::info!("🌐 Chromium 已启动");
+    Ok(browser)
+}
+
+/// 渲染页面并返回执行脚本后的 HTML
+pub async fn render(url: &str, config: &RequestConfig) -> anyhow::Result<Page> {
+    let browser = BROWSER.get_or_try_init(launch).await?;
+    let page = browser.new_page("about:blank").await?;
+
+    let result = async {
+        if let Some(ua) = &config.user_agent {
+            page.set_user_agent(ua.as_str()).await?;
+        }
+
+        let mut headers = serde_json::Map::new();
+        for (name, value) in &config.headers {
+            headers.insert(name.clone(), value.clone().into());
+        }
+        if let Some(cookie) = &config.cookie {
+            headers.insert("Cookie".to_string(), cookie.clone().into());
+        }
+        if !headers.is_empty() {
+            page.execute(SetExtraHttpHeadersParams::new(Headers::new(headers)))
+                .await?;
+        }
+
+        let mut params = NavigateParams::builder().url(url);
+        if let Some(referer) = &config.referer {
+            params = params.referrer(referer.as_str());
+        }
+        let params = params.build().map_err(|e| anyhow::anyhow!(e))?;
+
+        tokio::time::timeout(Duration::from_secs(CONFIG.timeout_seconds), page.goto(params))
+            .await
+            .context("页面加载超时")??;
+
+        // 等待异步脚本渲染结果
+        tokio::time::sleep(Duration::from_millis(CONFIG.webview_wait_ms)).await;
+
+        anyhow::Ok(Page {
+            body: page.content().await?,
+            final_url: page.url().await?.unwrap_or_else(|| url.to_string()),
+            // CDP 不直接提供主文档状态码，加载成功即视为 200
+            status: 200,
+            redirects: Vec::new(),
+        })
+    }
+    .await;
+
+    if let Err(e) = page.close().await {
+        tracing::debug!("关闭页面失败: {}", e);
+    }
+
+    result
+}
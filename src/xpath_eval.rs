@@ -0,0 +1,130 @@
+//! XPath 求值 (CSS 转换失败时的回退路径)
+//! 将 HTML 子树复制为 XML 文档，使用真正的 XPath 1.0 引擎求值，
+//! 再把结果映射回原始 HTML 元素，使无法转换为 CSS 的 Kazumi 规则也能直接使用
+
+use scraper::{ElementRef, Node};
+use sxd_document::dom::{Document, Element};
+use sxd_document::Package;
+use sxd_xpath::nodeset::Node as XNode;
+use sxd_xpath::{Context, Factory, Value, XPath};
+use tracing::debug;
+
+/// 记录原始元素下标的属性名 (复制时附加在每个元素上)
+const NODE_INDEX_ATTR: &str = "data-anime-search-node";
+
+/// 检查 XPath 是否可以被求值引擎解析
+pub fn validate(xpath: &str) -> Result<(), String> {
+    compile(xpath).map(|_| ())
+}
+
+/// 以 root 为文档根求值 XPath，返回匹配的元素 (按文档顺序，去重)
+///
+/// 与 CSS 查找保持一致，`//` 只在 root 子树内查找，`.//` 相对 root 查找；
+/// 结果为属性或文本节点时返回其所属元素
+pub fn select<'a>(xpath: &str, root: ElementRef<'a>) -> Vec<ElementRef<'a>> {
+    let compiled = match compile(xpath) {
+        Ok(compiled) => compiled,
+        Err(e) => {
+            debug!("XPath 编译失败 {}: {}", xpath, e);
+            return Vec::new();
+        }
+    };
+
+    let package = Package::new();
+    let document = package.as_document();
+    let mut originals = Vec::new();
+    let copy = copy_element(&document, root, &mut originals);
+    document.root().append_child(copy);
+
+    let nodes = match compiled.evaluate(&Context::new(), copy) {
+        Ok(Value::Nodeset(nodes)) => nodes,
+        Ok(_) => return Vec::new(),
+        Err(e) => {
+            debug!("XPath 求值失败 {}: {}", xpath, e);
+            return Vec::new();
+        }
+    };
+
+    let mut result: Vec<ElementRef<'a>> = Vec::new();
+    for node in nodes.document_order() {
+        let element = match node {
+            XNode::Element(element) => Some(element),
+            XNode::Attribute(attribute) => attribute.parent(),
+            XNode::Text(text) => text.parent(),
+            _ => None,
+        };
+        let original = element
+            .and_then(|e| e.attribute_value(NODE_INDEX_ATTR))
+            .and_then(|index| index.parse::<usize>().ok())
+            .and_then(|index| originals.get(index));
+        if let Some(original) = original {
+            if !result.iter().any(|e| e.id() == original.id()) {
+                result.push(*original);
+            }
+        }
+    }
+    result
+}
+
+fn compile(xpath: &str) -> Result<XPath, String> {
+    Factory::new()
+        .build(xpath)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "空的 XPath 表达式".to_string())
+}
+
+/// 递归复制元素 (保留属性与文本)，并记录复制后的下标到原始元素的映射
+fn copy_element<'d, 'a>(
+    document: &Document<'d>,
+    element: ElementRef<'a>,
+    originals: &mut Vec<ElementRef<'a>>,
+) -> Element<'d> {
+    let copy = document.create_element(element.value().name());
+    for (name, value) in element.value().attrs() {
+        copy.set_attribute_value(name, value);
+    }
+    copy.set_attribute_value(NODE_INDEX_ATTR, &originals.len().to_string());
+    originals.push(element);
+
+    for child in element.children() {
+        match child.value() {
+            Node::Element(_) => {
+                if let Some(child) = ElementRef::wrap(child) {
+                    copy.append_child(copy_element(document, child, originals));
+                }
+            }
+            Node::Text(text) => copy.append_child(document.create_text(text)),
+            _ => {}
+        }
+    }
+    copy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scraper::Html;
+
+    #[test]
+    fn test_select() {
+        let html = Html::parse_document(
+            r#"<ul><li class="ad"><a href="/ad">广告</a></li><li><a href="/1">第1集</a></li><li><a href="/2">第2集</a></li></ul>"#,
+        );
+        let root = html.root_element();
+
+        let result = select("//li[not(@class='ad')][a[contains(text(), '2')]]/a", root);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].value().attr("href"), Some("/2"));
+
+        // 属性节点映射回所属元素
+        let result = select("//li/a/@href", root);
+        assert_eq!(result.len(), 3);
+
+        // 相对 root 查找
+        let li = select("//li", root)[1];
+        let result = select(".//a", li);
+        assert_eq!(result[0].value().attr("href"), Some("/1"));
+
+        assert!(validate("//li[").is_err());
+    }
+}
@@ -2,6 +2,7 @@
 //! 支持 Kazumi 规则中常见的 XPath 表达式
 
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::sync::LazyLock;
 
 /// 将 XPath 表达式转换为 CSS 选择器
@@ -12,40 +13,176 @@ use std::sync::LazyLock;
 /// - `//div[@class='x']` → `div.x`
 /// - `//div[@id='x']` → `div#x`
 /// - `//div[contains(@class, 'x')]` → `div[class*="x"]`
+/// - `//a[@href]` → `a[href]`
+/// - `//a[starts-with(@href, '/play')]` → `a[href^="/play"]`
+/// - `//li[last()]` → `li:last-of-type`
+/// - `//li[last()-1]` → `li` + 位置过滤器 (倒数第 2 个)
+/// - `//li[position() > 1 and position() < 5]` → `li` + 位置过滤器 (第 2 ~ 4 个)
+/// - `//a[contains(text(), 'x')]` → `a` + 文本过滤器 (只能出现在最后一个路径段)
+/// - `//img/@data-original` → `img` + 取值属性 `data-original`
+/// - `//div[not(@class='ad')]` → `div:not(.ad)`
+/// - `//li[@class='x' or @class='y']` → `li.x, li.y` (and / or 组合)
+/// - `//div[@class='a']//a | //div[@class='b']//a` → `div.a a, div.b a` (并集)
+/// - `//h3/following-sibling::ul` → `h3 ~ ul`，`following-sibling::*[1]` → `+ *`
+/// - `//h3/following-sibling::ul[2]`、`preceding-sibling::x` → 在代码中遍历兄弟元素 (只能出现在末尾)
+/// - `//a[@class='title']/..`、`parent::div` → `a.title` + 在代码中取父元素 (只能出现在末尾)
 /// - `//div/a` → `div > a`
 /// - `//div//a` → `div a`
 /// - `//*[@id='x']` → `#x`
 /// - `.//a` → `a` (相对路径)
 pub fn xpath_to_css(xpath: &str) -> Result<CssSelector, String> {
+    xpath_to_css_with(xpath, PositionMode::default())
+}
+
+/// 位置谓词 `[n]` 的转换方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PositionMode {
+    /// 一律转换为 `:nth-of-type(n)` (兼容旧行为)
+    #[default]
+    NthOfType,
+    /// 遵循 XPath 语义：`*[n]` → `:nth-child(n)`，其他谓词之后的 `[n]` 在同一父元素下按匹配顺序计数
+    /// (只支持最后一个路径段，其余位置返回错误以回退为 XPath 求值)
+    XPath,
+}
+
+/// 按指定的位置语义转换 XPath
+pub fn xpath_to_css_with(xpath: &str, mode: PositionMode) -> Result<CssSelector, String> {
     let xpath = xpath.trim();
     
     if xpath.is_empty() {
         return Err("空的 XPath 表达式".to_string());
     }
 
-    // 解析并转换
-    let (css, position_filter) = convert_xpath(xpath)?;
-    
+    // 并集 (a | b) 逐个分支转换后以逗号连接，过滤器作用于合并后的结果，因此各分支需一致
+    // 位置过滤器按合并后的顺序计数，与各分支分别计数的 XPath 语义不同，含位置条件的并集回退为 XPath 求值
+    let branches = split_union(xpath);
+    let mut selectors = Vec::new();
+    let mut merged: Option<CssSelector> = None;
+    for branch in &branches {
+        if branch.trim().is_empty() {
+            return Err("并集 (|) 中存在空的分支".to_string());
+        }
+        let mut css = convert_xpath(branch.trim(), mode)?;
+        if branches.len() > 1 && css.position_filter.is_some() {
+            return Err("并集 (|) 的分支不支持 position() / last() - n 条件".to_string());
+        }
+        selectors.push(std::mem::take(&mut css.selector));
+        match &merged {
+            Some(existing) if *existing != css => {
+                return Err("并集 (|) 各分支的位置/文本过滤条件、轴步骤与取值属性必须一致".to_string());
+            }
+            Some(_) => {}
+            None => merged = Some(css),
+        }
+    }
+
     Ok(CssSelector {
-        selector: css,
-        position_filter,
+        selector: selectors.join(", "),
+        ..merged.unwrap_or_default()
     })
 }
 
+/// XPath 末尾的属性步骤 (`//img/@data-src` → `data-src`)
+pub fn attribute_step(xpath: &str) -> Option<String> {
+    RE_ATTRIBUTE_STEP.captures(xpath.trim()).map(|caps| caps[1].to_string())
+}
+
+/// 在谓词与引号之外按 `|` 拆分并集表达式
+fn split_union(xpath: &str) -> Vec<&str> {
+    let mut branches = Vec::new();
+    let mut depth = 0usize;
+    let mut quote: Option<char> = None;
+    let mut start = 0;
+
+    for (i, c) in xpath.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '(' | '[') => depth += 1,
+            (None, ')' | ']') => depth = depth.saturating_sub(1),
+            (None, '|') if depth == 0 => {
+                branches.push(&xpath[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    branches.push(&xpath[start..]);
+    branches
+}
+
 /// CSS 选择器结果
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CssSelector {
     /// CSS 选择器字符串
     pub selector: String,
     /// 位置过滤器 (用于处理 position() > n 等)
     pub position_filter: Option<PositionFilter>,
+    /// 文本过滤器 (用于处理 contains(text(), 'x'))
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_filter: Option<TextFilter>,
+    /// 取值属性 (XPath 以 /@attr 结尾时，从匹配元素的该属性取值)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attribute: Option<String>,
+    /// 同一父元素下的第 n 个匹配 (XPath 位置语义，如 `div[@class='x'][2]`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_index: Option<usize>,
+    /// 匹配后依次执行的轴步骤 (CSS 无法表达的兄弟元素跳转)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub axes: Vec<AxisStep>,
+}
+
+/// 需要在代码中处理的轴步骤
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AxisStep {
+    /// following-sibling::x[n]：之后第 n 个匹配 selector 的兄弟元素 (index 为 None 时取全部)
+    FollowingSibling { selector: String, index: Option<usize> },
+    /// preceding-sibling::x[n]：之前第 n 个 (由近及远) 匹配 selector 的兄弟元素
+    PrecedingSibling { selector: String, index: Option<usize> },
+    /// .. / parent::x：父元素 (指定 selector 时父元素需匹配)
+    Parent { selector: Option<String> },
+}
+
+/// 文本过滤器 (按元素文本内容筛选匹配到的元素)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TextFilter {
+    /// contains(text(), 'x')
+    Contains(String),
+}
+
+impl TextFilter {
+    pub fn matches(&self, text: &str) -> bool {
+        match self {
+            Self::Contains(needle) => text.contains(needle.as_str()),
+        }
+    }
 }
 
 /// 位置过滤器 (用于 position() > n 等无法用 CSS 表达的情况)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub enum PositionFilter {
-    /// position() > n (跳过前 n 个元素)
-    GreaterThan(usize),
+    /// position() 范围 (从 1 开始的闭区间，None 表示不限)，
+    /// 如 position() > 1 and position() <= 5 → min = 2, max = 5
+    Range { min: Option<usize>, max: Option<usize> },
+    /// last() - n (只保留倒数第 n + 1 个元素)
+    LastMinus(usize),
+}
+
+impl PositionFilter {
+    /// 第 position 个元素 (从 1 开始) 是否在范围内 (LastMinus 需要结合总数，在此恒为 true)
+    pub fn contains(&self, position: usize) -> bool {
+        match self {
+            Self::Range { min, max } => {
+                min.is_none_or(|min| position >= min) && max.is_none_or(|max| position <= max)
+            }
+            Self::LastMinus(_) => true,
+        }
+    }
 }
 
 // 正则表达式 (编译一次)
@@ -65,24 +202,59 @@ static RE_CONTAINS_CLASS: LazyLock<Regex> = LazyLock::new(|| {
     Regex::new(r#"\[contains\s*\(\s*@class\s*,\s*['"]([^'"]+)['"]\s*\)\]"#).unwrap()
 });
 
-static RE_POSITION_GT: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::new(r"\[position\s*\(\s*\)\s*>\s*(\d+)\]").unwrap()
+/// 整个 position() 谓词，支持用 and 组合多个比较 (如 `[position() > 1 and position() < 5]`)
+static RE_POSITION_PREDICATE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"\[\s*position\s*\(\s*\)\s*(?:[<>]=?|=)\s*\d+(?:\s+and\s+position\s*\(\s*\)\s*(?:[<>]=?|=)\s*\d+)*\s*\]",
+    )
+    .unwrap()
+});
+
+/// 谓词中的单个 position() 比较
+static RE_POSITION_COMPARE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"position\s*\(\s*\)\s*([<>]=?|=)\s*(\d+)").unwrap()
+});
+
+static RE_ATTR_EXISTS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\[@([a-zA-Z_][a-zA-Z0-9_-]*)\s*\]").unwrap()
+});
+
+static RE_LAST: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\[\s*last\s*\(\s*\)\s*\]").unwrap()
+});
+
+static RE_LAST_MINUS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\[\s*last\s*\(\s*\)\s*-\s*(\d+)\s*\]").unwrap()
+});
+
+/// 末尾的属性步骤 /@attr
+static RE_ATTRIBUTE_STEP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"/@([a-zA-Z_][a-zA-Z0-9_:-]*)\s*$").unwrap()
+});
+
+static RE_STARTS_WITH: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\[\s*starts-with\s*\(\s*@([a-zA-Z_][a-zA-Z0-9_-]*)\s*,\s*['"]([^'"]*)['"]\s*\)\s*\]"#).unwrap()
+});
+
+/// contains(text(), 'x') / contains(., 'x')
+static RE_CONTAINS_TEXT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\[\s*contains\s*\(\s*(?:text\s*\(\s*\)|\.)\s*,\s*(?:'([^']*)'|"([^"]*)")\s*\)\s*\]"#).unwrap()
 });
 
 static RE_GENERIC_ATTR: LazyLock<Regex> = LazyLock::new(|| {
     Regex::new(r#"\[@([a-zA-Z_][a-zA-Z0-9_-]*)=['"]([^'"]+)['"]\]"#).unwrap()
 });
 
-fn convert_xpath(xpath: &str) -> Result<(String, Option<PositionFilter>), String> {
+fn convert_xpath(xpath: &str, mode: PositionMode) -> Result<CssSelector, String> {
     let mut xpath = xpath.to_string();
     let mut position_filter = None;
+    let mut text_filter = None;
+    let mut attribute = None;
 
     // 移除开头的 // 或 .// 或 /
     if xpath.starts_with(".//") {
         xpath = xpath[3..].to_string();
-    } else if xpath.starts_with("//") {
-        xpath = xpath[2..].to_string();
-    } else if xpath.starts_with("./") {
+    } else if xpath.starts_with("//") || xpath.starts_with("./") {
         xpath = xpath[2..].to_string();
     } else if xpath.starts_with("/") {
         xpath = xpath[1..].to_string();
@@ -93,27 +265,127 @@ fn convert_xpath(xpath: &str) -> Result<(String, Option<PositionFilter>), String
         xpath = xpath[..xpath.len() - 7].to_string();
     }
 
-    // 检查 position() > n，需要在代码中过滤
-    if let Some(caps) = RE_POSITION_GT.captures(&xpath) {
-        if let Some(n) = caps.get(1).and_then(|m| m.as_str().parse::<usize>().ok()) {
-            position_filter = Some(PositionFilter::GreaterThan(n));
+    // 移除末尾的 /@attr，记录需要读取的属性
+    if let Some(caps) = RE_ATTRIBUTE_STEP.captures(&xpath) {
+        attribute = Some(caps[1].to_string());
+        xpath = xpath[..caps.get(0).map_or(0, |m| m.start())].to_string();
+    }
+
+    // 检查 position() 范围 (>、>=、<、<=、=)，需要在代码中过滤
+    // 过滤作用于全部匹配结果，出现在中间路径段时回退为 XPath 求值
+    if RE_POSITION_PREDICATE.is_match(&xpath) {
+        let segments = split_xpath_segments(&xpath);
+        if segments
+            .split_last()
+            .is_none_or(|(_, init)| init.iter().any(|s| RE_POSITION_PREDICATE.is_match(&s.element)))
+        {
+            return Err("position() 条件只支持出现在最后一个路径段".to_string());
+        }
+        let (mut min, mut max): (Option<usize>, Option<usize>) = (None, None);
+        for predicate in RE_POSITION_PREDICATE.find_iter(&xpath) {
+            for caps in RE_POSITION_COMPARE.captures_iter(predicate.as_str()) {
+                let n: usize = caps[2]
+                    .parse()
+                    .map_err(|_| format!("无效的位置: {}", &caps[2]))?;
+                let (low, high) = match &caps[1] {
+                    ">" => (Some(n + 1), None),
+                    ">=" => (Some(n), None),
+                    "<" => (None, Some(n.saturating_sub(1))),
+                    "<=" => (None, Some(n)),
+                    _ => (Some(n), Some(n)),
+                };
+                // 多个条件取交集
+                min = min.max(low);
+                max = match (max, high) {
+                    (Some(a), Some(b)) => Some(a.min(b)),
+                    (a, b) => a.or(b),
+                };
+            }
         }
-        xpath = RE_POSITION_GT.replace_all(&xpath, "").to_string();
+        position_filter = Some(PositionFilter::Range { min, max });
+        xpath = RE_POSITION_PREDICATE.replace_all(&xpath, "").to_string();
+    }
+
+    // 检查 last() - n，同样需要在代码中过滤
+    // 过滤作用于全部匹配结果，只有出现在最后一个路径段时才与 XPath 语义一致，其余情况回退为 XPath 求值
+    if RE_LAST_MINUS.is_match(&xpath) {
+        let segments = split_xpath_segments(&xpath);
+        let on_last_step = segments
+            .split_last()
+            .is_some_and(|(_, init)| !init.iter().any(|s| RE_LAST_MINUS.is_match(&s.element)));
+        if !on_last_step {
+            return Err("last() - n 只支持出现在最后一个路径段".to_string());
+        }
+        if position_filter.is_some() || RE_LAST_MINUS.find_iter(&xpath).count() > 1 {
+            return Err("last() - n 不能与其他位置条件组合".to_string());
+        }
+        if let Some(caps) = RE_LAST_MINUS.captures(&xpath) {
+            let n = caps[1].parse::<usize>().map_err(|_| format!("无效的位置: {}", &caps[1]))?;
+            position_filter = Some(PositionFilter::LastMinus(n));
+        }
+        xpath = RE_LAST_MINUS.replace_all(&xpath, "").to_string();
     }
 
     // 分割路径段
-    let segments = split_xpath_segments(&xpath);
-    let mut css = String::new();
+    let mut segments = split_xpath_segments(&xpath);
+
+    // 末尾无法用 CSS 表达的轴步骤，在代码中执行
+    let mut axes = Vec::new();
+    while let Some(step) = segments.last().map(|s| axis_step(s, mode)).transpose()?.flatten() {
+        axes.insert(0, step);
+        segments.pop();
+    }
+    if segments.is_empty() {
+        return Err("XPath 缺少元素路径".to_string());
+    }
+
+    // 检查 contains(text(), 'x')，CSS 无法按文本匹配，只支持出现在最后一个路径段并在代码中过滤
+    if let Some((last, init)) = segments.split_last_mut() {
+        if init.iter().any(|segment| RE_CONTAINS_TEXT.is_match(&segment.element)) {
+            return Err("contains(text(), ...) 只支持出现在最后一个路径段".to_string());
+        }
+        if let Some(caps) = RE_CONTAINS_TEXT.captures(&last.element) {
+            let needle = caps.get(1).or_else(|| caps.get(2)).map_or("", |m| m.as_str());
+            text_filter = Some(TextFilter::Contains(needle.to_string()));
+            last.element = RE_CONTAINS_TEXT.replace_all(&last.element, "").to_string();
+        }
+    }
+
+    // XPath 位置语义下，最后一个路径段中其他谓词之后的 [n] 在代码中按父元素计数
+    let mut parent_index = None;
+    if mode == PositionMode::XPath {
+        if let Some(last) = segments.last_mut() {
+            let (name, mut predicates) = split_predicates(&last.element)?;
+            if predicates.len() > 1 && predicates.last().is_some_and(|p| is_index(p)) {
+                let index = predicates.pop().unwrap_or_default();
+                parent_index = Some(index.trim().parse().map_err(|_| format!("无效的位置: {}", index))?);
+                let predicates: String = predicates.iter().map(|p| format!("[{}]", p)).collect();
+                last.element = format!("{}{}", name, predicates);
+            }
+        }
+    }
+
+    let mut alternatives = vec![String::new()];
 
     for (index, segment) in segments.iter().enumerate() {
-        let css_segment = convert_segment(segment, index == 0)?;
-        css.push_str(&css_segment);
+        alternatives = combine(&alternatives, &convert_segment(segment, index == 0, mode)?)?;
     }
 
-    // 清理多余空格
-    let css = css.trim().to_string();
+    // 清理多余空格，多个候选以逗号连接
+    let css = alternatives
+        .iter()
+        .map(|css| css.trim())
+        .collect::<Vec<_>>()
+        .join(", ");
     
-    Ok((css, position_filter))
+    Ok(CssSelector {
+        selector: css,
+        position_filter,
+        text_filter,
+        attribute,
+        parent_index,
+        axes,
+    })
 }
 
 /// 分割 XPath 路径段，处理 / 和 //
@@ -122,9 +394,21 @@ fn split_xpath_segments(xpath: &str) -> Vec<PathSegment> {
     let mut current = String::new();
     let mut chars = xpath.chars().peekable();
     let mut is_descendant = false;
+    // 谓词 [...] 与引号内的 / 不是路径分隔符
+    let mut depth = 0usize;
+    let mut quote: Option<char> = None;
 
     while let Some(c) = chars.next() {
-        if c == '/' {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '[') => depth += 1,
+            (None, ']') => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+
+        if c == '/' && quote.is_none() && depth == 0 {
             if !current.is_empty() {
                 segments.push(PathSegment {
                     element: current.clone(),
@@ -158,10 +442,12 @@ struct PathSegment {
     is_descendant: bool, // true = //, false = /
 }
 
+/// 谓词展开后最多产生的 CSS 选择器数量 (or 条件会相乘)
+const MAX_ALTERNATIVES: usize = 32;
+
 /// 转换单个路径段
-fn convert_segment(segment: &PathSegment, is_first: bool) -> Result<String, String> {
-    let mut element = segment.element.clone();
-    
+/// 谓词中的 or 条件无法用单个 CSS 表达，会展开为多个候选 (最终以逗号连接)
+fn convert_segment(segment: &PathSegment, is_first: bool, mode: PositionMode) -> Result<Vec<String>, String> {
     // 第一个段不需要组合符，后续段根据是否为后代选择决定
     let combinator = if is_first {
         ""
@@ -171,13 +457,248 @@ fn convert_segment(segment: &PathSegment, is_first: bool) -> Result<String, Stri
         " > " // 子选择器用 >
     };
 
-    // 处理通配符 *
-    if element == "*" || element.starts_with("*[") {
-        element = element.replacen("*", "", 1);
+    // 可以用 CSS 兄弟组合符表达的 following-sibling
+    if let Some(rest) = segment.element.strip_prefix("following-sibling::") {
+        if rest.trim() == "*[1]" {
+            return Ok(vec![" + *".to_string()]);
+        }
+        let (name, predicates) = split_predicates(rest)?;
+        if predicates.iter().any(|p| is_index(p)) {
+            return Err(format!("{} 只支持出现在 XPath 末尾", segment.element));
+        }
+        return node_test(name, &predicates, mode)
+            .map(|alternatives| alternatives.into_iter().map(|css| format!(" ~ {}", css)).collect());
+    }
+    if segment.element.contains("::") || segment.element.trim() == ".." {
+        return Err(format!("不支持的轴: {} (只能出现在 XPath 末尾)", segment.element));
+    }
+
+    let (name, predicates) = split_predicates(&segment.element)?;
+    Ok(node_test(name, &predicates, mode)?
+        .into_iter()
+        .map(|css| format!("{}{}", combinator, css))
+        .collect())
+}
+
+/// 转换元素名与谓词 (不含组合符)
+fn node_test(name: &str, predicates: &[&str], mode: PositionMode) -> Result<Vec<String>, String> {
+    let wildcard = name == "*";
+    // 处理通配符 * (没有其他条件时保留)
+    let name = match name {
+        "*" if !predicates.is_empty() => "",
+        name => name,
+    };
+
+    let mut alternatives = vec![name.to_string()];
+    for (i, predicate) in predicates.iter().enumerate() {
+        if mode == PositionMode::XPath && is_index(predicate) {
+            // [n] 只有作为第一个谓词时才等价于 CSS 的位置伪类
+            if i > 0 {
+                return Err(format!("位置谓词 [{}] 位于其他谓词之后，无法转换为 CSS", predicate.trim()));
+            }
+            if wildcard {
+                alternatives = combine(&alternatives, &[format!(":nth-child({})", predicate.trim())])?;
+                continue;
+            }
+        }
+        alternatives = combine(&alternatives, &convert_predicate(predicate)?)?;
+    }
+    Ok(alternatives)
+}
+
+/// 是否为位置索引谓词 ([n])
+fn is_index(predicate: &str) -> bool {
+    let predicate = predicate.trim();
+    !predicate.is_empty() && predicate.chars().all(|c| c.is_ascii_digit())
+}
+
+/// 需要在代码中执行的轴步骤 (CSS 可以表达的返回 None)
+fn axis_step(segment: &PathSegment, mode: PositionMode) -> Result<Option<AxisStep>, String> {
+    // 父元素
+    let element = segment.element.trim();
+    if element == ".." {
+        return Ok(Some(AxisStep::Parent { selector: None }));
+    }
+    if let Some(rest) = element.strip_prefix("parent::") {
+        let (name, predicates) = split_predicates(rest)?;
+        let selector = match (name, predicates.is_empty()) {
+            ("*" | "node()", true) => None,
+            _ => Some(node_test(name, &predicates, mode)?.join(", ")),
+        };
+        return Ok(Some(AxisStep::Parent { selector }));
     }
 
+    let (rest, following) = if let Some(rest) = segment.element.strip_prefix("following-sibling::") {
+        (rest, true)
+    } else if let Some(rest) = segment.element.strip_prefix("preceding-sibling::") {
+        (rest, false)
+    } else {
+        return Ok(None);
+    };
+
+    let (name, mut predicates) = split_predicates(rest)?;
+    let index = match predicates.last() {
+        Some(p) if is_index(p) => {
+            let index = p.trim().parse().map_err(|_| format!("无效的位置: {}", p))?;
+            predicates.pop();
+            Some(index)
+        }
+        _ => None,
+    };
+    // following-sibling::x (不带位置) 与 following-sibling::*[1] 可以直接用 CSS 表达
+    if following && (index.is_none() || (index == Some(1) && name == "*" && predicates.is_empty())) {
+        return Ok(None);
+    }
+
+    let selector = node_test(name, &predicates, mode)?.join(", ");
+    Ok(Some(if following {
+        AxisStep::FollowingSibling { selector, index }
+    } else {
+        AxisStep::PrecedingSibling { selector, index }
+    }))
+}
+
+/// 拆分元素名与各个谓词 (`a[@x][1]` → `a`, [`@x`, `1`])
+fn split_predicates(element: &str) -> Result<(&str, Vec<&str>), String> {
+    let name_end = element.find('[').unwrap_or(element.len());
+    let mut predicates = Vec::new();
+    let mut depth = 0usize;
+    let mut quote: Option<char> = None;
+    let mut start = name_end;
+
+    for (i, c) in element.char_indices().skip_while(|(i, _)| *i < name_end) {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '[') => {
+                if depth == 0 {
+                    start = i + 1;
+                }
+                depth += 1;
+            }
+            (None, ']') => {
+                depth = depth.checked_sub(1).ok_or_else(|| format!("括号不匹配: {}", element))?;
+                if depth == 0 {
+                    predicates.push(&element[start..i]);
+                }
+            }
+            (None, _) if depth == 0 => return Err(format!("无法解析的路径段: {}", element)),
+            _ => {}
+        }
+    }
+    if depth != 0 || quote.is_some() {
+        return Err(format!("括号或引号不匹配: {}", element));
+    }
+
+    Ok((element[..name_end].trim(), predicates))
+}
+
+/// 转换谓词表达式，支持 and / or 组合与括号分组
+/// 返回的每一项都是可直接拼接在元素名后的 CSS 片段，多项表示 "或"
+fn convert_predicate(expr: &str) -> Result<Vec<String>, String> {
+    let expr = expr.trim();
+
+    let branches = split_top_level(expr, "or");
+    if branches.len() > 1 {
+        let mut alternatives = Vec::new();
+        for branch in branches {
+            alternatives.extend(convert_predicate(branch)?);
+        }
+        if alternatives.len() > MAX_ALTERNATIVES {
+            return Err(format!("谓词展开后的选择器过多: {}", expr));
+        }
+        return Ok(alternatives);
+    }
+
+    let conditions = split_top_level(expr, "and");
+    if conditions.len() > 1 {
+        let mut alternatives = vec![String::new()];
+        for condition in conditions {
+            alternatives = combine(&alternatives, &convert_predicate(condition)?)?;
+        }
+        return Ok(alternatives);
+    }
+
+    if let Some(inner) = strip_parens(expr) {
+        return convert_predicate(inner);
+    }
+
+    // not(...) → :not(...)，内部的多个候选以逗号连接
+    if let Some(inner) = expr.strip_prefix("not").and_then(|rest| strip_parens(rest.trim_start())) {
+        let alternatives = convert_predicate(inner)?;
+        if alternatives.iter().any(|css| css.is_empty()) {
+            return Err(format!("不支持的谓词: {}", expr));
+        }
+        return Ok(vec![format!(":not({})", alternatives.join(", "))]);
+    }
+
+    convert_condition(expr).map(|css| vec![css])
+}
+
+/// 在最外层 (括号与引号之外) 按 and / or 关键字拆分
+fn split_top_level<'a>(expr: &'a str, keyword: &str) -> Vec<&'a str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut quote: Option<char> = None;
+    let mut start = 0;
+
+    for (i, c) in expr.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '(' | '[') => depth += 1,
+            (None, ')' | ']') => depth = depth.saturating_sub(1),
+            (None, c) if depth == 0 && c.is_whitespace() => {
+                let rest = &expr[i + c.len_utf8()..];
+                let is_keyword = rest
+                    .strip_prefix(keyword)
+                    .is_some_and(|after| after.starts_with(char::is_whitespace));
+                if is_keyword && i >= start {
+                    parts.push(&expr[start..i]);
+                    start = i + c.len_utf8() + keyword.len();
+                }
+            }
+            _ => {}
+        }
+    }
+    parts.push(&expr[start..]);
+    parts
+}
+
+/// 去掉包裹整个表达式的一对括号
+fn strip_parens(expr: &str) -> Option<&str> {
+    let inner = expr.strip_prefix('(')?.strip_suffix(')')?;
+    // 确认首尾括号是同一对 (排除 "(a) and (b)")
+    let mut depth = 0usize;
+    for c in inner.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.checked_sub(1)?,
+            _ => {}
+        }
+    }
+    (depth == 0).then_some(inner)
+}
+
+/// 两组候选的笛卡尔积 (用于 and 条件与路径段拼接)
+fn combine(left: &[String], right: &[String]) -> Result<Vec<String>, String> {
+    if left.len() * right.len() > MAX_ALTERNATIVES {
+        return Err(format!("谓词展开后的选择器超过 {} 个", MAX_ALTERNATIVES));
+    }
+    Ok(left
+        .iter()
+        .flat_map(|l| right.iter().map(move |r| format!("{}{}", l, r)))
+        .collect())
+}
+
+/// 转换单个条件 (如 `@class='x'`、`1`、`last()`)
+fn convert_condition(condition: &str) -> Result<String, String> {
+    let original = format!("[{}]", condition.trim());
+
     // 处理 [@class='xxx']
-    let element = RE_CLASS_ATTR.replace_all(&element, |caps: &regex::Captures| {
+    let element = RE_CLASS_ATTR.replace_all(&original, |caps: &regex::Captures| {
         let class_name = &caps[1];
         // 多个类名用空格分隔时，转换为 .class1.class2
         let classes: String = class_name
@@ -197,18 +718,33 @@ fn convert_segment(segment: &PathSegment, is_first: bool) -> Result<String, Stri
         format!("[class*=\"{}\"]", &caps[1])
     }).to_string();
 
+    // 处理 [starts-with(@attr, 'xxx')]
+    let element = RE_STARTS_WITH.replace_all(&element, |caps: &regex::Captures| {
+        format!("[{}^=\"{}\"]", &caps[1], &caps[2])
+    }).to_string();
+
     // 处理其他属性 [@attr='value']
     let element = RE_GENERIC_ATTR.replace_all(&element, |caps: &regex::Captures| {
         format!("[{}=\"{}\"]", &caps[1], &caps[2])
     }).to_string();
 
+    // 处理属性存在 [@attr]
+    let element = RE_ATTR_EXISTS.replace_all(&element, |caps: &regex::Captures| {
+        format!("[{}]", &caps[1])
+    }).to_string();
+
+    // 处理 [last()]
+    let element = RE_LAST.replace_all(&element, ":last-of-type").to_string();
+
     // 处理位置索引 [n]
     let element = RE_POSITION_INDEX.replace_all(&element, |caps: &regex::Captures| {
         format!(":nth-of-type({})", &caps[1])
     }).to_string();
 
-    // 组合结果
-    Ok(format!("{}{}", combinator, element))
+    if element == original {
+        return Err(format!("不支持的谓词: {}", original));
+    }
+    Ok(element)
 }
 
 #[cfg(test)]
@@ -257,6 +793,143 @@ mod tests {
         assert_eq!(result.selector, "div[class*=\"btn\"]");
     }
 
+    #[test]
+    fn test_attribute_exists() {
+        let result = xpath_to_css("//a[@href]").unwrap();
+        assert_eq!(result.selector, "a[href]");
+
+        let result = xpath_to_css("//div[@data-id]/a[@title][1]").unwrap();
+        assert_eq!(result.selector, "div[data-id] > a[title]:nth-of-type(1)");
+    }
+
+    #[test]
+    fn test_starts_with() {
+        let result = xpath_to_css("//div[starts-with(@class,'ep-')]").unwrap();
+        assert_eq!(result.selector, "div[class^=\"ep-\"]");
+
+        let result = xpath_to_css("//ul/li/a[starts-with(@href, '/play')]").unwrap();
+        assert_eq!(result.selector, "ul > li > a[href^=\"/play\"]");
+    }
+
+    #[test]
+    fn test_compound_predicates() {
+        let result = xpath_to_css("//div[@class='a' and @data-type='b']/a").unwrap();
+        assert_eq!(result.selector, "div.a[data-type=\"b\"] > a");
+
+        let result = xpath_to_css("//ul/li[@class='x' or @class='y']").unwrap();
+        assert_eq!(result.selector, "ul > li.x, ul > li.y");
+
+        let result = xpath_to_css("//div[@id='m' and (@class='x' or contains(@class, 'y'))]//a").unwrap();
+        assert_eq!(result.selector, "div#m.x a, div#m[class*=\"y\"] a");
+
+        // 引号内的 and / or 不是关键字
+        let result = xpath_to_css("//a[@title='tom and jerry']").unwrap();
+        assert_eq!(result.selector, "a[title=\"tom and jerry\"]");
+
+        assert!(xpath_to_css("//div[@class='a' and unknown()]").is_err());
+    }
+
+    #[test]
+    fn test_union() {
+        let result = xpath_to_css("//div[@class='list1']//a | //div[@class='list2']//a").unwrap();
+        assert_eq!(result.selector, "div.list1 a, div.list2 a");
+
+        let result = xpath_to_css("//ul[1]/li[@class='ep'] | //ol/li[@class='ep']").unwrap();
+        assert_eq!(result.selector, "ul:nth-of-type(1) > li.ep, ol > li.ep");
+
+        // 位置条件在 XPath 中按分支分别计数，回退为 XPath 求值
+        assert!(xpath_to_css("//ul[1]/li[position() > 1] | //ol/li[position() > 1]").is_err());
+
+        // 引号内的 | 不是并集
+        let result = xpath_to_css("//a[@title='a|b']").unwrap();
+        assert_eq!(result.selector, "a[title=\"a|b\"]");
+
+        assert!(xpath_to_css("//ul/li[position() > 1] | //ol/li").is_err());
+        assert!(xpath_to_css("//div | ").is_err());
+    }
+
+    #[test]
+    fn test_attribute_step() {
+        let result = xpath_to_css("//div[@class='pic']/img/@data-original").unwrap();
+        assert_eq!(result.selector, "div.pic > img");
+        assert_eq!(result.attribute.as_deref(), Some("data-original"));
+
+        let result = xpath_to_css("//ul/li/a/@href").unwrap();
+        assert_eq!(result.selector, "ul > li > a");
+        assert_eq!(result.attribute.as_deref(), Some("href"));
+
+        assert!(xpath_to_css("//a").unwrap().attribute.is_none());
+    }
+
+    #[test]
+    fn test_sibling_axes() {
+        let result = xpath_to_css("//h3[@class='title']/following-sibling::ul/li").unwrap();
+        assert_eq!(result.selector, "h3.title ~ ul > li");
+        assert!(result.axes.is_empty());
+
+        let result = xpath_to_css("//h3/following-sibling::*[1]").unwrap();
+        assert_eq!(result.selector, "h3 + *");
+
+        let result = xpath_to_css("//h3/following-sibling::div[@class='x'][2]").unwrap();
+        assert_eq!(result.selector, "h3");
+        assert_eq!(
+            result.axes,
+            vec![AxisStep::FollowingSibling { selector: "div.x".to_string(), index: Some(2) }]
+        );
+
+        let result = xpath_to_css("//span/preceding-sibling::a").unwrap();
+        assert_eq!(result.selector, "span");
+        assert_eq!(
+            result.axes,
+            vec![AxisStep::PrecedingSibling { selector: "a".to_string(), index: None }]
+        );
+
+        assert!(xpath_to_css("//h3/following-sibling::div[1]/a").is_err());
+
+        let result = xpath_to_css("//a[@class='title']/..").unwrap();
+        assert_eq!(result.selector, "a.title");
+        assert_eq!(result.axes, vec![AxisStep::Parent { selector: None }]);
+
+        let result = xpath_to_css("//a/parent::li[@class='ep']").unwrap();
+        assert_eq!(result.axes, vec![AxisStep::Parent { selector: Some("li.ep".to_string()) }]);
+
+        assert!(xpath_to_css("//a/../span").is_err());
+        assert!(xpath_to_css("//div/ancestor::ul").is_err());
+    }
+
+    #[test]
+    fn test_not_predicate() {
+        let result = xpath_to_css("//div[not(@class='ad')]").unwrap();
+        assert_eq!(result.selector, "div:not(.ad)");
+
+        let result = xpath_to_css("//ul/li[not(contains(@class,'hidden'))]/a").unwrap();
+        assert_eq!(result.selector, "ul > li:not([class*=\"hidden\"]) > a");
+
+        let result = xpath_to_css("//li[@data-id and not(@class='x' or @class='y')]").unwrap();
+        assert_eq!(result.selector, "li[data-id]:not(.x, .y)");
+    }
+
+    #[test]
+    fn test_xpath_position_mode() {
+        // 默认保持 nth-of-type
+        let result = xpath_to_css("//ul/*[2]").unwrap();
+        assert_eq!(result.selector, "ul > :nth-of-type(2)");
+
+        let result = xpath_to_css_with("//ul/*[2]", PositionMode::XPath).unwrap();
+        assert_eq!(result.selector, "ul > :nth-child(2)");
+
+        let result = xpath_to_css_with("//div[2]/a", PositionMode::XPath).unwrap();
+        assert_eq!(result.selector, "div:nth-of-type(2) > a");
+
+        // 其他谓词之后的位置在代码中按父元素计数
+        let result = xpath_to_css_with("//ul/li[@class='ep'][2]", PositionMode::XPath).unwrap();
+        assert_eq!(result.selector, "ul > li.ep");
+        assert_eq!(result.parent_index, Some(2));
+
+        // 中间路径段无法表达，交给 XPath 求值
+        assert!(xpath_to_css_with("//div[@class='x'][2]/a", PositionMode::XPath).is_err());
+    }
+
     #[test]
     fn test_complex_xpath() {
         let result = xpath_to_css("//div[1]/div[2]/div/ul/li").unwrap();
@@ -279,7 +952,71 @@ mod tests {
     fn test_position_filter() {
         let result = xpath_to_css("//div[position() > 1]").unwrap();
         assert_eq!(result.selector, "div");
-        assert!(matches!(result.position_filter, Some(PositionFilter::GreaterThan(1))));
+        assert_eq!(
+            result.position_filter,
+            Some(PositionFilter::Range { min: Some(2), max: None })
+        );
+    }
+
+    #[test]
+    fn test_position_filter_intermediate_step() {
+        // 中间路径段的位置条件无法在全部结果上等价过滤，回退为 XPath 求值
+        assert!(xpath_to_css("//ul/li[position() > 1]/a").is_err());
+        assert!(xpath_to_css("//li[position() < 3]/..").is_err());
+    }
+
+    #[test]
+    fn test_position_range() {
+        let result = xpath_to_css("//ul/li[position() < 4]").unwrap();
+        assert_eq!(result.selector, "ul > li");
+        assert_eq!(
+            result.position_filter,
+            Some(PositionFilter::Range { min: None, max: Some(3) })
+        );
+
+        let result = xpath_to_css("//li[position() >= 2 and position() <= 5]").unwrap();
+        let filter = result.position_filter.unwrap();
+        assert_eq!(filter, PositionFilter::Range { min: Some(2), max: Some(5) });
+        assert!(!filter.contains(1));
+        assert!(filter.contains(2) && filter.contains(5));
+        assert!(!filter.contains(6));
+
+        let result = xpath_to_css("//li[position() = 3]").unwrap();
+        assert_eq!(
+            result.position_filter,
+            Some(PositionFilter::Range { min: Some(3), max: Some(3) })
+        );
+    }
+
+    #[test]
+    fn test_last_position() {
+        let result = xpath_to_css("//ul/li[last()]").unwrap();
+        assert_eq!(result.selector, "ul > li:last-of-type");
+        assert!(result.position_filter.is_none());
+
+        let result = xpath_to_css("//ul/li/a[last() - 1]").unwrap();
+        assert_eq!(result.selector, "ul > li > a");
+        assert!(matches!(result.position_filter, Some(PositionFilter::LastMinus(1))));
+
+        // 中间路径段的 last() - n 无法在全部结果上等价过滤，回退为 XPath 求值
+        assert!(xpath_to_css("//ul/li[last() - 1]/a").is_err());
+        assert!(xpath_to_css("//li[last() - 1]/..").is_err());
+        // 冲突的位置条件
+        assert!(xpath_to_css("//li[position() > 1][last() - 1]").is_err());
+        assert!(xpath_to_css("//li[last() - 1][last() - 2]").is_err());
+    }
+
+    #[test]
+    fn test_contains_text() {
+        let result = xpath_to_css("//div[@class='play']//a[contains(text(),'线路')]").unwrap();
+        assert_eq!(result.selector, "div.play a");
+        assert_eq!(result.text_filter, Some(TextFilter::Contains("线路".to_string())));
+
+        let result = xpath_to_css(r#"//li[contains(., "第")]"#).unwrap();
+        assert_eq!(result.selector, "li");
+        assert!(result.text_filter.unwrap().matches("第1集"));
+
+        assert!(xpath_to_css("//div[contains(text(),'x')]/a").is_err());
     }
 
     #[test]